@@ -7,6 +7,32 @@ contract DIDRegistry {
     
     // DID文档映射
     mapping(string => string) private didDocuments;
+
+    // 锚定记录：只保存文档哈希和版本号，完整文档保存在链下
+    struct Anchor {
+        bytes32 documentHash;
+        uint64 versionId;
+        uint256 timestamp;
+    }
+
+    // DID锚定映射
+    mapping(string => Anchor) private anchors;
+
+    // 批次Merkle根映射（根哈希 => 上链时间）
+    mapping(bytes32 => uint256) private batchRoots;
+    // 批次大小映射
+    mapping(bytes32 => uint32) private batchSizes;
+
+    // 操作日志链头锚定记录，与批次根哈希分开保存
+    struct LogHead {
+        uint64 seq;
+        uint256 timestamp;
+    }
+
+    // 操作日志链头映射（日志哈希 => 锚定记录）
+    mapping(bytes32 => LogHead) private logHeads;
+    // 最近锚定的链头序号
+    uint64 private latestLogSeq;
     
     // 注册DID事件
     event DIDRegistered(string did, string document);
@@ -14,6 +40,12 @@ contract DIDRegistry {
     event DIDUpdated(string did, string document);
     // 停用DID事件
     event DIDDeactivated(string did);
+    // 锚定DID文档哈希事件
+    event DIDAnchored(string did, bytes32 documentHash, uint64 versionId);
+    // 锚定批次根哈希事件
+    event BatchAnchored(bytes32 root, uint32 size);
+    // 锚定操作日志链头事件
+    event LogHeadAnchored(bytes32 entryHash, uint64 seq);
 
     // 注册新的DID
     function register(string memory did, string memory document) public {
@@ -37,6 +69,56 @@ contract DIDRegistry {
         emit DIDDeactivated(did);
     }
 
+    // 锚定DID文档哈希，首次锚定时注册DID
+    function anchor(string memory did, bytes32 documentHash, uint64 versionId) public {
+        if (anchors[did].versionId == 0) {
+            require(!activeDIDs[did], "DID already exists");
+            activeDIDs[did] = true;
+        } else {
+            require(activeDIDs[did], "DID not found");
+            require(versionId > anchors[did].versionId, "Stale version");
+        }
+        anchors[did] = Anchor(documentHash, versionId, block.timestamp);
+        emit DIDAnchored(did, documentHash, versionId);
+    }
+
+    // 获取DID最新锚定记录
+    function getAnchor(string memory did) public view returns (bytes32, uint64, uint256) {
+        Anchor memory record = anchors[did];
+        require(record.versionId > 0, "DID not anchored");
+        return (record.documentHash, record.versionId, record.timestamp);
+    }
+
+    // 锚定一批DID操作的Merkle根哈希
+    function anchorBatch(bytes32 root, uint32 size) public {
+        require(batchRoots[root] == 0, "Batch already anchored");
+        batchRoots[root] = block.timestamp;
+        batchSizes[root] = size;
+        emit BatchAnchored(root, size);
+    }
+
+    // 获取批次锚定记录
+    function getBatch(bytes32 root) public view returns (uint32, uint256) {
+        require(batchRoots[root] > 0, "Batch not anchored");
+        return (batchSizes[root], batchRoots[root]);
+    }
+
+    // 锚定操作日志链头，序号必须递增
+    function anchorLogHead(bytes32 entryHash, uint64 seq) public {
+        require(logHeads[entryHash].seq == 0, "Log head already anchored");
+        require(seq > latestLogSeq, "Stale log head");
+        latestLogSeq = seq;
+        logHeads[entryHash] = LogHead(seq, block.timestamp);
+        emit LogHeadAnchored(entryHash, seq);
+    }
+
+    // 获取操作日志链头锚定记录
+    function getLogHead(bytes32 entryHash) public view returns (uint64, uint256) {
+        LogHead memory head = logHeads[entryHash];
+        require(head.seq > 0, "Log head not anchored");
+        return (head.seq, head.timestamp);
+    }
+
     // 获取DID状态
     function getStatus(string memory did) public view returns (bool) {
         return activeDIDs[did];
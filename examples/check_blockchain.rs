@@ -2,7 +2,6 @@
 
 use reqwest::Client;
 use serde_json::json;
-use tokio;
 
 #[tokio::main]
 async fn main() {
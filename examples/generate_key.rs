@@ -1,7 +1,6 @@
 //! 生成 Ed25519 密钥对的示例程序
 
 use ed25519_dalek::SigningKey;
-use bs58;
 use rand::rngs::OsRng;
 
 fn main() {
@@ -0,0 +1,38 @@
+//! 检查DID在区块链上注册状态的示例程序
+//!
+//! 用法: cargo run --example verify_did_registration -- <did>
+
+use reqwest::Client;
+use serde_json::Value;
+
+#[tokio::main]
+async fn main() {
+    let did = match std::env::args().nth(1) {
+        Some(did) => did,
+        None => {
+            println!("用法: verify_did_registration <did>");
+            return;
+        }
+    };
+
+    println!("正在检查DID注册状态: {}", did);
+
+    let client = Client::new();
+    let api_url = "http://localhost:5000";
+
+    match client.get(format!("{}/did/{}/status", api_url, did)).send().await {
+        Ok(response) => {
+            if response.status().is_success() {
+                let status: Value = response.json().await.unwrap_or_default();
+                match status.get("active").and_then(Value::as_bool) {
+                    Some(true) => println!("DID已在区块链上注册且处于活跃状态"),
+                    Some(false) => println!("DID未注册或已停用"),
+                    None => println!("无法识别的响应: {}", status),
+                }
+            } else {
+                println!("查询失败，状态码: {}", response.status());
+            }
+        },
+        Err(e) => println!("区块链API连接失败: {}", e),
+    }
+}
@@ -2,7 +2,7 @@ import axios from 'axios';
 import { ApiResponse, CreateDIDRequest, DIDDocument, UpdateDIDRequest, DeactivateDIDRequest } from '../types/did';
 
 const api = axios.create({
-  baseURL: '/api',
+  baseURL: '/api/v1',
   headers: {
     'Content-Type': 'application/json',
   },
@@ -41,16 +41,53 @@ except Exception as e:
     raise
 contract = w3.eth.contract(address=contract_address, abi=contract_abi)
 
+# 已处理的幂等键及其响应，避免出站队列重试时重复上链
+processed_requests = {}
+
+def idempotent(handler):
+    """根据Idempotency-Key请求头缓存成功的交易响应"""
+    from functools import wraps
+
+    @wraps(handler)
+    def wrapper(*args, **kwargs):
+        key = request.headers.get('Idempotency-Key')
+        if key and key in processed_requests:
+            logger.info(f'重复的幂等键，返回已有结果: {key}')
+            return jsonify(processed_requests[key])
+
+        result = handler(*args, **kwargs)
+        if key and isinstance(result, dict):
+            processed_requests[key] = result
+        return jsonify(result) if isinstance(result, dict) else result
+    return wrapper
+
+def current_contract():
+    """返回请求通过X-Contract-Address指定的合约，未指定时使用部署配置中的合约"""
+    address = request.headers.get('X-Contract-Address')
+    if not address:
+        return contract
+    return w3.eth.contract(address=Web3.to_checksum_address(address), abi=contract_abi)
+
+def transact(function):
+    """发送合约交易并等待回执，优先使用请求指定的操作员账户"""
+    account = request.headers.get('X-Operator-Account') or w3.eth.accounts[0]
+    tx_hash = function.transact({'from': account})
+    receipt = w3.eth.wait_for_transaction_receipt(tx_hash)
+    return {
+        'hash': tx_hash.hex(),
+        'status': 'success' if receipt.status == 1 else 'failed'
+    }
+
 @app.route('/did/<did>/status', methods=['GET'])
 def get_did_status(did):
     try:
         app.logger.info(f'正在获取DID状态: {did}')
         # 添加详细的调试日志
-        logger.info(f'合约地址: {contract.address}')
+        logger.info(f'合约地址: {current_contract().address}')
         logger.info(f'调用合约方法: getStatus({did})')
         
         # 调用合约的getStatus方法
-        status = contract.functions.getStatus(did).call()
+        status = current_contract().functions.getStatus(did).call()
         logger.info(f'合约返回状态: {status}')
         
         response = {'active': status}
@@ -64,6 +101,7 @@ def get_did_status(did):
         return jsonify({'error': str(e)}), 500
 
 @app.route('/did/register', methods=['POST'])
+@idempotent
 def register_did():
     try:
         data = request.get_data()
@@ -71,17 +109,8 @@ def register_did():
         did = data[:data.index(b'\0')].decode('utf-8') if b'\0' in data else data.decode('utf-8')
         public_key = data[len(did)+1:] if len(data) > len(did) else b''
         
-        # 获取默认账户
-        account = w3.eth.accounts[0]
-        
         # 调用合约的register方法
-        tx_hash = contract.functions.register(did, public_key).transact({'from': account})
-        receipt = w3.eth.wait_for_transaction_receipt(tx_hash)
-        
-        return jsonify({
-            'hash': tx_hash.hex(),
-            'status': 'success' if receipt.status == 1 else 'failed'
-        })
+        return transact(current_contract().functions.register(did, public_key.hex()))
     except Exception as e:
         app.logger.error(f'注册DID失败: {str(e)}')
         return jsonify({'error': str(e)}), 500
@@ -89,32 +118,116 @@ def register_did():
 @app.route('/did/<did>', methods=['GET'])
 def get_did_document(did):
     try:
-        # 调用合约的resolve方法
-        document = contract.functions.resolve(did).call()
-        return jsonify(document)
+        if not current_contract().functions.getStatus(did).call():
+            return jsonify({'error': 'DID not found or deactivated'}), 404
+
+        # 调用合约的getDocument方法；仅注册过公钥、尚未存储文档时返回404
+        document = current_contract().functions.getDocument(did).call()
+        try:
+            parsed = json.loads(document)
+        except ValueError:
+            parsed = None
+        if not isinstance(parsed, dict):
+            return jsonify({'error': 'DID document not stored on chain'}), 404
+        return jsonify(parsed)
     except Exception as e:
         app.logger.error(f'获取DID文档失败: {str(e)}')
         return jsonify({'error': str(e)}), 500
 
 @app.route('/did/store', methods=['POST'])
+@idempotent
 def store_did_document():
     try:
-        document = request.get_json()
+        document = request.get_json(force=True)
         
-        # 获取默认账户
-        account = w3.eth.accounts[0]
+        # 调用合约的update方法
+        return transact(current_contract().functions.update(document['id'], json.dumps(document)))
+    except Exception as e:
+        app.logger.error(f'存储DID文档失败: {str(e)}')
+        return jsonify({'error': str(e)}), 500
+
+@app.route('/did/deactivate', methods=['POST'])
+@idempotent
+def deactivate_did():
+    try:
+        did = request.get_data().decode('utf-8')
         
-        # 调用合约的store方法
-        tx_hash = contract.functions.store(document).transact({'from': account})
-        receipt = w3.eth.wait_for_transaction_receipt(tx_hash)
+        # 调用合约的deactivate方法
+        return transact(current_contract().functions.deactivate(did))
+    except Exception as e:
+        app.logger.error(f'停用DID失败: {str(e)}')
+        return jsonify({'error': str(e)}), 500
+
+@app.route('/did/anchor', methods=['POST'])
+@idempotent
+def anchor_did_document():
+    try:
+        data = request.get_json(force=True)
+        document_hash = bytes.fromhex(data['hash'])
         
-        return jsonify({
-            'hash': tx_hash.hex(),
-            'status': 'success' if receipt.status == 1 else 'failed'
-        })
+        # 调用合约的anchor方法，只上链文档哈希和版本号
+        return transact(current_contract().functions.anchor(data['did'], document_hash, int(data['version_id'])))
     except Exception as e:
-        app.logger.error(f'存储DID文档失败: {str(e)}')
+        app.logger.error(f'锚定DID文档失败: {str(e)}')
+        return jsonify({'error': str(e)}), 500
+
+@app.route('/did/<did>/anchor', methods=['GET'])
+def get_did_anchor(did):
+    try:
+        document_hash, version_id, _ = current_contract().functions.getAnchor(did).call()
+        return jsonify({'hash': document_hash.hex(), 'version_id': version_id})
+    except Exception as e:
+        if 'DID not anchored' in str(e):
+            return jsonify({'error': 'DID not anchored'}), 404
+        app.logger.error(f'获取DID锚定记录失败: {str(e)}')
+        return jsonify({'error': str(e)}), 500
+
+@app.route('/anchor/batch', methods=['POST'])
+@idempotent
+def anchor_batch():
+    try:
+        data = request.get_json(force=True)
+        
+        # 调用合约的anchorBatch方法，只上链批次的Merkle根哈希
+        return transact(current_contract().functions.anchorBatch(bytes.fromhex(data['root']), int(data['size'])))
+    except Exception as e:
+        app.logger.error(f'锚定批次失败: {str(e)}')
+        return jsonify({'error': str(e)}), 500
+
+@app.route('/anchor/batch/<root>', methods=['GET'])
+def get_batch_anchor(root):
+    try:
+        size, timestamp = current_contract().functions.getBatch(bytes.fromhex(root)).call()
+        return jsonify({'root': root, 'size': size, 'timestamp': timestamp})
+    except Exception as e:
+        if 'Batch not anchored' in str(e):
+            return jsonify({'error': 'Batch not anchored'}), 404
+        app.logger.error(f'获取批次锚定记录失败: {str(e)}')
+        return jsonify({'error': str(e)}), 500
+
+@app.route('/anchor/log-head', methods=['POST'])
+@idempotent
+def anchor_log_head():
+    try:
+        data = request.get_json(force=True)
+        
+        # 调用合约的anchorLogHead方法，链头与批次根哈希分开登记
+        return transact(current_contract().functions.anchorLogHead(bytes.fromhex(data['entry_hash']), int(data['seq'])))
+    except Exception as e:
+        app.logger.error(f'锚定操作日志链头失败: {str(e)}')
+        return jsonify({'error': str(e)}), 500
+
+@app.route('/anchor/log-head/<entry_hash>', methods=['GET'])
+def get_log_head_anchor(entry_hash):
+    try:
+        seq, timestamp = current_contract().functions.getLogHead(bytes.fromhex(entry_hash)).call()
+        return jsonify({'entry_hash': entry_hash, 'seq': seq, 'timestamp': timestamp})
+    except Exception as e:
+        if 'Log head not anchored' in str(e):
+            return jsonify({'error': 'Log head not anchored'}), 404
+        app.logger.error(f'获取操作日志链头锚定记录失败: {str(e)}')
         return jsonify({'error': str(e)}), 500
 
 if __name__ == '__main__':
-    app.run(host='0.0.0.0', port=3000, debug=True)
\ No newline at end of file
+    # 默认使用5000端口，避免与DID服务的3000端口冲突
+    app.run(host='0.0.0.0', port=int(os.environ.get('LEDGER_API_PORT', 5000)), debug=True)
\ No newline at end of file
@@ -0,0 +1,109 @@
+//! Merkle树 - 为批量锚定的操作生成根哈希和包含证明
+
+use serde::{Deserialize, Serialize};
+use crate::utils;
+use utoipa::ToSchema;
+
+/// 叶子节点哈希前缀，与内部节点区分以防止第二原像攻击
+const LEAF_PREFIX: u8 = 0x00;
+/// 内部节点哈希前缀
+const NODE_PREFIX: u8 = 0x01;
+
+/// 兄弟节点相对于当前节点的位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Position {
+    Left,
+    Right,
+}
+
+/// 包含证明中的一步
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ProofStep {
+    /// 兄弟节点哈希（十六进制）
+    pub hash: String,
+    /// 兄弟节点位置
+    pub position: Position,
+}
+
+/// 计算叶子节点哈希
+pub fn leaf_hash(data: &[u8]) -> Vec<u8> {
+    utils::sha256(&[&[LEAF_PREFIX], data].concat())
+}
+
+/// 计算内部节点哈希
+fn node_hash(left: &[u8], right: &[u8]) -> Vec<u8> {
+    utils::sha256(&[&[NODE_PREFIX], left, right].concat())
+}
+
+/// Merkle树，奇数个节点时最后一个节点直接提升到上一层
+#[derive(Debug, Clone)]
+pub struct MerkleTree {
+    levels: Vec<Vec<Vec<u8>>>,
+}
+
+impl MerkleTree {
+    /// 由叶子节点哈希构建Merkle树
+    pub fn new(leaves: Vec<Vec<u8>>) -> Self {
+        let mut levels = vec![leaves];
+
+        while levels.last().map(|level| level.len() > 1).unwrap_or(false) {
+            let next = levels.last().unwrap()
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => node_hash(left, right),
+                    [single] => single.clone(),
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next);
+        }
+
+        Self { levels }
+    }
+
+    /// 根哈希，空树返回None
+    pub fn root(&self) -> Option<&[u8]> {
+        self.levels.last().and_then(|level| level.first()).map(Vec::as_slice)
+    }
+
+    /// 生成指定叶子的包含证明
+    pub fn proof(&self, index: usize) -> Option<Vec<ProofStep>> {
+        if index >= self.levels.first()?.len() {
+            return None;
+        }
+
+        let mut steps = Vec::new();
+        let mut position = index;
+        for level in &self.levels[..self.levels.len() - 1] {
+            let sibling = position ^ 1;
+            if let Some(hash) = level.get(sibling) {
+                steps.push(ProofStep {
+                    hash: utils::to_hex(hash),
+                    position: if sibling < position { Position::Left } else { Position::Right },
+                });
+            }
+            position /= 2;
+        }
+
+        Some(steps)
+    }
+}
+
+/// 验证叶子哈希通过包含证明能够得到指定的根哈希
+pub fn verify_proof(leaf: &[u8], proof: &[ProofStep], root: &[u8]) -> bool {
+    let mut current = leaf.to_vec();
+
+    for step in proof {
+        let sibling = match utils::from_hex(&step.hash) {
+            Ok(hash) => hash,
+            Err(_) => return false,
+        };
+        current = match step.position {
+            Position::Left => node_hash(&sibling, &current),
+            Position::Right => node_hash(&current, &sibling),
+        };
+    }
+
+    current == root
+}
@@ -0,0 +1,309 @@
+//! 批量锚定模块 - 将多个DID操作汇总为Merkle树，只将根哈希锚定到区块链
+//!
+//! 批处理任务按时间窗口或数量上限收集出站队列中的锚定和停用操作，
+//! 为每个操作保存包含证明，解析DID时可以据此验证文档已被锚定。
+
+pub mod merkle;
+
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use crate::blockchain::{self, LedgerMode};
+use crate::db::{DidRecord, DidStore, NewAnchorProof, SharedStore};
+use crate::did;
+use crate::outbox::{self, OutboxConfig, OutboxEntry, OutboxOperation, OutboxStatus};
+use crate::types::Error;
+use crate::utils;
+use self::merkle::{MerkleTree, ProofStep};
+use utoipa::ToSchema;
+
+/// 批量锚定配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BatchConfig {
+    /// 单个批次最多包含的操作数
+    pub max_batch_size: usize,
+    /// 最早的操作等待多久（秒）后即使未满也封装批次
+    pub max_wait_secs: u64,
+    /// 检查间隔（秒）
+    pub poll_interval_secs: u64,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self {
+            max_batch_size: 256,
+            max_wait_secs: 30,
+            poll_interval_secs: 2,
+        }
+    }
+}
+
+impl BatchConfig {
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_secs(self.poll_interval_secs)
+    }
+}
+
+/// Merkle树叶子对应的操作内容
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct AnchorLeaf {
+    pub did: String,
+    pub version_id: u64,
+    pub operation: OutboxOperation,
+    /// 文档规范化哈希，停用操作为空
+    pub hash: Option<String>,
+}
+
+impl AnchorLeaf {
+    /// 由出站记录构造叶子
+    pub fn from_entry(entry: &OutboxEntry) -> Result<Self, Error> {
+        let version_id = entry.version_id.ok_or_else(|| {
+            Error::InvalidState(format!("Outbox entry {} has no version id", entry.id))
+        })?;
+        Self::from_operation(&entry.did, version_id, entry.operation, &entry.payload)
+    }
+
+    /// 由写入出站队列的操作构造叶子，锚定操作的载荷为文档哈希
+    pub fn from_operation(did: &str, version_id: u64, operation: OutboxOperation, payload: &[u8]) -> Result<Self, Error> {
+        let hash = match operation {
+            OutboxOperation::Anchor => Some(
+                String::from_utf8(payload.to_vec())
+                    .map_err(|e| Error::SerializationError(e.to_string()))?,
+            ),
+            _ => None,
+        };
+
+        Ok(Self {
+            did: did.to_string(),
+            version_id,
+            operation,
+            hash,
+        })
+    }
+
+    /// 由本地DID记录构造期望的叶子
+    pub fn from_record(record: &DidRecord) -> Result<Self, Error> {
+        if record.is_active {
+            Ok(Self {
+                did: record.did.clone(),
+                version_id: record.version_id,
+                operation: OutboxOperation::Anchor,
+                hash: Some(did::document_hash(&record.document)?),
+            })
+        } else {
+            Ok(Self {
+                did: record.did.clone(),
+                version_id: record.version_id,
+                operation: OutboxOperation::Deactivate,
+                hash: None,
+            })
+        }
+    }
+
+    /// 叶子的规范化序列化
+    pub fn canonical_bytes(&self) -> Result<Vec<u8>, Error> {
+        serde_json::to_vec(self).map_err(|e| Error::SerializationError(e.to_string()))
+    }
+}
+
+/// 批次中单个操作的包含证明
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct AnchorProof {
+    pub did: String,
+    pub version_id: u64,
+    pub leaf: AnchorLeaf,
+    pub leaf_index: u64,
+    pub proof: Vec<ProofStep>,
+    pub batch_id: i64,
+    /// 批次所在的账本
+    pub ledger: String,
+    /// Merkle根哈希（十六进制）
+    pub root: String,
+    /// 批次状态
+    pub status: OutboxStatus,
+    pub tx_hash: Option<String>,
+}
+
+/// 锚定批次
+#[derive(Debug, Clone)]
+pub struct AnchorBatch {
+    pub id: i64,
+    pub ledger: String,
+    pub root: String,
+    pub size: u64,
+    pub idempotency_key: String,
+    pub attempts: u32,
+}
+
+/// 包含证明的验证结果
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum InclusionStatus {
+    /// 证明有效且根哈希已上链
+    Verified { proof: AnchorProof },
+    /// 操作仍在等待封装或上链
+    Pending { proof: Option<AnchorProof> },
+    /// 当前版本没有锚定记录
+    Missing,
+    /// 本地文档与证明或链上根哈希不一致
+    Mismatch { proof: AnchorProof, reason: String },
+}
+
+/// 启动批量锚定任务，为每个批量锚定模式的账本独立封装批次
+pub fn spawn_batcher(
+    store: SharedStore,
+    batch_config: BatchConfig,
+    outbox_config: OutboxConfig,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        log::info!(
+            "批量锚定任务已启动，批次上限: {}，时间窗口: {}秒",
+            batch_config.max_batch_size,
+            batch_config.max_wait_secs
+        );
+        loop {
+            let batched_ledgers = blockchain::ledgers().iter()
+                .filter(|ledger| ledger.mode() == LedgerMode::Batch);
+            for ledger in batched_ledgers {
+                if let Err(e) = seal_batch_if_ready(store.as_ref(), ledger.name(), &batch_config).await {
+                    log::error!("封装账本{}的锚定批次失败: {}", ledger.name(), e);
+                }
+            }
+            if let Err(e) = submit_due_batches(store.as_ref(), &outbox_config).await {
+                log::error!("提交锚定批次失败: {}", e);
+            }
+            tokio::time::sleep(batch_config.poll_interval()).await;
+        }
+    })
+}
+
+/// 指定账本的操作达到数量上限或时间窗口时封装一个批次，返回新批次的ID
+pub async fn seal_batch_if_ready(
+    store: &dyn DidStore,
+    ledger: &str,
+    config: &BatchConfig,
+) -> Result<Option<i64>, Error> {
+    let entries = store.fetch_unbatched_anchor_entries(ledger, config.max_batch_size).await?;
+    let oldest = match entries.first() {
+        Some(entry) => entry.created_at,
+        None => return Ok(None),
+    };
+
+    let window_elapsed = utils::current_timestamp().saturating_sub(oldest) >= config.max_wait_secs;
+    if entries.len() < config.max_batch_size && !window_elapsed {
+        return Ok(None);
+    }
+
+    seal_batch(store, ledger, &entries).await.map(Some)
+}
+
+/// 将同一账本的出站记录封装为一个批次并保存每个操作的包含证明
+pub async fn seal_batch(store: &dyn DidStore, ledger: &str, entries: &[OutboxEntry]) -> Result<i64, Error> {
+    let leaves = entries.iter()
+        .map(|entry| AnchorLeaf::from_entry(entry).map(|leaf| (entry.id, leaf)))
+        .collect::<Result<Vec<_>, _>>()?;
+    seal_leaves(store, ledger, leaves).await
+}
+
+/// 将同一账本上尚未封装的操作（出站记录ID和叶子）封装为一个批次，返回批次ID
+///
+/// 操作已被其他批次封装时返回`InvalidState`，不创建批次。
+pub async fn seal_leaves(store: &dyn DidStore, ledger: &str, entries: Vec<(i64, AnchorLeaf)>) -> Result<i64, Error> {
+    let (outbox_ids, leaves): (Vec<_>, Vec<_>) = entries.into_iter().unzip();
+    let leaf_hashes = leaves.iter()
+        .map(|leaf| leaf.canonical_bytes().map(|bytes| merkle::leaf_hash(&bytes)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let tree = MerkleTree::new(leaf_hashes);
+    let root = tree.root()
+        .map(utils::to_hex)
+        .ok_or_else(|| Error::InvalidInput("Cannot seal an empty batch".to_string()))?;
+
+    let proofs = outbox_ids.into_iter()
+        .zip(leaves)
+        .enumerate()
+        .map(|(index, (outbox_id, leaf))| {
+            let proof = tree.proof(index)
+                .ok_or_else(|| Error::InternalError(format!("Missing proof for leaf {}", index)))?;
+            Ok(NewAnchorProof {
+                outbox_id,
+                leaf,
+                leaf_index: index as u64,
+                proof,
+            })
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let batch_id = store.create_anchor_batch(ledger, &root, &outbox::new_idempotency_key(), &proofs).await?;
+    log::info!("已为账本{}封装锚定批次{}，包含{}个操作，根哈希: {}", ledger, batch_id, proofs.len(), root);
+
+    Ok(batch_id)
+}
+
+/// 提交所有到期的批次根哈希到区块链
+pub async fn submit_due_batches(store: &dyn DidStore, config: &OutboxConfig) -> Result<usize, Error> {
+    let batches = store.fetch_due_anchor_batches(utils::current_timestamp(), config.batch_size).await?;
+    let count = batches.len();
+
+    for batch in batches {
+        match blockchain::anchor_batch(&batch.ledger, &batch.root, batch.size, &batch.idempotency_key).await {
+            Ok(tx_hash) => {
+                log::info!("锚定批次{}已上链: {} ({})", batch.id, batch.root, tx_hash);
+                store.mark_anchor_batch_delivered(batch.id, &tx_hash).await?;
+            }
+            Err(e) => {
+                let attempts = batch.attempts + 1;
+                if attempts >= config.max_attempts {
+                    log::error!("锚定批次{}超过最大重试次数，进入死信状态: {}", batch.id, e);
+                    store.mark_anchor_batch_failed(batch.id, attempts, &e.to_string(), None).await?;
+                } else {
+                    let next_attempt_at = utils::current_timestamp() + config.backoff_delay(attempts);
+                    log::warn!("锚定批次{}提交失败（第{}次），将于{}重试: {}", batch.id, attempts, next_attempt_at, e);
+                    store.mark_anchor_batch_failed(batch.id, attempts, &e.to_string(), Some(next_attempt_at)).await?;
+                }
+            }
+        }
+    }
+
+    Ok(count)
+}
+
+/// 验证DID当前版本已被批量锚定
+pub async fn verify_inclusion(store: &dyn DidStore, record: &DidRecord) -> Result<InclusionStatus, Error> {
+    let proof = match store.get_anchor_proof(&record.did, record.version_id).await? {
+        Some(proof) => proof,
+        None => {
+            let pending = store.count_outbox_entries_for_did(&record.did, OutboxStatus::Pending).await? > 0;
+            return Ok(if pending { InclusionStatus::Pending { proof: None } } else { InclusionStatus::Missing });
+        }
+    };
+
+    let expected = AnchorLeaf::from_record(record)?;
+    if proof.leaf != expected {
+        return Ok(InclusionStatus::Mismatch {
+            proof,
+            reason: "Local DID document does not match the anchored operation".to_string(),
+        });
+    }
+
+    let root = utils::from_hex(&proof.root).map_err(Error::SerializationError)?;
+    if !merkle::verify_proof(&merkle::leaf_hash(&expected.canonical_bytes()?), &proof.proof, &root) {
+        return Ok(InclusionStatus::Mismatch {
+            proof,
+            reason: "Inclusion proof does not lead to the batch root".to_string(),
+        });
+    }
+
+    if proof.status != OutboxStatus::Delivered {
+        return Ok(InclusionStatus::Pending { proof: Some(proof) });
+    }
+
+    if blockchain::get_batch_anchor(&proof.ledger, &proof.root).await?.is_none() {
+        return Ok(InclusionStatus::Mismatch {
+            proof,
+            reason: "Batch root is not anchored on the ledger".to_string(),
+        });
+    }
+
+    Ok(InclusionStatus::Verified { proof })
+}
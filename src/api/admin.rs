@@ -0,0 +1,345 @@
+//! 管理相关的HTTP接口处理函数，全部要求`did:admin`权限范围
+
+use axum::body::Bytes;
+use axum::extract::{DefaultBodyLimit, State};
+use axum::http::{header, StatusCode};
+use axum::middleware;
+use axum::response::IntoResponse;
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use crate::api::{auth, ApiResponse, AppState};
+use crate::api::extract::{Json, Path, Query};
+use crate::backup::{self, ConflictPolicy, ImportReport};
+use crate::blockchain::{self, BlockchainConfig, LedgerCheckpoint, LedgerRoute};
+use crate::did::cache::{self, CacheStats};
+use crate::outbox::{OutboxEntry, OutboxStatus};
+use crate::oplog::{self, ChainReport, HeadAnchor};
+use crate::reconcile::{self, DriftReport};
+use crate::types::Error;
+use utoipa::{IntoParams, ToSchema};
+use utoipa_axum::router::OpenApiRouter;
+use utoipa_axum::routes;
+
+/// 导入接口允许的最大请求体（字节）
+const IMPORT_BODY_LIMIT: usize = 256 * 1024 * 1024;
+
+/// 管理路由，只允许拥有`did:admin`权限范围的主体访问
+pub fn routes() -> OpenApiRouter<Arc<AppState>> {
+    let (schemas, paths, import) = routes!(import_registry);
+
+    OpenApiRouter::new()
+        .routes(routes!(list_outbox))
+        .routes(routes!(retry_outbox_entry))
+        .routes(routes!(reconcile_ledger))
+        .routes(routes!(repair_ledger_drift))
+        .routes(routes!(list_ledgers))
+        .routes(routes!(verify_operation_log))
+        .routes(routes!(anchor_operation_log))
+        .routes(routes!(cache_stats, invalidate_cache))
+        .routes(routes!(export_registry))
+        .routes((schemas, paths, import.layer(DefaultBodyLimit::max(IMPORT_BODY_LIMIT))))
+        .route_layer(middleware::from_fn(auth::require_admin))
+}
+
+/// 出站队列查询参数
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct OutboxQuery {
+    /// 记录状态（pending、delivered、dead），默认为dead
+    pub status: Option<String>,
+}
+
+/// 列出出站队列记录处理函数
+#[utoipa::path(
+    get,
+    path = "/admin/outbox",
+    tag = "admin",
+    security(("bearer" = ["did:admin"]), ("api_key" = ["did:admin"])),
+    params(OutboxQuery),
+    responses((status = 200, description = "出站队列记录", body = ApiResponse<Vec<OutboxEntry>>)),
+)]
+pub async fn list_outbox(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<OutboxQuery>,
+) -> Result<(StatusCode, Json<ApiResponse<Vec<OutboxEntry>>>), Error> {
+    let status = match query.status.as_deref() {
+        Some(status) => status.parse::<OutboxStatus>()?,
+        None => OutboxStatus::Dead,
+    };
+
+    let entries = state.store.list_outbox_entries(status).await?;
+
+    Ok((StatusCode::OK, Json(ApiResponse {
+        success: true,
+        data: Some(entries),
+        error: None,
+    })))
+}
+
+/// 重新投递死信记录处理函数
+#[utoipa::path(
+    post,
+    path = "/admin/outbox/{id}/retry",
+    tag = "admin",
+    security(("bearer" = ["did:admin"]), ("api_key" = ["did:admin"])),
+    params(("id" = i64, Path, description = "出站记录ID")),
+    responses((status = 200, description = "重新放回队列的记录", body = ApiResponse<OutboxEntry>)),
+)]
+pub async fn retry_outbox_entry(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> Result<(StatusCode, Json<ApiResponse<OutboxEntry>>), Error> {
+    let entry = state.store.requeue_outbox_entry(id).await?;
+    log::info!("死信记录{}已重新放回出站队列: {}", id, entry.did);
+
+    Ok((StatusCode::OK, Json(ApiResponse {
+        success: true,
+        data: Some(entry),
+        error: None,
+    })))
+}
+
+/// 数据库与区块链对账处理函数，只输出漂移报告
+#[utoipa::path(
+    get,
+    path = "/admin/reconcile",
+    tag = "admin",
+    security(("bearer" = ["did:admin"]), ("api_key" = ["did:admin"])),
+    responses((status = 200, description = "漂移报告", body = ApiResponse<DriftReport>)),
+)]
+pub async fn reconcile_ledger(
+    State(state): State<Arc<AppState>>,
+) -> Result<(StatusCode, Json<ApiResponse<DriftReport>>), Error> {
+    let report = reconcile::reconcile(state.store.as_ref(), false).await?;
+
+    Ok((StatusCode::OK, Json(ApiResponse {
+        success: true,
+        data: Some(report),
+        error: None,
+    })))
+}
+
+/// 以区块链为准修复本地状态处理函数，返回修复前的漂移报告
+#[utoipa::path(
+    post,
+    path = "/admin/reconcile/repair",
+    tag = "admin",
+    security(("bearer" = ["did:admin"]), ("api_key" = ["did:admin"])),
+    responses((status = 200, description = "漂移报告和修复结果", body = ApiResponse<DriftReport>)),
+)]
+pub async fn repair_ledger_drift(
+    State(state): State<Arc<AppState>>,
+) -> Result<(StatusCode, Json<ApiResponse<DriftReport>>), Error> {
+    let report = reconcile::reconcile(state.store.as_ref(), true).await?;
+
+    Ok((StatusCode::OK, Json(ApiResponse {
+        success: true,
+        data: Some(report),
+        error: None,
+    })))
+}
+
+/// 账本状态
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LedgerStatus {
+    #[serde(flatten)]
+    pub config: BlockchainConfig,
+    /// 是否为默认账本
+    pub default: bool,
+    /// 账本检查点，尚未有操作上链时为空
+    pub checkpoint: Option<LedgerCheckpoint>,
+}
+
+/// 账本列表
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LedgersOverview {
+    pub ledgers: Vec<LedgerStatus>,
+    pub routes: Vec<LedgerRoute>,
+}
+
+/// 列出已配置的账本、路由规则及各账本检查点处理函数
+#[utoipa::path(
+    get,
+    path = "/admin/ledgers",
+    tag = "admin",
+    security(("bearer" = ["did:admin"]), ("api_key" = ["did:admin"])),
+    responses((status = 200, description = "账本、路由规则和检查点", body = ApiResponse<LedgersOverview>)),
+)]
+pub async fn list_ledgers(
+    State(state): State<Arc<AppState>>,
+) -> Result<(StatusCode, Json<ApiResponse<LedgersOverview>>), Error> {
+    let registry = blockchain::registry()?;
+    let mut checkpoints = state.store.list_ledger_checkpoints().await?;
+    let default = registry.default_ledger().name();
+
+    let ledgers = registry.ledgers().iter()
+        .map(|ledger| LedgerStatus {
+            config: ledger.config().clone(),
+            default: ledger.name() == default,
+            checkpoint: checkpoints.iter()
+                .position(|checkpoint| checkpoint.ledger == ledger.name())
+                .map(|index| checkpoints.swap_remove(index)),
+        })
+        .collect();
+
+    Ok((StatusCode::OK, Json(ApiResponse {
+        success: true,
+        data: Some(LedgersOverview {
+            ledgers,
+            routes: registry.routes().to_vec(),
+        }),
+        error: None,
+    })))
+}
+
+/// 校验操作日志哈希链处理函数
+#[utoipa::path(
+    get,
+    path = "/admin/oplog/verify",
+    tag = "admin",
+    security(("bearer" = ["did:admin"]), ("api_key" = ["did:admin"])),
+    responses((status = 200, description = "哈希链校验报告", body = ApiResponse<ChainReport>)),
+)]
+pub async fn verify_operation_log(
+    State(state): State<Arc<AppState>>,
+) -> Result<(StatusCode, Json<ApiResponse<ChainReport>>), Error> {
+    let report = oplog::verify_chain(state.store.as_ref()).await?;
+
+    Ok((StatusCode::OK, Json(ApiResponse {
+        success: true,
+        data: Some(report),
+        error: None,
+    })))
+}
+
+/// 将操作日志链头锚定到默认账本处理函数
+#[utoipa::path(
+    post,
+    path = "/admin/oplog/anchor",
+    tag = "admin",
+    security(("bearer" = ["did:admin"]), ("api_key" = ["did:admin"])),
+    responses((status = 200, description = "已锚定的链头", body = ApiResponse<HeadAnchor>)),
+)]
+pub async fn anchor_operation_log(
+    State(state): State<Arc<AppState>>,
+) -> Result<(StatusCode, Json<ApiResponse<HeadAnchor>>), Error> {
+    let anchor = oplog::anchor_head(state.store.as_ref()).await?;
+
+    Ok((StatusCode::OK, Json(ApiResponse {
+        success: true,
+        data: Some(anchor),
+        error: None,
+    })))
+}
+
+/// 解析缓存统计处理函数
+#[utoipa::path(
+    get,
+    path = "/admin/cache",
+    tag = "admin",
+    security(("bearer" = ["did:admin"]), ("api_key" = ["did:admin"])),
+    responses((status = 200, description = "缓存统计", body = ApiResponse<CacheStats>)),
+)]
+pub async fn cache_stats() -> Result<(StatusCode, Json<ApiResponse<CacheStats>>), Error> {
+    let cache = cache::global()
+        .ok_or_else(|| Error::InvalidState("Resolver cache is not enabled".to_string()))?;
+
+    Ok((StatusCode::OK, Json(ApiResponse {
+        success: true,
+        data: Some(cache.stats()),
+        error: None,
+    })))
+}
+
+/// 缓存失效参数
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct InvalidateQuery {
+    /// 只使指定DID的缓存失效，为空时清空缓存
+    pub did: Option<String>,
+}
+
+/// 使解析缓存失效处理函数
+#[utoipa::path(
+    delete,
+    path = "/admin/cache",
+    tag = "admin",
+    security(("bearer" = ["did:admin"]), ("api_key" = ["did:admin"])),
+    params(InvalidateQuery),
+    responses((status = 200, description = "失效后的缓存统计", body = ApiResponse<CacheStats>)),
+)]
+pub async fn invalidate_cache(
+    Query(query): Query<InvalidateQuery>,
+) -> Result<(StatusCode, Json<ApiResponse<CacheStats>>), Error> {
+    let cache = cache::global()
+        .ok_or_else(|| Error::InvalidState("Resolver cache is not enabled".to_string()))?;
+    match &query.did {
+        Some(did) => cache.invalidate(did),
+        None => cache.clear(),
+    }
+
+    Ok((StatusCode::OK, Json(ApiResponse {
+        success: true,
+        data: Some(cache.stats()),
+        error: None,
+    })))
+}
+
+/// 导出注册表处理函数，返回JSON Lines归档
+#[utoipa::path(
+    get,
+    path = "/admin/export",
+    tag = "admin",
+    security(("bearer" = ["did:admin"]), ("api_key" = ["did:admin"])),
+    responses((status = 200, description = "JSON Lines归档", body = String, content_type = "application/x-ndjson")),
+)]
+pub async fn export_registry(
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, Error> {
+    let mut archive = Vec::new();
+    let manifest = backup::export(state.store.as_ref(), &mut archive).await?;
+    let filename = format!("attachment; filename=\"did-export-{}.jsonl\"", manifest.exported_at);
+
+    Ok((
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "application/x-ndjson".to_string()), (header::CONTENT_DISPOSITION, filename)],
+        archive,
+    ))
+}
+
+/// 导入查询参数
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ImportQuery {
+    /// DID已存在时的处理方式（skip、overwrite、fail），默认为fail
+    pub on_conflict: Option<String>,
+}
+
+/// 导入注册表处理函数，请求体为导出的归档
+#[utoipa::path(
+    post,
+    path = "/admin/import",
+    tag = "admin",
+    security(("bearer" = ["did:admin"]), ("api_key" = ["did:admin"])),
+    params(ImportQuery),
+    request_body(content = String, description = "导出的JSON Lines归档", content_type = "application/x-ndjson"),
+    responses((status = 200, description = "导入报告", body = ApiResponse<ImportReport>)),
+)]
+pub async fn import_registry(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ImportQuery>,
+    body: Bytes,
+) -> Result<(StatusCode, Json<ApiResponse<ImportReport>>), Error> {
+    let policy = match query.on_conflict.as_deref() {
+        Some(policy) => policy.parse::<ConflictPolicy>()?,
+        None => ConflictPolicy::Fail,
+    };
+
+    let report = backup::import(state.store.as_ref(), &mut body.as_ref(), policy).await?;
+
+    Ok((StatusCode::OK, Json(ApiResponse {
+        success: true,
+        data: Some(report),
+        error: None,
+    })))
+}
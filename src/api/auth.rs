@@ -0,0 +1,204 @@
+//! 认证中间件 - 验证请求凭据并将认证主体交给处理函数
+//!
+//! 凭据可以放在`Authorization: Bearer <凭据>`或`X-API-Key`头中，以`didk_`开头的是API密钥，其他视为令牌。
+//! 凭据无效时直接返回401；未提供凭据的请求以匿名主体继续处理，公开接口不提取`Principal`，
+//! 其他接口提取`Principal`时拒绝匿名主体，再由处理函数检查所需的权限范围。
+//!
+//! `/auth/challenge`和`/auth/verify`实现DID Auth登录，为证明了DID控制权的客户端签发会话令牌。
+
+use std::sync::Arc;
+use axum::async_trait;
+use axum::extract::{FromRequestParts, Request, State};
+use axum::http::request::Parts;
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use utoipa_axum::router::OpenApiRouter;
+use utoipa_axum::routes;
+use crate::api::extract::Json;
+use crate::api::AppState;
+use crate::auth::did_auth::{self, Challenge};
+use crate::auth::{Credential, Principal, Scope};
+use crate::types::Error;
+use crate::utils;
+
+/// API密钥头
+pub const API_KEY_HEADER: &str = "x-api-key";
+/// 401响应的认证质询
+const CHALLENGE: &str = "Bearer realm=\"did-system\"";
+
+/// 请求质询
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ChallengeRequest {
+    pub did: String,
+}
+
+/// 质询
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ChallengeResponse {
+    pub did: String,
+    /// 待签名的质询消息（JSON字符串，包含`type`、`audience`、`did`、`nonce`、`issued_at`和`expires_at`），
+    /// 签名内容为其UTF-8字节
+    pub message: String,
+    /// 过期时间（Unix秒）
+    pub expires_at: u64,
+}
+
+impl From<Challenge> for ChallengeResponse {
+    fn from(challenge: Challenge) -> Self {
+        Self {
+            did: challenge.did,
+            message: challenge.message,
+            expires_at: challenge.expires_at,
+        }
+    }
+}
+
+/// 提交质询签名
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct VerifyRequest {
+    pub did: String,
+    /// `/auth/challenge`返回的质询消息，与签名的内容逐字节相同
+    pub message: String,
+    /// 签名使用的认证方法ID，如`did:web:<公钥>#keys-1`
+    pub verification_method: String,
+    /// Base58编码的Ed25519签名
+    pub signature: String,
+}
+
+/// 会话令牌（字段与RFC 6749的令牌响应相同）
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SessionResponse {
+    pub access_token: String,
+    pub token_type: String,
+    /// 有效期（秒）
+    pub expires_in: u64,
+    pub did: String,
+}
+
+/// DID Auth路由，不要求凭据
+pub fn routes() -> OpenApiRouter<Arc<AppState>> {
+    OpenApiRouter::new()
+        .routes(routes!(challenge))
+        .routes(routes!(verify))
+}
+
+/// 为DID签发一次性质询，与其他写请求一同限流
+#[utoipa::path(
+    post,
+    path = "/auth/challenge",
+    tag = "auth",
+    request_body = ChallengeRequest,
+    responses((status = 200, description = "质询，有效期5分钟", body = ChallengeResponse)),
+)]
+pub async fn challenge(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<ChallengeRequest>,
+) -> Result<Json<ChallengeResponse>, Error> {
+    if !state.auth.issues_tokens() {
+        return Err(Error::InvalidState("DID Auth requires auth.issuer_key_file".to_string()));
+    }
+
+    let challenge = did_auth::issue_challenge(state.store.as_ref(), state.auth.issuer(), &request.did).await?;
+    Ok(Json(challenge.into()))
+}
+
+/// 校验质询签名并签发只能操作该DID的会话令牌
+#[utoipa::path(
+    post,
+    path = "/auth/verify",
+    tag = "auth",
+    request_body = VerifyRequest,
+    responses((status = 200, description = "会话令牌", body = SessionResponse)),
+)]
+pub async fn verify(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<VerifyRequest>,
+) -> Result<Json<SessionResponse>, Error> {
+    let signature = utils::decode_base58(&request.signature)
+        .map_err(|e| Error::validation("signature", format!("Invalid Base58 encoding: {}", e)))?;
+    did_auth::verify_response(
+        state.store.as_ref(),
+        state.auth.issuer(),
+        &request.did,
+        &request.message,
+        &request.verification_method,
+        &signature,
+    ).await?;
+
+    let (access_token, expires_in) = state.auth.issue_session(&request.did)?;
+    log::info!("DID {} 通过DID Auth登录", request.did);
+
+    Ok(Json(SessionResponse {
+        access_token,
+        token_type: "Bearer".to_string(),
+        expires_in,
+        did: request.did,
+    }))
+}
+
+/// 认证中间件：验证凭据并将`Principal`放入请求扩展
+pub async fn authenticate(State(state): State<Arc<AppState>>, mut request: Request, next: Next) -> Response {
+    let principal = match credential(request.headers()) {
+        Ok(credential) => state.auth.authenticate(state.store.as_ref(), credential).await,
+        Err(e) => Err(e),
+    };
+
+    let mut response = match principal {
+        Ok(principal) => {
+            request.extensions_mut().insert(principal);
+            next.run(request).await
+        }
+        Err(e) => e.into_response(),
+    };
+
+    if response.status() == StatusCode::UNAUTHORIZED {
+        response.headers_mut().insert(header::WWW_AUTHENTICATE, HeaderValue::from_static(CHALLENGE));
+    }
+    response
+}
+
+/// 管理接口的权限检查，要求`did:admin`权限范围
+pub async fn require_admin(principal: Principal, request: Request, next: Next) -> Result<Response, Error> {
+    principal.require(Scope::DidAdmin)?;
+    Ok(next.run(request).await)
+}
+
+/// 从请求头中读取凭据，两个头同时存在时优先使用`Authorization`
+fn credential(headers: &HeaderMap) -> Result<Option<&str>, Error> {
+    if let Some(value) = headers.get(header::AUTHORIZATION) {
+        let value = value.to_str()
+            .map_err(|_| Error::Unauthorized("Malformed Authorization header".to_string()))?;
+        let (scheme, credential) = value.split_once(' ')
+            .ok_or_else(|| Error::Unauthorized("Malformed Authorization header".to_string()))?;
+        if !scheme.eq_ignore_ascii_case("bearer") {
+            return Err(Error::Unauthorized(format!("Unsupported authorization scheme: {}", scheme)));
+        }
+        return Ok(Some(credential.trim()));
+    }
+
+    headers.get(API_KEY_HEADER)
+        .map(|value| value.to_str()
+            .map(str::trim)
+            .map_err(|_| Error::Unauthorized("Malformed X-API-Key header".to_string())))
+        .transpose()
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Principal {
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let principal = parts.extensions.get::<Principal>()
+            .cloned()
+            .ok_or_else(|| Error::InternalError("Authentication middleware is not installed".to_string()))?;
+
+        // 在解析请求体之前拒绝匿名请求
+        if principal.credential == Credential::Anonymous {
+            return Err(Error::Unauthorized("Authentication required".to_string()));
+        }
+        Ok(principal)
+    }
+}
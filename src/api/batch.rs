@@ -0,0 +1,245 @@
+//! 批量操作接口 - 在一个请求中提交多个DID的签名操作
+//!
+//! 挂载在v2下，与v2的单个操作接口使用相同的签名操作和错误格式。
+//! `Accept: application/x-ndjson`时每个操作完成后立即输出一行结果，最后一行是汇总；
+//! 否则所有操作完成后返回一个JSON对象。客户端断开连接不会中断批次的执行。
+//! 每个操作计入一次每日写操作配额，执行前按操作数占用，批次结束后退还未写入的操作。
+
+use std::sync::Arc;
+use axum::body::Body;
+use axum::extract::State;
+use axum::Extension;
+use axum::http::{header, HeaderMap, HeaderValue};
+use axum::response::{IntoResponse, Response};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use utoipa::ToSchema;
+use crate::api::error::{current_request_id, Problem};
+use crate::api::extract::Json;
+use crate::api::idempotency::IdempotencyKeyHeader;
+use crate::api::ratelimit::WriteQuota;
+use crate::api::v2::ResolutionResult;
+use crate::api::{negotiate, AppState};
+use crate::auth::{Principal, Scope};
+use crate::did::batch::{self, BatchOperation, BatchOutcome};
+use crate::types::Error;
+
+/// 逐行输出结果的媒体类型
+pub const NDJSON_MEDIA_TYPE: &str = "application/x-ndjson";
+
+/// 批量操作请求
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct BatchRequest {
+    /// 按顺序执行的签名操作，最多1000个
+    pub operations: Vec<BatchOperation>,
+    /// 是否全部成功或全部不写入
+    #[serde(default)]
+    pub atomic: bool,
+}
+
+/// 单个操作的结果状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum BatchItemStatus {
+    /// 已写入
+    Applied,
+    /// 操作失败
+    Failed,
+    /// 原子模式下因其他操作失败而未写入
+    Aborted,
+}
+
+/// 单个操作的结果
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchItemResult {
+    /// 操作在请求中的序号，从0开始
+    pub index: usize,
+    pub did: String,
+    pub status: BatchItemStatus,
+    /// 变更后的解析结果
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<ResolutionResult>,
+    /// 失败原因
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<Problem>,
+}
+
+impl BatchItemResult {
+    fn new(index: usize, did: &str, outcome: BatchOutcome, request_id: &Option<String>) -> Self {
+        let (status, result, error) = match outcome {
+            BatchOutcome::Applied(record) => match ResolutionResult::from_record(&record) {
+                Ok(result) => (BatchItemStatus::Applied, Some(result), None),
+                Err(e) => (BatchItemStatus::Applied, None, Some(problem(e, request_id))),
+            },
+            BatchOutcome::Failed(e) => (BatchItemStatus::Failed, None, Some(problem(e, request_id))),
+            BatchOutcome::Aborted => (BatchItemStatus::Aborted, None, None),
+        };
+        Self { index, did: did.to_string(), status, result, error }
+    }
+}
+
+/// 批次汇总
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchTotals {
+    pub atomic: bool,
+    pub applied: usize,
+    pub failed: usize,
+    pub aborted: usize,
+    /// 为批次中的操作封装的锚定批次ID
+    pub anchor_batches: Vec<i64>,
+    /// 批次整体失败的原因，只出现在逐行输出中
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<Problem>,
+}
+
+impl BatchTotals {
+    fn new(atomic: bool, summary: batch::BatchSummary) -> Self {
+        Self {
+            atomic,
+            applied: summary.applied,
+            failed: summary.failed,
+            aborted: summary.aborted,
+            anchor_batches: summary.anchor_batches,
+            error: None,
+        }
+    }
+}
+
+/// 批量操作的响应
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BatchResponse {
+    pub summary: BatchTotals,
+    pub items: Vec<BatchItemResult>,
+}
+
+/// 逐行输出中的一行
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum BatchEvent {
+    /// 一个操作的结果
+    Item(BatchItemResult),
+    /// 最后一行：批次汇总
+    Summary(BatchTotals),
+}
+
+/// 在后台任务中构造的问题详情没有请求上下文，请求ID在处理器中取得
+fn problem(error: Error, request_id: &Option<String>) -> Problem {
+    let mut problem = Problem::from(error);
+    problem.request_id = request_id.clone();
+    problem
+}
+
+/// 批量提交签名操作
+///
+/// 每个操作的格式与`POST /v2/dids/{did}/operations`的请求体相同，另加`did`字段；
+/// 创建操作要求`did:create`权限范围，其他操作要求`did:update`权限范围。
+/// 默认逐个写入，失败的操作不影响其他操作；`atomic`为`true`时所有操作在同一个事务中写入，
+/// 任何一个操作失败时都不写入，其他操作的状态为`aborted`。
+/// 写入批量锚定模式账本的操作立即封装为一个锚定批次。
+/// `Accept: application/x-ndjson`时逐行输出每个操作的结果，最后一行是汇总。
+/// 每个操作计入一次每日写操作配额，剩余配额不足以执行整个批次时返回429。
+#[utoipa::path(
+    post,
+    path = "/batch",
+    operation_id = "submit_did_batch",
+    tag = "did",
+    security(("bearer" = ["did:create", "did:update"]), ("api_key" = ["did:create", "did:update"])),
+    params(IdempotencyKeyHeader),
+    request_body = BatchRequest,
+    responses(
+        (
+            status = 200,
+            description = "每个操作的结果和汇总",
+            content(
+                (BatchResponse = "application/json"),
+                (BatchEvent = "application/x-ndjson"),
+            ),
+        ),
+        (status = 428, description = "要求前提条件时，非创建操作缺少`previousVersionId`", body = Problem),
+        (status = 429, description = "剩余的每日写操作配额不足以执行整个批次", body = Problem),
+    ),
+)]
+pub async fn submit_batch(
+    State(state): State<Arc<AppState>>,
+    principal: Principal,
+    quota: Option<Extension<WriteQuota>>,
+    headers: HeaderMap,
+    Json(request): Json<BatchRequest>,
+) -> Result<Response, Error> {
+    batch::validate(&request.operations)?;
+    for (index, item) in request.operations.iter().enumerate() {
+        let scope = if item.is_create() { Scope::DidCreate } else { Scope::DidUpdate };
+        principal.require_did(scope, &item.did)?;
+        if state.require_preconditions && !item.is_create() && item.change.previous_version_id.is_none() {
+            return Err(Error::PreconditionRequired(format!(
+                "Operation {} must include previousVersionId to modify a DID", index
+            )));
+        }
+    }
+
+    let quota = quota.map(|Extension(quota)| quota);
+    let count = request.operations.len() as u64;
+    if let Some(quota) = &quota {
+        quota.reserve(state.store.as_ref(), count).await?;
+    }
+
+    let request_id = current_request_id();
+    let ranges = negotiate::accepted_media_ranges(&headers);
+    if negotiate::select(&ranges, &["application/json", NDJSON_MEDIA_TYPE]) == Some(NDJSON_MEDIA_TYPE) {
+        return Ok(stream(state, request, quota, request_id));
+    }
+
+    let mut items = Vec::with_capacity(request.operations.len());
+    let result = batch::execute(state.store.as_ref(), request.operations, request.atomic, |index, did, outcome| {
+        items.push(BatchItemResult::new(index, did, outcome, &request_id));
+    }).await;
+    if let Some(quota) = &quota {
+        let applied = result.as_ref().map_or(0, |summary| summary.applied as u64);
+        quota.release(state.store.as_ref(), count - applied).await;
+    }
+    let summary = result?;
+
+    Ok(Json(BatchResponse {
+        summary: BatchTotals::new(request.atomic, summary),
+        items,
+    }).into_response())
+}
+
+/// 在后台任务中执行批次，逐行输出结果
+fn stream(state: Arc<AppState>, request: BatchRequest, quota: Option<WriteQuota>, request_id: Option<String>) -> Response {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        let (atomic, count) = (request.atomic, request.operations.len());
+        let result = batch::execute(state.store.as_ref(), request.operations, atomic, |index, did, outcome| {
+            // 客户端断开后继续执行，丢弃结果
+            let _ = sender.send(BatchEvent::Item(BatchItemResult::new(index, did, outcome, &request_id)));
+        }).await;
+        let totals = match result {
+            Ok(summary) => BatchTotals::new(atomic, summary),
+            // 只有原子模式的存储写入会失败，所有操作都未写入
+            Err(e) => {
+                log::error!("批量操作失败: {}", e);
+                BatchTotals {
+                    aborted: count,
+                    error: Some(problem(e, &request_id)),
+                    ..BatchTotals::new(atomic, batch::BatchSummary::default())
+                }
+            }
+        };
+        if let Some(quota) = &quota {
+            quota.release(state.store.as_ref(), (count - totals.applied) as u64).await;
+        }
+        let _ = sender.send(BatchEvent::Summary(totals));
+    });
+
+    let lines = futures_util::stream::unfold(receiver, |mut receiver| async move {
+        let event = receiver.recv().await?;
+        let line = serde_json::to_string(&event).map(|line| line + "\n");
+        Some((line, receiver))
+    });
+    let mut response = Body::from_stream(lines).into_response();
+    response.headers_mut().insert(header::CONTENT_TYPE, HeaderValue::from_static(NDJSON_MEDIA_TYPE));
+    response
+}
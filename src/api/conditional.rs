@@ -0,0 +1,70 @@
+//! 条件请求模块 - 以DID文档的版本号作为实体标签，处理`If-Match`和`If-None-Match`
+//!
+//! 实体标签为强标签`"<versionId>"`，同一版本的各种表示共用一个标签，响应带有`Vary: Accept`。
+//! `If-Match`按强比较匹配，`If-None-Match`按弱比较匹配（RFC 9110）。
+
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use utoipa::IntoParams;
+use crate::types::Error;
+
+/// `If-Match`请求头，只用于OpenAPI文档
+#[derive(Debug, IntoParams)]
+#[into_params(parameter_in = Header)]
+pub struct IfMatchHeader {
+    /// 解析时得到的`ETag`，文档已被修改到其他版本时返回412
+    #[param(rename = "If-Match")]
+    pub if_match: Option<String>,
+}
+
+/// 版本号对应的实体标签
+pub fn etag(version_id: u64) -> HeaderValue {
+    HeaderValue::from_str(&format!("\"{}\"", version_id)).expect("entity tag is a valid header value")
+}
+
+/// 解析`If-Match`头，返回客户端所基于的版本号；`*`匹配任意版本，返回`None`
+///
+/// `required`为真时缺少`If-Match`返回428；弱标签和不是版本号的标签不可能匹配，返回412。
+pub fn if_match(headers: &HeaderMap, required: bool) -> Result<Option<u64>, Error> {
+    let tags = entity_tags(headers, header::IF_MATCH);
+    let tag = match tags.as_slice() {
+        [] if required => {
+            return Err(Error::PreconditionRequired("If-Match with the current ETag is required to modify a DID".to_string()));
+        }
+        [] => return Ok(None),
+        [tag] if tag == "*" => return Ok(None),
+        [tag] => tag,
+        _ => return Err(Error::InvalidInput("If-Match must contain a single entity tag".to_string())),
+    };
+
+    tag.strip_prefix('"')
+        .and_then(|tag| tag.strip_suffix('"'))
+        .and_then(|version| version.parse().ok())
+        .map(Some)
+        .ok_or_else(|| Error::PreconditionFailed(format!("Entity tag {} does not match any version", tag)))
+}
+
+/// `If-None-Match`是否与当前版本匹配，匹配时返回304而不是文档
+pub fn is_not_modified(headers: &HeaderMap, version_id: u64) -> bool {
+    let current = format!("\"{}\"", version_id);
+    entity_tags(headers, header::IF_NONE_MATCH).iter()
+        .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == current)
+}
+
+/// 304响应，不带响应体
+pub fn not_modified(version_id: u64) -> Response {
+    (
+        StatusCode::NOT_MODIFIED,
+        [(header::ETAG, etag(version_id)), (header::VARY, HeaderValue::from_static("accept"))],
+    ).into_response()
+}
+
+/// 请求头中以逗号分隔的全部实体标签
+fn entity_tags(headers: &HeaderMap, name: header::HeaderName) -> Vec<String> {
+    headers.get_all(name).iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|tag| tag.trim().to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect()
+}
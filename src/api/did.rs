@@ -1,227 +1,621 @@
 //! DID相关的HTTP接口处理函数
 
-use axum::extract::{Path, Json};
-use axum::http::StatusCode;
+use axum::extract::State;
+use std::fmt;
+use std::sync::Arc;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
 use ed25519_dalek::SigningKey;
-use serde::Deserialize;
-use crate::did::{self, DIDDocument, PublicKeyInfo};
+use serde::{Deserialize, Serialize};
+use crate::did::{self, DIDDocument, DidPage, Service};
+use crate::did::representation;
 use crate::types::Error;
-use crate::api::{ApiResponse, ApiError};
-use crate::db;
-use crate::utils;
+use crate::anchoring::InclusionStatus;
+use crate::auth::{Principal, Scope};
+use crate::api::conditional::{self, IfMatchHeader};
+use crate::api::idempotency::IdempotencyKeyHeader;
+use crate::api::error::Problem;
+use crate::api::{negotiate, ApiResponse, AppState};
+use crate::api::extract::{Json, Path, Query};
+use crate::db::{DidQuery, DidRecord, DidStatus, DidStore};
+use crate::oplog::OperationLogEntry;
+use utoipa::{IntoParams, ToSchema};
+
+/// Base58编码的Ed25519私钥
+// 不实现`Display`，`Debug`输出不包含密钥，私钥不会被写入日志
+#[derive(Deserialize, ToSchema)]
+#[serde(transparent)]
+#[schema(value_type = String)]
+pub struct EncodedSigningKey(String);
+
+impl fmt::Debug for EncodedSigningKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EncodedSigningKey(<redacted>)")
+    }
+}
 
 /// 创建DID请求
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct CreateDIDRequest {
     /// 签名密钥（Base58编码）
-    pub signing_key: String,
+    pub signing_key: EncodedSigningKey,
+    /// 网络段（如`testnet`），生成`did:web:<network>:<id>`形式的DID；为空时使用默认账本
+    #[serde(default)]
+    pub network: Option<String>,
 }
 
 /// 更新DID请求
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct UpdateDIDRequest {
     /// 签名密钥（Base58编码）
-    pub signing_key: String,
+    pub signing_key: EncodedSigningKey,
     /// 更新后的DID文档
     pub document: DIDDocument,
 }
 
 /// 停用DID请求
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct DeactivateDIDRequest {
     /// 签名密钥（Base58编码）
-    pub signing_key: String,
+    pub signing_key: EncodedSigningKey,
+}
+
+/// 轮换公钥请求
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RotateKeyRequest {
+    /// 签名密钥（Base58编码）
+    pub signing_key: EncodedSigningKey,
+    /// 要轮换的验证方法ID，为空时轮换签名密钥对应的验证方法
+    #[serde(default)]
+    pub key_id: Option<String>,
+    /// 新公钥（Base58编码）
+    pub new_public_key: String,
+}
+
+/// 添加服务端点请求
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct AddServiceRequest {
+    /// 签名密钥（Base58编码）
+    pub signing_key: EncodedSigningKey,
+    pub service: Service,
+}
+
+/// 删除服务端点请求
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RemoveServiceRequest {
+    /// 签名密钥（Base58编码）
+    pub signing_key: EncodedSigningKey,
 }
 
 /// 创建DID处理函数
+#[utoipa::path(
+    post,
+    path = "/did",
+    tag = "did",
+    security(("bearer" = ["did:create"]), ("api_key" = ["did:create"])),
+    params(IdempotencyKeyHeader),
+    request_body = CreateDIDRequest,
+    responses((status = 201, description = "DID已创建", body = ApiResponse<DIDDocument>)),
+)]
 pub async fn create_did(
+    State(state): State<Arc<AppState>>,
+    principal: Principal,
     Json(request): Json<CreateDIDRequest>,
-) -> Result<(StatusCode, Json<ApiResponse<DIDDocument>>), (StatusCode, Json<ApiResponse<()>>)> {
-    match process_create_did(request).await {
-        Ok(document) => Ok((StatusCode::CREATED, Json(ApiResponse {
-            success: true,
-            data: Some(document),
-            error: None,
-        }))),
-        Err(e) => {
-            let api_error: ApiError = e.into();
-            Err((StatusCode::from_u16(api_error.code).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
-                Json(ApiResponse {
-                    success: false,
-                    data: None,
-                    error: Some(api_error),
-                })))
-        }
-    }
+) -> Result<(StatusCode, Json<ApiResponse<DIDDocument>>), Error> {
+    principal.require(Scope::DidCreate)?;
+    let document = process_create_did(state.store.as_ref(), request).await?;
+
+    Ok((StatusCode::CREATED, Json(ApiResponse {
+        success: true,
+        data: Some(document),
+        error: None,
+    })))
 }
 
-async fn process_create_did(request: CreateDIDRequest) -> Result<DIDDocument, Error> {
+async fn process_create_did(store: &dyn DidStore, request: CreateDIDRequest) -> Result<DIDDocument, Error> {
     log::info!("开始处理创建DID请求");
-    log::debug!("收到的签名密钥: {}", request.signing_key);
-    
-    // Base58解码签名密钥
-    let key_bytes = match bs58::decode(&request.signing_key).into_vec() {
-        Ok(bytes) => {
-            log::debug!("Base58解码成功，密钥字节长度: {}", bytes.len());
-            if bytes.len() != 32 {
-                log::error!("私钥长度错误：期望32字节，实际{}字节", bytes.len());
-                return Err(Error::InvalidInput(
-                    format!("Ed25519私钥长度必须为32字节，实际为{}字节", bytes.len())
-                ));
-            }
-            bytes
-        },
-        Err(e) => {
-            log::error!("Base58解码失败: {}", e);
-            return Err(Error::InvalidInput(format!("无效的Base58编码: {}", e)));
-        }
-    };
-
-    // 创建签名密钥
-    let signing_key = {
-        let key_array: [u8; 32] = key_bytes.try_into().map_err(|_| {
-            log::error!("无法将字节转换为固定长度数组");
-            Error::InvalidInput("无法处理私钥数据".to_string())
-        })?;
-        
-        SigningKey::from_bytes(&key_array)
-    };
-
-    // 获取验证密钥（公钥）
-    let verifying_key = signing_key.verifying_key();
-    let public_key_bytes = verifying_key.to_bytes();
-    let public_key_base58 = bs58::encode(&public_key_bytes).into_string();
-
-    // 生成DID
-    let did = format!("did:web:{}", public_key_base58);
-    log::debug!("生成的DID: {}", did);
-
-    // 创建DID文档
-    let document = DIDDocument {
-        id: did.clone(),
-        public_keys: vec![PublicKeyInfo {
-            id: format!("{}#keys-1", did),
-            type_: "Ed25519VerificationKey2020".to_string(),
-            controller: did.clone(),
-            public_key_base58: public_key_base58.clone(),
-        }],
-        authentication: vec![format!("{}#keys-1", did)],
-        services: vec![],
-        created: utils::current_timestamp(),
-        updated: utils::current_timestamp(),
-    };
 
-    // 存储DID文档
-    db::store_did_document(&did, &document, false)?;
+    let signing_key = decode_signing_key(&request.signing_key)?;
+    let document = did::create_did(store, &signing_key, request.network.as_deref()).await?;
 
-    log::info!("DID创建成功: {}", did);
+    log::info!("DID创建成功: {}", document.id);
     Ok(document)
 }
 
-/// 解析DID处理函数
+/// 解析DID查询参数
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ResolveQuery {
+    /// DID文档表示的媒体类型（如`application/did+ld+json`），优先于`Accept`头
+    pub accept: Option<String>,
+}
+
+/// 解析DID处理函数，按`Accept`头或`accept`参数返回`ApiResponse`信封或DID文档表示
+#[utoipa::path(
+    get,
+    path = "/did/{did}",
+    tag = "did",
+    params(
+        ("did" = String, Path, description = "DID"),
+        ResolveQuery,
+        ("If-None-Match" = Option<String>, Header, description = "上次响应的`ETag`，版本未变化时返回304"),
+    ),
+    responses(
+        (
+            status = 200,
+            description = "DID文档，按内容协商返回`ApiResponse`信封或DID Core表示",
+            headers(("ETag" = String, description = "文档版本号")),
+            content(
+                (ApiResponse<DIDDocument> = "application/json"),
+                (serde_json::Value = "application/did+json"),
+                (serde_json::Value = "application/did+ld+json"),
+                (serde_json::Value = "application/did+cbor"),
+            ),
+        ),
+        (status = 304, description = "文档版本与`If-None-Match`一致"),
+    ),
+)]
 pub async fn resolve_did(
+    State(state): State<Arc<AppState>>,
     Path(did): Path<String>,
-) -> Result<(StatusCode, Json<ApiResponse<DIDDocument>>), (StatusCode, Json<ApiResponse<()>>)> {
-    match did::resolve_did(&did).await {
-        Ok(document) => Ok((StatusCode::OK, Json(ApiResponse {
+    Query(query): Query<ResolveQuery>,
+    headers: HeaderMap,
+) -> Result<Response, Error> {
+    let representation = negotiate::document_representation(query.accept.as_deref(), &headers)?;
+    let record = did::cache::resolve(&state.store, &did).await?;
+    if conditional::is_not_modified(&headers, record.version_id) {
+        return Ok(conditional::not_modified(record.version_id));
+    }
+    let etag = conditional::etag(record.version_id);
+
+    let Some(representation) = representation else {
+        return Ok((StatusCode::OK, [(header::VARY, "accept")], [(header::ETAG, etag)], Json(ApiResponse {
             success: true,
-            data: Some(document),
+            data: Some(record.document),
             error: None,
-        }))),
-        Err(e) => {
-            let api_error: ApiError = e.into();
-            Err((StatusCode::from_u16(api_error.code).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
-                Json(ApiResponse {
-                    success: false,
-                    data: None,
-                    error: Some(api_error),
-                })))
-        }
-    }
+        })).into_response());
+    };
+
+    Ok((
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, representation.media_type()), (header::VARY, "accept")],
+        [(header::ETAG, etag)],
+        representation::produce(&record.document, representation)?,
+    ).into_response())
 }
 
 /// 更新DID处理函数
+#[utoipa::path(
+    put,
+    path = "/did/{did}",
+    tag = "did",
+    security(("bearer" = ["did:update"]), ("api_key" = ["did:update"])),
+    params(("did" = String, Path, description = "DID"), IfMatchHeader, IdempotencyKeyHeader),
+    request_body = UpdateDIDRequest,
+    responses(
+        (status = 200, description = "更新后的DID文档", body = ApiResponse<DIDDocument>, headers(("ETag" = String, description = "新的文档版本号"))),
+        (status = 412, description = "文档已被修改，版本与`If-Match`不一致", body = Problem),
+    ),
+)]
 pub async fn update_did(
+    State(state): State<Arc<AppState>>,
+    principal: Principal,
     Path(did): Path<String>,
+    headers: HeaderMap,
     Json(request): Json<UpdateDIDRequest>,
-) -> Result<(StatusCode, Json<ApiResponse<DIDDocument>>), (StatusCode, Json<ApiResponse<()>>)> {
-    match process_update_did(did, request).await {
-        Ok(document) => Ok((StatusCode::OK, Json(ApiResponse {
-            success: true,
-            data: Some(document),
-            error: None,
-        }))),
-        Err(e) => {
-            let api_error: ApiError = e.into();
-            Err((StatusCode::from_u16(api_error.code).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
-                Json(ApiResponse {
-                    success: false,
-                    data: None,
-                    error: Some(api_error),
-                })))
-        }
-    }
-}
-
-async fn process_update_did(did: String, request: UpdateDIDRequest) -> Result<DIDDocument, Error> {
-    // 解码签名密钥
-    let key_bytes = bs58::decode(&request.signing_key)
-        .into_vec()
-        .map_err(|e| Error::InvalidInput(format!("Invalid signing key encoding: {}", e)))?;
+) -> Result<Response, Error> {
+    principal.require_did(Scope::DidUpdate, &did)?;
+    let expected_version = conditional::if_match(&headers, state.require_preconditions)?;
+    let record = process_update_did(state.store.as_ref(), did, request, expected_version).await?;
 
-    if key_bytes.len() != 32 {
-        return Err(Error::InvalidInput(format!("Ed25519私钥长度必须为32字节，实际为{}字节", key_bytes.len())));
-    }
+    Ok(changed(StatusCode::OK, record))
+}
 
-    let key_array: [u8; 32] = key_bytes.try_into().map_err(|_| {
-        Error::InvalidInput("无法处理私钥数据".to_string())
-    })?;
-    
-    let signing_key = SigningKey::from_bytes(&key_array);
+async fn process_update_did(
+    store: &dyn DidStore,
+    did: String,
+    request: UpdateDIDRequest,
+    expected_version: Option<u64>,
+) -> Result<DidRecord, Error> {
+    let signing_key = decode_signing_key(&request.signing_key)?;
 
     // 更新DID文档
-    did::update_did(&did, &signing_key, request.document).await
+    did::update_did(store, &did, &signing_key, request.document, expected_version).await
 }
 
 /// 停用DID处理函数
+#[utoipa::path(
+    delete,
+    path = "/did/{did}",
+    tag = "did",
+    security(("bearer" = ["did:update"]), ("api_key" = ["did:update"])),
+    params(("did" = String, Path, description = "DID"), IfMatchHeader, IdempotencyKeyHeader),
+    request_body = DeactivateDIDRequest,
+    responses(
+        (status = 200, description = "DID已停用，`data`为`null`", body = ApiResponse<serde_json::Value>),
+        (status = 412, description = "文档已被修改，版本与`If-Match`不一致", body = Problem),
+    ),
+)]
 pub async fn deactivate_did(
+    State(state): State<Arc<AppState>>,
+    principal: Principal,
     Path(did): Path<String>,
+    headers: HeaderMap,
     Json(request): Json<DeactivateDIDRequest>,
-) -> Result<(StatusCode, Json<ApiResponse<()>>), (StatusCode, Json<ApiResponse<()>>)> {
-    match process_deactivate_did(did, request).await {
-        Ok(()) => Ok((StatusCode::OK, Json(ApiResponse {
-            success: true,
-            data: Some(()),
-            error: None,
-        }))),
-        Err(e) => {
-            let api_error: ApiError = e.into();
-            Err((StatusCode::from_u16(api_error.code).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
-                Json(ApiResponse {
-                    success: false,
-                    data: None,
-                    error: Some(api_error),
-                })))
-        }
-    }
+) -> Result<(StatusCode, Json<ApiResponse<()>>), Error> {
+    principal.require_did(Scope::DidUpdate, &did)?;
+    let expected_version = conditional::if_match(&headers, state.require_preconditions)?;
+    process_deactivate_did(state.store.as_ref(), did, request, expected_version).await?;
+
+    Ok((StatusCode::OK, Json(ApiResponse {
+        success: true,
+        data: Some(()),
+        error: None,
+    })))
+}
+
+async fn process_deactivate_did(
+    store: &dyn DidStore,
+    did: String,
+    request: DeactivateDIDRequest,
+    expected_version: Option<u64>,
+) -> Result<(), Error> {
+    let signing_key = decode_signing_key(&request.signing_key)?;
+
+    // 停用DID
+    did::deactivate_did(store, &did, &signing_key, expected_version).await
+}
+
+/// 变更成功的响应，`ETag`为变更后的版本号
+fn changed(status: StatusCode, record: DidRecord) -> Response {
+    (status, [(header::ETAG, conditional::etag(record.version_id))], Json(ApiResponse {
+        success: true,
+        data: Some(record.document),
+        error: None,
+    })).into_response()
 }
 
-async fn process_deactivate_did(did: String, request: DeactivateDIDRequest) -> Result<(), Error> {
-    // 解码签名密钥
-    let key_bytes = bs58::decode(&request.signing_key)
+/// 解码Base58编码的Ed25519签名密钥
+fn decode_signing_key(encoded: &EncodedSigningKey) -> Result<SigningKey, Error> {
+    let key_bytes = bs58::decode(&encoded.0)
         .into_vec()
-        .map_err(|e| Error::InvalidInput(format!("Invalid signing key encoding: {}", e)))?;
+        .map_err(|e| Error::validation("signing_key", format!("Invalid Base58 encoding: {}", e)))?;
+
+    let key_array: [u8; 32] = key_bytes.try_into().map_err(|bytes: Vec<u8>| {
+        Error::validation("signing_key", format!("Ed25519私钥长度必须为32字节，实际为{}字节", bytes.len()))
+    })?;
 
-    if key_bytes.len() != 32 {
-        return Err(Error::InvalidInput(format!("Ed25519私钥长度必须为32字节，实际为{}字节", key_bytes.len())));
+    Ok(SigningKey::from_bytes(&key_array))
+}
+
+/// 轮换公钥处理函数
+#[utoipa::path(
+    post,
+    path = "/did/{did}/keys/rotate",
+    tag = "did",
+    security(("bearer" = ["did:update"]), ("api_key" = ["did:update"])),
+    params(("did" = String, Path, description = "DID"), IfMatchHeader, IdempotencyKeyHeader),
+    request_body = RotateKeyRequest,
+    responses(
+        (status = 200, description = "更新后的DID文档", body = ApiResponse<DIDDocument>, headers(("ETag" = String, description = "新的文档版本号"))),
+        (status = 412, description = "文档已被修改，版本与`If-Match`不一致", body = Problem),
+    ),
+)]
+pub async fn rotate_key(
+    State(state): State<Arc<AppState>>,
+    principal: Principal,
+    Path(did): Path<String>,
+    headers: HeaderMap,
+    Json(request): Json<RotateKeyRequest>,
+) -> Result<Response, Error> {
+    principal.require_did(Scope::DidUpdate, &did)?;
+    let expected_version = conditional::if_match(&headers, state.require_preconditions)?;
+    let signing_key = decode_signing_key(&request.signing_key)?;
+    let record = did::rotate_key(
+        state.store.as_ref(),
+        &did,
+        &signing_key,
+        request.key_id.as_deref(),
+        &request.new_public_key,
+        expected_version,
+    ).await?;
+
+    Ok(changed(StatusCode::OK, record))
+}
+
+/// 添加服务端点处理函数
+#[utoipa::path(
+    post,
+    path = "/did/{did}/services",
+    tag = "did",
+    security(("bearer" = ["did:update"]), ("api_key" = ["did:update"])),
+    params(("did" = String, Path, description = "DID"), IfMatchHeader, IdempotencyKeyHeader),
+    request_body = AddServiceRequest,
+    responses(
+        (status = 201, description = "更新后的DID文档", body = ApiResponse<DIDDocument>, headers(("ETag" = String, description = "新的文档版本号"))),
+        (status = 412, description = "文档已被修改，版本与`If-Match`不一致", body = Problem),
+    ),
+)]
+pub async fn add_service(
+    State(state): State<Arc<AppState>>,
+    principal: Principal,
+    Path(did): Path<String>,
+    headers: HeaderMap,
+    Json(request): Json<AddServiceRequest>,
+) -> Result<Response, Error> {
+    principal.require_did(Scope::DidUpdate, &did)?;
+    let expected_version = conditional::if_match(&headers, state.require_preconditions)?;
+    let signing_key = decode_signing_key(&request.signing_key)?;
+    let record = did::add_service(state.store.as_ref(), &did, &signing_key, request.service, expected_version).await?;
+
+    Ok(changed(StatusCode::CREATED, record))
+}
+
+/// 删除服务端点处理函数
+#[utoipa::path(
+    delete,
+    path = "/did/{did}/services/{service_id}",
+    tag = "did",
+    security(("bearer" = ["did:update"]), ("api_key" = ["did:update"])),
+    params(
+        ("did" = String, Path, description = "DID"),
+        ("service_id" = String, Path, description = "URL编码的服务ID"),
+        IfMatchHeader,
+        IdempotencyKeyHeader,
+    ),
+    request_body = RemoveServiceRequest,
+    responses(
+        (status = 200, description = "更新后的DID文档", body = ApiResponse<DIDDocument>, headers(("ETag" = String, description = "新的文档版本号"))),
+        (status = 412, description = "文档已被修改，版本与`If-Match`不一致", body = Problem),
+    ),
+)]
+pub async fn remove_service(
+    State(state): State<Arc<AppState>>,
+    principal: Principal,
+    Path((did, service_id)): Path<(String, String)>,
+    headers: HeaderMap,
+    Json(request): Json<RemoveServiceRequest>,
+) -> Result<Response, Error> {
+    principal.require_did(Scope::DidUpdate, &did)?;
+    let expected_version = conditional::if_match(&headers, state.require_preconditions)?;
+    let signing_key = decode_signing_key(&request.signing_key)?;
+    let record = did::remove_service(state.store.as_ref(), &did, &signing_key, &service_id, expected_version).await?;
+
+    Ok(changed(StatusCode::OK, record))
+}
+
+/// 获取DID操作日志处理函数
+#[utoipa::path(
+    get,
+    path = "/did/{did}/operations",
+    tag = "did",
+    params(("did" = String, Path, description = "DID")),
+    responses((status = 200, description = "按顺序排列的签名操作", body = ApiResponse<Vec<OperationLogEntry>>)),
+)]
+pub async fn list_did_operations(
+    State(state): State<Arc<AppState>>,
+    Path(did): Path<String>,
+) -> Result<(StatusCode, Json<ApiResponse<Vec<OperationLogEntry>>>), Error> {
+    let entries = did::list_operations(state.store.as_ref(), &did).await?;
+
+    Ok((StatusCode::OK, Json(ApiResponse {
+        success: true,
+        data: Some(entries),
+        error: None,
+    })))
+}
+
+/// 获取DID批量锚定包含证明处理函数
+#[utoipa::path(
+    get,
+    path = "/did/{did}/proof",
+    tag = "did",
+    params(("did" = String, Path, description = "DID")),
+    responses((status = 200, description = "包含证明及其验证结果", body = ApiResponse<InclusionStatus>)),
+)]
+pub async fn get_did_proof(
+    State(state): State<Arc<AppState>>,
+    Path(did): Path<String>,
+) -> Result<(StatusCode, Json<ApiResponse<InclusionStatus>>), Error> {
+    let status = did::get_inclusion_proof(state.store.as_ref(), &did).await?;
+
+    Ok((StatusCode::OK, Json(ApiResponse {
+        success: true,
+        data: Some(status),
+        error: None,
+    })))
+}
+
+/// DID列表查询参数，时间为Unix秒，范围为左闭右开区间
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListDIDsQuery {
+    /// DID状态（active、deactivated）
+    pub status: Option<String>,
+    /// DID方法（如`web`）
+    pub method: Option<String>,
+    /// 验证方法的控制者
+    pub controller: Option<String>,
+    /// 验证方法类型（如`Ed25519VerificationKey2020`）
+    pub verification_method_type: Option<String>,
+    /// 服务类型
+    pub service_type: Option<String>,
+    pub created_after: Option<u64>,
+    pub created_before: Option<u64>,
+    pub updated_after: Option<u64>,
+    pub updated_before: Option<u64>,
+    /// 上一页返回的游标
+    pub cursor: Option<String>,
+    /// 每页数量
+    pub limit: Option<usize>,
+}
+
+impl ListDIDsQuery {
+    /// 转换为DID查询条件
+    pub fn to_did_query(&self) -> Result<DidQuery, Error> {
+        Ok(DidQuery {
+            status: parse_status(self.status.as_deref())?,
+            method: self.method.clone(),
+            controller: self.controller.clone(),
+            verification_method_type: self.verification_method_type.clone(),
+            service_type: self.service_type.clone(),
+            public_key: None,
+            service_endpoint: None,
+            created_after: self.created_after,
+            created_before: self.created_before,
+            updated_after: self.updated_after,
+            updated_before: self.updated_before,
+            after: None,
+            limit: self.limit.unwrap_or(did::DEFAULT_PAGE_SIZE),
+        })
     }
+}
 
-    let key_array: [u8; 32] = key_bytes.try_into().map_err(|_| {
-        Error::InvalidInput("无法处理私钥数据".to_string())
-    })?;
-    
-    let signing_key = SigningKey::from_bytes(&key_array);
+/// DID列表项
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DIDListItem {
+    pub did: String,
+    pub is_active: bool,
+    pub version_id: u64,
+    pub created_at: u64,
+    pub updated_at: u64,
+    pub document: DIDDocument,
+}
 
-    // 停用DID
-    did::deactivate_did(&did, &signing_key).await
-}
\ No newline at end of file
+/// DID列表
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DIDList {
+    pub items: Vec<DIDListItem>,
+    /// 下一页的游标，没有更多结果时为空
+    pub next_cursor: Option<String>,
+}
+
+/// 列出DID处理函数
+#[utoipa::path(
+    get,
+    path = "/dids",
+    tag = "did",
+    security(("bearer" = ["did:read"]), ("api_key" = ["did:read"])),
+    params(ListDIDsQuery),
+    responses((status = 200, description = "一页DID", body = ApiResponse<DIDList>)),
+)]
+pub async fn list_dids(
+    State(state): State<Arc<AppState>>,
+    principal: Principal,
+    Query(params): Query<ListDIDsQuery>,
+) -> Result<(StatusCode, Json<ApiResponse<DIDList>>), Error> {
+    principal.require(Scope::DidRead)?;
+    let query = params.to_did_query()?;
+    let page = did::list_dids(state.store.as_ref(), query, params.cursor.as_deref()).await?;
+
+    Ok((StatusCode::OK, Json(ApiResponse {
+        success: true,
+        data: Some(page.into()),
+        error: None,
+    })))
+}
+
+impl From<DidPage> for DIDList {
+    fn from(page: DidPage) -> Self {
+        let items = page.records.into_iter()
+            .map(|record| DIDListItem {
+                did: record.did,
+                is_active: record.is_active,
+                version_id: record.version_id,
+                created_at: record.created_at,
+                updated_at: record.updated_at,
+                document: record.document,
+            })
+            .collect();
+
+        DIDList { items, next_cursor: page.next_cursor }
+    }
+}
+
+/// 解析`status`查询参数
+fn parse_status(status: Option<&str>) -> Result<Option<DidStatus>, Error> {
+    status.map(str::parse)
+        .transpose()
+        .map_err(|e| match e {
+            Error::InvalidInput(message) => Error::validation("status", message),
+            e => e,
+        })
+}
+
+/// 反向查询参数
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LookupQuery {
+    /// 服务端点（仅用于按服务端点查询）
+    pub endpoint: Option<String>,
+    /// DID状态（active、deactivated），为空时只返回活跃的DID
+    pub status: Option<String>,
+    /// 上一页返回的游标
+    pub cursor: Option<String>,
+    /// 每页数量
+    pub limit: Option<usize>,
+}
+
+impl LookupQuery {
+    /// 转换为DID查询条件；未指定状态时不返回已停用的DID
+    pub fn to_did_query(&self) -> Result<DidQuery, Error> {
+        Ok(DidQuery {
+            status: Some(parse_status(self.status.as_deref())?.unwrap_or(DidStatus::Active)),
+            limit: self.limit.unwrap_or(did::DEFAULT_PAGE_SIZE),
+            ..DidQuery::default()
+        })
+    }
+}
+
+/// 按公钥反向查询DID处理函数
+#[utoipa::path(
+    get,
+    path = "/lookup/key/{multibase}",
+    tag = "lookup",
+    security(("bearer" = ["did:read"]), ("api_key" = ["did:read"])),
+    params(("multibase" = String, Path, description = "base58btc multibase编码的公钥"), LookupQuery),
+    responses((status = 200, description = "引用该公钥的DID", body = ApiResponse<DIDList>)),
+)]
+pub async fn lookup_by_key(
+    State(state): State<Arc<AppState>>,
+    principal: Principal,
+    Path(multibase): Path<String>,
+    Query(params): Query<LookupQuery>,
+) -> Result<(StatusCode, Json<ApiResponse<DIDList>>), Error> {
+    principal.require(Scope::DidRead)?;
+    let query = params.to_did_query()?;
+    let page = did::lookup_by_key(state.store.as_ref(), &multibase, query, params.cursor.as_deref()).await?;
+
+    Ok((StatusCode::OK, Json(ApiResponse {
+        success: true,
+        data: Some(page.into()),
+        error: None,
+    })))
+}
+
+/// 按服务端点反向查询DID处理函数
+#[utoipa::path(
+    get,
+    path = "/lookup/service",
+    tag = "lookup",
+    security(("bearer" = ["did:read"]), ("api_key" = ["did:read"])),
+    params(LookupQuery),
+    responses((status = 200, description = "引用该服务端点的DID", body = ApiResponse<DIDList>)),
+)]
+pub async fn lookup_by_service(
+    State(state): State<Arc<AppState>>,
+    principal: Principal,
+    Query(params): Query<LookupQuery>,
+) -> Result<(StatusCode, Json<ApiResponse<DIDList>>), Error> {
+    principal.require(Scope::DidRead)?;
+    let endpoint = params.endpoint.as_deref()
+        .ok_or_else(|| Error::validation("endpoint", "Missing endpoint parameter"))?;
+    let query = params.to_did_query()?;
+    let page = did::lookup_by_service(state.store.as_ref(), endpoint, query, params.cursor.as_deref()).await?;
+
+    Ok((StatusCode::OK, Json(ApiResponse {
+        success: true,
+        data: Some(page.into()),
+        error: None,
+    })))
+}
@@ -0,0 +1,255 @@
+//! 错误响应模块 - 将系统错误转换为HTTP错误响应
+//!
+//! 默认返回RFC 9457格式的`application/problem+json`；`Accept`中只声明了`application/json`
+//! 的旧客户端仍收到`ApiResponse`信封。每个请求都带有请求ID，随响应头`x-request-id`返回并写入错误体。
+
+use axum::extract::Request;
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use serde::Serialize;
+use crate::api::{negotiate, v2, ApiResponse};
+use crate::types::{Error, FieldError};
+use crate::utils;
+use utoipa::ToSchema;
+
+/// 请求ID头
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+/// 问题详情的媒体类型
+const PROBLEM_CONTENT_TYPE: &str = "application/problem+json";
+/// 错误类型URI前缀
+const PROBLEM_TYPE_PREFIX: &str = "urn:did-system:error:";
+/// 接受客户端传入的请求ID的最大长度
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// 错误响应格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorFormat {
+    /// `application/problem+json`
+    Problem,
+    /// `ApiResponse`信封
+    Legacy,
+}
+
+impl ErrorFormat {
+    /// 确定请求的错误格式：v2接口总是返回问题详情，其他接口按`Accept`协商
+    fn for_request(request: &Request) -> Self {
+        if request.uri().path().starts_with(v2::PREFIX) {
+            ErrorFormat::Problem
+        } else {
+            Self::negotiate(request.headers())
+        }
+    }
+
+    /// 按`Accept`协商错误格式：声明了`application/problem+json`或未声明`application/json`时返回问题详情
+    fn negotiate(headers: &HeaderMap) -> Self {
+        let ranges = negotiate::accepted_media_ranges(headers);
+        let accepts = |media_type: &str| ranges.iter().any(|range| range.media_type == media_type && range.quality > 0.0);
+
+        if !accepts(PROBLEM_CONTENT_TYPE) && accepts("application/json") {
+            ErrorFormat::Legacy
+        } else {
+            ErrorFormat::Problem
+        }
+    }
+}
+
+/// 当前请求的上下文
+#[derive(Debug, Clone)]
+struct RequestContext {
+    request_id: String,
+    format: ErrorFormat,
+}
+
+tokio::task_local! {
+    static REQUEST_CONTEXT: RequestContext;
+}
+
+/// 当前请求的ID，不在请求中时为空
+pub fn current_request_id() -> Option<String> {
+    REQUEST_CONTEXT.try_with(|context| context.request_id.clone()).ok()
+}
+
+/// 请求上下文中间件：确定请求ID和错误格式，并在响应头中返回请求ID
+pub async fn request_context(request: Request, next: Next) -> Response {
+    let request_id = request.headers().get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| is_valid_request_id(value))
+        .map(str::to_string)
+        .unwrap_or_else(|| hex::encode(utils::generate_random_bytes(16)));
+    let context = RequestContext {
+        request_id: request_id.clone(),
+        format: ErrorFormat::for_request(&request),
+    };
+
+    let mut response = REQUEST_CONTEXT.scope(context, next.run(request)).await;
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+/// 客户端传入的请求ID只允许可见ASCII字符
+fn is_valid_request_id(value: &str) -> bool {
+    !value.is_empty()
+        && value.len() <= MAX_REQUEST_ID_LEN
+        && value.bytes().all(|byte| byte.is_ascii_graphic())
+}
+
+/// 问题详情（RFC 9457）
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Problem {
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub title: String,
+    pub status: u16,
+    pub detail: String,
+    /// 稳定的机器可读错误码
+    pub code: String,
+    pub request_id: Option<String>,
+    /// 校验失败的字段
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>,
+}
+
+impl Problem {
+    pub fn new(status: StatusCode, code: &str, detail: impl Into<String>) -> Self {
+        Self {
+            type_: format!("{}{}", PROBLEM_TYPE_PREFIX, code),
+            title: status.canonical_reason().unwrap_or("Error").to_string(),
+            status: status.as_u16(),
+            detail: detail.into(),
+            code: code.to_string(),
+            request_id: current_request_id(),
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn with_errors(mut self, errors: Vec<FieldError>) -> Self {
+        self.errors = errors;
+        self
+    }
+
+    fn status_code(&self) -> StatusCode {
+        StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
+
+impl From<Error> for Problem {
+    fn from(err: Error) -> Self {
+        let status = status_of(&err);
+        let code = err.code();
+        let detail = match err {
+            Error::NotFound(msg)
+            | Error::InvalidInput(msg)
+            | Error::Unauthorized(msg)
+            | Error::Forbidden(msg)
+            | Error::RateLimited(msg)
+            | Error::QuotaExceeded(msg)
+            | Error::PreconditionFailed(msg)
+            | Error::PreconditionRequired(msg)
+            | Error::IdempotencyKeyInUse(msg)
+            | Error::IdempotencyKeyMismatch(msg)
+            | Error::InvalidState(msg)
+            | Error::RepresentationNotSupported(msg) => msg,
+            Error::Validation(errors) => return Problem::new(status, code, "Request validation failed").with_errors(errors),
+            err => err.to_string(),
+        };
+
+        Problem::new(status, code, detail)
+    }
+}
+
+/// 错误对应的HTTP状态码
+fn status_of(err: &Error) -> StatusCode {
+    match err {
+        Error::NotFound(_) => StatusCode::NOT_FOUND,
+        Error::InvalidInput(_) => StatusCode::BAD_REQUEST,
+        Error::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+        Error::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+        Error::Forbidden(_) => StatusCode::FORBIDDEN,
+        Error::InvalidState(_) => StatusCode::CONFLICT,
+        Error::RateLimited(_) | Error::QuotaExceeded(_) => StatusCode::TOO_MANY_REQUESTS,
+        Error::PreconditionFailed(_) => StatusCode::PRECONDITION_FAILED,
+        Error::PreconditionRequired(_) => StatusCode::PRECONDITION_REQUIRED,
+        Error::IdempotencyKeyInUse(_) => StatusCode::CONFLICT,
+        Error::IdempotencyKeyMismatch(_) => StatusCode::UNPROCESSABLE_ENTITY,
+        Error::RepresentationNotSupported(_) => StatusCode::NOT_ACCEPTABLE,
+        Error::DatabaseError(_)
+        | Error::BlockchainError(_)
+        | Error::CryptoError(_)
+        | Error::SerializationError(_)
+        | Error::NetworkError(_)
+        | Error::InternalError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// 旧版API错误，位于`ApiResponse`信封中
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ApiError {
+    pub message: String,
+    /// HTTP状态码
+    pub code: u16,
+    /// 稳定的机器可读错误码
+    pub error_code: String,
+    pub request_id: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>,
+}
+
+impl From<Problem> for ApiError {
+    fn from(problem: Problem) -> Self {
+        let message = match problem.errors.as_slice() {
+            [] => problem.detail,
+            errors => errors.iter()
+                .map(|error| format!("{}: {}", error.field, error.message))
+                .collect::<Vec<_>>()
+                .join("; "),
+        };
+
+        ApiError {
+            message,
+            code: problem.status,
+            error_code: problem.code,
+            request_id: problem.request_id,
+            errors: problem.errors,
+        }
+    }
+}
+
+impl IntoResponse for Problem {
+    fn into_response(self) -> Response {
+        let status = self.status_code();
+        if status.is_server_error() {
+            log::error!("请求{}失败: {} ({})", self.request_id.as_deref().unwrap_or("-"), self.detail, self.code);
+        }
+
+        let format = REQUEST_CONTEXT.try_with(|context| context.format).unwrap_or(ErrorFormat::Problem);
+        match format {
+            ErrorFormat::Problem => (
+                status,
+                [(header::CONTENT_TYPE, PROBLEM_CONTENT_TYPE)],
+                axum::Json(self),
+            ).into_response(),
+            ErrorFormat::Legacy => (
+                status,
+                axum::Json(ApiResponse::<()> {
+                    success: false,
+                    data: None,
+                    error: Some(self.into()),
+                }),
+            ).into_response(),
+        }
+    }
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        Problem::from(self).into_response()
+    }
+}
+
+/// 未匹配任何路由时的处理函数
+pub async fn route_not_found() -> Problem {
+    Problem::new(StatusCode::NOT_FOUND, "route_not_found", "No route matches the request")
+}
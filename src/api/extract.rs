@@ -0,0 +1,159 @@
+//! 请求提取器 - 包装axum的提取器，提取失败时返回问题详情
+//!
+//! JSON请求体和查询参数反序列化失败时报告出错字段的路径，如`service.endpoint`。
+
+use axum::async_trait;
+use axum::body::Bytes;
+use axum::extract::{FromRequest, FromRequestParts, Request};
+use axum::http::request::Parts;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use crate::api::error::Problem;
+use crate::types::{Error, FieldError};
+
+/// JSON请求体和响应体
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Json<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for Json<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = Problem;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        if !is_json_content_type(request.headers()) {
+            return Err(Problem::new(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "unsupported_media_type",
+                "Expected request with `Content-Type: application/json`",
+            ));
+        }
+
+        let bytes = Bytes::from_request(request, state).await
+            .map_err(|rejection| {
+                let code = match rejection.status() {
+                    StatusCode::PAYLOAD_TOO_LARGE => "payload_too_large",
+                    _ => "invalid_body",
+                };
+                Problem::new(rejection.status(), code, rejection.body_text())
+            })?;
+
+        let mut deserializer = serde_json::Deserializer::from_slice(&bytes);
+        let value = serde_path_to_error::deserialize(&mut deserializer).map_err(json_problem)?;
+        deserializer.end()
+            .map_err(|e| Problem::new(StatusCode::BAD_REQUEST, "malformed_json", strip_position(&e.to_string())))?;
+
+        Ok(Json(value))
+    }
+}
+
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> Response {
+        axum::Json(self.0).into_response()
+    }
+}
+
+/// 请求体是否声明为JSON（`application/json`或`application/*+json`）
+fn is_json_content_type(headers: &HeaderMap) -> bool {
+    headers.get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.split(';').next().unwrap_or_default().trim().to_ascii_lowercase())
+        .is_some_and(|media| {
+            media == "application/json"
+                || (media.starts_with("application/") && media.ends_with("+json"))
+        })
+}
+
+/// JSON反序列化错误：语法错误返回400，字段错误返回带字段路径的校验错误
+fn json_problem(err: serde_path_to_error::Error<serde_json::Error>) -> Problem {
+    let path = err.path().to_string();
+    let inner = err.into_inner();
+    let message = strip_position(&inner.to_string());
+
+    match inner.classify() {
+        serde_json::error::Category::Data => {
+            Error::Validation(vec![field_error(&path, &message)]).into()
+        }
+        _ => Problem::new(StatusCode::BAD_REQUEST, "malformed_json", message),
+    }
+}
+
+/// 构造字段错误；缺少字段时字段路径指向缺少的字段本身
+fn field_error(path: &str, message: &str) -> FieldError {
+    let parent = if path == "." { "" } else { path };
+    let missing = message.strip_prefix("missing field `")
+        .and_then(|rest| rest.split('`').next());
+
+    let field = match (missing, parent) {
+        (Some(name), "") => name.to_string(),
+        (Some(name), parent) => format!("{}.{}", parent, name),
+        (None, "") => "body".to_string(),
+        (None, parent) => parent.to_string(),
+    };
+    FieldError { field, message: message.to_string() }
+}
+
+/// 去掉serde_json错误信息末尾的行列位置
+fn strip_position(message: &str) -> String {
+    match message.rfind(" at line ") {
+        Some(index) => message[..index].to_string(),
+        None => message.to_string(),
+    }
+}
+
+/// 查询参数
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Query<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for Query<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = Problem;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let query = parts.uri.query().unwrap_or_default();
+        let deserializer = serde_urlencoded::Deserializer::new(form_urlencoded::parse(query.as_bytes()));
+
+        let value = serde_path_to_error::deserialize(deserializer)
+            .map_err(|err| {
+                let path = err.path().to_string();
+                let message = err.into_inner().to_string();
+                let field = match message.strip_prefix("missing field `").and_then(|rest| rest.split('`').next()) {
+                    Some(name) => name.to_string(),
+                    None if path == "." => "query".to_string(),
+                    None => path,
+                };
+                Problem::from(Error::validation(field, message))
+            })?;
+
+        Ok(Query(value))
+    }
+}
+
+/// 路径参数
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Path<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for Path<T>
+where
+    T: DeserializeOwned + Send,
+    S: Send + Sync,
+{
+    type Rejection = Problem;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let axum::extract::Path(value) = axum::extract::Path::<T>::from_request_parts(parts, state).await
+            .map_err(|rejection| Problem::new(rejection.status(), "invalid_path", rejection.body_text()))?;
+
+        Ok(Path(value))
+    }
+}
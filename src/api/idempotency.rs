@@ -0,0 +1,205 @@
+//! 幂等中间件 - 处理写请求的`Idempotency-Key`头
+//!
+//! 位于限流中间件之内：重放的响应同样消耗限流令牌，但不计入每日写操作配额。
+//! 幂等键按已认证的主体隔离，未认证的请求共用一个命名空间。带幂等键的请求体最大2 MiB。
+//! 重放的响应与第一次的响应相同，另外带有`Idempotency-Replayed: true`头。
+//! 逐行输出的批量操作响应在全部输出后保存，之前的重试返回409；汇总行带有错误时释放幂等键。
+
+use std::sync::Arc;
+use axum::body::{to_bytes, Body};
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderMap, HeaderName, HeaderValue, Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use futures_util::StreamExt;
+use tokio::sync::mpsc;
+use utoipa::IntoParams;
+use crate::api::error::Problem;
+use crate::api::{batch, AppState};
+use crate::auth::Principal;
+use crate::idempotency::{self, StoredResponse};
+use crate::types::Error;
+use crate::utils;
+
+/// 幂等键请求头
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+/// 标记重放响应的响应头
+pub const REPLAYED_HEADER: &str = "idempotency-replayed";
+/// 带幂等键的请求体的最大字节数
+const MAX_BODY_BYTES: usize = 2 * 1024 * 1024;
+
+/// `Idempotency-Key`请求头，只用于OpenAPI文档
+#[derive(Debug, IntoParams)]
+#[into_params(parameter_in = Header)]
+pub struct IdempotencyKeyHeader {
+    /// 客户端生成的唯一键（如UUID），超时重试时使用同一个键；保留期内相同的重试返回第一次的响应，
+    /// 同一个键用于不同的请求时返回422，第一次请求仍在处理中时返回409
+    #[param(rename = "Idempotency-Key")]
+    pub idempotency_key: Option<String>,
+}
+
+/// 幂等中间件，读请求和不带幂等键的请求直接放行
+pub async fn idempotent(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    if !state.idempotency.enabled || matches!(*request.method(), Method::GET | Method::HEAD | Method::OPTIONS) {
+        return next.run(request).await;
+    }
+    let Some(key) = request.headers().get(IDEMPOTENCY_KEY_HEADER) else {
+        return next.run(request).await;
+    };
+    // 不是可见ASCII的值按空键校验
+    let key = key.to_str().unwrap_or_default().to_string();
+    if let Err(e) = idempotency::validate_key(&key) {
+        return e.into_response();
+    }
+    let tenant = request.extensions().get::<Principal>()
+        .and_then(Principal::client_id)
+        .unwrap_or_else(|| "anonymous".to_string());
+
+    let (parts, body) = request.into_parts();
+    let body = match to_bytes(body, MAX_BODY_BYTES).await {
+        Ok(body) => body,
+        Err(_) => {
+            return Problem::new(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "payload_too_large",
+                format!("Requests with an Idempotency-Key are limited to {} bytes", MAX_BODY_BYTES),
+            ).into_response();
+        }
+    };
+    let uri = parts.uri.path_and_query().map(|uri| uri.as_str()).unwrap_or_else(|| parts.uri.path());
+    let fingerprint = idempotency::fingerprint(parts.method.as_str(), uri, &body);
+
+    let now = utils::current_timestamp();
+    let expires_at = now + state.idempotency.retention_secs;
+    match state.store.claim_idempotency_key(&tenant, &key, &fingerprint, now, expires_at).await {
+        Ok(None) => {}
+        Ok(Some(record)) if record.fingerprint != fingerprint => {
+            return Error::IdempotencyKeyMismatch(format!(
+                "Idempotency-Key {} was already used for a different request", key
+            )).into_response();
+        }
+        Ok(Some(record)) => match record.response {
+            Some(stored) => return replay(stored),
+            None => {
+                return Error::IdempotencyKeyInUse(format!(
+                    "A request with Idempotency-Key {} is still being processed", key
+                )).into_response();
+            }
+        },
+        Err(e) => return e.into_response(),
+    }
+
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+    if !idempotency::is_storable(response.status().as_u16()) {
+        release(&state, &tenant, &key).await;
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let headers = parts.headers.iter()
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect();
+    let stored = StoredResponse { status: parts.status.as_u16(), headers, body: String::new() };
+    if is_streamed(&parts.headers) {
+        return Response::from_parts(parts, record_stream(state, tenant, key, stored, body));
+    }
+
+    let body = match to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(e) => {
+            release(&state, &tenant, &key).await;
+            return Error::InternalError(format!("Failed to read response body: {}", e)).into_response();
+        }
+    };
+    complete(&state, &tenant, &key, stored, body.to_vec()).await;
+
+    Response::from_parts(parts, Body::from(body))
+}
+
+/// 逐行输出的响应边转发边保存，全部输出后保存响应
+fn is_streamed(headers: &HeaderMap) -> bool {
+    headers.get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with(batch::NDJSON_MEDIA_TYPE))
+}
+
+/// 在后台任务中读取流式响应体并转发给客户端，客户端断开后仍读取到结束并保存响应
+///
+/// 最后一块在保存响应后才转发，客户端读完响应后的重试总能重放。
+fn record_stream(state: Arc<AppState>, tenant: String, key: String, stored: StoredResponse, body: Body) -> Body {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        let mut chunks = body.into_data_stream();
+        let mut buffer = Vec::new();
+        let mut last = None;
+        while let Some(chunk) = chunks.next().await {
+            match chunk {
+                Ok(chunk) => {
+                    buffer.extend_from_slice(&chunk);
+                    if let Some(previous) = last.replace(chunk) {
+                        let _ = sender.send(Ok(previous));
+                    }
+                }
+                Err(e) => {
+                    release(&state, &tenant, &key).await;
+                    let _ = sender.send(Err(e));
+                    return;
+                }
+            }
+        }
+        // 批次整体失败时没有写入任何操作，与失败的非流式响应一样允许重试
+        if stream_succeeded(&buffer) {
+            complete(&state, &tenant, &key, stored, buffer).await;
+        } else {
+            release(&state, &tenant, &key).await;
+        }
+        if let Some(last) = last {
+            let _ = sender.send(Ok(last));
+        }
+    });
+
+    Body::from_stream(futures_util::stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|chunk| (chunk, receiver))
+    }))
+}
+
+/// 逐行输出是否以不带错误的汇总行结束
+fn stream_succeeded(body: &[u8]) -> bool {
+    body.split(|byte| *byte == b'\n')
+        .rfind(|line| !line.is_empty())
+        .and_then(|line| serde_json::from_slice::<serde_json::Value>(line).ok())
+        .is_some_and(|summary| summary["type"] == "summary" && summary.get("error").is_none())
+}
+
+/// 保存响应，之后的重试重放该响应；响应体不是UTF-8时释放幂等键
+async fn complete(state: &AppState, tenant: &str, key: &str, mut stored: StoredResponse, body: Vec<u8>) {
+    match String::from_utf8(body) {
+        Ok(text) => {
+            stored.body = text;
+            if let Err(e) = state.store.complete_idempotency_key(tenant, key, &stored).await {
+                log::error!("保存幂等键{}的响应失败: {}", key, e);
+            }
+        }
+        Err(_) => release(state, tenant, key).await,
+    }
+}
+
+/// 释放幂等键，之后的重试重新执行请求
+async fn release(state: &AppState, tenant: &str, key: &str) {
+    if let Err(e) = state.store.release_idempotency_key(tenant, key).await {
+        log::error!("释放幂等键{}失败: {}", key, e);
+    }
+}
+
+/// 重放保存的响应
+fn replay(stored: StoredResponse) -> Response {
+    let mut response = Response::new(Body::from(stored.body));
+    *response.status_mut() = StatusCode::from_u16(stored.status).unwrap_or(StatusCode::OK);
+    for (name, value) in stored.headers {
+        if let (Ok(name), Ok(value)) = (HeaderName::try_from(name), HeaderValue::try_from(value)) {
+            response.headers_mut().append(name, value);
+        }
+    }
+    response.headers_mut().insert(HeaderName::from_static(REPLAYED_HEADER), HeaderValue::from_static("true"));
+    response
+}
@@ -1,26 +1,43 @@
 //! API模块 - 提供HTTP API接口
 
 use axum::{
-    response::Json,
-    routing::{get, post, put, delete},
+    http::{header, HeaderName, HeaderValue},
+    middleware,
     Router,
 };
 use serde::Serialize;
 use std::sync::Arc;
-use tower_http::cors::CorsLayer;
+use tower_http::cors::{Any, CorsLayer};
+use crate::auth::Authenticator;
+use crate::config::{ApiConfig, Config};
+use crate::db::SharedStore;
+use crate::idempotency::IdempotencyConfig;
+use crate::ratelimit::RateLimiter;
 use crate::types::Error;
+use utoipa::OpenApi;
+use utoipa_axum::router::OpenApiRouter;
+use utoipa_axum::routes;
 
+pub mod admin;
+pub mod auth;
+pub mod batch;
+pub mod conditional;
 pub mod did;
+pub mod error;
+pub mod extract;
+pub mod idempotency;
+pub mod negotiate;
+pub mod openapi;
+pub mod ratelimit;
+pub mod v1;
+pub mod v2;
 
-/// API错误响应
-#[derive(Debug, Serialize)]
-pub struct ApiError {
-    pub message: String,
-    pub code: u16,
-}
+pub use error::ApiError;
+use extract::Json;
+use utoipa::ToSchema;
 
 /// API响应
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct ApiResponse<T> {
     pub success: bool,
     pub data: Option<T>,
@@ -30,10 +47,25 @@ pub struct ApiResponse<T> {
 /// 应用状态
 #[derive(Clone)]
 pub struct AppState {
-    // 可以添加数据库连接池等共享状态
+    /// DID存储
+    pub store: SharedStore,
+    /// 请求凭据验证
+    pub auth: Arc<Authenticator>,
+    /// 限流器，未启用限流时为`None`
+    pub limiter: Option<Arc<RateLimiter>>,
+    /// 修改已有DID时是否要求前提条件
+    pub require_preconditions: bool,
+    /// 写请求的幂等键处理
+    pub idempotency: IdempotencyConfig,
 }
 
 /// 健康检查接口
+#[utoipa::path(
+    get,
+    path = "/health",
+    tag = "system",
+    responses((status = 200, description = "服务正常", body = ApiResponse<String>)),
+)]
 pub async fn health_check() -> Json<ApiResponse<String>> {
     Json(ApiResponse {
         success: true,
@@ -43,35 +75,76 @@ pub async fn health_check() -> Json<ApiResponse<String>> {
 }
 
 /// 创建API路由
-pub fn create_router() -> Router {
-    let state = Arc::new(AppState {});
-    
-    Router::new()
-        .route("/health", get(health_check))
-        .route("/did", post(did::create_did))
-        .route("/did/:did", get(did::resolve_did))
-        .route("/did/:did", put(did::update_did))
-        .route("/did/:did", delete(did::deactivate_did))
-        .layer(CorsLayer::permissive())
-        .with_state(state)
+pub fn create_router(store: SharedStore, config: &Config) -> Result<Router, Error> {
+    let state = Arc::new(AppState {
+        store,
+        auth: Arc::new(Authenticator::from_config(&config.auth)?),
+        limiter: config.rate_limit.enabled.then(|| Arc::new(RateLimiter::new(&config.rate_limit))),
+        require_preconditions: config.api.require_preconditions,
+        idempotency: config.idempotency.clone(),
+    });
+    let cors = cors_layer(config)?;
+    let (router, spec) = routes(&config.api).split_for_parts();
+    // 不带版本前缀的旧路径作为v1的别名保留，不写入文档
+    let (legacy, _) = v1::routes(&config.api).split_for_parts();
+
+    let router = router
+        .merge(legacy)
+        .merge(openapi::docs_router(openapi::finish(spec)))
+        .fallback(error::route_not_found)
+        .layer(middleware::from_fn_with_state(state.clone(), idempotency::idempotent))
+        .layer(middleware::from_fn_with_state(state.clone(), ratelimit::limit))
+        .layer(middleware::from_fn_with_state(state.clone(), auth::authenticate))
+        .layer(middleware::from_fn(error::request_context))
+        .layer(cors)
+        .with_state(state);
+
+    Ok(router)
+}
+
+/// 生成OpenAPI文档，与`create_router`使用同一组路由
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    openapi::finish(routes(&ApiConfig::default()).into_openapi())
 }
 
-/// 错误转换
-impl From<Error> for ApiError {
-    fn from(err: Error) -> Self {
-        let (message, code) = match err {
-            Error::NotFound(msg) => (msg, 404),
-            Error::InvalidInput(msg) => (msg, 400),
-            Error::Unauthorized(msg) => (msg, 401),
-            Error::InvalidState(msg) => (msg, 409),
-            Error::DatabaseError(msg) => (format!("Database error: {}", msg), 500),
-            Error::BlockchainError(msg) => (format!("Blockchain error: {}", msg), 500),
-            Error::CryptoError(msg) => (format!("Crypto error: {}", msg), 500),
-            Error::SerializationError(msg) => (format!("Serialization error: {}", msg), 500),
-            Error::NetworkError(msg) => (format!("Network error: {}", msg), 500),
-            Error::InternalError(msg) => (format!("Internal error: {}", msg), 500),
-        };
-        
-        ApiError { message, code }
+/// 全部API路由，OpenAPI文档中的路径由处理函数上的`#[utoipa::path]`生成；
+/// DID接口按版本挂载在`/v1`和`/v2`下，管理和系统接口不带版本前缀
+fn routes(config: &ApiConfig) -> OpenApiRouter<Arc<AppState>> {
+    OpenApiRouter::with_openapi(openapi::ApiDoc::openapi())
+        .routes(routes!(health_check))
+        .merge(auth::routes())
+        .nest(v1::PREFIX, v1::routes(config))
+        .nest(v2::PREFIX, v2::routes())
+        .merge(admin::routes())
+}
+
+/// 按配置的来源构造跨域访问层
+fn cors_layer(config: &Config) -> Result<CorsLayer, Error> {
+    if config.allows_any_origin() {
+        return Ok(CorsLayer::permissive());
     }
-}
\ No newline at end of file
+
+    let origins = config.server.cors_origins.iter()
+        .map(|origin| HeaderValue::from_str(origin)
+            .map_err(|_| Error::InvalidInput(format!("Invalid CORS origin: {}", origin))))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(CorsLayer::new()
+        .allow_origin(origins)
+        .allow_methods(Any)
+        .allow_headers(Any)
+        .expose_headers([
+            HeaderName::from_static(error::REQUEST_ID_HEADER),
+            header::WWW_AUTHENTICATE,
+            HeaderName::from_static("deprecation"),
+            HeaderName::from_static("sunset"),
+            header::LINK,
+            header::RETRY_AFTER,
+            header::ETAG,
+            HeaderName::from_static(idempotency::REPLAYED_HEADER),
+            HeaderName::from_static("ratelimit-limit"),
+            HeaderName::from_static("ratelimit-remaining"),
+            HeaderName::from_static("ratelimit-reset"),
+            HeaderName::from_static("ratelimit-policy"),
+        ]))
+}
@@ -0,0 +1,99 @@
+//! 内容协商模块 - 解析`Accept`头并选择响应格式
+
+use axum::http::{header, HeaderMap};
+use crate::did::representation::Representation;
+use crate::types::Error;
+
+/// `Accept`中的一个媒体范围
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaRange {
+    /// 小写的媒体类型，可以是`type/*`或`*/*`
+    pub media_type: String,
+    /// 权重，`q=0`表示不接受
+    pub quality: f32,
+}
+
+impl MediaRange {
+    /// 与具体媒体类型匹配时的精确度：完全匹配为2，`type/*`为1，`*/*`为0
+    fn specificity(&self, media_type: &str) -> Option<u8> {
+        if self.media_type == media_type {
+            return Some(2);
+        }
+        if self.media_type == "*/*" {
+            return Some(0);
+        }
+        let (range_type, range_subtype) = self.media_type.split_once('/')?;
+        let (type_, _) = media_type.split_once('/')?;
+        (range_subtype == "*" && range_type == type_).then_some(1)
+    }
+}
+
+/// 解析请求的全部`Accept`头，忽略无法解析的项
+pub fn accepted_media_ranges(headers: &HeaderMap) -> Vec<MediaRange> {
+    headers.get_all(header::ACCEPT).iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|item| {
+            let mut parts = item.split(';');
+            let media_type = parts.next()?.trim().to_ascii_lowercase();
+            if !media_type.contains('/') {
+                return None;
+            }
+            let quality = parts
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            Some(MediaRange { media_type, quality })
+        })
+        .collect()
+}
+
+/// 按`Accept`从候选媒体类型中选择权重最高、匹配最精确的一个；权重相同时取靠前的候选。
+/// 没有`Accept`头时返回第一个候选，所有候选都不被接受时返回空
+pub fn select<'a>(ranges: &[MediaRange], candidates: &[&'a str]) -> Option<&'a str> {
+    if ranges.is_empty() {
+        return candidates.first().copied();
+    }
+
+    candidates.iter()
+        .enumerate()
+        .filter_map(|(index, candidate)| {
+            let (specificity, quality) = ranges.iter()
+                .filter_map(|range| range.specificity(candidate).map(|specificity| (specificity, range.quality)))
+                .max_by_key(|(specificity, _)| *specificity)?;
+            (quality > 0.0).then_some((quality, specificity, index, *candidate))
+        })
+        .max_by(|a, b| {
+            a.0.total_cmp(&b.0)
+                .then(a.1.cmp(&b.1))
+                .then(b.2.cmp(&a.2))
+        })
+        .map(|(_, _, _, candidate)| candidate)
+}
+
+/// `ApiResponse`信封的媒体类型
+const ENVELOPE_MEDIA_TYPE: &str = "application/json";
+
+/// 选择DID文档的表示：`accept`查询参数优先于`Accept`头，返回空时使用`ApiResponse`信封
+pub fn document_representation(accept: Option<&str>, headers: &HeaderMap) -> Result<Option<Representation>, Error> {
+    if let Some(accept) = accept {
+        // 未编码的`+`在查询参数中会被解码为空格
+        let accept = accept.trim().replace(' ', "+");
+        if accept.eq_ignore_ascii_case(ENVELOPE_MEDIA_TYPE) {
+            return Ok(None);
+        }
+        return accept.parse().map(Some);
+    }
+
+    let mut candidates = vec![ENVELOPE_MEDIA_TYPE];
+    candidates.extend(Representation::ALL.iter().map(|representation| representation.media_type()));
+
+    let ranges = accepted_media_ranges(headers);
+    match select(&ranges, &candidates) {
+        Some(media_type) => Ok(Representation::from_media_type(media_type)),
+        None => Err(Error::RepresentationNotSupported(format!(
+            "None of the accepted media types is supported, expected one of: {}",
+            candidates.join(", ")
+        ))),
+    }
+}
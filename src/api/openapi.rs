@@ -0,0 +1,113 @@
+//! OpenAPI模块 - 由处理函数生成OpenAPI 3.1文档，并提供`/openapi.json`和Swagger UI页面
+
+use std::sync::Arc;
+use axum::http::header;
+use axum::response::{Html, IntoResponse};
+use axum::routing::get;
+use axum::Router;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::openapi::{ContentBuilder, OpenApi as OpenApiDocument, Ref, ResponseBuilder};
+use utoipa::{Modify, OpenApi};
+use crate::api::auth::API_KEY_HEADER;
+use crate::api::error::Problem;
+use crate::api::AppState;
+use crate::types::FieldError;
+
+/// OpenAPI文档的路径
+pub const OPENAPI_PATH: &str = "/openapi.json";
+/// Swagger UI页面的路径
+pub const DOCS_PATH: &str = "/docs";
+
+/// OpenAPI文档的基础信息，路径由路由表中的处理函数补充
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "DID System API", description = "去中心化身份管理系统HTTP接口", license(name = "MIT")),
+    tags(
+        (name = "did", description = "DID生命周期管理"),
+        (name = "lookup", description = "按公钥或服务端点反向查询DID"),
+        (name = "auth", description = "以DID登录，签发会话令牌"),
+        (name = "admin", description = "出站队列、对账、账本、操作日志、缓存和备份管理"),
+        (name = "system", description = "服务状态"),
+    ),
+    components(schemas(Problem, FieldError)),
+    modifiers(&SecuritySchemes),
+)]
+pub struct ApiDoc;
+
+/// 认证方式：签发者签名的JWT和API密钥，安全要求中列出所需的权限范围
+struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut OpenApiDocument) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).bearer_format("JWT").build()),
+        );
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::with_description(
+                API_KEY_HEADER,
+                "`didk_`开头的API密钥，也可以放在`Authorization: Bearer`中",
+            ))),
+        );
+    }
+}
+
+/// 为每个操作补充默认的错误响应
+pub fn finish(mut spec: OpenApiDocument) -> OpenApiDocument {
+    let problem = ResponseBuilder::new()
+        .description("错误。默认以`application/problem+json`返回，只接受`application/json`的客户端收到`ApiResponse`信封")
+        .content("application/problem+json", ContentBuilder::new().schema(Some(Ref::from_schema_name("Problem"))).build())
+        .build();
+
+    for item in spec.paths.paths.values_mut() {
+        let operations = [
+            &mut item.get, &mut item.put, &mut item.post, &mut item.delete,
+            &mut item.options, &mut item.head, &mut item.patch, &mut item.trace,
+        ];
+        for operation in operations.into_iter().flatten() {
+            operation.responses.responses
+                .entry("default".to_string())
+                .or_insert_with(|| problem.clone().into());
+        }
+    }
+    spec
+}
+
+/// 提供OpenAPI文档和Swagger UI页面的路由
+pub fn docs_router(spec: OpenApiDocument) -> Router<Arc<AppState>> {
+    let spec = Arc::new(spec.to_pretty_json().unwrap_or_default());
+
+    Router::new()
+        .route(OPENAPI_PATH, get(move || {
+            let spec = spec.clone();
+            async move { ([(header::CONTENT_TYPE, "application/json")], spec.as_ref().clone()).into_response() }
+        }))
+        .route(DOCS_PATH, get(swagger_ui))
+}
+
+/// Swagger UI页面，静态资源从CDN加载
+async fn swagger_ui() -> Html<String> {
+    Html(format!(
+        r##"<!DOCTYPE html>
+<html lang="zh-CN">
+<head>
+  <meta charset="utf-8">
+  <title>DID System API</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css">
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js" crossorigin></script>
+  <script>
+    window.onload = () => {{
+      window.ui = SwaggerUIBundle({{ url: "{}", dom_id: "#swagger-ui" }});
+    }};
+  </script>
+</body>
+</html>
+"##,
+        OPENAPI_PATH
+    ))
+}
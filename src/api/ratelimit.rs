@@ -0,0 +1,169 @@
+//! 限流中间件 - 按客户端的令牌桶限流，并检查和记录每日写操作配额
+//!
+//! 位于认证中间件之内：已认证的请求按API密钥、令牌主体或DID会话的DID计数，其他请求按客户端IP计数。
+//! `GET`、`HEAD`和`OPTIONS`是读请求，其他方法是写请求。写请求在执行前原子地占用每日配额，
+//! 失败的写请求和重放的幂等响应退还占用的配额。批量操作按操作数计入配额：中间件不占用，
+//! 由处理器通过请求扩展中的[`WriteQuota`]按操作数占用，并退还未写入的操作。
+//! 每个响应带有`RateLimit-*`头，被拒绝的请求返回429和`Retry-After`头。
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::{header, HeaderMap, HeaderName, HeaderValue, Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use crate::api::{idempotency, AppState};
+use crate::auth::{Credential, Principal};
+use crate::db::DidStore;
+use crate::ratelimit::{self, Class, Decision, RateLimitConfig};
+use crate::types::Error;
+use crate::utils;
+
+/// 不限流的路径
+const EXEMPT_PATHS: &[&str] = &["/health"];
+/// 由处理器按操作数占用每日配额的路径
+const PER_OPERATION_PATHS: &[&str] = &["/v2/batch"];
+
+/// 请求所属租户当天的写操作配额，由处理器按实际的写操作数占用和退还
+#[derive(Debug, Clone)]
+pub struct WriteQuota {
+    tenant: String,
+    day: u64,
+    quota: u64,
+}
+
+impl WriteQuota {
+    /// 原子地占用`count`次写操作，配额不足时不占用并返回429
+    pub async fn reserve(&self, store: &dyn DidStore, count: u64) -> Result<(), Error> {
+        match store.reserve_write(&self.tenant, self.day, self.quota, count).await? {
+            Some(_) => Ok(()),
+            None => Err(Error::QuotaExceeded(format!(
+                "Daily write quota of {} does not allow {} more writes", self.quota, count
+            ))),
+        }
+    }
+
+    /// 退还未执行的写操作
+    pub async fn release(&self, store: &dyn DidStore, count: u64) {
+        if count == 0 {
+            return;
+        }
+        if let Err(e) = store.release_write(&self.tenant, self.day, count).await {
+            log::error!("退还客户端{}的写操作配额失败: {}", self.tenant, e);
+        }
+    }
+}
+
+/// 限流中间件，未启用限流时直接放行
+pub async fn limit(State(state): State<Arc<AppState>>, mut request: Request, next: Next) -> Response {
+    let Some(limiter) = state.limiter.as_ref() else {
+        return next.run(request).await;
+    };
+    if EXEMPT_PATHS.contains(&request.uri().path()) {
+        return next.run(request).await;
+    }
+
+    let config = limiter.config();
+    let class = match *request.method() {
+        Method::GET | Method::HEAD | Method::OPTIONS => Class::Read,
+        _ => Class::Write,
+    };
+    let principal = request.extensions().get::<Principal>();
+    let tenant = tenant(principal, &request, config);
+
+    let decision = limiter.check(&tenant, class);
+    if !decision.allowed {
+        log::warn!("客户端{}的{}请求超出限流", tenant, if class == Class::Read { "读" } else { "写" });
+        let mut response = Error::RateLimited(format!("Too many requests, retry in {} seconds", decision.retry_after_secs))
+            .into_response();
+        insert_headers(response.headers_mut(), &decision);
+        insert_retry_after(response.headers_mut(), decision.retry_after_secs);
+        return response;
+    }
+
+    // 写请求检查每日配额
+    let quota = match principal.map(|principal| &principal.credential) {
+        Some(Credential::ApiKey { daily_write_quota: Some(quota), .. }) => *quota,
+        _ => config.daily_write_quota,
+    };
+    let now = utils::current_timestamp();
+    let day = ratelimit::day_of(now);
+    let per_operation = PER_OPERATION_PATHS.contains(&request.uri().path());
+    let reserved = class == Class::Write && quota > 0 && !per_operation;
+    if class == Class::Write && quota > 0 && per_operation {
+        request.extensions_mut().insert(WriteQuota { tenant: tenant.clone(), day, quota });
+    }
+    if reserved {
+        // 在执行处理函数之前占用配额，并发的写请求不会超出配额
+        match state.store.reserve_write(&tenant, day, quota, 1).await {
+            Ok(Some(_)) => {}
+            Ok(None) => {
+                let retry_after = ratelimit::seconds_until_next_day(now);
+                let mut response = Error::QuotaExceeded(format!("Daily write quota of {} reached", quota)).into_response();
+                insert_headers(response.headers_mut(), &decision);
+                insert_retry_after(response.headers_mut(), retry_after);
+                return response;
+            }
+            Err(e) => return e.into_response(),
+        }
+    }
+
+    let mut response = next.run(request).await;
+    // 失败的写请求和重放的幂等响应没有执行写操作，退还占用的配额
+    let replayed = response.headers().contains_key(idempotency::REPLAYED_HEADER);
+    if reserved && (!response.status().is_success() || replayed) {
+        if let Err(e) = state.store.release_write(&tenant, day, 1).await {
+            log::error!("退还客户端{}的写操作配额失败: {}", tenant, e);
+        }
+    }
+    // 处理器拒绝超出配额的批次
+    if response.status() == StatusCode::TOO_MANY_REQUESTS && !response.headers().contains_key(header::RETRY_AFTER) {
+        insert_retry_after(response.headers_mut(), ratelimit::seconds_until_next_day(now));
+    }
+    insert_headers(response.headers_mut(), &decision);
+    response
+}
+
+/// 计数的租户：已认证的主体，或客户端IP
+fn tenant(principal: Option<&Principal>, request: &Request, config: &RateLimitConfig) -> String {
+    principal.and_then(Principal::client_id)
+        .unwrap_or_else(|| format!("ip:{}", client_ip(request, config)))
+}
+
+/// 客户端IP，信任反向代理时取`X-Forwarded-For`中由最外层可信代理追加的地址
+fn client_ip(request: &Request, config: &RateLimitConfig) -> String {
+    let forwarded = config.trust_forwarded_for
+        .then(|| request.headers().get_all("x-forwarded-for"))
+        .and_then(|values| {
+            // 多个同名头按顺序合并为一个列表
+            let addresses = values.iter()
+                .map(|value| value.to_str().ok())
+                .collect::<Option<Vec<_>>>()?
+                .into_iter()
+                .flat_map(|value| value.split(','))
+                .map(str::trim)
+                .filter(|address| !address.is_empty())
+                .collect::<Vec<_>>();
+            // 地址数不足代理层数时，整个列表都由可信代理写入，最左边的地址就是客户端
+            let index = addresses.len().saturating_sub(config.trusted_proxy_hops);
+            addresses.get(index).map(|address| address.to_string())
+        });
+
+    forwarded
+        .or_else(|| request.extensions().get::<ConnectInfo<SocketAddr>>().map(|info| info.0.ip().to_string()))
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// 写入`RateLimit-*`头
+fn insert_headers(headers: &mut HeaderMap, decision: &Decision) {
+    headers.insert(HeaderName::from_static("ratelimit-limit"), HeaderValue::from(decision.limit));
+    headers.insert(HeaderName::from_static("ratelimit-remaining"), HeaderValue::from(decision.remaining));
+    headers.insert(HeaderName::from_static("ratelimit-reset"), HeaderValue::from(decision.reset_secs));
+    if let Ok(policy) = HeaderValue::from_str(&format!("{};w=60;burst={}", decision.per_minute, decision.limit)) {
+        headers.insert(HeaderName::from_static("ratelimit-policy"), policy);
+    }
+}
+
+fn insert_retry_after(headers: &mut HeaderMap, seconds: u64) {
+    headers.insert(header::RETRY_AFTER, HeaderValue::from(seconds));
+}
@@ -0,0 +1,77 @@
+//! v1接口 - 以`ApiResponse`信封返回内部格式的DID文档，写操作由服务端使用请求中的私钥签名
+//!
+//! v1已弃用，所有响应带有`Deprecation`头（RFC 9745）和指向v2的`Link`头，配置了下线时间时还带有`Sunset`头（RFC 8594）。
+//! 不带版本前缀的旧路径是v1的别名，行为相同。
+
+use std::sync::Arc;
+use axum::http::{header, HeaderMap, HeaderName, HeaderValue};
+use axum::middleware;
+use axum::response::Response;
+use utoipa::openapi::Deprecated;
+use utoipa_axum::router::OpenApiRouter;
+use utoipa_axum::routes;
+use crate::api::{did, v2, AppState};
+use crate::config::ApiConfig;
+
+/// v1接口的路径前缀
+pub const PREFIX: &str = "/v1";
+
+/// v1路由，响应带有弃用信息
+pub fn routes(config: &ApiConfig) -> OpenApiRouter<Arc<AppState>> {
+    let mut router = OpenApiRouter::new()
+        .routes(routes!(did::create_did))
+        .routes(routes!(did::list_dids))
+        .routes(routes!(did::resolve_did, did::update_did, did::deactivate_did))
+        .routes(routes!(did::get_did_proof))
+        .routes(routes!(did::list_did_operations))
+        .routes(routes!(did::rotate_key))
+        .routes(routes!(did::add_service))
+        .routes(routes!(did::remove_service))
+        .routes(routes!(did::lookup_by_key))
+        .routes(routes!(did::lookup_by_service));
+
+    // 在文档中将全部v1操作标记为弃用
+    let mut spec = router.to_openapi();
+    for item in spec.paths.paths.values_mut() {
+        let operations = [&mut item.get, &mut item.put, &mut item.post, &mut item.delete, &mut item.patch];
+        for operation in operations.into_iter().flatten() {
+            operation.deprecated = Some(Deprecated::True);
+        }
+    }
+
+    let headers = deprecation_headers(config);
+    OpenApiRouter::with_openapi(spec)
+        .merge(router)
+        .layer(middleware::map_response(move |mut response: Response| {
+            let headers = headers.clone();
+            async move {
+                response.headers_mut().extend(headers);
+                response
+            }
+        }))
+}
+
+/// v1响应附带的弃用信息
+fn deprecation_headers(config: &ApiConfig) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+
+    headers.insert(
+        HeaderName::from_static("deprecation"),
+        HeaderValue::from_str(&format!("@{}", config.v1_deprecated_at.timestamp()))
+            .expect("deprecation date is a valid header value"),
+    );
+    headers.insert(
+        header::LINK,
+        HeaderValue::from_str(&format!("<{}>; rel=\"successor-version\"", v2::PREFIX))
+            .expect("successor link is a valid header value"),
+    );
+    if let Some(sunset) = config.v1_sunset {
+        headers.insert(
+            HeaderName::from_static("sunset"),
+            HeaderValue::from_str(&sunset.format("%a, %d %b %Y %H:%M:%S GMT").to_string())
+                .expect("sunset date is a valid header value"),
+        );
+    }
+
+    headers
+}
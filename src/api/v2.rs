@@ -0,0 +1,322 @@
+//! v2接口 - 返回符合DID Core规范的文档和解析元数据，写操作由客户端签名
+//!
+//! 与v1共用`did`模块中的服务：写操作的请求体就是操作日志中的签名操作，
+//! 服务端只校验签名而不接触私钥。成功响应直接返回资源，不再使用`ApiResponse`信封，错误总是问题详情。
+
+use std::sync::Arc;
+use axum::extract::State;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use serde::Serialize;
+use utoipa::ToSchema;
+use utoipa_axum::router::OpenApiRouter;
+use utoipa_axum::routes;
+use crate::anchoring::InclusionStatus;
+use crate::auth::{Principal, Scope};
+use crate::api::did::{ListDIDsQuery, LookupQuery, ResolveQuery};
+use crate::api::extract::{Json, Path, Query};
+use crate::api::conditional::{self, IfMatchHeader};
+use crate::api::idempotency::IdempotencyKeyHeader;
+use crate::api::error::Problem;
+use crate::api::{batch, negotiate, AppState};
+use crate::db::{datetime, DidRecord};
+use crate::did::representation::{self, CoreDocument, Representation};
+use crate::did::{self, DIDDocument, DidPage};
+use crate::oplog::{DidOperation, OperationLogEntry, SignedOperation};
+use crate::types::Error;
+
+/// v2接口的路径前缀
+pub const PREFIX: &str = "/v2";
+
+/// DID文档元数据
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentMetadata {
+    /// 创建时间（RFC 3339）
+    pub created: String,
+    /// 最后更新时间（RFC 3339）
+    pub updated: String,
+    /// 文档版本，与响应的`ETag`相同（不含引号）
+    pub version_id: String,
+    /// DID是否已停用
+    pub deactivated: bool,
+}
+
+/// DID解析结果
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolutionResult {
+    /// JSON-LD表示的DID文档
+    pub did_document: CoreDocument,
+    pub did_document_metadata: DocumentMetadata,
+}
+
+impl ResolutionResult {
+    fn new(document: &DIDDocument, metadata: DocumentMetadata) -> Result<Self, Error> {
+        Ok(Self {
+            did_document: CoreDocument::from_document(document, Representation::JsonLd)?,
+            did_document_metadata: metadata,
+        })
+    }
+
+    /// 存储中的记录，元数据取自记录
+    pub fn from_record(record: &DidRecord) -> Result<Self, Error> {
+        Self::new(&record.document, DocumentMetadata {
+            created: datetime::to_rfc3339(record.created_at),
+            updated: datetime::to_rfc3339(record.updated_at),
+            version_id: record.version_id.to_string(),
+            deactivated: !record.is_active,
+        })
+    }
+}
+
+/// 一页DID解析结果
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolutionList {
+    pub items: Vec<ResolutionResult>,
+    /// 下一页的游标，没有更多结果时为空
+    pub next_cursor: Option<String>,
+}
+
+impl TryFrom<DidPage> for ResolutionList {
+    type Error = Error;
+
+    fn try_from(page: DidPage) -> Result<Self, Error> {
+        Ok(Self {
+            items: page.records.iter().map(ResolutionResult::from_record).collect::<Result<_, _>>()?,
+            next_cursor: page.next_cursor,
+        })
+    }
+}
+
+/// v2路由，错误总是以问题详情返回（见`error::request_context`）
+pub fn routes() -> OpenApiRouter<Arc<AppState>> {
+    OpenApiRouter::new()
+        .routes(routes!(list))
+        .routes(routes!(resolve))
+        .routes(routes!(list_operations, submit_operation))
+        .routes(routes!(get_proof))
+        .routes(routes!(lookup_key))
+        .routes(routes!(lookup_service))
+        .routes(routes!(batch::submit_batch))
+}
+
+/// 解析DID，按`Accept`头或`accept`参数返回解析结果或DID文档表示；已停用的DID返回410和停用前的文档
+#[utoipa::path(
+    get,
+    path = "/dids/{did}",
+    operation_id = "resolve_did_v2",
+    tag = "did",
+    params(
+        ("did" = String, Path, description = "DID"),
+        ResolveQuery,
+        ("If-None-Match" = Option<String>, Header, description = "上次响应的`ETag`，版本未变化时返回304"),
+    ),
+    responses(
+        (
+            status = 200,
+            description = "DID解析结果，按内容协商返回解析结果或DID Core表示",
+            headers(("ETag" = String, description = "文档版本号")),
+            content(
+                (ResolutionResult = "application/json"),
+                (serde_json::Value = "application/did+json"),
+                (serde_json::Value = "application/did+ld+json"),
+                (serde_json::Value = "application/did+cbor"),
+            ),
+        ),
+        (status = 304, description = "文档版本与`If-None-Match`一致"),
+        (
+            status = 410,
+            description = "DID已停用，按内容协商返回停用前的解析结果或DID Core表示",
+            headers(("ETag" = String, description = "文档版本号")),
+            content(
+                (ResolutionResult = "application/json"),
+                (serde_json::Value = "application/did+json"),
+                (serde_json::Value = "application/did+ld+json"),
+                (serde_json::Value = "application/did+cbor"),
+            ),
+        ),
+    ),
+)]
+pub async fn resolve(
+    State(state): State<Arc<AppState>>,
+    Path(did): Path<String>,
+    Query(query): Query<ResolveQuery>,
+    headers: HeaderMap,
+) -> Result<Response, Error> {
+    let representation = negotiate::document_representation(query.accept.as_deref(), &headers)?;
+
+    let (status, record) = match did::cache::resolve(&state.store, &did).await {
+        Ok(record) => (StatusCode::OK, record),
+        Err(Error::NotFound(message)) => {
+            // 停用的DID不参与解析，从存储中读取停用前的文档，同样支持条件请求和内容协商
+            match state.store.get_did_record(&did).await?.filter(|record| !record.is_active) {
+                Some(record) => (StatusCode::GONE, record),
+                None => return Err(Error::NotFound(message)),
+            }
+        }
+        Err(e) => return Err(e),
+    };
+    if conditional::is_not_modified(&headers, record.version_id) {
+        return Ok(conditional::not_modified(record.version_id));
+    }
+    let etag = [(header::ETAG, conditional::etag(record.version_id))];
+
+    let Some(representation) = representation else {
+        return Ok((
+            status,
+            [(header::VARY, "accept")],
+            etag,
+            Json(ResolutionResult::from_record(&record)?),
+        ).into_response());
+    };
+
+    Ok((
+        status,
+        [(header::CONTENT_TYPE, representation.media_type()), (header::VARY, "accept")],
+        etag,
+        representation::produce(&record.document, representation)?,
+    ).into_response())
+}
+
+/// 按条件分页列出DID
+#[utoipa::path(
+    get,
+    path = "/dids",
+    operation_id = "list_dids_v2",
+    tag = "did",
+    security(("bearer" = ["did:read"]), ("api_key" = ["did:read"])),
+    params(ListDIDsQuery),
+    responses((status = 200, description = "一页DID解析结果", body = ResolutionList)),
+)]
+pub async fn list(
+    State(state): State<Arc<AppState>>,
+    principal: Principal,
+    Query(params): Query<ListDIDsQuery>,
+) -> Result<Json<ResolutionList>, Error> {
+    principal.require(Scope::DidRead)?;
+    let page = did::list_dids(state.store.as_ref(), params.to_did_query()?, params.cursor.as_deref()).await?;
+    Ok(Json(page.try_into()?))
+}
+
+/// 提交由客户端签名的操作
+///
+/// 签名内容为`{"did": <DID>, "operation": <operation>, "previousVersionId": <版本>}`的紧凑JSON，
+/// 字段顺序与操作日志相同，没有`previousVersionId`时省略该字段。
+/// 创建操作要求`did:create`权限范围并返回201，其他操作要求`did:update`权限范围并返回200。
+/// `previousVersionId`和`If-Match`都必须与当前版本一致，否则返回412。
+#[utoipa::path(
+    post,
+    path = "/dids/{did}/operations",
+    operation_id = "submit_did_operation",
+    tag = "did",
+    security(("bearer" = ["did:create", "did:update"]), ("api_key" = ["did:create", "did:update"])),
+    params(("did" = String, Path, description = "DID"), IfMatchHeader, IdempotencyKeyHeader),
+    request_body = SignedOperation,
+    responses(
+        (status = 201, description = "DID已创建", body = ResolutionResult, headers(("ETag" = String, description = "文档版本号"))),
+        (status = 200, description = "变更后的解析结果", body = ResolutionResult, headers(("ETag" = String, description = "新的文档版本号"))),
+        (status = 412, description = "文档已被修改，版本与`previousVersionId`或`If-Match`不一致", body = Problem),
+    ),
+)]
+pub async fn submit_operation(
+    State(state): State<Arc<AppState>>,
+    principal: Principal,
+    Path(did): Path<String>,
+    headers: HeaderMap,
+    Json(operation): Json<SignedOperation>,
+) -> Result<Response, Error> {
+    let (status, scope) = match operation.operation {
+        DidOperation::Create { .. } => (StatusCode::CREATED, Scope::DidCreate),
+        _ => (StatusCode::OK, Scope::DidUpdate),
+    };
+    principal.require_did(scope, &did)?;
+    // 签名操作中的previousVersionId同样满足前提条件的要求
+    let required = state.require_preconditions
+        && status == StatusCode::OK
+        && operation.previous_version_id.is_none();
+    let expected_version = conditional::if_match(&headers, required)?;
+    let record = did::submit_operation(state.store.as_ref(), &did, operation, expected_version).await?;
+
+    Ok((
+        status,
+        [(header::ETAG, conditional::etag(record.version_id))],
+        Json(ResolutionResult::from_record(&record)?),
+    ).into_response())
+}
+
+/// 按顺序列出DID的签名操作
+#[utoipa::path(
+    get,
+    path = "/dids/{did}/operations",
+    operation_id = "list_did_operations_v2",
+    tag = "did",
+    params(("did" = String, Path, description = "DID")),
+    responses((status = 200, description = "按顺序排列的签名操作", body = Vec<OperationLogEntry>)),
+)]
+pub async fn list_operations(
+    State(state): State<Arc<AppState>>,
+    Path(did): Path<String>,
+) -> Result<Json<Vec<OperationLogEntry>>, Error> {
+    Ok(Json(did::list_operations(state.store.as_ref(), &did).await?))
+}
+
+/// 获取DID当前版本的批量锚定包含证明
+#[utoipa::path(
+    get,
+    path = "/dids/{did}/proof",
+    operation_id = "get_did_proof_v2",
+    tag = "did",
+    params(("did" = String, Path, description = "DID")),
+    responses((status = 200, description = "包含证明及其验证结果", body = InclusionStatus)),
+)]
+pub async fn get_proof(
+    State(state): State<Arc<AppState>>,
+    Path(did): Path<String>,
+) -> Result<Json<InclusionStatus>, Error> {
+    Ok(Json(did::get_inclusion_proof(state.store.as_ref(), &did).await?))
+}
+
+/// 按公钥反向查询DID
+#[utoipa::path(
+    get,
+    path = "/lookup/key/{multibase}",
+    operation_id = "lookup_by_key_v2",
+    tag = "lookup",
+    security(("bearer" = ["did:read"]), ("api_key" = ["did:read"])),
+    params(("multibase" = String, Path, description = "base58btc multibase编码的公钥"), LookupQuery),
+    responses((status = 200, description = "引用该公钥的DID", body = ResolutionList)),
+)]
+pub async fn lookup_key(
+    State(state): State<Arc<AppState>>,
+    principal: Principal,
+    Path(multibase): Path<String>,
+    Query(params): Query<LookupQuery>,
+) -> Result<Json<ResolutionList>, Error> {
+    principal.require(Scope::DidRead)?;
+    let page = did::lookup_by_key(state.store.as_ref(), &multibase, params.to_did_query()?, params.cursor.as_deref()).await?;
+    Ok(Json(page.try_into()?))
+}
+
+/// 按服务端点反向查询DID
+#[utoipa::path(
+    get,
+    path = "/lookup/service",
+    operation_id = "lookup_by_service_v2",
+    tag = "lookup",
+    security(("bearer" = ["did:read"]), ("api_key" = ["did:read"])),
+    params(LookupQuery),
+    responses((status = 200, description = "引用该服务端点的DID", body = ResolutionList)),
+)]
+pub async fn lookup_service(
+    State(state): State<Arc<AppState>>,
+    principal: Principal,
+    Query(params): Query<LookupQuery>,
+) -> Result<Json<ResolutionList>, Error> {
+    principal.require(Scope::DidRead)?;
+    let endpoint = params.endpoint.as_deref()
+        .ok_or_else(|| Error::validation("endpoint", "Missing endpoint parameter"))?;
+    let page = did::lookup_by_service(state.store.as_ref(), endpoint, params.to_did_query()?, params.cursor.as_deref()).await?;
+    Ok(Json(page.try_into()?))
+}
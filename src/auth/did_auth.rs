@@ -0,0 +1,111 @@
+//! DID Auth - 以DID文档中的认证密钥签名一次性质询来证明对DID的控制
+//!
+//! 质询是一条JSON消息，写明用途、受众（本服务的令牌签发者）、DID、一次性随机数和过期时间，
+//! 客户端对消息字符串的UTF-8字节签名，服务端逐项核对后才接受签名，签给其他服务或其他DID的质询不能重用。
+//! 随机数保存在`nonces`表中，签发对象为DID；验证时先取出并删除随机数，因此每个质询只能使用一次，
+//! 验证失败也会作废质询。
+
+use serde::{Deserialize, Serialize};
+use crate::db::DidStore;
+use crate::did;
+use crate::types::Error;
+use crate::utils;
+
+/// 质询的有效期（秒）
+pub const CHALLENGE_TTL_SECS: u64 = 300;
+/// 质询消息的用途
+pub const CHALLENGE_TYPE: &str = "did-auth";
+/// 质询的随机字节数
+const CHALLENGE_BYTES: usize = 32;
+
+/// 客户端签名的质询消息
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChallengeMessage {
+    /// 消息用途，固定为`did-auth`
+    #[serde(rename = "type")]
+    pub type_: String,
+    /// 签发质询的服务，即`auth.token_issuer`
+    pub audience: String,
+    pub did: String,
+    /// 一次性随机数
+    pub nonce: String,
+    /// 签发时间（Unix秒）
+    pub issued_at: u64,
+    /// 过期时间（Unix秒）
+    pub expires_at: u64,
+}
+
+/// 签发给DID的质询
+#[derive(Debug, Clone, Serialize)]
+pub struct Challenge {
+    pub did: String,
+    /// 待签名的质询消息，即`ChallengeMessage`的JSON
+    pub message: String,
+    /// 过期时间（Unix秒）
+    pub expires_at: u64,
+}
+
+/// 为活跃的DID签发质询，`audience`为本服务的令牌签发者
+pub async fn issue_challenge(store: &dyn DidStore, audience: &str, did: &str) -> Result<Challenge, Error> {
+    did::resolve_did(store, did).await?;
+
+    let issued_at = utils::current_timestamp();
+    let message = ChallengeMessage {
+        type_: CHALLENGE_TYPE.to_string(),
+        audience: audience.to_string(),
+        did: did.to_string(),
+        nonce: utils::encode_base58(&utils::generate_random_bytes(CHALLENGE_BYTES)),
+        issued_at,
+        expires_at: issued_at + CHALLENGE_TTL_SECS,
+    };
+    store.store_nonce(&message.nonce, did, message.expires_at).await?;
+
+    Ok(Challenge {
+        did: did.to_string(),
+        message: serde_json::to_string(&message).map_err(|e| Error::SerializationError(e.to_string()))?,
+        expires_at: message.expires_at,
+    })
+}
+
+/// 校验质询消息的签名：消息必须由本服务签发给该DID且未过期，`verification_method`必须是文档中的认证方法
+pub async fn verify_response(
+    store: &dyn DidStore,
+    audience: &str,
+    did: &str,
+    message: &str,
+    verification_method: &str,
+    signature: &[u8],
+) -> Result<(), Error> {
+    let challenge: ChallengeMessage = serde_json::from_str(message)
+        .map_err(|e| Error::Unauthorized(format!("Malformed challenge message: {}", e)))?;
+    if store.consume_nonce(&challenge.nonce).await?.as_deref() != Some(did) {
+        return Err(Error::Unauthorized("Unknown or expired challenge".to_string()));
+    }
+    if challenge.type_ != CHALLENGE_TYPE {
+        return Err(Error::Unauthorized(format!("Challenge message is not a {} challenge", CHALLENGE_TYPE)));
+    }
+    if challenge.audience != audience {
+        return Err(Error::Unauthorized(format!("Challenge was issued for another audience: {}", challenge.audience)));
+    }
+    if challenge.did != did {
+        return Err(Error::Unauthorized(format!("Challenge was issued for another DID: {}", challenge.did)));
+    }
+    if challenge.expires_at <= utils::current_timestamp() {
+        return Err(Error::Unauthorized("Challenge has expired".to_string()));
+    }
+
+    let document = did::resolve_did(store, did).await?;
+    if !document.authentication.iter().any(|id| id == verification_method) {
+        return Err(Error::Unauthorized(format!("{} is not an authentication method of {}", verification_method, did)));
+    }
+    let key = document.public_keys.iter()
+        .find(|key| key.id == verification_method)
+        .ok_or_else(|| Error::Unauthorized(format!("Verification method not found: {}", verification_method)))?;
+    let public_key = utils::decode_base58(&key.public_key_base58).map_err(Error::CryptoError)?;
+
+    match utils::verify_signature(message.as_bytes(), signature, &public_key) {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(Error::Unauthorized("Invalid challenge signature".to_string())),
+        Err(e) => Err(Error::Unauthorized(format!("Invalid challenge signature: {}", e))),
+    }
+}
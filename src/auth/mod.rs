@@ -0,0 +1,435 @@
+//! 认证模块 - API密钥、签发者令牌以及基于角色的权限范围
+//!
+//! API密钥只在创建时显示一次，数据库中只保存密钥的SHA-256哈希；令牌是由签发者Ed25519私钥签名的JWT。
+//! 两种凭据都解析为带有权限范围的`Principal`，角色是一组预定义的权限范围。
+//! DID的控制者还可以通过DID Auth质询登录，得到只能操作该DID的会话令牌。
+
+pub mod did_auth;
+pub mod token;
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::path::Path;
+use ed25519_dalek::SigningKey;
+use serde::{Deserialize, Serialize};
+use crate::config::AuthConfig;
+use crate::db::DidStore;
+use crate::types::Error;
+use crate::utils;
+
+/// API密钥的前缀，格式为`didk_<ID>_<密钥>`
+pub const API_KEY_PREFIX: &str = "didk_";
+/// API密钥ID的随机字节数
+const API_KEY_ID_BYTES: usize = 8;
+/// API密钥的随机字节数
+const API_KEY_SECRET_BYTES: usize = 32;
+
+/// 权限范围
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Scope {
+    /// 列出和反向查询DID
+    #[serde(rename = "did:read")]
+    DidRead,
+    /// 创建DID
+    #[serde(rename = "did:create")]
+    DidCreate,
+    /// 更新、轮换密钥、管理服务端点和停用DID
+    #[serde(rename = "did:update")]
+    DidUpdate,
+    /// 访问管理接口
+    #[serde(rename = "did:admin")]
+    DidAdmin,
+}
+
+impl Scope {
+    /// 全部权限范围
+    pub const ALL: [Scope; 4] = [Scope::DidRead, Scope::DidCreate, Scope::DidUpdate, Scope::DidAdmin];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::DidRead => "did:read",
+            Scope::DidCreate => "did:create",
+            Scope::DidUpdate => "did:update",
+            Scope::DidAdmin => "did:admin",
+        }
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for Scope {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL.into_iter()
+            .find(|scope| scope.as_str() == s)
+            .ok_or_else(|| Error::InvalidInput(format!("Unknown scope: {}", s)))
+    }
+}
+
+/// 角色，每个角色对应一组权限范围
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// 只读
+    Reader,
+    /// 管理DID
+    Writer,
+    /// 全部权限，包括管理接口
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Reader => "reader",
+            Role::Writer => "writer",
+            Role::Admin => "admin",
+        }
+    }
+
+    /// 角色授予的权限范围
+    pub fn scopes(&self) -> &'static [Scope] {
+        match self {
+            Role::Reader => &[Scope::DidRead],
+            Role::Writer => &[Scope::DidRead, Scope::DidCreate, Scope::DidUpdate],
+            Role::Admin => &Scope::ALL,
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for Role {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reader" => Ok(Role::Reader),
+            "writer" => Ok(Role::Writer),
+            "admin" => Ok(Role::Admin),
+            other => Err(Error::InvalidInput(format!("Unknown role: {}", other))),
+        }
+    }
+}
+
+/// 角色和单独授予的权限范围合并后的权限
+pub fn grant(role: Option<Role>, scopes: &[Scope]) -> BTreeSet<Scope> {
+    role.map(|role| role.scopes()).unwrap_or_default().iter()
+        .chain(scopes)
+        .copied()
+        .collect()
+}
+
+/// 将权限范围列表格式化为空格分隔的字符串
+pub fn format_scopes<'a>(scopes: impl IntoIterator<Item = &'a Scope>) -> String {
+    scopes.into_iter().map(Scope::as_str).collect::<Vec<_>>().join(" ")
+}
+
+/// 解析空格分隔的权限范围
+pub fn parse_scopes(value: &str) -> Result<Vec<Scope>, Error> {
+    value.split_whitespace().map(str::parse).collect()
+}
+
+/// 认证主体使用的凭据
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Credential {
+    /// 未提供凭据
+    Anonymous,
+    /// 未启用认证，所有请求都拥有全部权限
+    Disabled,
+    ApiKey {
+        id: String,
+        /// 密钥单独设置的每日写操作配额
+        daily_write_quota: Option<u64>,
+    },
+    Token,
+}
+
+/// 认证主体
+#[derive(Debug, Clone, Serialize)]
+pub struct Principal {
+    /// API密钥名称或令牌的`sub`
+    pub subject: String,
+    pub credential: Credential,
+    pub role: Option<Role>,
+    pub scopes: BTreeSet<Scope>,
+    /// DID会话只能操作的DID，为空时不限DID
+    pub did: Option<String>,
+}
+
+impl Principal {
+    /// 未提供凭据的请求
+    pub fn anonymous() -> Self {
+        Self {
+            subject: "anonymous".to_string(),
+            credential: Credential::Anonymous,
+            role: None,
+            scopes: BTreeSet::new(),
+            did: None,
+        }
+    }
+
+    /// 未启用认证时的请求，拥有全部权限
+    pub fn unrestricted() -> Self {
+        Self {
+            subject: "anonymous".to_string(),
+            credential: Credential::Disabled,
+            role: Some(Role::Admin),
+            scopes: Scope::ALL.into_iter().collect(),
+            did: None,
+        }
+    }
+
+    /// 已认证主体的标识：API密钥ID、DID会话的DID或令牌主体；未认证时为`None`
+    pub fn client_id(&self) -> Option<String> {
+        match (&self.credential, &self.did) {
+            (Credential::ApiKey { id, .. }, _) => Some(format!("key:{}", id)),
+            (Credential::Token, Some(did)) => Some(format!("did:{}", did)),
+            (Credential::Token, None) => Some(format!("sub:{}", self.subject)),
+            _ => None,
+        }
+    }
+
+    pub fn has(&self, scope: Scope) -> bool {
+        self.scopes.contains(&scope)
+    }
+
+    /// 要求主体拥有指定权限范围：未提供凭据时返回401，权限不足时返回403
+    pub fn require(&self, scope: Scope) -> Result<(), Error> {
+        if self.has(scope) {
+            return Ok(());
+        }
+        match self.credential {
+            Credential::Anonymous => Err(Error::Unauthorized("Authentication required".to_string())),
+            _ => Err(Error::Forbidden(format!("{} lacks the {} scope", self.subject, scope))),
+        }
+    }
+
+    /// 要求主体拥有指定权限范围，且DID会话只能操作自己的DID
+    pub fn require_did(&self, scope: Scope, did: &str) -> Result<(), Error> {
+        self.require(scope)?;
+        match &self.did {
+            Some(own) if own != did => Err(Error::Forbidden(format!("{} may only act on its own DID", self.subject))),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// API密钥记录
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiKey {
+    pub id: String,
+    pub name: String,
+    pub role: Option<Role>,
+    /// 角色之外单独授予的权限范围
+    pub scopes: Vec<Scope>,
+    /// 密钥的SHA-256十六进制哈希
+    #[serde(skip_serializing)]
+    pub secret_hash: String,
+    pub created_at: u64,
+    pub expires_at: Option<u64>,
+    pub revoked_at: Option<u64>,
+    /// 每日写操作配额，为空时使用`rate_limit.daily_write_quota`，为0时不限制
+    pub daily_write_quota: Option<u64>,
+}
+
+impl ApiKey {
+    /// 密钥当前是否可用
+    pub fn is_usable(&self, now: u64) -> bool {
+        self.revoked_at.is_none() && self.expires_at.is_none_or(|expires_at| expires_at > now)
+    }
+}
+
+/// 创建API密钥，返回密钥记录和只显示这一次的完整密钥
+pub async fn create_api_key(
+    store: &dyn DidStore,
+    name: &str,
+    role: Option<Role>,
+    scopes: Vec<Scope>,
+    ttl_secs: Option<u64>,
+    daily_write_quota: Option<u64>,
+) -> Result<(ApiKey, String), Error> {
+    if name.trim().is_empty() {
+        return Err(Error::InvalidInput("API key name must not be empty".to_string()));
+    }
+    if role.is_none() && scopes.is_empty() {
+        return Err(Error::InvalidInput("An API key needs a role or at least one scope".to_string()));
+    }
+
+    let id = utils::to_hex(&utils::generate_random_bytes(API_KEY_ID_BYTES));
+    let secret = utils::encode_base58(&utils::generate_random_bytes(API_KEY_SECRET_BYTES));
+    let now = utils::current_timestamp();
+    let key = ApiKey {
+        id: id.clone(),
+        name: name.to_string(),
+        role,
+        scopes,
+        secret_hash: hash_secret(&secret),
+        created_at: now,
+        expires_at: ttl_secs.map(|ttl| now + ttl),
+        revoked_at: None,
+        daily_write_quota,
+    };
+
+    store.create_api_key(&key).await?;
+    Ok((key, format!("{}{}_{}", API_KEY_PREFIX, id, secret)))
+}
+
+/// API密钥的哈希
+fn hash_secret(secret: &str) -> String {
+    utils::to_hex(&utils::sha256(secret.as_bytes()))
+}
+
+/// 验证API密钥
+async fn authenticate_api_key(store: &dyn DidStore, presented: &str) -> Result<Principal, Error> {
+    let invalid = || Error::Unauthorized("Invalid API key".to_string());
+    let (id, secret) = presented.strip_prefix(API_KEY_PREFIX)
+        .and_then(|rest| rest.split_once('_'))
+        .ok_or_else(invalid)?;
+
+    let key = store.get_api_key(id).await?.ok_or_else(invalid)?;
+    if !utils::constant_time_eq(hash_secret(secret).as_bytes(), key.secret_hash.as_bytes()) {
+        return Err(invalid());
+    }
+    if !key.is_usable(utils::current_timestamp()) {
+        return Err(Error::Unauthorized("API key has expired or been revoked".to_string()));
+    }
+
+    Ok(Principal {
+        subject: key.name.clone(),
+        credential: Credential::ApiKey { id: key.id.clone(), daily_write_quota: key.daily_write_quota },
+        role: key.role,
+        scopes: grant(key.role, &key.scopes),
+        did: None,
+    })
+}
+
+/// 读取Base58编码的签发者私钥文件
+pub fn load_issuer_key(path: &Path) -> Result<SigningKey, Error> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| Error::InvalidInput(format!("Failed to read issuer key file {}: {}", path.display(), e)))?;
+    let bytes: [u8; 32] = utils::decode_base58(content.trim())
+        .map_err(Error::CryptoError)?
+        .try_into()
+        .map_err(|_| Error::CryptoError(format!("Issuer key in {} must be 32 bytes", path.display())))?;
+
+    Ok(SigningKey::from_bytes(&bytes))
+}
+
+/// 按认证配置验证请求凭据
+#[derive(Debug, Clone)]
+pub struct Authenticator {
+    enabled: bool,
+    issuer: String,
+    issuer_key: Option<SigningKey>,
+    token_ttl_secs: u64,
+    session_ttl_secs: u64,
+}
+
+impl Authenticator {
+    pub fn from_config(config: &AuthConfig) -> Result<Self, Error> {
+        let issuer_key = config.issuer_key_file.as_deref().map(load_issuer_key).transpose()?;
+        if config.enabled && issuer_key.is_none() {
+            log::warn!("未配置令牌签发者私钥，只接受API密钥");
+        }
+
+        Ok(Self {
+            enabled: config.enabled,
+            issuer: config.token_issuer.clone(),
+            issuer_key,
+            token_ttl_secs: config.token_ttl_secs,
+            session_ttl_secs: config.session_ttl_secs,
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// 验证凭据：以`didk_`开头的是API密钥，其他视为令牌；没有凭据时返回匿名主体
+    pub async fn authenticate(&self, store: &dyn DidStore, credential: Option<&str>) -> Result<Principal, Error> {
+        if !self.enabled {
+            return Ok(Principal::unrestricted());
+        }
+
+        match credential {
+            None => Ok(Principal::anonymous()),
+            Some(key) if key.starts_with(API_KEY_PREFIX) => authenticate_api_key(store, key).await,
+            Some(token) => {
+                let issuer_key = self.issuer_key.as_ref()
+                    .ok_or_else(|| Error::Unauthorized("Bearer tokens are not accepted by this server".to_string()))?;
+                let claims = token::verify(token, &issuer_key.verifying_key(), &self.issuer, utils::current_timestamp())?;
+                let scopes = parse_scopes(&claims.scope)
+                    .map_err(|e| Error::Unauthorized(format!("Invalid token scope: {}", e)))?;
+
+                Ok(Principal {
+                    scopes: grant(claims.role, &scopes),
+                    subject: claims.sub,
+                    credential: Credential::Token,
+                    role: claims.role,
+                    did: claims.did,
+                })
+            }
+        }
+    }
+
+    /// 令牌签发者，也是DID Auth质询的受众
+    pub fn issuer(&self) -> &str {
+        &self.issuer
+    }
+
+    /// 是否配置了签发者私钥，可以签发令牌
+    pub fn issues_tokens(&self) -> bool {
+        self.issuer_key.is_some()
+    }
+
+    /// 签发令牌，`ttl_secs`为空时使用配置的有效期
+    pub fn issue_token(&self, subject: &str, role: Option<Role>, scopes: &[Scope], ttl_secs: Option<u64>) -> Result<String, Error> {
+        if role.is_none() && scopes.is_empty() {
+            return Err(Error::InvalidInput("A token needs a role or at least one scope".to_string()));
+        }
+
+        let now = utils::current_timestamp();
+        self.sign(token::Claims {
+            iss: self.issuer.clone(),
+            sub: subject.to_string(),
+            iat: now,
+            exp: now + ttl_secs.unwrap_or(self.token_ttl_secs),
+            role,
+            scope: format_scopes(scopes),
+            did: None,
+        })
+    }
+
+    /// 为通过DID Auth的DID签发会话令牌，令牌只授予对该DID的`did:update`权限范围
+    pub fn issue_session(&self, did: &str) -> Result<(String, u64), Error> {
+        let now = utils::current_timestamp();
+        let token = self.sign(token::Claims {
+            iss: self.issuer.clone(),
+            sub: did.to_string(),
+            iat: now,
+            exp: now + self.session_ttl_secs,
+            role: None,
+            scope: Scope::DidUpdate.to_string(),
+            did: Some(did.to_string()),
+        })?;
+
+        Ok((token, self.session_ttl_secs))
+    }
+
+    fn sign(&self, claims: token::Claims) -> Result<String, Error> {
+        let issuer_key = self.issuer_key.as_ref()
+            .ok_or_else(|| Error::InvalidInput("auth.issuer_key_file is required to issue tokens".to_string()))?;
+        token::sign(&claims, issuer_key)
+    }
+}
@@ -0,0 +1,95 @@
+//! 令牌 - 以签发者Ed25519私钥签名的JWT（RFC 7519，`alg`为`EdDSA`）
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use crate::types::Error;
+use super::Role;
+
+/// JWS签名算法
+const ALGORITHM: &str = "EdDSA";
+
+/// JWS头
+#[derive(Debug, Serialize, Deserialize)]
+struct Header {
+    alg: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    typ: Option<String>,
+}
+
+/// 令牌声明
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Claims {
+    /// 签发者
+    pub iss: String,
+    /// 主体
+    pub sub: String,
+    /// 签发时间（Unix秒）
+    pub iat: u64,
+    /// 过期时间（Unix秒）
+    pub exp: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<Role>,
+    /// 空格分隔的权限范围
+    #[serde(default)]
+    pub scope: String,
+    /// DID会话令牌限定的DID
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub did: Option<String>,
+}
+
+/// 签名并编码令牌
+pub fn sign(claims: &Claims, key: &SigningKey) -> Result<String, Error> {
+    let header = Header { alg: ALGORITHM.to_string(), typ: Some("JWT".to_string()) };
+    let signing_input = format!("{}.{}", encode_part(&header)?, encode_part(claims)?);
+    let signature = key.sign(signing_input.as_bytes());
+
+    Ok(format!("{}.{}", signing_input, URL_SAFE_NO_PAD.encode(signature.to_bytes())))
+}
+
+/// 校验令牌的签名、签发者和有效期，返回声明
+pub fn verify(token: &str, key: &VerifyingKey, issuer: &str, now: u64) -> Result<Claims, Error> {
+    let invalid = |reason: &str| Error::Unauthorized(format!("Invalid token: {}", reason));
+
+    let mut parts = token.split('.');
+    let (Some(header), Some(payload), Some(signature), None) = (parts.next(), parts.next(), parts.next(), parts.next()) else {
+        return Err(invalid("expected three dot-separated parts"));
+    };
+
+    let header: Header = decode_part(header).ok_or_else(|| invalid("malformed header"))?;
+    if header.alg != ALGORITHM {
+        return Err(invalid("unsupported algorithm"));
+    }
+
+    let signature: [u8; 64] = URL_SAFE_NO_PAD.decode(signature).ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| invalid("malformed signature"))?;
+    let signing_input = &token[..token.len() - signature_len(token)];
+    key.verify(signing_input.as_bytes(), &Signature::from_bytes(&signature))
+        .map_err(|_| invalid("bad signature"))?;
+
+    let claims: Claims = decode_part(payload).ok_or_else(|| invalid("malformed claims"))?;
+    if claims.iss != issuer {
+        return Err(invalid("unknown issuer"));
+    }
+    if claims.exp <= now {
+        return Err(Error::Unauthorized("Token has expired".to_string()));
+    }
+
+    Ok(claims)
+}
+
+/// 最后一个`.`及签名部分的长度
+fn signature_len(token: &str) -> usize {
+    token.rfind('.').map(|index| token.len() - index).unwrap_or(0)
+}
+
+fn encode_part<T: Serialize>(value: &T) -> Result<String, Error> {
+    let json = serde_json::to_vec(value).map_err(|e| Error::SerializationError(e.to_string()))?;
+    Ok(URL_SAFE_NO_PAD.encode(json))
+}
+
+fn decode_part<T: for<'de> Deserialize<'de>>(part: &str) -> Option<T> {
+    serde_json::from_slice(&URL_SAFE_NO_PAD.decode(part).ok()?).ok()
+}
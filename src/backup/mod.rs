@@ -0,0 +1,349 @@
+//! 备份模块 - 以JSON Lines格式导出和导入DID注册表
+//!
+//! 归档第一行为清单，记录格式版本、导出时的架构版本、各类记录的数量以及其余各行的SHA-256校验和；
+//! 之后每行一条记录，依次为DID记录、历史版本和操作日志。
+//! 校验和不带密钥，只用于发现损坏的归档；导入前沿哈希链重放归档中的操作并校验控制者签名，
+//! DID记录和历史版本必须与重放结果一致。
+
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, Write};
+use serde::{Deserialize, Serialize};
+use crate::db::{DidRecord, DidStore, DidVersion, migrations};
+use crate::did;
+use crate::oplog::{self, OperationLogEntry, SignedOperation, replay};
+use crate::types::Error;
+use crate::utils;
+use utoipa::ToSchema;
+
+/// 归档格式名称
+pub const ARCHIVE_FORMAT: &str = "did-system-export";
+/// 当前的归档格式版本
+pub const ARCHIVE_VERSION: u32 = 1;
+
+/// 导入时DID已存在的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    /// 保留本地记录
+    Skip,
+    /// 以归档中的记录覆盖本地记录
+    Overwrite,
+    /// 存在冲突时整个导入失败
+    Fail,
+}
+
+impl std::str::FromStr for ConflictPolicy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "skip" => Ok(Self::Skip),
+            "overwrite" => Ok(Self::Overwrite),
+            "fail" => Ok(Self::Fail),
+            other => Err(Error::InvalidInput(format!("Unknown conflict policy: {}", other))),
+        }
+    }
+}
+
+/// 注册表的全部数据
+#[derive(Debug, Clone, Default)]
+pub struct Registry {
+    pub dids: Vec<DidRecord>,
+    pub versions: Vec<DidVersion>,
+    pub operations: Vec<OperationLogEntry>,
+}
+
+/// 各类记录的数量
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordCounts {
+    pub dids: u64,
+    pub versions: u64,
+    pub operations: u64,
+}
+
+impl RecordCounts {
+    /// 统计注册表中的记录数量
+    fn of(registry: &Registry) -> Self {
+        Self {
+            dids: registry.dids.len() as u64,
+            versions: registry.versions.len() as u64,
+            operations: registry.operations.len() as u64,
+        }
+    }
+}
+
+/// 归档清单
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub format: String,
+    pub format_version: u32,
+    /// 导出时间（Unix秒）
+    pub exported_at: u64,
+    /// 导出时数据库的架构版本
+    pub schema_version: u32,
+    pub counts: RecordCounts,
+    /// 清单之后各行内容的SHA-256十六进制
+    pub sha256: String,
+}
+
+/// 归档中的一行
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum ArchiveLine {
+    Manifest(Manifest),
+    Did(DidRecord),
+    Version(DidVersion),
+    Operation(OperationLogEntry),
+}
+
+/// 导入结果
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct ImportReport {
+    /// 新增的DID数量
+    pub imported: u64,
+    /// 被覆盖的本地DID数量
+    pub overwritten: u64,
+    /// 因本地已存在而跳过的DID
+    pub skipped: Vec<String>,
+    /// 写入的历史版本数量
+    pub versions: u64,
+    /// 原样恢复的操作日志条数；本地日志不为空时为0，归档中的签名操作改为追加到本地日志
+    pub operations_restored: u64,
+}
+
+/// 导出整个注册表并写入归档，返回清单
+pub async fn export<W: Write>(store: &dyn DidStore, writer: &mut W) -> Result<Manifest, Error> {
+    let registry = store.export_registry().await?;
+
+    let mut body = Vec::new();
+    for did in &registry.dids {
+        write_line(&mut body, &ArchiveLine::Did(did.clone()))?;
+    }
+    for version in &registry.versions {
+        write_line(&mut body, &ArchiveLine::Version(version.clone()))?;
+    }
+    for operation in &registry.operations {
+        write_line(&mut body, &ArchiveLine::Operation(operation.clone()))?;
+    }
+
+    let manifest = Manifest {
+        format: ARCHIVE_FORMAT.to_string(),
+        format_version: ARCHIVE_VERSION,
+        exported_at: utils::current_timestamp(),
+        schema_version: migrations::latest_version(),
+        counts: RecordCounts::of(&registry),
+        sha256: utils::to_hex(&utils::sha256(&body)),
+    };
+
+    let mut header = Vec::new();
+    write_line(&mut header, &ArchiveLine::Manifest(manifest.clone()))?;
+    writer.write_all(&header)
+        .and_then(|_| writer.write_all(&body))
+        .and_then(|_| writer.flush())
+        .map_err(|e| Error::InternalError(format!("Failed to write archive: {}", e)))?;
+
+    log::info!(
+        "已导出{}个DID、{}个历史版本和{}条操作日志",
+        manifest.counts.dids, manifest.counts.versions, manifest.counts.operations
+    );
+    Ok(manifest)
+}
+
+/// 读取并校验归档，再按冲突策略导入
+pub async fn import<R: BufRead>(store: &dyn DidStore, reader: &mut R, policy: ConflictPolicy) -> Result<ImportReport, Error> {
+    let (manifest, registry) = read_archive(reader)?;
+    let unsigned = verify_registry(&registry)?;
+    if unsigned > 0 {
+        log::warn!("归档中有{}条没有控制者签名的系统操作，只能原样恢复到空的注册表", unsigned);
+    }
+    log::info!(
+        "开始导入归档（导出于{}，架构版本{}）: {}个DID",
+        manifest.exported_at, manifest.schema_version, manifest.counts.dids
+    );
+
+    let report = store.import_registry(&registry, policy).await?;
+    for record in &registry.dids {
+        did::cache::invalidate(&record.did);
+    }
+    log::info!(
+        "导入完成: 新增{}，覆盖{}，跳过{}",
+        report.imported, report.overwritten, report.skipped.len()
+    );
+
+    Ok(report)
+}
+
+/// 读取归档并校验清单、校验和与记录数量
+pub fn read_archive<R: BufRead>(reader: &mut R) -> Result<(Manifest, Registry), Error> {
+    let mut lines = reader.lines();
+
+    let first = lines.next().transpose().map_err(read_error)?
+        .ok_or_else(|| Error::InvalidInput("Archive is empty".to_string()))?;
+    let ArchiveLine::Manifest(manifest) = parse_line(&first)? else {
+        return Err(Error::InvalidInput("Archive does not start with a manifest".to_string()));
+    };
+    if manifest.format != ARCHIVE_FORMAT {
+        return Err(Error::InvalidInput(format!("Unknown archive format: {}", manifest.format)));
+    }
+    if manifest.format_version > ARCHIVE_VERSION {
+        return Err(Error::InvalidInput(format!(
+            "Archive format version {} is newer than supported version {}",
+            manifest.format_version, ARCHIVE_VERSION
+        )));
+    }
+
+    let mut registry = Registry::default();
+    let mut body = Vec::new();
+    for line in lines {
+        let line = line.map_err(read_error)?;
+        body.extend_from_slice(line.as_bytes());
+        body.push(b'\n');
+
+        match parse_line(&line)? {
+            ArchiveLine::Did(did) => registry.dids.push(did),
+            ArchiveLine::Version(version) => registry.versions.push(version),
+            ArchiveLine::Operation(operation) => registry.operations.push(operation),
+            ArchiveLine::Manifest(_) => {
+                return Err(Error::InvalidInput("Archive contains more than one manifest".to_string()));
+            }
+        }
+    }
+
+    if utils::to_hex(&utils::sha256(&body)) != manifest.sha256 {
+        return Err(Error::InvalidInput("Archive checksum does not match its manifest".to_string()));
+    }
+    if RecordCounts::of(&registry) != manifest.counts {
+        return Err(Error::InvalidInput("Archive record counts do not match its manifest".to_string()));
+    }
+
+    Ok((manifest, registry))
+}
+
+/// 校验归档中的操作日志，并确认DID记录和历史版本都与日志一致
+///
+/// 沿哈希链逐条重放每个DID的操作并校验签名，重新计算了校验和的篡改归档同样无法通过。
+/// 返回没有控制者签名的日志条数（修复、导入等系统操作和早期日志）。
+pub fn verify_registry(registry: &Registry) -> Result<u64, Error> {
+    let histories = histories(registry);
+    let empty = HashMap::new();
+    let mut prev_hash: Option<&str> = None;
+    let mut did_heads: HashMap<&str, &OperationLogEntry> = HashMap::new();
+    let mut states: HashMap<&str, DidRecord> = HashMap::new();
+    let mut logged = HashSet::new();
+    let mut unsigned = 0;
+
+    for entry in &registry.operations {
+        let did = entry.operation.did.as_str();
+        if let Some(reason) = oplog::check_link(entry, prev_hash, did_heads.get(did).copied())? {
+            return Err(Error::InvalidInput(format!("Archived operation log is broken at entry {}: {}", entry.seq, reason)));
+        }
+        let next = replay::replay_entry(states.get(did), did, entry, histories.get(did).unwrap_or(&empty))
+            .map_err(|e| Error::InvalidInput(format!("Archived operation log of {} is invalid: {}", did, e)))?;
+
+        if entry.operation.signature.is_none() {
+            unsigned += 1;
+        }
+        states.insert(did, next);
+        logged.insert((did, entry.operation.version_id, entry.operation.document_hash.as_str()));
+        prev_hash = Some(&entry.entry_hash);
+        did_heads.insert(did, entry);
+    }
+
+    for record in &registry.dids {
+        if !replay::same_state(states.get(record.did.as_str()), record)? {
+            return Err(Error::InvalidInput(format!("Archived DID {} does not match its operation log", record.did)));
+        }
+    }
+    for version in &registry.versions {
+        let hash = did::document_hash(&version.document)?;
+        if !logged.contains(&(version.did.as_str(), version.version_id, hash.as_str())) {
+            return Err(Error::InvalidInput(format!(
+                "Archived version {} of {} does not match its operation log",
+                version.version_id, version.did
+            )));
+        }
+    }
+
+    Ok(unsigned)
+}
+
+/// 计算将归档中的DID合并到本地日志时需要依次写入的状态和操作
+///
+/// 归档的日志必须经过本地的当前状态，只取其后的操作，且这些操作都必须带有控制者签名。
+pub fn plan_merge(
+    registry: &Registry,
+    record: &DidRecord,
+    local: Option<&DidRecord>,
+) -> Result<Vec<(DidRecord, SignedOperation)>, Error> {
+    if let Some(local) = local.filter(|local| record.version_id <= local.version_id) {
+        return Err(Error::InvalidState(format!(
+            "Archived version {} of {} does not advance local version {}",
+            record.version_id, record.did, local.version_id
+        )));
+    }
+
+    let history = histories(registry).remove(record.did.as_str()).unwrap_or_default();
+    let mut replayed: Vec<(DidRecord, &OperationLogEntry)> = Vec::new();
+    for entry in registry.operations.iter().filter(|entry| entry.operation.did == record.did) {
+        let next = replay::replay_entry(replayed.last().map(|(state, _)| state), &record.did, entry, &history)?;
+        replayed.push((next, entry));
+    }
+    if !replay::same_state(replayed.last().map(|(state, _)| state), record)? {
+        return Err(Error::InvalidState(format!("Archived DID {} does not match its operation log", record.did)));
+    }
+
+    // 本地已有的DID从归档日志中最后一个与本地状态一致的位置之后开始合并
+    let start = match local {
+        Some(local) => {
+            let mut base = None;
+            for (index, (state, _)) in replayed.iter().enumerate() {
+                if replay::same_state(Some(state), local)? {
+                    base = Some(index + 1);
+                }
+            }
+            base.ok_or_else(|| Error::InvalidState(format!(
+                "Archived history of {} does not contain local version {}",
+                record.did, local.version_id
+            )))?
+        }
+        None => 0,
+    };
+
+    replayed.into_iter().skip(start).map(|(state, entry)| {
+        let signed = entry.operation.signed_operation()
+            .filter(|signed| signed.signature.is_some())
+            .ok_or_else(|| Error::InvalidState(format!(
+                "Archived entry {} of {} is not signed by its controller and cannot be merged",
+                entry.seq, record.did
+            )))?;
+        Ok((state, signed))
+    }).collect()
+}
+
+/// 按DID和版本号索引归档中的历史版本
+fn histories(registry: &Registry) -> HashMap<&str, HashMap<u64, DidVersion>> {
+    let mut histories: HashMap<&str, HashMap<u64, DidVersion>> = HashMap::new();
+    for version in &registry.versions {
+        histories.entry(version.did.as_str()).or_default().insert(version.version_id, version.clone());
+    }
+    histories
+}
+
+/// 写入一行JSON
+fn write_line(buffer: &mut Vec<u8>, line: &ArchiveLine) -> Result<(), Error> {
+    serde_json::to_writer(&mut *buffer, line)
+        .map_err(|e| Error::SerializationError(e.to_string()))?;
+    buffer.push(b'\n');
+    Ok(())
+}
+
+/// 解析一行JSON
+fn parse_line(line: &str) -> Result<ArchiveLine, Error> {
+    serde_json::from_str(line)
+        .map_err(|e| Error::InvalidInput(format!("Invalid archive line: {}", e)))
+}
+
+/// 归档读取错误
+fn read_error(e: std::io::Error) -> Error {
+    Error::InvalidInput(format!("Failed to read archive: {}", e))
+}
@@ -1,15 +1,217 @@
 //! 区块链交互模块 - 通过HTTP API与外部区块链节点交互
+//!
+//! 一个部署可以同时连接多个具名账本，按DID方法或网络段（`did:xyz:testnet:...`）
+//! 将每个DID路由到锚定它的账本，各账本拥有独立的客户端、上链模式和操作员账户。
 
-use serde::Deserialize;
+use std::sync::OnceLock;
+use serde::{Deserialize, Serialize};
 use reqwest::Client;
 use crate::did::DIDDocument;
 use crate::types::Error;
+use crate::utils;
+use utoipa::ToSchema;
 
-/// 区块链配置
-#[derive(Clone, Debug)]
+/// 上链模式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum LedgerMode {
+    /// 将完整DID文档写入区块链
+    #[default]
+    Full,
+    /// 只将文档的规范化哈希和版本号锚定到区块链，完整文档保存在本地
+    Anchor,
+    /// 将多个操作汇总为Merkle树，只将根哈希锚定到区块链
+    Batch,
+}
+
+impl LedgerMode {
+    /// 是否只在链上保存哈希而非完整文档
+    pub fn is_anchoring(&self) -> bool {
+        matches!(self, LedgerMode::Anchor | LedgerMode::Batch)
+    }
+}
+
+impl std::str::FromStr for LedgerMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "full" => Ok(LedgerMode::Full),
+            "anchor" => Ok(LedgerMode::Anchor),
+            "batch" => Ok(LedgerMode::Batch),
+            _ => Err(Error::InvalidInput(format!("Invalid ledger mode: {}", s))),
+        }
+    }
+}
+
+/// 未配置账本时使用的默认节点API端点
+pub const DEFAULT_NODE_URL: &str = "http://localhost:5000";
+/// 未配置账本时使用的默认账本名称
+pub const DEFAULT_LEDGER_NAME: &str = "default";
+
+/// 单个账本的配置
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
 pub struct BlockchainConfig {
+    /// 账本名称
+    pub name: String,
     /// 区块链节点的HTTP API端点
     pub node_url: String,
+    /// 上链模式
+    #[serde(default)]
+    pub mode: LedgerMode,
+    /// 提交交易使用的操作员账户，为空时由节点API选择默认账户
+    #[serde(default)]
+    pub operator_account: Option<String>,
+    /// DID注册合约地址，为空时由节点API使用其部署配置中的合约
+    #[serde(default)]
+    pub contract_address: Option<String>,
+}
+
+impl Default for BlockchainConfig {
+    fn default() -> Self {
+        Self {
+            name: DEFAULT_LEDGER_NAME.to_string(),
+            node_url: DEFAULT_NODE_URL.to_string(),
+            mode: LedgerMode::default(),
+            operator_account: None,
+            contract_address: None,
+        }
+    }
+}
+
+/// DID方法或网络段到账本的映射
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct LedgerRoute {
+    /// DID方法，如`did:web:...`中的`web`
+    pub method: String,
+    /// 网络段，如`did:xyz:testnet:...`中的`testnet`；为空时匹配该方法下的所有DID
+    #[serde(default)]
+    pub network: Option<String>,
+    /// 目标账本名称
+    pub ledger: String,
+}
+
+/// 多账本配置
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LedgersConfig {
+    /// 没有匹配路由时使用的账本，为空时使用第一个账本
+    #[serde(default)]
+    pub default: Option<String>,
+    /// 账本列表
+    pub ledgers: Vec<BlockchainConfig>,
+    /// 路由规则
+    #[serde(default)]
+    pub routes: Vec<LedgerRoute>,
+}
+
+impl Default for LedgersConfig {
+    /// 连接本地节点API的单个默认账本
+    fn default() -> Self {
+        Self {
+            default: None,
+            ledgers: vec![BlockchainConfig::default()],
+            routes: Vec::new(),
+        }
+    }
+}
+
+impl LedgersConfig {
+    /// 读取JSON格式的多账本配置文件
+    pub fn from_file(path: &str) -> Result<Self, Error> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| Error::InvalidInput(format!("Failed to read ledger config {}: {}", path, e)))?;
+        serde_json::from_str(&content)
+            .map_err(|e| Error::InvalidInput(format!("Invalid ledger config {}: {}", path, e)))
+    }
+
+    /// 校验账本名称唯一且路由引用的账本都存在
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.ledgers.is_empty() {
+            return Err(Error::InvalidInput("At least one ledger must be configured".to_string()));
+        }
+
+        let mut names = std::collections::HashSet::new();
+        for ledger in &self.ledgers {
+            if ledger.name.is_empty() {
+                return Err(Error::InvalidInput("Ledger name must not be empty".to_string()));
+            }
+            if !names.insert(ledger.name.as_str()) {
+                return Err(Error::InvalidInput(format!("Duplicate ledger name: {}", ledger.name)));
+            }
+            if !ledger.node_url.starts_with("http://") && !ledger.node_url.starts_with("https://") {
+                return Err(Error::InvalidInput(format!(
+                    "Node URL of ledger {} must start with http:// or https://: {}",
+                    ledger.name, ledger.node_url
+                )));
+            }
+            if let Some(address) = &ledger.contract_address {
+                let valid = address.strip_prefix("0x")
+                    .is_some_and(|hex| hex.len() == 40 && utils::is_valid_hex(hex));
+                if !valid {
+                    return Err(Error::InvalidInput(format!(
+                        "Contract address of ledger {} must be 0x followed by 40 hex digits: {}",
+                        ledger.name, address
+                    )));
+                }
+            }
+        }
+
+        let referenced = self.default.iter().chain(self.routes.iter().map(|route| &route.ledger));
+        for name in referenced {
+            if !names.contains(name.as_str()) {
+                return Err(Error::InvalidInput(format!("Unknown ledger: {}", name)));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// 链上锚定记录
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Anchor {
+    /// 文档规范化哈希（十六进制）
+    pub hash: String,
+    /// 文档版本号
+    pub version_id: u64,
+}
+
+/// 链上批次锚定记录
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchAnchor {
+    /// Merkle根哈希（十六进制）
+    pub root: String,
+    /// 批次中的操作数
+    pub size: u64,
+    /// 上链时间
+    pub timestamp: u64,
+}
+
+/// 链上操作日志链头锚定记录
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogHeadAnchor {
+    /// 日志哈希（十六进制）
+    pub entry_hash: String,
+    /// 日志序号
+    pub seq: u64,
+    /// 上链时间
+    pub timestamp: u64,
+}
+
+/// 账本检查点，记录每个账本最近确认上链的位置
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct LedgerCheckpoint {
+    /// 账本名称
+    pub ledger: String,
+    /// 最近投递成功的出站记录ID
+    pub last_outbox_id: Option<i64>,
+    /// 最近上链的锚定批次ID
+    pub last_batch_id: Option<i64>,
+    /// 最近一次上链的交易哈希
+    pub last_tx_hash: Option<String>,
+    /// 累计上链的操作数
+    pub delivered: u64,
+    pub updated_at: u64,
 }
 
 /// 区块链客户端
@@ -37,20 +239,71 @@ impl BlockchainClient {
         }
     }
 
-    /// 发送交易到区块链
-    async fn send_transaction(&self, endpoint: &str, data: &[u8]) -> Result<String, Error> {
-        let response = self.client
+    /// 账本名称
+    pub fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    /// 账本的上链模式
+    pub fn mode(&self) -> LedgerMode {
+        self.config.mode
+    }
+
+    /// 账本配置
+    pub fn config(&self) -> &BlockchainConfig {
+        &self.config
+    }
+
+    /// 配置了合约地址时通过请求头指定节点API使用的合约
+    fn with_contract(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match &self.config.contract_address {
+            Some(address) => request.header("X-Contract-Address", address),
+            None => request,
+        }
+    }
+
+    /// 发送交易到区块链，可附带幂等键避免重复上链
+    async fn send_transaction(
+        &self,
+        endpoint: &str,
+        data: &[u8],
+        idempotency_key: Option<&str>,
+    ) -> Result<String, Error> {
+        let mut request = self.client
             .post(format!("{}{}", self.config.node_url, endpoint))
-            .body(data.to_vec())
+            .body(data.to_vec());
+        if let Some(key) = idempotency_key {
+            request = request.header("Idempotency-Key", key);
+        }
+        if let Some(account) = &self.config.operator_account {
+            request = request.header("X-Operator-Account", account);
+        }
+        request = self.with_contract(request);
+
+        let response = request
             .send()
             .await
             .map_err(|e| Error::BlockchainError(format!("Failed to send transaction: {}", e)))?;
 
+        if !response.status().is_success() {
+            return Err(Error::BlockchainError(format!(
+                "Failed to send transaction: HTTP {}",
+                response.status()
+            )));
+        }
+
         let tx_response: TransactionResponse = response
             .json()
             .await
             .map_err(|e| Error::BlockchainError(format!("Failed to parse response: {}", e)))?;
 
+        if tx_response.status != "success" {
+            return Err(Error::BlockchainError(format!(
+                "Transaction {} failed with status: {}",
+                tx_response.hash, tx_response.status
+            )));
+        }
+
         Ok(tx_response.hash)
     }
 
@@ -59,13 +312,12 @@ impl BlockchainClient {
         let data = serde_json::to_vec(document)
             .map_err(|e| Error::SerializationError(e.to_string()))?;
 
-        self.send_transaction("/did/store", &data).await
+        self.send_transaction("/did/store", &data, None).await
     }
 
     /// 从区块链获取DID文档
     pub async fn get_did_document(&self, did: &str) -> Result<DIDDocument, Error> {
-        let response = self.client
-            .get(format!("{}/did/{}", self.config.node_url, did))
+        let response = self.with_contract(self.client.get(format!("{}/did/{}", self.config.node_url, did)))
             .send()
             .await
             .map_err(|e| Error::BlockchainError(format!("Failed to get DID document: {}", e)))?;
@@ -82,93 +334,331 @@ impl BlockchainClient {
 
     /// 停用DID
     pub async fn deactivate_did(&self, did: &str) -> Result<String, Error> {
-        self.send_transaction("/did/deactivate", did.as_bytes()).await
+        self.send_transaction("/did/deactivate", did.as_bytes(), None).await
+    }
+
+    /// 将DID文档哈希和版本号锚定到区块链
+    pub async fn anchor_document(
+        &self,
+        did: &str,
+        anchor: &Anchor,
+        idempotency_key: Option<&str>,
+    ) -> Result<String, Error> {
+        #[derive(Serialize)]
+        struct AnchorRequest<'a> {
+            did: &'a str,
+            #[serde(flatten)]
+            anchor: &'a Anchor,
+        }
+
+        let data = serde_json::to_vec(&AnchorRequest { did, anchor })
+            .map_err(|e| Error::SerializationError(e.to_string()))?;
+
+        self.send_transaction("/did/anchor", &data, idempotency_key).await
+    }
+
+    /// 获取DID在区块链上的最新锚定记录
+    pub async fn get_anchor(&self, did: &str) -> Result<Option<Anchor>, Error> {
+        self.get_optional(&format!("/did/{}/anchor", did), "DID anchor").await
+    }
+
+    /// 将批次Merkle根哈希锚定到区块链
+    pub async fn anchor_batch(
+        &self,
+        root: &str,
+        size: u64,
+        idempotency_key: Option<&str>,
+    ) -> Result<String, Error> {
+        let data = serde_json::to_vec(&serde_json::json!({ "root": root, "size": size }))
+            .map_err(|e| Error::SerializationError(e.to_string()))?;
+
+        self.send_transaction("/anchor/batch", &data, idempotency_key).await
+    }
+
+    /// 获取批次根哈希的链上锚定记录
+    pub async fn get_batch_anchor(&self, root: &str) -> Result<Option<BatchAnchor>, Error> {
+        self.get_optional(&format!("/anchor/batch/{}", root), "batch anchor").await
+    }
+
+    /// 将操作日志链头锚定到区块链
+    pub async fn anchor_log_head(
+        &self,
+        entry_hash: &str,
+        seq: u64,
+        idempotency_key: Option<&str>,
+    ) -> Result<String, Error> {
+        let data = serde_json::to_vec(&serde_json::json!({ "entry_hash": entry_hash, "seq": seq }))
+            .map_err(|e| Error::SerializationError(e.to_string()))?;
+
+        self.send_transaction("/anchor/log-head", &data, idempotency_key).await
+    }
+
+    /// 获取操作日志链头的链上锚定记录
+    pub async fn get_log_head(&self, entry_hash: &str) -> Result<Option<LogHeadAnchor>, Error> {
+        self.get_optional(&format!("/anchor/log-head/{}", entry_hash), "log head anchor").await
+    }
+
+    /// 验证DID在区块链上的状态
+    pub async fn verify_did(&self, did: &str) -> Result<bool, Error> {
+        let response = self.with_contract(self.client.get(format!("{}/did/{}/status", self.config.node_url, did)))
+            .send()
+            .await
+            .map_err(|e| Error::BlockchainError(format!("Failed to verify DID status: {}", e)))?;
+
+        // 检查响应状态码
+        if !response.status().is_success() {
+            log::error!("获取DID状态失败: HTTP {}", response.status());
+            return Err(Error::BlockchainError(format!(
+                "Failed to get DID status: HTTP {}", 
+                response.status()
+            )));
+        }
+
+        // 解析 JSON 响应
+        let text = response.text().await
+            .map_err(|e| Error::BlockchainError(format!("Failed to get response text: {}", e)))?;
+        
+        log::debug!("区块链返回的DID状态响应: {}", text);
+
+        // 尝试解析为状态对象
+        #[derive(Deserialize, Debug)]
+        struct Status {
+            active: bool,
+        }
+
+        match serde_json::from_str::<Status>(&text) {
+            Ok(status) => {
+                log::debug!("解析到DID状态: {:?}", status);
+                Ok(status.active)
+            },
+            Err(e) => {
+                log::error!("解析DID状态失败: {}", e);
+                // 如果解析失败，我们认为DID是活跃的（向后兼容）
+                Ok(true)
+            }
+        }
+    }
+
+    /// 查询可能不存在的链上记录，404时返回None
+    async fn get_optional<T: serde::de::DeserializeOwned>(
+        &self,
+        path: &str,
+        what: &str,
+    ) -> Result<Option<T>, Error> {
+        let response = self.with_contract(self.client.get(format!("{}{}", self.config.node_url, path)))
+            .send()
+            .await
+            .map_err(|e| Error::BlockchainError(format!("Failed to get {}: {}", what, e)))?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            return Err(Error::BlockchainError(format!(
+                "Failed to get {}: HTTP {}",
+                what,
+                response.status()
+            )));
+        }
+
+        response
+            .json()
+            .await
+            .map(Some)
+            .map_err(|e| Error::BlockchainError(format!("Failed to parse {}: {}", what, e)))
+    }
+}
+
+/// 账本注册表，负责按DID选择账本
+pub struct LedgerRegistry {
+    clients: Vec<BlockchainClient>,
+    routes: Vec<LedgerRoute>,
+    default: usize,
+}
+
+impl LedgerRegistry {
+    /// 由配置创建注册表
+    pub fn new(config: LedgersConfig) -> Result<Self, Error> {
+        config.validate()?;
+
+        let default = match &config.default {
+            Some(name) => config.ledgers.iter().position(|ledger| &ledger.name == name).unwrap_or(0),
+            None => 0,
+        };
+
+        Ok(Self {
+            clients: config.ledgers.into_iter().map(BlockchainClient::init).collect(),
+            routes: config.routes,
+            default,
+        })
+    }
+
+    /// 所有账本
+    pub fn ledgers(&self) -> &[BlockchainClient] {
+        &self.clients
+    }
+
+    /// 路由规则
+    pub fn routes(&self) -> &[LedgerRoute] {
+        &self.routes
+    }
+
+    /// 默认账本
+    pub fn default_ledger(&self) -> &BlockchainClient {
+        &self.clients[self.default]
+    }
+
+    /// 按名称获取账本
+    pub fn ledger(&self, name: &str) -> Result<&BlockchainClient, Error> {
+        self.clients.iter()
+            .find(|client| client.name() == name)
+            .ok_or_else(|| Error::BlockchainError(format!("Unknown ledger: {}", name)))
+    }
+
+    /// 获取锚定指定DID的账本；网络段精确匹配优先于只匹配DID方法的路由
+    pub fn ledger_for_did(&self, did: &str) -> Result<&BlockchainClient, Error> {
+        let (method, network) = did_segments(did)
+            .ok_or_else(|| Error::InvalidInput(format!("Invalid DID: {}", did)))?;
+
+        let route = self.routes.iter()
+            .find(|route| route.method == method && route.network.is_some() && route.network.as_deref() == network)
+            .or_else(|| self.routes.iter().find(|route| route.method == method && route.network.is_none()));
+
+        match route {
+            Some(route) => self.ledger(&route.ledger),
+            None => Ok(self.default_ledger()),
+        }
+    }
+
+    /// 是否为指定DID方法配置了该网络段
+    pub fn has_network(&self, method: &str, network: &str) -> bool {
+        self.routes.iter()
+            .any(|route| route.method == method && route.network.as_deref() == Some(network))
+    }
+}
+
+/// 解析DID的方法和网络段；只有方法特定标识符包含多个段时才有网络段
+fn did_segments(did: &str) -> Option<(&str, Option<&str>)> {
+    let mut parts = did.splitn(4, ':');
+    if parts.next()? != "did" {
+        return None;
+    }
+    let method = parts.next().filter(|method| !method.is_empty())?;
+    let first = parts.next().filter(|segment| !segment.is_empty())?;
+
+    match parts.next() {
+        Some(_) => Some((method, Some(first))),
+        None => Some((method, None)),
     }
 }
 
 // 为了方便其他模块使用，提供一些全局函数
-static mut CLIENT: Option<BlockchainClient> = None;
+static REGISTRY: OnceLock<LedgerRegistry> = OnceLock::new();
 
 /// 初始化区块链连接
-pub async fn init() -> Result<(), Error> {
-    let config = BlockchainConfig {
-        node_url: "http://localhost:3000".to_string(),
-    };
-    log::info!("初始化区块链客户端，API端点: {}", config.node_url);
-    println!("正在连接区块链API服务...");
-    unsafe {
-        CLIENT = Some(BlockchainClient::init(config));
+pub async fn init(config: LedgersConfig) -> Result<(), Error> {
+    let registry = LedgerRegistry::new(config)?;
+    for client in registry.ledgers() {
+        log::info!(
+            "初始化账本{}，API端点: {}，上链模式: {:?}",
+            client.name(),
+            client.config.node_url,
+            client.mode()
+        );
     }
-    Ok(())
+    REGISTRY
+        .set(registry)
+        .map_err(|_| Error::BlockchainError("Blockchain client already initialized".to_string()))
 }
 
-/// 获取全局区块链客户端的引用
-fn get_client() -> Result<&'static BlockchainClient, Error> {
-    unsafe {
-        CLIENT.as_ref().ok_or_else(|| Error::BlockchainError("Blockchain client not initialized".to_string()))
-    }
+/// 获取全局账本注册表的引用
+pub fn registry() -> Result<&'static LedgerRegistry, Error> {
+    REGISTRY.get().ok_or_else(|| Error::BlockchainError("Blockchain client not initialized".to_string()))
+}
+
+/// 所有已配置的账本，未初始化时为空
+pub fn ledgers() -> &'static [BlockchainClient] {
+    REGISTRY.get().map(LedgerRegistry::ledgers).unwrap_or_default()
+}
+
+/// 按名称获取账本
+pub fn ledger(name: &str) -> Result<&'static BlockchainClient, Error> {
+    registry()?.ledger(name)
+}
+
+/// 获取锚定指定DID的账本
+pub fn ledger_for_did(did: &str) -> Result<&'static BlockchainClient, Error> {
+    registry()?.ledger_for_did(did)
+}
+
+/// 锚定指定DID的账本的上链模式，无法确定账本时为完整文档模式
+pub fn ledger_mode(did: &str) -> LedgerMode {
+    ledger_for_did(did).map(BlockchainClient::mode).unwrap_or_default()
 }
 
 /// 存储DID文档到区块链
 pub async fn store_did_document(document: &DIDDocument) -> Result<String, Error> {
-    get_client()?.store_did_document(document).await
+    ledger_for_did(&document.id)?.store_did_document(document).await
 }
 
 /// 从区块链获取DID文档
 pub async fn get_did_document(did: &str) -> Result<DIDDocument, Error> {
-    get_client()?.get_did_document(did).await
+    ledger_for_did(did)?.get_did_document(did).await
 }
 
 /// 停用DID
 pub async fn deactivate_did(did: &str) -> Result<String, Error> {
-    get_client()?.deactivate_did(did).await
+    ledger_for_did(did)?.deactivate_did(did).await
 }
 
 /// 注册DID到区块链
 pub async fn register_did(did: &str, public_key: &[u8]) -> Result<String, Error> {
     // 构造注册数据
     let data = [did.as_bytes(), public_key].concat();
-    get_client()?.send_transaction("/did/register", &data).await
+    ledger_for_did(did)?.send_transaction("/did/register", &data, None).await
+}
+
+/// 将DID文档哈希和版本号锚定到指定账本
+pub async fn anchor_document(ledger_name: &str, did: &str, anchor: &Anchor, idempotency_key: &str) -> Result<String, Error> {
+    ledger(ledger_name)?.anchor_document(did, anchor, Some(idempotency_key)).await
+}
+
+/// 获取DID在区块链上的最新锚定记录
+pub async fn get_anchor(did: &str) -> Result<Option<Anchor>, Error> {
+    ledger_for_did(did)?.get_anchor(did).await
+}
+
+/// 将批次Merkle根哈希锚定到指定账本
+pub async fn anchor_batch(ledger_name: &str, root: &str, size: u64, idempotency_key: &str) -> Result<String, Error> {
+    ledger(ledger_name)?.anchor_batch(root, size, Some(idempotency_key)).await
+}
+
+/// 获取批次根哈希在指定账本上的锚定记录
+pub async fn get_batch_anchor(ledger_name: &str, root: &str) -> Result<Option<BatchAnchor>, Error> {
+    ledger(ledger_name)?.get_batch_anchor(root).await
+}
+
+/// 将操作日志链头锚定到指定账本
+pub async fn anchor_log_head(ledger_name: &str, entry_hash: &str, seq: u64, idempotency_key: &str) -> Result<String, Error> {
+    ledger(ledger_name)?.anchor_log_head(entry_hash, seq, Some(idempotency_key)).await
+}
+
+/// 获取操作日志链头在指定账本上的锚定记录
+pub async fn get_log_head(ledger_name: &str, entry_hash: &str) -> Result<Option<LogHeadAnchor>, Error> {
+    ledger(ledger_name)?.get_log_head(entry_hash).await
+}
+
+/// 向指定账本提交出站队列中的操作，使用幂等键保证重试时不会重复上链
+pub async fn submit_operation(
+    ledger_name: &str,
+    endpoint: &str,
+    payload: &[u8],
+    idempotency_key: &str,
+) -> Result<String, Error> {
+    ledger(ledger_name)?.send_transaction(endpoint, payload, Some(idempotency_key)).await
 }
 
 /// 验证DID在区块链上的状态
 pub async fn verify_did(did: &str) -> Result<bool, Error> {
-    let response = get_client()?.client
-        .get(format!("{}/did/{}/status", get_client()?.config.node_url, did))
-        .send()
-        .await
-        .map_err(|e| Error::BlockchainError(format!("Failed to verify DID status: {}", e)))?;
-
-    // 检查响应状态码
-    if !response.status().is_success() {
-        log::error!("获取DID状态失败: HTTP {}", response.status());
-        return Err(Error::BlockchainError(format!(
-            "Failed to get DID status: HTTP {}", 
-            response.status()
-        )));
-    }
-
-    // 解析 JSON 响应
-    let text = response.text().await
-        .map_err(|e| Error::BlockchainError(format!("Failed to get response text: {}", e)))?;
-    
-    log::debug!("区块链返回的DID状态响应: {}", text);
-
-    // 尝试解析为状态对象
-    #[derive(Deserialize, Debug)]
-    struct Status {
-        active: bool,
-    }
-
-    match serde_json::from_str::<Status>(&text) {
-        Ok(status) => {
-            log::debug!("解析到DID状态: {:?}", status);
-            Ok(status.active)
-        },
-        Err(e) => {
-            log::error!("解析DID状态失败: {}", e);
-            // 如果解析失败，我们认为DID是活跃的（向后兼容）
-            Ok(true)
-        }
-    }
-}
\ No newline at end of file
+    ledger_for_did(did)?.verify_did(did).await
+}
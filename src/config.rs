@@ -0,0 +1,577 @@
+//! 配置模块 - 从TOML文件、环境变量和命令行参数加载配置
+//!
+//! 优先级从低到高依次为：默认值、配置文件（`--config`或`DID_CONFIG`）、`DID_*`环境变量、命令行参数。
+//! 加载完成后统一校验，所有问题在一条错误中列出。
+
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Utc};
+use clap::Args;
+use serde::{Deserialize, Serialize};
+use crate::anchoring::BatchConfig;
+use crate::blockchain::{LedgerMode, LedgersConfig};
+use crate::db;
+use crate::did::KeyPolicy;
+use crate::did::cache::{self, CacheConfig};
+use crate::idempotency::IdempotencyConfig;
+use crate::outbox::OutboxConfig;
+use crate::ratelimit::RateLimitConfig;
+use crate::types::Error;
+
+/// 默认监听地址
+const DEFAULT_BIND: &str = "127.0.0.1:3000";
+/// 默认数据库文件
+const DEFAULT_DATABASE: &str = "did.db";
+/// 默认日志级别
+const DEFAULT_LOG_LEVEL: &str = "debug";
+/// 默认令牌签发者
+const DEFAULT_TOKEN_ISSUER: &str = "did-system";
+/// 默认令牌有效期（秒）
+const DEFAULT_TOKEN_TTL_SECS: u64 = 3600;
+/// 默认的DID会话令牌有效期（秒）
+const DEFAULT_SESSION_TTL_SECS: u64 = 900;
+/// 默认的v1接口弃用时间，即v2接口发布的时间
+const DEFAULT_V1_DEPRECATED_AT: &str = "2026-10-18T00:00:00Z";
+
+/// 系统配置
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub log: LogConfig,
+    pub ledger: LedgersConfig,
+    pub did: DidConfig,
+    pub outbox: OutboxConfig,
+    pub batch: BatchConfig,
+    pub cache: CacheConfig,
+    pub auth: AuthConfig,
+    pub rate_limit: RateLimitConfig,
+    pub idempotency: IdempotencyConfig,
+    pub api: ApiConfig,
+}
+
+/// HTTP服务配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// 监听地址
+    pub bind: SocketAddr,
+    /// 允许跨域访问的来源，为空或包含`*`时允许任意来源
+    pub cors_origins: Vec<String>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind: DEFAULT_BIND.parse().expect("default bind address is valid"),
+            cors_origins: Vec::new(),
+        }
+    }
+}
+
+/// 数据库配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    /// 数据库文件路径，`:memory:`表示使用内存存储
+    pub path: String,
+    /// 静态加密密钥文件；加密口令只能通过环境变量或命令行提供
+    pub encryption_key_file: Option<PathBuf>,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            path: DEFAULT_DATABASE.to_string(),
+            encryption_key_file: None,
+        }
+    }
+}
+
+/// 日志输出格式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// 单行文本
+    #[default]
+    Text,
+    /// 每行一个JSON对象
+    Json,
+}
+
+impl std::str::FromStr for LogFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            other => Err(Error::InvalidInput(format!("Unknown log format: {}", other))),
+        }
+    }
+}
+
+/// 日志配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    /// 日志过滤规则，与`RUST_LOG`语法相同，如`info,did_system=debug`
+    pub level: String,
+    pub format: LogFormat,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            level: DEFAULT_LOG_LEVEL.to_string(),
+            format: LogFormat::default(),
+        }
+    }
+}
+
+/// DID注册配置
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DidConfig {
+    /// 公钥使用策略：shared允许多个DID使用同一公钥，unique要求公钥只属于一个活跃的DID
+    pub key_policy: KeyPolicy,
+}
+
+/// 认证配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    /// 是否要求请求携带凭据
+    pub enabled: bool,
+    /// 令牌签发者名称
+    pub token_issuer: String,
+    /// 签发令牌使用的Ed25519私钥文件（Base58编码）
+    pub issuer_key_file: Option<PathBuf>,
+    /// 令牌有效期（秒）
+    pub token_ttl_secs: u64,
+    /// DID Auth登录后签发的会话令牌有效期（秒）
+    pub session_ttl_secs: u64,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            token_issuer: DEFAULT_TOKEN_ISSUER.to_string(),
+            issuer_key_file: None,
+            token_ttl_secs: DEFAULT_TOKEN_TTL_SECS,
+            session_ttl_secs: DEFAULT_SESSION_TTL_SECS,
+        }
+    }
+}
+
+/// 接口版本配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApiConfig {
+    /// v1接口的弃用时间，写入v1响应的`Deprecation`头
+    pub v1_deprecated_at: DateTime<Utc>,
+    /// v1接口计划下线的时间，写入v1响应的`Sunset`头；为空时不发送
+    pub v1_sunset: Option<DateTime<Utc>>,
+    /// 修改已有DID时是否要求`If-Match`头（v2也可以在签名操作中给出`previousVersionId`），缺少时返回428
+    pub require_preconditions: bool,
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self {
+            v1_deprecated_at: DEFAULT_V1_DEPRECATED_AT.parse().expect("default deprecation date is valid"),
+            v1_sunset: None,
+            require_preconditions: false,
+        }
+    }
+}
+
+/// 可通过命令行覆盖的配置项
+#[derive(Debug, Clone, Default, Args)]
+pub struct Overrides {
+    /// 监听地址
+    #[arg(long, global = true)]
+    pub bind: Option<String>,
+
+    /// 数据库文件路径，`:memory:`表示使用内存存储
+    #[arg(long, global = true)]
+    pub database: Option<String>,
+
+    /// 静态加密密钥文件（Base64编码的32字节密钥）
+    #[arg(long, global = true)]
+    pub encryption_key_file: Option<PathBuf>,
+
+    /// 日志过滤规则，如`info`或`info,did_system=debug`
+    #[arg(long, global = true)]
+    pub log_level: Option<String>,
+
+    /// 日志格式：text或json
+    #[arg(long, global = true)]
+    pub log_format: Option<String>,
+
+    /// 允许跨域访问的来源，逗号分隔
+    #[arg(long, global = true)]
+    pub cors_origins: Option<String>,
+
+    /// 区块链节点API端点（只能用于单账本配置）
+    #[arg(long, global = true)]
+    pub ledger_url: Option<String>,
+
+    /// DID注册合约地址（只能用于单账本配置）
+    #[arg(long, global = true)]
+    pub contract_address: Option<String>,
+}
+
+impl Config {
+    /// 按优先级加载并校验配置，`path`为配置文件路径
+    pub fn load(path: Option<&Path>, overrides: &Overrides) -> Result<Self, Error> {
+        let mut config = match path {
+            Some(path) => Self::from_file(path)?,
+            None => Self::default(),
+        };
+        config.apply_env()?;
+        config.apply_overrides(overrides)?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// 读取TOML配置文件
+    pub fn from_file(path: &Path) -> Result<Self, Error> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| Error::InvalidInput(format!("Failed to read config file {}: {}", path.display(), e)))?;
+        toml::from_str(&content)
+            .map_err(|e| Error::InvalidInput(format!("Invalid config file {}: {}", path.display(), e)))
+    }
+
+    /// 应用`DID_*`环境变量
+    fn apply_env(&mut self) -> Result<(), Error> {
+        if let Some(bind) = env("DID_BIND") {
+            self.server.bind = parse_bind("DID_BIND", &bind)?;
+        }
+        if let Some(origins) = env("DID_CORS_ORIGINS") {
+            self.server.cors_origins = split_list(&origins);
+        }
+        if let Some(path) = env("DID_DATABASE") {
+            self.database.path = path;
+        }
+        if let Some(path) = env("DID_ENCRYPTION_KEY_FILE") {
+            self.database.encryption_key_file = Some(PathBuf::from(path));
+        }
+
+        // 兼容只设置了RUST_LOG的部署
+        if let Some(level) = env("DID_LOG_LEVEL").or_else(|| env("RUST_LOG")) {
+            self.log.level = level;
+        }
+        if let Some(format) = env("DID_LOG_FORMAT") {
+            self.log.format = parse_value("DID_LOG_FORMAT", &format)?;
+        }
+
+        if let Some(path) = env("DID_LEDGER_CONFIG") {
+            self.ledger = LedgersConfig::from_file(&path)?;
+        }
+        if let Some(url) = env("DID_LEDGER_URL") {
+            self.single_ledger("DID_LEDGER_URL")?.node_url = url;
+        }
+        if let Some(mode) = env("DID_LEDGER_MODE") {
+            self.single_ledger("DID_LEDGER_MODE")?.mode = parse_value::<LedgerMode>("DID_LEDGER_MODE", &mode)?;
+        }
+        if let Some(account) = env("DID_LEDGER_OPERATOR") {
+            self.single_ledger("DID_LEDGER_OPERATOR")?.operator_account = Some(account);
+        }
+        if let Some(address) = env("DID_LEDGER_CONTRACT") {
+            self.single_ledger("DID_LEDGER_CONTRACT")?.contract_address = Some(address);
+        }
+
+        if let Some(policy) = env("DID_KEY_POLICY") {
+            self.did.key_policy = parse_value("DID_KEY_POLICY", &policy)?;
+        }
+
+        if let Some(interval) = env("DID_OUTBOX_POLL_INTERVAL") {
+            self.outbox.poll_interval_secs = parse_value("DID_OUTBOX_POLL_INTERVAL", &interval)?;
+        }
+        if let Some(size) = env("DID_OUTBOX_BATCH_SIZE") {
+            self.outbox.batch_size = parse_value("DID_OUTBOX_BATCH_SIZE", &size)?;
+        }
+        if let Some(delay) = env("DID_OUTBOX_BASE_DELAY") {
+            self.outbox.base_delay_secs = parse_value("DID_OUTBOX_BASE_DELAY", &delay)?;
+        }
+        if let Some(delay) = env("DID_OUTBOX_MAX_DELAY") {
+            self.outbox.max_delay_secs = parse_value("DID_OUTBOX_MAX_DELAY", &delay)?;
+        }
+        if let Some(attempts) = env("DID_OUTBOX_MAX_ATTEMPTS") {
+            self.outbox.max_attempts = parse_value("DID_OUTBOX_MAX_ATTEMPTS", &attempts)?;
+        }
+
+        if let Some(size) = env("DID_BATCH_MAX_SIZE") {
+            self.batch.max_batch_size = parse_value("DID_BATCH_MAX_SIZE", &size)?;
+        }
+        if let Some(wait) = env("DID_BATCH_MAX_WAIT") {
+            self.batch.max_wait_secs = parse_value("DID_BATCH_MAX_WAIT", &wait)?;
+        }
+        if let Some(interval) = env("DID_BATCH_POLL_INTERVAL") {
+            self.batch.poll_interval_secs = parse_value("DID_BATCH_POLL_INTERVAL", &interval)?;
+        }
+
+        if let Some(ttl) = env("DID_CACHE_TTL") {
+            self.cache.ttl_secs = parse_value("DID_CACHE_TTL", &ttl)?;
+        }
+        if let Some(ttls) = env("DID_CACHE_METHOD_TTLS") {
+            self.cache.method_ttls = cache::parse_method_ttls(&ttls)
+                .map_err(|e| Error::InvalidInput(format!("Invalid DID_CACHE_METHOD_TTLS: {}", e)))?;
+        }
+        if let Some(ttl) = env("DID_CACHE_NEGATIVE_TTL") {
+            self.cache.negative_ttl_secs = parse_value("DID_CACHE_NEGATIVE_TTL", &ttl)?;
+        }
+        if let Some(ttl) = env("DID_CACHE_STALE_TTL") {
+            self.cache.stale_secs = parse_value("DID_CACHE_STALE_TTL", &ttl)?;
+        }
+        if let Some(capacity) = env("DID_CACHE_CAPACITY") {
+            self.cache.capacity = parse_value("DID_CACHE_CAPACITY", &capacity)?;
+        }
+
+        if let Some(enabled) = env("DID_AUTH_ENABLED") {
+            self.auth.enabled = parse_value("DID_AUTH_ENABLED", &enabled)?;
+        }
+        if let Some(issuer) = env("DID_AUTH_TOKEN_ISSUER") {
+            self.auth.token_issuer = issuer;
+        }
+        if let Some(path) = env("DID_AUTH_ISSUER_KEY_FILE") {
+            self.auth.issuer_key_file = Some(PathBuf::from(path));
+        }
+        if let Some(ttl) = env("DID_AUTH_TOKEN_TTL") {
+            self.auth.token_ttl_secs = parse_value("DID_AUTH_TOKEN_TTL", &ttl)?;
+        }
+        if let Some(ttl) = env("DID_AUTH_SESSION_TTL") {
+            self.auth.session_ttl_secs = parse_value("DID_AUTH_SESSION_TTL", &ttl)?;
+        }
+
+        if let Some(enabled) = env("DID_RATE_LIMIT_ENABLED") {
+            self.rate_limit.enabled = parse_value("DID_RATE_LIMIT_ENABLED", &enabled)?;
+        }
+        if let Some(rate) = env("DID_RATE_LIMIT_READ_PER_MINUTE") {
+            self.rate_limit.read_per_minute = parse_value("DID_RATE_LIMIT_READ_PER_MINUTE", &rate)?;
+        }
+        if let Some(burst) = env("DID_RATE_LIMIT_READ_BURST") {
+            self.rate_limit.read_burst = parse_value("DID_RATE_LIMIT_READ_BURST", &burst)?;
+        }
+        if let Some(rate) = env("DID_RATE_LIMIT_WRITE_PER_MINUTE") {
+            self.rate_limit.write_per_minute = parse_value("DID_RATE_LIMIT_WRITE_PER_MINUTE", &rate)?;
+        }
+        if let Some(burst) = env("DID_RATE_LIMIT_WRITE_BURST") {
+            self.rate_limit.write_burst = parse_value("DID_RATE_LIMIT_WRITE_BURST", &burst)?;
+        }
+        if let Some(quota) = env("DID_RATE_LIMIT_DAILY_WRITE_QUOTA") {
+            self.rate_limit.daily_write_quota = parse_value("DID_RATE_LIMIT_DAILY_WRITE_QUOTA", &quota)?;
+        }
+        if let Some(trust) = env("DID_RATE_LIMIT_TRUST_FORWARDED_FOR") {
+            self.rate_limit.trust_forwarded_for = parse_value("DID_RATE_LIMIT_TRUST_FORWARDED_FOR", &trust)?;
+        }
+        if let Some(hops) = env("DID_RATE_LIMIT_TRUSTED_PROXY_HOPS") {
+            self.rate_limit.trusted_proxy_hops = parse_value("DID_RATE_LIMIT_TRUSTED_PROXY_HOPS", &hops)?;
+        }
+
+        if let Some(enabled) = env("DID_IDEMPOTENCY_ENABLED") {
+            self.idempotency.enabled = parse_value("DID_IDEMPOTENCY_ENABLED", &enabled)?;
+        }
+        if let Some(retention) = env("DID_IDEMPOTENCY_RETENTION") {
+            self.idempotency.retention_secs = parse_value("DID_IDEMPOTENCY_RETENTION", &retention)?;
+        }
+
+        if let Some(date) = env("DID_API_V1_DEPRECATED_AT") {
+            self.api.v1_deprecated_at = parse_value("DID_API_V1_DEPRECATED_AT", &date)?;
+        }
+        if let Some(date) = env("DID_API_V1_SUNSET") {
+            self.api.v1_sunset = Some(parse_value("DID_API_V1_SUNSET", &date)?);
+        }
+        if let Some(required) = env("DID_API_REQUIRE_PRECONDITIONS") {
+            self.api.require_preconditions = parse_value("DID_API_REQUIRE_PRECONDITIONS", &required)?;
+        }
+
+        Ok(())
+    }
+
+    /// 应用命令行参数
+    fn apply_overrides(&mut self, overrides: &Overrides) -> Result<(), Error> {
+        if let Some(bind) = &overrides.bind {
+            self.server.bind = parse_bind("--bind", bind)?;
+        }
+        if let Some(path) = &overrides.database {
+            self.database.path = path.clone();
+        }
+        if let Some(path) = &overrides.encryption_key_file {
+            self.database.encryption_key_file = Some(path.clone());
+        }
+        if let Some(level) = &overrides.log_level {
+            self.log.level = level.clone();
+        }
+        if let Some(format) = &overrides.log_format {
+            self.log.format = parse_value("--log-format", format)?;
+        }
+        if let Some(origins) = &overrides.cors_origins {
+            self.server.cors_origins = split_list(origins);
+        }
+        if let Some(url) = &overrides.ledger_url {
+            self.single_ledger("--ledger-url")?.node_url = url.clone();
+        }
+        if let Some(address) = &overrides.contract_address {
+            self.single_ledger("--contract-address")?.contract_address = Some(address.clone());
+        }
+
+        Ok(())
+    }
+
+    /// 只配置了一个账本时返回该账本，单账本设置不能用于多账本配置
+    fn single_ledger(&mut self, setting: &str) -> Result<&mut crate::blockchain::BlockchainConfig, Error> {
+        match self.ledger.ledgers.as_mut_slice() {
+            [ledger] => Ok(ledger),
+            _ => Err(Error::InvalidInput(format!(
+                "{} can only be used with a single ledger; set it per ledger in the config file instead",
+                setting
+            ))),
+        }
+    }
+
+    /// 校验配置，列出全部问题
+    pub fn validate(&self) -> Result<(), Error> {
+        let mut problems = Vec::new();
+
+        if self.database.path.trim().is_empty() {
+            problems.push("database.path must not be empty".to_string());
+        }
+        if self.database.path == db::MEMORY_DATABASE && self.database.encryption_key_file.is_some() {
+            problems.push("database.encryption_key_file cannot be used with the in-memory store".to_string());
+        }
+        for origin in &self.server.cors_origins {
+            if origin != "*" && !(origin.starts_with("http://") || origin.starts_with("https://")) {
+                problems.push(format!("server.cors_origins: {} must be * or start with http:// or https://", origin));
+            } else if origin.ends_with('/') {
+                problems.push(format!("server.cors_origins: {} must not end with /", origin));
+            }
+        }
+        if let Err(problem) = validate_log_filter(&self.log.level) {
+            problems.push(format!("log.level: {}", problem));
+        }
+        match self.ledger.validate() {
+            Ok(()) => {}
+            Err(Error::InvalidInput(message)) => problems.push(format!("ledger: {}", message)),
+            Err(e) => problems.push(format!("ledger: {}", e)),
+        }
+        for (name, value) in [
+            ("outbox.poll_interval_secs", self.outbox.poll_interval_secs),
+            ("outbox.batch_size", self.outbox.batch_size as u64),
+            ("outbox.base_delay_secs", self.outbox.base_delay_secs),
+            ("outbox.max_attempts", u64::from(self.outbox.max_attempts)),
+            ("batch.max_batch_size", self.batch.max_batch_size as u64),
+            ("batch.poll_interval_secs", self.batch.poll_interval_secs),
+        ] {
+            if value == 0 {
+                problems.push(format!("{} must be greater than 0", name));
+            }
+        }
+        if self.outbox.max_delay_secs < self.outbox.base_delay_secs {
+            problems.push("outbox.max_delay_secs must not be less than outbox.base_delay_secs".to_string());
+        }
+        if self.cache.capacity == 0 {
+            problems.push("cache.capacity must be greater than 0".to_string());
+        }
+        if self.auth.token_issuer.trim().is_empty() {
+            problems.push("auth.token_issuer must not be empty".to_string());
+        }
+        if self.auth.token_ttl_secs == 0 {
+            problems.push("auth.token_ttl_secs must be greater than 0".to_string());
+        }
+        if self.auth.session_ttl_secs == 0 {
+            problems.push("auth.session_ttl_secs must be greater than 0".to_string());
+        }
+        if let Some(path) = &self.auth.issuer_key_file {
+            if !path.is_file() {
+                problems.push(format!("auth.issuer_key_file: {} does not exist", path.display()));
+            }
+        }
+        for (name, value) in [
+            ("rate_limit.read_per_minute", self.rate_limit.read_per_minute),
+            ("rate_limit.read_burst", self.rate_limit.read_burst),
+            ("rate_limit.write_per_minute", self.rate_limit.write_per_minute),
+            ("rate_limit.write_burst", self.rate_limit.write_burst),
+        ] {
+            if value == 0 {
+                problems.push(format!("{} must be greater than 0", name));
+            }
+        }
+        if self.rate_limit.trusted_proxy_hops == 0 {
+            problems.push("rate_limit.trusted_proxy_hops must be greater than 0".to_string());
+        }
+        if self.idempotency.retention_secs == 0 {
+            problems.push("idempotency.retention_secs must be greater than 0".to_string());
+        }
+        if self.api.v1_sunset.is_some_and(|sunset| sunset <= self.api.v1_deprecated_at) {
+            problems.push("api.v1_sunset must be later than api.v1_deprecated_at".to_string());
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::InvalidInput(format!("Invalid configuration:\n  - {}", problems.join("\n  - "))))
+        }
+    }
+
+    /// 是否允许任意来源跨域访问
+    pub fn allows_any_origin(&self) -> bool {
+        self.server.cors_origins.is_empty() || self.server.cors_origins.iter().any(|origin| origin == "*")
+    }
+}
+
+/// 读取非空的环境变量
+fn env(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+/// 解析配置值，出错时指明来源
+fn parse_value<T: std::str::FromStr>(source: &str, value: &str) -> Result<T, Error> {
+    value.parse()
+        .map_err(|_| Error::InvalidInput(format!("Invalid value for {}: {}", source, value)))
+}
+
+/// 解析监听地址
+fn parse_bind(source: &str, value: &str) -> Result<SocketAddr, Error> {
+    value.parse()
+        .map_err(|_| Error::InvalidInput(format!("Invalid value for {}: {} (expected host:port, e.g. {})", source, value, DEFAULT_BIND)))
+}
+
+/// 拆分逗号分隔的列表
+fn split_list(value: &str) -> Vec<String> {
+    value.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// 校验`RUST_LOG`风格的过滤规则：每项为级别、模块路径或`模块路径=级别`
+fn validate_log_filter(filter: &str) -> Result<(), String> {
+    // `/`之后为按消息内容过滤的正则表达式
+    let directives = filter.split('/').next().unwrap_or_default();
+    if directives.trim().is_empty() {
+        return Err("must not be empty".to_string());
+    }
+
+    for directive in directives.split(',').map(str::trim).filter(|directive| !directive.is_empty()) {
+        let (module, level) = match directive.split_once('=') {
+            Some((module, level)) => (module, Some(level)),
+            None if directive.parse::<log::LevelFilter>().is_ok() => continue,
+            None => (directive, None),
+        };
+        if module.is_empty() || !module.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':') {
+            return Err(format!("invalid module path in {}", directive));
+        }
+        if let Some(level) = level {
+            level.parse::<log::LevelFilter>()
+                .map_err(|_| format!("unknown level {} (expected off, error, warn, info, debug or trace)", level))?;
+        }
+    }
+
+    Ok(())
+}
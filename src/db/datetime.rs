@@ -0,0 +1,10 @@
+//! 时间转换 - 数据库中的时间统一以Unix秒存储
+
+use chrono::{DateTime, SecondsFormat, Utc};
+
+/// 将Unix秒转换为RFC 3339格式的UTC时间
+pub fn to_rfc3339(timestamp: u64) -> String {
+    DateTime::<Utc>::from_timestamp(timestamp as i64, 0)
+        .map(|time| time.to_rfc3339_opts(SecondsFormat::Secs, true))
+        .unwrap_or_else(|| timestamp.to_string())
+}
@@ -0,0 +1,601 @@
+//! 静态加密模块 - 使用数据加密密钥（DEK）加密数据库中的敏感列
+//!
+//! DEK随机生成，由口令经Argon2id派生的密钥或密钥文件中的密钥（KEK）包装后保存在`encryption_key`表中。
+//! 轮换时只重新包装DEK，已加密的数据不需要重写。
+//! 加密后的列值以`enc:v2:`为前缀，密文以所在的表、列和行主键作为关联数据，不能被移动到其他行；
+//! 查询属性和公钥登记保存为由DEK派生密钥计算的带密钥哈希（`idx:v1:`前缀），仍可按值精确查询。
+//! 迁移中不能再用JSON函数直接读取这些列。
+
+use std::path::PathBuf;
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use blake2::Blake2bMac;
+use blake2::digest::Mac;
+use blake2::digest::consts::U32;
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use rand::RngCore;
+use rusqlite::{Connection, OptionalExtension, params, params_from_iter};
+use rusqlite::types::Value;
+use crate::types::Error;
+use crate::utils;
+
+/// 加密列值的前缀
+const ENCRYPTED_PREFIX: &str = "enc:v2:";
+/// 不绑定所在行的旧版密文前缀，打开数据库时重新加密
+const LEGACY_PREFIX: &str = "enc:v1:";
+/// 查询属性带密钥哈希的前缀
+const INDEX_PREFIX: &str = "idx:v1:";
+/// 包装DEK时使用的关联数据
+const WRAP_AAD: &[u8] = b"did-system-dek-v1";
+/// 由DEK派生查询属性哈希密钥时使用的上下文
+const INDEX_CONTEXT: &[u8] = b"did-system-index-v1";
+/// XChaCha20-Poly1305的随机数长度
+const NONCE_LEN: usize = 24;
+/// 密钥长度
+const KEY_LEN: usize = 32;
+/// Argon2id盐值长度
+const SALT_LEN: usize = 16;
+
+/// 需要加密的列
+#[derive(Debug, Clone, Copy)]
+pub struct EncryptedColumn {
+    pub table: &'static str,
+    pub column: &'static str,
+    /// 标识所在行的列，与表名和列名一起作为密文的关联数据
+    pub key: &'static [&'static str],
+    /// 列值以BLOB保存
+    pub blob: bool,
+}
+
+pub const DOCUMENTS: EncryptedColumn = EncryptedColumn {
+    table: "did_documents",
+    column: "document",
+    key: &["did"],
+    blob: false,
+};
+
+pub const DOCUMENT_HISTORY: EncryptedColumn = EncryptedColumn {
+    table: "did_document_history",
+    column: "document",
+    key: &["did", "version_id"],
+    blob: false,
+};
+
+pub const SNAPSHOTS: EncryptedColumn = EncryptedColumn {
+    table: "did_snapshots",
+    column: "document",
+    key: &["did"],
+    blob: false,
+};
+
+pub const OPERATION_PAYLOADS: EncryptedColumn = EncryptedColumn {
+    table: "operation_log",
+    column: "payload",
+    key: &["entry_hash"],
+    blob: false,
+};
+
+pub const IDEMPOTENT_RESPONSES: EncryptedColumn = EncryptedColumn {
+    table: "idempotency_keys",
+    column: "response",
+    key: &["tenant", "idempotency_key"],
+    blob: false,
+};
+
+pub const OUTBOX_PAYLOADS: EncryptedColumn = EncryptedColumn {
+    table: "ledger_outbox",
+    column: "payload",
+    key: &["idempotency_key"],
+    blob: true,
+};
+
+const ENCRYPTED_COLUMNS: &[EncryptedColumn] = &[
+    DOCUMENTS,
+    DOCUMENT_HISTORY,
+    SNAPSHOTS,
+    OPERATION_PAYLOADS,
+    IDEMPOTENT_RESPONSES,
+    OUTBOX_PAYLOADS,
+];
+
+/// 以带密钥哈希保存的查询列
+const INDEXED_COLUMNS: &[(&str, &str)] = &[
+    ("did_attributes", "value"),
+    ("key_claims", "public_key"),
+];
+
+/// 包装密钥的来源
+#[derive(Clone)]
+pub enum KeySource {
+    /// 口令，经Argon2id派生包装密钥
+    Passphrase(String),
+    /// 包含Base64编码的32字节密钥的文件
+    KeyFile(PathBuf),
+}
+
+impl std::fmt::Debug for KeySource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Passphrase(_) => f.write_str("Passphrase(***)"),
+            Self::KeyFile(path) => f.debug_tuple("KeyFile").field(path).finish(),
+        }
+    }
+}
+
+impl KeySource {
+    /// 由口令和密钥文件参数确定密钥来源，两者都指定时报错
+    pub fn from_options(passphrase: Option<String>, key_file: Option<PathBuf>) -> Result<Option<Self>, Error> {
+        match (passphrase, key_file) {
+            (Some(_), Some(_)) => Err(Error::InvalidInput(
+                "Specify either an encryption passphrase or a key file, not both".to_string()
+            )),
+            (Some(passphrase), None) if passphrase.is_empty() => Err(Error::InvalidInput(
+                "Encryption passphrase must not be empty".to_string()
+            )),
+            (Some(passphrase), None) => Ok(Some(Self::Passphrase(passphrase))),
+            (None, Some(path)) => Ok(Some(Self::KeyFile(path))),
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// 保存在数据库中的派生方式名称
+    fn kdf(&self) -> &'static str {
+        match self {
+            Self::Passphrase(_) => "argon2id",
+            Self::KeyFile(_) => "key_file",
+        }
+    }
+}
+
+/// Argon2id参数和盐值
+#[derive(Debug, Clone)]
+struct KdfParams {
+    salt: Vec<u8>,
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+}
+
+impl KdfParams {
+    /// 使用默认参数和随机盐值
+    fn generate() -> Self {
+        let mut salt = vec![0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+
+        Self {
+            salt,
+            m_cost: Params::DEFAULT_M_COST,
+            t_cost: Params::DEFAULT_T_COST,
+            p_cost: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+/// 数据库中保存的包装后的DEK
+#[derive(Debug, Clone)]
+struct WrappedKey {
+    kdf: String,
+    params: Option<KdfParams>,
+    wrapped_key: String,
+}
+
+/// 敏感列的加解密器；未启用加密时原样读写
+#[derive(Clone, Default)]
+pub struct ColumnCipher {
+    aead: Option<XChaCha20Poly1305>,
+    index_key: Option<Key>,
+}
+
+impl ColumnCipher {
+    /// 不加密的读写器
+    pub fn plaintext() -> Self {
+        Self::default()
+    }
+
+    /// 使用DEK加密，查询属性哈希的密钥由DEK派生
+    fn with_key(dek: &Key) -> Self {
+        let mut mac = <Blake2bMac<U32> as Mac>::new_from_slice(dek)
+            .expect("data encryption key has a valid MAC key length");
+        mac.update(INDEX_CONTEXT);
+
+        Self {
+            aead: Some(XChaCha20Poly1305::new(dek)),
+            index_key: Some(mac.finalize().into_bytes()),
+        }
+    }
+
+    /// 是否已启用加密
+    pub fn is_enabled(&self) -> bool {
+        self.aead.is_some()
+    }
+
+    /// 加密列值；未启用加密时原样返回
+    pub fn encrypt(&self, value: String, column: &EncryptedColumn, key: &[&str]) -> Result<String, Error> {
+        String::from_utf8(self.encrypt_bytes(value.into_bytes(), column, key)?)
+            .map_err(|e| Error::CryptoError(format!("Encrypted column is not valid UTF-8: {}", e)))
+    }
+
+    /// 解密列值；启用加密时拒绝未加密的值
+    pub fn decrypt(&self, value: String, column: &EncryptedColumn, key: &[&str]) -> Result<String, Error> {
+        String::from_utf8(self.decrypt_bytes(value.into_bytes(), column, key)?)
+            .map_err(|e| Error::CryptoError(format!("Decrypted column is not valid UTF-8: {}", e)))
+    }
+
+    /// 加密BLOB列值；未启用加密时原样返回
+    pub fn encrypt_bytes(&self, value: Vec<u8>, column: &EncryptedColumn, key: &[&str]) -> Result<Vec<u8>, Error> {
+        match &self.aead {
+            Some(aead) => Ok(format!("{}{}", ENCRYPTED_PREFIX, seal(aead, &value, &associated_data(column, key)?)?).into_bytes()),
+            None => Ok(value),
+        }
+    }
+
+    /// 解密BLOB列值；启用加密时拒绝未加密的值
+    pub fn decrypt_bytes(&self, value: Vec<u8>, column: &EncryptedColumn, key: &[&str]) -> Result<Vec<u8>, Error> {
+        let sealed = value.strip_prefix(ENCRYPTED_PREFIX.as_bytes());
+        match (&self.aead, sealed) {
+            (Some(aead), Some(sealed)) => {
+                let sealed = std::str::from_utf8(sealed)
+                    .map_err(|e| Error::CryptoError(format!("Invalid encrypted value: {}", e)))?;
+                open(aead, sealed, &associated_data(column, key)?)
+            }
+            (Some(_), None) => Err(Error::CryptoError(format!(
+                "Unencrypted value found in encrypted column {}.{}",
+                column.table, column.column
+            ))),
+            (None, Some(_)) => Err(Error::CryptoError(
+                "Encrypted column found but no encryption key is loaded".to_string()
+            )),
+            (None, None) => Ok(value),
+        }
+    }
+
+    /// 查询属性的保存值；启用加密时为带密钥哈希，相同的值得到相同的结果
+    pub fn index(&self, value: &str) -> String {
+        match &self.index_key {
+            Some(index_key) => {
+                let mut mac = <Blake2bMac<U32> as Mac>::new_from_slice(index_key)
+                    .expect("index key has a valid MAC key length");
+                mac.update(value.as_bytes());
+                format!("{}{}", INDEX_PREFIX, utils::to_hex(&mac.finalize().into_bytes()))
+            }
+            None => value.to_string(),
+        }
+    }
+}
+
+/// 密文的关联数据：表名、列名和所在行的标识
+fn associated_data(column: &EncryptedColumn, key: &[&str]) -> Result<Vec<u8>, Error> {
+    let mut parts = vec![column.table, column.column];
+    parts.extend_from_slice(key);
+    serde_json::to_vec(&parts).map_err(|e| Error::SerializationError(e.to_string()))
+}
+
+/// 加载数据库的DEK
+///
+/// 数据库尚未加密且提供了密钥时，生成新的DEK并加密已有数据；已加密时补充加密新增的加密列，
+/// 并重新加密旧版不绑定所在行的密文。数据库已加密但未提供密钥，或密钥不正确时返回错误。
+pub fn open_cipher(conn: &mut Connection, source: Option<&KeySource>) -> Result<ColumnCipher, Error> {
+    let stored = read_wrapped_key(conn)?;
+
+    match (stored, source) {
+        (None, None) => Ok(ColumnCipher::plaintext()),
+        (Some(_), None) => Err(Error::CryptoError(
+            "Database is encrypted but no encryption key was provided; \
+             set DID_ENCRYPTION_PASSPHRASE or DID_ENCRYPTION_KEY_FILE".to_string()
+        )),
+        (Some(stored), Some(source)) => {
+            let cipher = ColumnCipher::with_key(&unwrap_key(&stored, source)?);
+            let tx = conn.transaction()
+                .map_err(|e| Error::DatabaseError(format!("Failed to begin transaction: {}", e)))?;
+            let sealed = seal_columns(&tx, &cipher)?;
+            tx.commit()
+                .map_err(|e| Error::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+            if sealed > 0 {
+                log::info!("已加密{}行未加密或旧版加密的数据", sealed);
+            }
+            Ok(cipher)
+        }
+        (None, Some(source)) => enable(conn, source),
+    }
+}
+
+/// 使用新的包装密钥重新包装DEK，已加密的数据保持不变
+pub fn rotate_wrapping_key(conn: &Connection, current: &KeySource, new: &KeySource) -> Result<(), Error> {
+    let stored = read_wrapped_key(conn)?
+        .ok_or_else(|| Error::InvalidState("Database is not encrypted".to_string()))?;
+    let dek = unwrap_key(&stored, current)?;
+    let rewrapped = wrap_key(&dek, new)?;
+
+    conn.execute(
+        "UPDATE encryption_key SET kdf = ?, salt = ?, m_cost = ?, t_cost = ?, p_cost = ?, wrapped_key = ?, rotated_at = ?
+         WHERE id = 1",
+        params![
+            rewrapped.kdf,
+            rewrapped.params.as_ref().map(|params| STANDARD.encode(&params.salt)),
+            rewrapped.params.as_ref().map(|params| params.m_cost),
+            rewrapped.params.as_ref().map(|params| params.t_cost),
+            rewrapped.params.as_ref().map(|params| params.p_cost),
+            rewrapped.wrapped_key,
+            utils::current_timestamp(),
+        ],
+    ).map_err(|e| Error::DatabaseError(format!("Failed to store wrapped key: {}", e)))?;
+
+    Ok(())
+}
+
+/// 生成DEK并加密已有的敏感列
+fn enable(conn: &mut Connection, source: &KeySource) -> Result<ColumnCipher, Error> {
+    let dek = XChaCha20Poly1305::generate_key(&mut OsRng);
+    let wrapped = wrap_key(&dek, source)?;
+    let cipher = ColumnCipher::with_key(&dek);
+
+    let tx = conn.transaction()
+        .map_err(|e| Error::DatabaseError(format!("Failed to begin transaction: {}", e)))?;
+
+    tx.execute(
+        "INSERT INTO encryption_key (id, kdf, salt, m_cost, t_cost, p_cost, wrapped_key, created_at)
+         VALUES (1, ?, ?, ?, ?, ?, ?, ?)",
+        params![
+            wrapped.kdf,
+            wrapped.params.as_ref().map(|params| STANDARD.encode(&params.salt)),
+            wrapped.params.as_ref().map(|params| params.m_cost),
+            wrapped.params.as_ref().map(|params| params.t_cost),
+            wrapped.params.as_ref().map(|params| params.p_cost),
+            wrapped.wrapped_key,
+            utils::current_timestamp(),
+        ],
+    ).map_err(|e| Error::DatabaseError(format!("Failed to store wrapped key: {}", e)))?;
+
+    let encrypted = seal_columns(&tx, &cipher)?;
+    tx.commit()
+        .map_err(|e| Error::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+    log::info!("已启用静态加密（{}），加密了{}行已有数据", source.kdf(), encrypted);
+
+    Ok(cipher)
+}
+
+/// 加密所有加密列中未加密或旧版加密的值，并将查询列替换为带密钥哈希，返回改写的行数
+fn seal_columns(conn: &Connection, cipher: &ColumnCipher) -> Result<usize, Error> {
+    let mut sealed = 0;
+    for column in ENCRYPTED_COLUMNS {
+        sealed += encrypt_column(conn, cipher, column)?;
+    }
+    for (table, column) in INDEXED_COLUMNS {
+        sealed += index_column(conn, cipher, table, column)?;
+    }
+    Ok(sealed)
+}
+
+/// 加密表中尚未按所在行加密的列值，返回加密的行数
+///
+/// 操作日志只允许追加，加密时临时移除其触发器；日志哈希基于明文内容计算，不受影响。
+fn encrypt_column(conn: &Connection, cipher: &ColumnCipher, column: &EncryptedColumn) -> Result<usize, Error> {
+    let EncryptedColumn { table, column: name, key, blob } = *column;
+    let key_columns: Vec<_> = key.iter().map(|key| format!("CAST({} AS TEXT)", key)).collect();
+    let rows = {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT rowid, CAST({name} AS BLOB), {keys} FROM {table}
+             WHERE {name} IS NOT NULL AND substr(CAST({name} AS BLOB), 1, ?) <> CAST(? AS BLOB)",
+            keys = key_columns.join(", "),
+        )).map_err(|e| Error::DatabaseError(format!("Failed to prepare statement: {}", e)))?;
+        let rows = stmt.query_map(
+            params![ENCRYPTED_PREFIX.len() as i64, ENCRYPTED_PREFIX],
+            |row| {
+                let keys = (0..key.len()).map(|index| row.get::<_, String>(index + 2)).collect::<Result<Vec<_>, _>>()?;
+                Ok((row.get::<_, i64>(0)?, row.get::<_, Vec<u8>>(1)?, keys))
+            },
+        ).map_err(|e| Error::DatabaseError(format!("Failed to execute query: {}", e)))?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| Error::DatabaseError(format!("Failed to fetch row: {}", e)))?
+    };
+    if rows.is_empty() {
+        return Ok(0);
+    }
+
+    let aead = cipher.aead.as_ref()
+        .ok_or_else(|| Error::CryptoError("No encryption key is loaded".to_string()))?;
+    without_triggers(conn, table, || {
+        for (rowid, value, keys) in &rows {
+            let plaintext = match value.strip_prefix(LEGACY_PREFIX.as_bytes()) {
+                Some(sealed) => open(aead, &String::from_utf8_lossy(sealed), b"")?,
+                None => value.clone(),
+            };
+            let keys: Vec<_> = keys.iter().map(String::as_str).collect();
+            let encrypted = cipher.encrypt_bytes(plaintext, column, &keys)?;
+            let encrypted = if blob {
+                Value::Blob(encrypted)
+            } else {
+                Value::Text(String::from_utf8_lossy(&encrypted).into_owned())
+            };
+            conn.execute(
+                &format!("UPDATE {} SET {} = ? WHERE rowid = ?", table, name),
+                params![encrypted, rowid],
+            ).map_err(|e| Error::DatabaseError(format!("Failed to encrypt {}.{}: {}", table, name, e)))?;
+        }
+        Ok(())
+    })?;
+
+    Ok(rows.len())
+}
+
+/// 将查询列中的明文替换为带密钥哈希，返回改写的行数
+fn index_column(conn: &Connection, cipher: &ColumnCipher, table: &str, column: &str) -> Result<usize, Error> {
+    let values = {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT DISTINCT {column} FROM {table} WHERE substr({column}, 1, ?) <> ?"
+        )).map_err(|e| Error::DatabaseError(format!("Failed to prepare statement: {}", e)))?;
+        let rows = stmt.query_map(params![INDEX_PREFIX.len() as i64, INDEX_PREFIX], |row| row.get::<_, String>(0))
+            .map_err(|e| Error::DatabaseError(format!("Failed to execute query: {}", e)))?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| Error::DatabaseError(format!("Failed to fetch row: {}", e)))?
+    };
+
+    let mut indexed = 0;
+    for value in &values {
+        indexed += conn.execute(
+            &format!("UPDATE OR IGNORE {} SET {} = ? WHERE {} = ?", table, column, column),
+            params_from_iter([cipher.index(value), value.clone()]),
+        ).map_err(|e| Error::DatabaseError(format!("Failed to index {}.{}: {}", table, column, e)))?;
+    }
+
+    Ok(indexed)
+}
+
+/// 临时移除表上的触发器执行`f`，完成后恢复
+fn without_triggers<F>(conn: &Connection, table: &str, f: F) -> Result<(), Error>
+where
+    F: FnOnce() -> Result<(), Error>,
+{
+    let triggers = {
+        let mut stmt = conn.prepare("SELECT name, sql FROM sqlite_master WHERE type = 'trigger' AND tbl_name = ?")
+            .map_err(|e| Error::DatabaseError(format!("Failed to prepare statement: {}", e)))?;
+        let rows = stmt.query_map(params![table], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+            .map_err(|e| Error::DatabaseError(format!("Failed to execute query: {}", e)))?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| Error::DatabaseError(format!("Failed to fetch row: {}", e)))?
+    };
+    for (name, _) in &triggers {
+        conn.execute(&format!("DROP TRIGGER {}", name), [])
+            .map_err(|e| Error::DatabaseError(format!("Failed to drop trigger {}: {}", name, e)))?;
+    }
+
+    f()?;
+
+    for (name, sql) in &triggers {
+        conn.execute_batch(sql)
+            .map_err(|e| Error::DatabaseError(format!("Failed to restore trigger {}: {}", name, e)))?;
+    }
+
+    Ok(())
+}
+
+/// 读取包装后的DEK
+fn read_wrapped_key(conn: &Connection) -> Result<Option<WrappedKey>, Error> {
+    conn.query_row(
+        "SELECT kdf, salt, m_cost, t_cost, p_cost, wrapped_key FROM encryption_key WHERE id = 1",
+        [],
+        |row| Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, Option<String>>(1)?,
+            row.get::<_, Option<u32>>(2)?,
+            row.get::<_, Option<u32>>(3)?,
+            row.get::<_, Option<u32>>(4)?,
+            row.get::<_, String>(5)?,
+        )),
+    ).optional()
+        .map_err(|e| Error::DatabaseError(format!("Failed to read encryption key: {}", e)))?
+        .map(|(kdf, salt, m_cost, t_cost, p_cost, wrapped_key)| {
+            let params = match (salt, m_cost, t_cost, p_cost) {
+                (Some(salt), Some(m_cost), Some(t_cost), Some(p_cost)) => Some(KdfParams {
+                    salt: STANDARD.decode(salt)
+                        .map_err(|e| Error::CryptoError(format!("Invalid key derivation salt: {}", e)))?,
+                    m_cost,
+                    t_cost,
+                    p_cost,
+                }),
+                _ => None,
+            };
+            Ok(WrappedKey { kdf, params, wrapped_key })
+        })
+        .transpose()
+}
+
+/// 使用包装密钥加密DEK
+fn wrap_key(dek: &Key, source: &KeySource) -> Result<WrappedKey, Error> {
+    let params = match source {
+        KeySource::Passphrase(_) => Some(KdfParams::generate()),
+        KeySource::KeyFile(_) => None,
+    };
+    let kek = derive_wrapping_key(source, params.as_ref())?;
+
+    Ok(WrappedKey {
+        kdf: source.kdf().to_string(),
+        wrapped_key: seal(&XChaCha20Poly1305::new(&kek), dek, WRAP_AAD)?,
+        params,
+    })
+}
+
+/// 使用包装密钥解密DEK
+fn unwrap_key(stored: &WrappedKey, source: &KeySource) -> Result<Key, Error> {
+    if stored.kdf != source.kdf() {
+        return Err(Error::CryptoError(format!(
+            "Database encryption key is wrapped with {}, but a {} was provided",
+            stored.kdf,
+            match source {
+                KeySource::Passphrase(_) => "passphrase",
+                KeySource::KeyFile(_) => "key file",
+            }
+        )));
+    }
+
+    let kek = derive_wrapping_key(source, stored.params.as_ref())?;
+    let dek = open(&XChaCha20Poly1305::new(&kek), &stored.wrapped_key, WRAP_AAD)
+        .map_err(|_| Error::CryptoError(
+            "Failed to unwrap the data encryption key: wrong passphrase or key file".to_string()
+        ))?;
+    if dek.len() != KEY_LEN {
+        return Err(Error::CryptoError("Data encryption key has an invalid length".to_string()));
+    }
+
+    Ok(*Key::from_slice(&dek))
+}
+
+/// 由口令或密钥文件得到包装密钥
+fn derive_wrapping_key(source: &KeySource, params: Option<&KdfParams>) -> Result<Key, Error> {
+    match source {
+        KeySource::Passphrase(passphrase) => {
+            let params = params
+                .ok_or_else(|| Error::CryptoError("Missing key derivation parameters".to_string()))?;
+            let argon2 = Argon2::new(
+                Algorithm::Argon2id,
+                Version::V0x13,
+                Params::new(params.m_cost, params.t_cost, params.p_cost, Some(KEY_LEN))
+                    .map_err(|e| Error::CryptoError(format!("Invalid key derivation parameters: {}", e)))?,
+            );
+
+            let mut key = Key::default();
+            argon2.hash_password_into(passphrase.as_bytes(), &params.salt, &mut key)
+                .map_err(|e| Error::CryptoError(format!("Failed to derive wrapping key: {}", e)))?;
+            Ok(key)
+        }
+        KeySource::KeyFile(path) => {
+            let contents = std::fs::read_to_string(path)
+                .map_err(|e| Error::CryptoError(format!("Failed to read key file {}: {}", path.display(), e)))?;
+            let bytes = STANDARD.decode(contents.trim())
+                .map_err(|e| Error::CryptoError(format!("Key file {} is not valid base64: {}", path.display(), e)))?;
+            if bytes.len() != KEY_LEN {
+                return Err(Error::CryptoError(format!(
+                    "Key file {} must contain {} bytes, found {}",
+                    path.display(), KEY_LEN, bytes.len()
+                )));
+            }
+            Ok(*Key::from_slice(&bytes))
+        }
+    }
+}
+
+/// 加密并编码为Base64（随机数在前）
+fn seal(aead: &XChaCha20Poly1305, plaintext: &[u8], aad: &[u8]) -> Result<String, Error> {
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = aead.encrypt(&nonce, Payload { msg: plaintext, aad })
+        .map_err(|e| Error::CryptoError(format!("Encryption failed: {}", e)))?;
+
+    let mut sealed = nonce.to_vec();
+    sealed.extend_from_slice(&ciphertext);
+    Ok(STANDARD.encode(sealed))
+}
+
+/// 解码并解密`seal`的结果
+fn open(aead: &XChaCha20Poly1305, sealed: &str, aad: &[u8]) -> Result<Vec<u8>, Error> {
+    let bytes = STANDARD.decode(sealed)
+        .map_err(|e| Error::CryptoError(format!("Invalid encrypted value: {}", e)))?;
+    if bytes.len() < NONCE_LEN {
+        return Err(Error::CryptoError("Encrypted value is too short".to_string()));
+    }
+
+    let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+    aead.decrypt(XNonce::from_slice(nonce), Payload { msg: ciphertext, aad })
+        .map_err(|_| Error::CryptoError("Failed to decrypt value: data is corrupted or the key is wrong".to_string()))
+}
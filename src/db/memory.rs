@@ -0,0 +1,922 @@
+//! 内存存储后端 - 数据只保存在进程内，适用于测试和临时部署
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Mutex, MutexGuard};
+use async_trait::async_trait;
+use crate::anchoring::{AnchorBatch, AnchorProof};
+use crate::auth::ApiKey;
+use crate::backup::{self, ConflictPolicy, ImportReport, Registry};
+use crate::blockchain::LedgerCheckpoint;
+use crate::did::{self, DIDDocument, KeyPolicy};
+use crate::idempotency::{self, IdempotencyRecord, StoredResponse};
+use crate::oplog::{DidOperation, HeadAnchor, LoggedOperation, OperationLogEntry, SignedOperation, replay::DidSnapshot};
+use crate::outbox::{self, OutboxEntry, OutboxOperation, OutboxStatus, PendingOperation};
+use crate::ratelimit;
+use crate::types::Error;
+use crate::utils;
+use super::{DidQuery, DidRecord, DidStatus, DidStore, DidVersion, DidWrite, NewAnchorProof, WrittenChange};
+
+/// 出站记录及其所属批次
+#[derive(Debug, Clone)]
+struct StoredOutboxEntry {
+    entry: OutboxEntry,
+    batch_id: Option<i64>,
+}
+
+/// 锚定批次及其投递状态
+#[derive(Debug, Clone)]
+struct StoredBatch {
+    batch: AnchorBatch,
+    status: OutboxStatus,
+    next_attempt_at: u64,
+    last_error: Option<String>,
+    tx_hash: Option<String>,
+}
+
+/// 包含证明及其所属批次
+#[derive(Debug, Clone)]
+struct StoredProof {
+    batch_id: i64,
+    proof: NewAnchorProof,
+}
+
+/// 内存中的全部数据
+#[derive(Debug, Clone, Default)]
+struct MemoryState {
+    documents: BTreeMap<String, DidRecord>,
+    history: BTreeMap<(String, u64), DidVersion>,
+    operation_log: Vec<OperationLogEntry>,
+    snapshots: BTreeMap<String, DidSnapshot>,
+    head_anchors: BTreeMap<i64, HeadAnchor>,
+    outbox: BTreeMap<i64, StoredOutboxEntry>,
+    batches: BTreeMap<i64, StoredBatch>,
+    proofs: BTreeMap<i64, StoredProof>,
+    checkpoints: BTreeMap<String, LedgerCheckpoint>,
+    nonces: HashMap<String, (String, u64)>,
+    api_keys: BTreeMap<String, ApiKey>,
+    write_usage: HashMap<(String, u64), u64>,
+    idempotency_keys: HashMap<(String, String), (IdempotencyRecord, u64)>,
+    next_outbox_id: i64,
+    next_batch_id: i64,
+}
+
+impl MemoryState {
+    /// 以DID文档的当前状态追加一条操作日志
+    fn append_log(&mut self, did: &str, operation: &SignedOperation) -> Result<(), Error> {
+        let record = self.documents.get(did)
+            .ok_or_else(|| Error::NotFound(format!("DID not found: {}", did)))?;
+        let prev_hash = self.operation_log.last().map(|entry| entry.entry_hash.clone());
+        let prev_did_hash = self.operation_log.iter().rev()
+            .find(|entry| entry.operation.did == did)
+            .map(|entry| entry.entry_hash.clone());
+
+        let mut entry = OperationLogEntry::new(
+            LoggedOperation::from_record(record, operation)?,
+            prev_did_hash,
+            prev_hash,
+            utils::current_timestamp(),
+        )?;
+        entry.seq = self.operation_log.len() as i64 + 1;
+        self.operation_log.push(entry);
+
+        Ok(())
+    }
+
+    /// 将DID文档的当前状态写入历史版本
+    fn record_version(&mut self, did: &str) {
+        if let Some(record) = self.documents.get(did) {
+            self.history.insert((did.to_string(), record.version_id), DidVersion {
+                did: did.to_string(),
+                version_id: record.version_id,
+                document: record.document.clone(),
+                is_active: record.is_active,
+                recorded_at: utils::current_timestamp(),
+            });
+        }
+    }
+
+    /// 公钥唯一策略下检查文档中的公钥没有被其他活跃的DID使用
+    fn check_keys_unclaimed(&self, did: &str, document: &DIDDocument) -> Result<(), Error> {
+        if did::key_policy() != KeyPolicy::Unique {
+            return Ok(());
+        }
+
+        for key in &document.public_keys {
+            let owner = self.documents.values().find(|record| {
+                record.is_active
+                    && record.did != did
+                    && record.document.public_keys.iter().any(|other| other.public_key_base58 == key.public_key_base58)
+            });
+            if let Some(owner) = owner {
+                return Err(Error::InvalidState(format!(
+                    "Public key {} is already registered to {}",
+                    key.public_key_base58, owner.did
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 写入出站记录，返回记录ID
+    fn enqueue_operation(&mut self, did: &str, version_id: u64, operation: &PendingOperation) -> i64 {
+        let now = utils::current_timestamp();
+        self.next_outbox_id += 1;
+
+        self.outbox.insert(self.next_outbox_id, StoredOutboxEntry {
+            entry: OutboxEntry {
+                id: self.next_outbox_id,
+                did: did.to_string(),
+                ledger: operation.ledger.clone(),
+                operation: operation.operation,
+                payload: operation.payload.clone(),
+                idempotency_key: outbox::new_idempotency_key(),
+                version_id: Some(version_id),
+                status: OutboxStatus::Pending,
+                attempts: 0,
+                next_attempt_at: now,
+                last_error: None,
+                tx_hash: None,
+                created_at: now,
+                updated_at: now,
+            },
+            batch_id: None,
+        });
+        self.next_outbox_id
+    }
+
+    /// 存储DID文档并写入出站记录
+    fn store_document(
+        &mut self,
+        did: &str,
+        document: &DIDDocument,
+        base_version: Option<u64>,
+        change: &SignedOperation,
+        operation: &PendingOperation,
+    ) -> Result<WrittenChange, Error> {
+        let is_update = !matches!(change.operation, DidOperation::Create { .. });
+        self.check_keys_unclaimed(did, document)?;
+
+        let version_id = match (self.documents.get_mut(did), is_update) {
+            (Some(_), false) => return Err(Error::InvalidInput(format!("DID already exists: {}", did))),
+            (None, true) => return Err(Error::NotFound(format!("DID not found: {}", did))),
+            (Some(record), true) => {
+                check_version(record, base_version)?;
+                record.document = document.clone();
+                record.updated_at = document.updated;
+                record.version_id += 1;
+                record.version_id
+            }
+            (None, false) => {
+                self.documents.insert(did.to_string(), DidRecord {
+                    did: did.to_string(),
+                    document: document.clone(),
+                    is_active: true,
+                    created_at: document.created,
+                    updated_at: document.updated,
+                    version_id: 1,
+                });
+                1
+            }
+        };
+
+        self.record_version(did);
+        self.append_log(did, change)?;
+        let outbox_id = self.enqueue_operation(did, version_id, operation);
+        Ok(WrittenChange { version_id, outbox_id })
+    }
+
+    /// 停用DID并写入出站记录
+    fn deactivate(
+        &mut self,
+        did: &str,
+        base_version: Option<u64>,
+        change: &SignedOperation,
+        operation: &PendingOperation,
+    ) -> Result<WrittenChange, Error> {
+        let record = self.documents.get_mut(did)
+            .filter(|record| record.is_active)
+            .ok_or_else(|| Error::NotFound(format!("DID not found: {}", did)))?;
+        check_version(record, base_version)?;
+        record.is_active = false;
+        record.updated_at = utils::current_timestamp();
+        record.version_id += 1;
+        let version_id = record.version_id;
+
+        self.record_version(did);
+        self.append_log(did, change)?;
+        let outbox_id = self.enqueue_operation(did, version_id, operation);
+        Ok(WrittenChange { version_id, outbox_id })
+    }
+
+    /// 获取出站记录
+    fn outbox_entry_mut(&mut self, id: i64) -> Result<&mut StoredOutboxEntry, Error> {
+        self.outbox.get_mut(&id)
+            .ok_or_else(|| Error::NotFound(format!("Outbox entry not found: {}", id)))
+    }
+
+    /// 推进账本检查点
+    fn advance_checkpoint(
+        &mut self,
+        ledger: &str,
+        outbox_id: Option<i64>,
+        batch_id: Option<i64>,
+        tx_hash: &str,
+        delivered: u64,
+    ) {
+        let checkpoint = self.checkpoints.entry(ledger.to_string()).or_insert_with(|| LedgerCheckpoint {
+            ledger: ledger.to_string(),
+            last_outbox_id: None,
+            last_batch_id: None,
+            last_tx_hash: None,
+            delivered: 0,
+            updated_at: 0,
+        });
+
+        checkpoint.last_outbox_id = checkpoint.last_outbox_id.max(outbox_id);
+        checkpoint.last_batch_id = checkpoint.last_batch_id.max(batch_id);
+        checkpoint.last_tx_hash = Some(tx_hash.to_string());
+        checkpoint.delivered += delivered;
+        checkpoint.updated_at = utils::current_timestamp();
+    }
+
+    /// 按条件筛选出站记录
+    fn filter_outbox<F>(&self, limit: usize, predicate: F) -> Vec<OutboxEntry>
+    where
+        F: Fn(&StoredOutboxEntry) -> bool,
+    {
+        self.outbox.values()
+            .filter(|stored| predicate(stored))
+            .take(limit)
+            .map(|stored| stored.entry.clone())
+            .collect()
+    }
+}
+
+/// 判断DID记录是否满足查询条件
+fn matches_query(record: &DidRecord, query: &DidQuery) -> bool {
+    let in_range = |value: u64, after: Option<u64>, before: Option<u64>| {
+        after.is_none_or(|after| value >= after) && before.is_none_or(|before| value < before)
+    };
+
+    if let Some(status) = query.status {
+        if record.is_active != (status == DidStatus::Active) {
+            return false;
+        }
+    }
+    if !in_range(record.created_at, query.created_after, query.created_before)
+        || !in_range(record.updated_at, query.updated_after, query.updated_before)
+    {
+        return false;
+    }
+
+    let attributes = super::did_attributes(&record.did, &record.document);
+    query.attribute_filters().iter().all(|(name, value)| {
+        attributes.iter().any(|(attribute, attribute_value)| attribute == name && attribute_value == value)
+    })
+}
+
+/// 检查记录的当前版本是否为变更所基于的版本
+fn check_version(record: &DidRecord, base_version: Option<u64>) -> Result<(), Error> {
+    match base_version {
+        Some(base_version) if base_version != record.version_id => Err(Error::PreconditionFailed(format!(
+            "DID {} is at version {}, not version {}",
+            record.did, record.version_id, base_version
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// 内存存储后端
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    state: Mutex<MemoryState>,
+}
+
+impl MemoryStore {
+    /// 创建空的内存存储
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 获取内部状态的锁
+    fn state(&self) -> MutexGuard<'_, MemoryState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[async_trait]
+impl DidStore for MemoryStore {
+    async fn store_did_document(
+        &self,
+        did: &str,
+        document: &DIDDocument,
+        base_version: Option<u64>,
+        change: &SignedOperation,
+        operation: &PendingOperation,
+    ) -> Result<u64, Error> {
+        let written = self.state().store_document(did, document, base_version, change, operation)?;
+        Ok(written.version_id)
+    }
+
+    async fn get_did_document(&self, did: &str) -> Result<Option<DIDDocument>, Error> {
+        Ok(self.state().documents.get(did)
+            .filter(|record| record.is_active)
+            .map(|record| record.document.clone()))
+    }
+
+    async fn deactivate_did(
+        &self,
+        did: &str,
+        base_version: Option<u64>,
+        change: &SignedOperation,
+        operation: &PendingOperation,
+    ) -> Result<(), Error> {
+        self.state().deactivate(did, base_version, change, operation)?;
+        Ok(())
+    }
+
+    async fn apply_did_writes(&self, writes: &[DidWrite]) -> Result<Vec<WrittenChange>, Error> {
+        let mut state = self.state();
+        // 在副本上应用，全部成功后才替换
+        let mut next = state.clone();
+
+        let written = writes.iter()
+            .map(|write| match write.change.operation {
+                DidOperation::Deactivate => next.deactivate(&write.did, write.base_version, &write.change, &write.operation),
+                _ => next.store_document(&write.did, &write.document, write.base_version, &write.change, &write.operation),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        *state = next;
+        Ok(written)
+    }
+
+    async fn get_did_record(&self, did: &str) -> Result<Option<DidRecord>, Error> {
+        Ok(self.state().documents.get(did).cloned())
+    }
+
+    async fn list_did_records(&self) -> Result<Vec<DidRecord>, Error> {
+        Ok(self.state().documents.values().cloned().collect())
+    }
+
+    async fn query_did_records(&self, query: &DidQuery) -> Result<Vec<DidRecord>, Error> {
+        Ok(self.state().documents.values()
+            .filter(|record| query.after.as_ref().is_none_or(|after| &record.did > after))
+            .filter(|record| matches_query(record, query))
+            .take(query.limit)
+            .cloned()
+            .collect())
+    }
+
+    async fn overwrite_did_document(&self, did: &str, document: &DIDDocument, is_active: bool) -> Result<(), Error> {
+        let mut state = self.state();
+        if is_active {
+            state.check_keys_unclaimed(did, document)?;
+        }
+
+        match state.documents.get_mut(did) {
+            Some(record) => {
+                record.document = document.clone();
+                record.is_active = is_active;
+                record.updated_at = document.updated;
+                record.version_id += 1;
+            }
+            None => {
+                state.documents.insert(did.to_string(), DidRecord {
+                    did: did.to_string(),
+                    document: document.clone(),
+                    is_active,
+                    created_at: document.created,
+                    updated_at: document.updated,
+                    version_id: 1,
+                });
+            }
+        }
+
+        state.record_version(did);
+        let repair = SignedOperation::system(DidOperation::Repair { document: document.clone(), is_active });
+        state.append_log(did, &repair)
+    }
+
+    async fn set_did_active(&self, did: &str, is_active: bool) -> Result<(), Error> {
+        let mut state = self.state();
+
+        let document = state.documents.get(did)
+            .map(|record| record.document.clone())
+            .ok_or_else(|| Error::NotFound(format!("DID not found: {}", did)))?;
+        if is_active {
+            state.check_keys_unclaimed(did, &document)?;
+        }
+        let record = state.documents.get_mut(did)
+            .ok_or_else(|| Error::NotFound(format!("DID not found: {}", did)))?;
+        record.is_active = is_active;
+        record.updated_at = utils::current_timestamp();
+        record.version_id += 1;
+
+        state.record_version(did);
+        let repair = SignedOperation::system(DidOperation::Repair { document, is_active });
+        state.append_log(did, &repair)
+    }
+
+    async fn list_did_versions(&self, did: &str) -> Result<Vec<DidVersion>, Error> {
+        Ok(self.state().history.values()
+            .filter(|version| version.did == did)
+            .cloned()
+            .collect())
+    }
+
+    async fn fetch_due_outbox_entries(
+        &self,
+        ledger: &str,
+        now: u64,
+        limit: usize,
+        skip_batched: bool,
+    ) -> Result<Vec<OutboxEntry>, Error> {
+        let state = self.state();
+        Ok(state.filter_outbox(limit, |stored| {
+            let entry = &stored.entry;
+            let batched = matches!(entry.operation, OutboxOperation::Anchor | OutboxOperation::Deactivate);
+            entry.ledger == ledger
+                && entry.status == OutboxStatus::Pending
+                && entry.next_attempt_at <= now
+                && !(skip_batched && batched)
+                && !state.outbox.range(..entry.id).any(|(_, earlier)| {
+                    earlier.entry.did == entry.did && earlier.entry.status != OutboxStatus::Delivered
+                })
+        }))
+    }
+
+    async fn list_outbox_entries(&self, status: OutboxStatus) -> Result<Vec<OutboxEntry>, Error> {
+        Ok(self.state().filter_outbox(usize::MAX, |stored| stored.entry.status == status))
+    }
+
+    async fn mark_outbox_delivered(&self, id: i64, tx_hash: &str) -> Result<(), Error> {
+        let mut state = self.state();
+
+        let entry = &mut state.outbox_entry_mut(id)?.entry;
+        entry.status = OutboxStatus::Delivered;
+        entry.tx_hash = Some(tx_hash.to_string());
+        entry.last_error = None;
+        entry.updated_at = utils::current_timestamp();
+        let ledger = entry.ledger.clone();
+
+        state.advance_checkpoint(&ledger, Some(id), None, tx_hash, 1);
+        Ok(())
+    }
+
+    async fn mark_outbox_failed(
+        &self,
+        id: i64,
+        attempts: u32,
+        error: &str,
+        next_attempt_at: Option<u64>,
+    ) -> Result<(), Error> {
+        let mut state = self.state();
+        let now = utils::current_timestamp();
+
+        let entry = &mut state.outbox_entry_mut(id)?.entry;
+        entry.status = if next_attempt_at.is_some() { OutboxStatus::Pending } else { OutboxStatus::Dead };
+        entry.attempts = attempts;
+        entry.last_error = Some(error.to_string());
+        entry.next_attempt_at = next_attempt_at.unwrap_or(now);
+        entry.updated_at = now;
+
+        Ok(())
+    }
+
+    async fn requeue_outbox_entry(&self, id: i64) -> Result<OutboxEntry, Error> {
+        let mut state = self.state();
+        let now = utils::current_timestamp();
+
+        let stored = state.outbox_entry_mut(id)?;
+        if stored.entry.status != OutboxStatus::Dead {
+            return Err(Error::InvalidState(format!("Outbox entry {} is not dead-lettered", id)));
+        }
+        stored.entry.status = OutboxStatus::Pending;
+        stored.entry.attempts = 0;
+        stored.entry.next_attempt_at = now;
+        stored.entry.updated_at = now;
+        stored.batch_id = None;
+
+        Ok(stored.entry.clone())
+    }
+
+    async fn list_dids_missing_locally(&self) -> Result<Vec<String>, Error> {
+        let state = self.state();
+
+        let mut dids: Vec<String> = state.outbox.values()
+            .map(|stored| &stored.entry.did)
+            .filter(|did| !state.documents.contains_key(*did))
+            .cloned()
+            .collect();
+        dids.sort();
+        dids.dedup();
+
+        Ok(dids)
+    }
+
+    async fn count_outbox_entries_for_did(&self, did: &str, status: OutboxStatus) -> Result<u64, Error> {
+        Ok(self.state().outbox.values()
+            .filter(|stored| stored.entry.did == did && stored.entry.status == status)
+            .count() as u64)
+    }
+
+    async fn list_outbox_entries_for_did(&self, did: &str, status: OutboxStatus) -> Result<Vec<OutboxEntry>, Error> {
+        Ok(self.state().filter_outbox(usize::MAX, |stored| stored.entry.did == did && stored.entry.status == status))
+    }
+
+    async fn list_ledger_checkpoints(&self) -> Result<Vec<LedgerCheckpoint>, Error> {
+        Ok(self.state().checkpoints.values().cloned().collect())
+    }
+
+    async fn list_operation_log(&self, after: i64, limit: usize) -> Result<Vec<OperationLogEntry>, Error> {
+        Ok(self.state().operation_log.iter()
+            .filter(|entry| entry.seq > after)
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+
+    async fn last_operation_log_entry(&self) -> Result<Option<OperationLogEntry>, Error> {
+        Ok(self.state().operation_log.last().cloned())
+    }
+
+    async fn record_head_anchor(&self, anchor: &HeadAnchor) -> Result<(), Error> {
+        self.state().head_anchors.insert(anchor.seq, anchor.clone());
+        Ok(())
+    }
+
+    async fn latest_head_anchor(&self) -> Result<Option<HeadAnchor>, Error> {
+        Ok(self.state().head_anchors.values().next_back().cloned())
+    }
+
+    async fn list_did_operations(&self, did: &str, after: i64) -> Result<Vec<OperationLogEntry>, Error> {
+        Ok(self.state().operation_log.iter()
+            .filter(|entry| entry.operation.did == did && entry.seq > after)
+            .cloned()
+            .collect())
+    }
+
+    async fn list_logged_dids(&self) -> Result<Vec<String>, Error> {
+        let mut dids: Vec<_> = self.state().operation_log.iter()
+            .map(|entry| entry.operation.did.clone())
+            .collect();
+        dids.sort();
+        dids.dedup();
+        Ok(dids)
+    }
+
+    async fn get_did_snapshot(&self, did: &str) -> Result<Option<DidSnapshot>, Error> {
+        Ok(self.state().snapshots.get(did).cloned())
+    }
+
+    async fn save_did_snapshot(&self, snapshot: &DidSnapshot) -> Result<(), Error> {
+        self.state().snapshots.insert(snapshot.record.did.clone(), snapshot.clone());
+        Ok(())
+    }
+
+    async fn restore_did_record(&self, record: &DidRecord) -> Result<(), Error> {
+        let mut state = self.state();
+        state.documents.insert(record.did.clone(), record.clone());
+        state.record_version(&record.did);
+        Ok(())
+    }
+
+    async fn export_registry(&self) -> Result<Registry, Error> {
+        let state = self.state();
+        Ok(Registry {
+            dids: state.documents.values().cloned().collect(),
+            versions: state.history.values().cloned().collect(),
+            operations: state.operation_log.clone(),
+        })
+    }
+
+    async fn import_registry(&self, registry: &Registry, policy: ConflictPolicy) -> Result<ImportReport, Error> {
+        let mut state = self.state();
+        let restore_log = state.documents.is_empty() && state.operation_log.is_empty();
+
+        let existing: HashSet<String> = registry.dids.iter()
+            .filter(|record| state.documents.contains_key(&record.did))
+            .map(|record| record.did.clone())
+            .collect();
+        if policy == ConflictPolicy::Fail && !existing.is_empty() {
+            let mut conflicts: Vec<_> = existing.into_iter().collect();
+            conflicts.sort();
+            return Err(Error::InvalidState(format!("DIDs already exist: {}", conflicts.join(", "))));
+        }
+
+        // 先为所有DID计算合并计划，任何一个DID无法合并时不修改本地数据
+        let mut plans = HashMap::new();
+        if !restore_log {
+            for record in &registry.dids {
+                if !(existing.contains(&record.did) && policy == ConflictPolicy::Skip) {
+                    plans.insert(record.did.as_str(), backup::plan_merge(registry, record, state.documents.get(&record.did))?);
+                }
+            }
+        }
+
+        let mut report = ImportReport::default();
+        if restore_log {
+            state.operation_log = registry.operations.clone();
+            report.operations_restored = registry.operations.len() as u64;
+        }
+
+        for record in &registry.dids {
+            let exists = existing.contains(&record.did);
+            if exists && policy == ConflictPolicy::Skip {
+                report.skipped.push(record.did.clone());
+                continue;
+            }
+            if exists {
+                report.overwritten += 1;
+            } else {
+                report.imported += 1;
+            }
+
+            // 本地已有操作日志时依次追加归档中本地之后的签名操作
+            if let Some(changes) = plans.remove(record.did.as_str()) {
+                for (next, change) in changes {
+                    if next.is_active {
+                        state.check_keys_unclaimed(&record.did, &next.document)?;
+                    }
+                    state.documents.insert(record.did.clone(), next);
+                    state.record_version(&record.did);
+                    state.append_log(&record.did, &change)?;
+                    report.versions += 1;
+                }
+                continue;
+            }
+
+            if record.is_active {
+                state.check_keys_unclaimed(&record.did, &record.document)?;
+            }
+            state.documents.insert(record.did.clone(), record.clone());
+            for version in registry.versions.iter().filter(|version| version.did == record.did) {
+                state.history.insert((version.did.clone(), version.version_id), version.clone());
+                report.versions += 1;
+            }
+        }
+
+        Ok(report)
+    }
+
+    async fn fetch_unbatched_anchor_entries(&self, ledger: &str, limit: usize) -> Result<Vec<OutboxEntry>, Error> {
+        Ok(self.state().filter_outbox(limit, |stored| {
+            stored.entry.ledger == ledger
+                && stored.entry.status == OutboxStatus::Pending
+                && stored.batch_id.is_none()
+                && matches!(stored.entry.operation, OutboxOperation::Anchor | OutboxOperation::Deactivate)
+        }))
+    }
+
+    async fn create_anchor_batch(
+        &self,
+        ledger: &str,
+        root: &str,
+        idempotency_key: &str,
+        proofs: &[NewAnchorProof],
+    ) -> Result<i64, Error> {
+        let mut state = self.state();
+        let now = utils::current_timestamp();
+
+        // 同一个操作不能出现在两个批次中
+        for proof in proofs {
+            if state.outbox.get(&proof.outbox_id).is_none_or(|stored| stored.batch_id.is_some()) {
+                return Err(Error::InvalidState(format!("Outbox entry {} is missing or already batched", proof.outbox_id)));
+            }
+        }
+
+        state.next_batch_id += 1;
+        let batch_id = state.next_batch_id;
+        state.batches.insert(batch_id, StoredBatch {
+            batch: AnchorBatch {
+                id: batch_id,
+                ledger: ledger.to_string(),
+                root: root.to_string(),
+                size: proofs.len() as u64,
+                idempotency_key: idempotency_key.to_string(),
+                attempts: 0,
+            },
+            status: OutboxStatus::Pending,
+            next_attempt_at: now,
+            last_error: None,
+            tx_hash: None,
+        });
+
+        for proof in proofs {
+            state.proofs.insert(proof.outbox_id, StoredProof { batch_id, proof: proof.clone() });
+            let stored = state.outbox_entry_mut(proof.outbox_id)?;
+            stored.batch_id = Some(batch_id);
+            stored.entry.updated_at = now;
+        }
+
+        Ok(batch_id)
+    }
+
+    async fn fetch_due_anchor_batches(&self, now: u64, limit: usize) -> Result<Vec<AnchorBatch>, Error> {
+        Ok(self.state().batches.values()
+            .filter(|stored| stored.status == OutboxStatus::Pending && stored.next_attempt_at <= now)
+            .take(limit)
+            .map(|stored| stored.batch.clone())
+            .collect())
+    }
+
+    async fn mark_anchor_batch_delivered(&self, batch_id: i64, tx_hash: &str) -> Result<(), Error> {
+        let mut state = self.state();
+        let now = utils::current_timestamp();
+
+        let batch = state.batches.get_mut(&batch_id)
+            .ok_or_else(|| Error::NotFound(format!("Anchor batch not found: {}", batch_id)))?;
+        batch.status = OutboxStatus::Delivered;
+        batch.tx_hash = Some(tx_hash.to_string());
+        batch.last_error = None;
+        let ledger = batch.batch.ledger.clone();
+
+        let mut delivered = 0;
+        for stored in state.outbox.values_mut().filter(|stored| stored.batch_id == Some(batch_id)) {
+            stored.entry.status = OutboxStatus::Delivered;
+            stored.entry.tx_hash = Some(tx_hash.to_string());
+            stored.entry.last_error = None;
+            stored.entry.updated_at = now;
+            delivered += 1;
+        }
+
+        state.advance_checkpoint(&ledger, None, Some(batch_id), tx_hash, delivered);
+        Ok(())
+    }
+
+    async fn mark_anchor_batch_failed(
+        &self,
+        batch_id: i64,
+        attempts: u32,
+        error: &str,
+        next_attempt_at: Option<u64>,
+    ) -> Result<(), Error> {
+        let mut state = self.state();
+        let now = utils::current_timestamp();
+        let status = if next_attempt_at.is_some() { OutboxStatus::Pending } else { OutboxStatus::Dead };
+
+        let batch = state.batches.get_mut(&batch_id)
+            .ok_or_else(|| Error::NotFound(format!("Anchor batch not found: {}", batch_id)))?;
+        batch.status = status;
+        batch.batch.attempts = attempts;
+        batch.last_error = Some(error.to_string());
+        batch.next_attempt_at = next_attempt_at.unwrap_or(now);
+
+        for stored in state.outbox.values_mut().filter(|stored| stored.batch_id == Some(batch_id)) {
+            stored.entry.status = status;
+            stored.entry.attempts = attempts;
+            stored.entry.last_error = Some(error.to_string());
+            stored.entry.updated_at = now;
+        }
+
+        Ok(())
+    }
+
+    async fn get_anchor_proof(&self, did: &str, version_id: u64) -> Result<Option<AnchorProof>, Error> {
+        let state = self.state();
+
+        let stored = state.proofs.values()
+            .filter(|stored| stored.proof.leaf.did == did && stored.proof.leaf.version_id == version_id)
+            .max_by_key(|stored| stored.batch_id);
+        let (stored, batch) = match stored.and_then(|stored| Some((stored, state.batches.get(&stored.batch_id)?))) {
+            Some(found) => found,
+            None => return Ok(None),
+        };
+
+        Ok(Some(AnchorProof {
+            did: did.to_string(),
+            version_id,
+            leaf: stored.proof.leaf.clone(),
+            leaf_index: stored.proof.leaf_index,
+            proof: stored.proof.proof.clone(),
+            batch_id: stored.batch_id,
+            ledger: batch.batch.ledger.clone(),
+            root: batch.batch.root.clone(),
+            status: batch.status,
+            tx_hash: batch.tx_hash.clone(),
+        }))
+    }
+
+    async fn store_nonce(&self, nonce: &str, subject: &str, expires_at: u64) -> Result<(), Error> {
+        let mut state = self.state();
+
+        if state.nonces.contains_key(nonce) {
+            return Err(Error::InvalidInput(format!("Nonce already exists: {}", nonce)));
+        }
+        state.nonces.insert(nonce.to_string(), (subject.to_string(), expires_at));
+
+        Ok(())
+    }
+
+    async fn consume_nonce(&self, nonce: &str) -> Result<Option<String>, Error> {
+        let mut state = self.state();
+        let now = utils::current_timestamp();
+
+        let subject = state.nonces.remove(nonce)
+            .filter(|(_, expires_at)| *expires_at > now)
+            .map(|(subject, _)| subject);
+        state.nonces.retain(|_, (_, expires_at)| *expires_at > now);
+
+        Ok(subject)
+    }
+
+    async fn create_api_key(&self, key: &ApiKey) -> Result<(), Error> {
+        let mut state = self.state();
+
+        if state.api_keys.contains_key(&key.id) {
+            return Err(Error::InvalidInput(format!("API key already exists: {}", key.id)));
+        }
+        state.api_keys.insert(key.id.clone(), key.clone());
+
+        Ok(())
+    }
+
+    async fn get_api_key(&self, id: &str) -> Result<Option<ApiKey>, Error> {
+        Ok(self.state().api_keys.get(id).cloned())
+    }
+
+    async fn list_api_keys(&self) -> Result<Vec<ApiKey>, Error> {
+        let mut keys: Vec<ApiKey> = self.state().api_keys.values().cloned().collect();
+        keys.sort_by(|a, b| (a.created_at, &a.id).cmp(&(b.created_at, &b.id)));
+        Ok(keys)
+    }
+
+    async fn revoke_api_key(&self, id: &str, revoked_at: u64) -> Result<bool, Error> {
+        let mut state = self.state();
+
+        match state.api_keys.get_mut(id) {
+            Some(key) if key.revoked_at.is_none() => {
+                key.revoked_at = Some(revoked_at);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn get_write_usage(&self, tenant: &str, day: u64) -> Result<u64, Error> {
+        Ok(self.state().write_usage.get(&(tenant.to_string(), day)).copied().unwrap_or_default())
+    }
+
+    async fn reserve_write(&self, tenant: &str, day: u64, quota: u64, count: u64) -> Result<Option<u64>, Error> {
+        let mut state = self.state();
+        let oldest = day.saturating_sub(ratelimit::USAGE_RETENTION_DAYS);
+
+        state.write_usage.retain(|(_, usage_day), _| *usage_day >= oldest);
+        let writes = state.write_usage.entry((tenant.to_string(), day)).or_default();
+        if *writes + count > quota {
+            return Ok(None);
+        }
+        *writes += count;
+
+        Ok(Some(*writes))
+    }
+
+    async fn release_write(&self, tenant: &str, day: u64, count: u64) -> Result<(), Error> {
+        if let Some(writes) = self.state().write_usage.get_mut(&(tenant.to_string(), day)) {
+            *writes = writes.saturating_sub(count);
+        }
+        Ok(())
+    }
+
+    async fn claim_idempotency_key(
+        &self,
+        tenant: &str,
+        key: &str,
+        fingerprint: &str,
+        now: u64,
+        expires_at: u64,
+    ) -> Result<Option<IdempotencyRecord>, Error> {
+        let mut state = self.state();
+        state.idempotency_keys.retain(|_, (_, record_expires_at)| *record_expires_at > now);
+
+        let id = (tenant.to_string(), key.to_string());
+        if let Some((record, _)) = state.idempotency_keys.get(&id) {
+            let abandoned = record.response.is_none() && record.created_at + idempotency::LOCK_TIMEOUT_SECS <= now;
+            if !abandoned {
+                return Ok(Some(record.clone()));
+            }
+        }
+
+        let record = IdempotencyRecord { fingerprint: fingerprint.to_string(), created_at: now, response: None };
+        state.idempotency_keys.insert(id, (record, expires_at));
+        Ok(None)
+    }
+
+    async fn complete_idempotency_key(&self, tenant: &str, key: &str, response: &StoredResponse) -> Result<(), Error> {
+        if let Some((record, _)) = self.state().idempotency_keys.get_mut(&(tenant.to_string(), key.to_string())) {
+            record.response = Some(response.clone());
+        }
+        Ok(())
+    }
+
+    async fn release_idempotency_key(&self, tenant: &str, key: &str) -> Result<(), Error> {
+        let mut state = self.state();
+        let id = (tenant.to_string(), key.to_string());
+        if state.idempotency_keys.get(&id).is_some_and(|(record, _)| record.response.is_none()) {
+            state.idempotency_keys.remove(&id);
+        }
+        Ok(())
+    }
+}
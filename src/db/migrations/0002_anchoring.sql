@@ -0,0 +1,38 @@
+-- 批量锚定与账本检查点
+CREATE TABLE IF NOT EXISTS anchor_batches (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    ledger TEXT NOT NULL DEFAULT 'default',
+    root TEXT NOT NULL,
+    size INTEGER NOT NULL,
+    idempotency_key TEXT NOT NULL UNIQUE,
+    status TEXT NOT NULL DEFAULT 'pending',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at INTEGER NOT NULL,
+    last_error TEXT,
+    tx_hash TEXT,
+    created_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS anchor_proofs (
+    outbox_id INTEGER PRIMARY KEY,
+    batch_id INTEGER NOT NULL,
+    did TEXT NOT NULL,
+    version_id INTEGER NOT NULL,
+    leaf TEXT NOT NULL,
+    leaf_index INTEGER NOT NULL,
+    proof TEXT NOT NULL,
+    created_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_anchor_proofs_did
+    ON anchor_proofs (did, version_id);
+
+CREATE TABLE IF NOT EXISTS ledger_checkpoints (
+    ledger TEXT PRIMARY KEY,
+    last_outbox_id INTEGER,
+    last_batch_id INTEGER,
+    last_tx_hash TEXT,
+    delivered INTEGER NOT NULL DEFAULT 0,
+    updated_at INTEGER NOT NULL
+);
@@ -0,0 +1,34 @@
+-- DID文档历史版本
+CREATE TABLE did_document_history (
+    did TEXT NOT NULL,
+    version_id INTEGER NOT NULL,
+    document TEXT NOT NULL,
+    is_active INTEGER NOT NULL,
+    recorded_at INTEGER NOT NULL,
+    PRIMARY KEY (did, version_id)
+);
+
+-- 现有文档的当前版本作为历史的起点
+INSERT INTO did_document_history (did, version_id, document, is_active, recorded_at)
+    SELECT did, version_id, document, is_active, updated_at FROM did_documents;
+
+-- 一次性随机数
+CREATE TABLE IF NOT EXISTS nonces (
+    nonce TEXT PRIMARY KEY,
+    subject TEXT NOT NULL,
+    expires_at INTEGER NOT NULL,
+    created_at INTEGER NOT NULL
+);
+
+-- 已上链的交易
+CREATE TABLE ledger_transactions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    ledger TEXT NOT NULL,
+    tx_hash TEXT NOT NULL,
+    outbox_id INTEGER,
+    batch_id INTEGER,
+    confirmed_at INTEGER NOT NULL
+);
+
+CREATE INDEX idx_ledger_transactions_hash
+    ON ledger_transactions (ledger, tx_hash);
@@ -0,0 +1,33 @@
+-- DID查询索引：状态和时间范围直接使用did_documents的列，多值属性写入did_attributes
+CREATE INDEX idx_did_documents_active ON did_documents (is_active, did);
+CREATE INDEX idx_did_documents_created ON did_documents (created_at);
+CREATE INDEX idx_did_documents_updated ON did_documents (updated_at);
+
+CREATE TABLE did_attributes (
+    did TEXT NOT NULL,
+    name TEXT NOT NULL,
+    value TEXT NOT NULL,
+    PRIMARY KEY (did, name, value)
+);
+
+CREATE INDEX idx_did_attributes_value ON did_attributes (name, value, did);
+
+-- 为现有文档补充索引属性
+INSERT OR IGNORE INTO did_attributes (did, name, value)
+    SELECT did, 'method', substr(did, 5, instr(substr(did, 5), ':') - 1)
+    FROM did_documents WHERE did LIKE 'did:%:%';
+
+INSERT OR IGNORE INTO did_attributes (did, name, value)
+    SELECT d.did, 'controller', json_extract(k.value, '$.controller')
+    FROM did_documents d, json_each(d.document, '$.public_keys') k
+    WHERE json_extract(k.value, '$.controller') IS NOT NULL;
+
+INSERT OR IGNORE INTO did_attributes (did, name, value)
+    SELECT d.did, 'verification_method_type', json_extract(k.value, '$.type_')
+    FROM did_documents d, json_each(d.document, '$.public_keys') k
+    WHERE json_extract(k.value, '$.type_') IS NOT NULL;
+
+INSERT OR IGNORE INTO did_attributes (did, name, value)
+    SELECT d.did, 'service_type', json_extract(s.value, '$.type_')
+    FROM did_documents d, json_each(d.document, '$.services') s
+    WHERE json_extract(s.value, '$.type_') IS NOT NULL;
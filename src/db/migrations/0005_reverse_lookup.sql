@@ -0,0 +1,10 @@
+-- 公钥和服务端点的反向查询属性
+INSERT OR IGNORE INTO did_attributes (did, name, value)
+    SELECT d.did, 'public_key', json_extract(k.value, '$.public_key_base58')
+    FROM did_documents d, json_each(d.document, '$.public_keys') k
+    WHERE json_extract(k.value, '$.public_key_base58') IS NOT NULL;
+
+INSERT OR IGNORE INTO did_attributes (did, name, value)
+    SELECT d.did, 'service_endpoint', json_extract(s.value, '$.endpoint')
+    FROM did_documents d, json_each(d.document, '$.services') s
+    WHERE json_extract(s.value, '$.endpoint') IS NOT NULL;
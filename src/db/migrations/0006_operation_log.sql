@@ -0,0 +1,25 @@
+-- 哈希链操作日志，只允许追加
+CREATE TABLE operation_log (
+    seq INTEGER PRIMARY KEY AUTOINCREMENT,
+    did TEXT NOT NULL,
+    version_id INTEGER NOT NULL,
+    operation TEXT NOT NULL,
+    is_active INTEGER NOT NULL,
+    document_hash TEXT NOT NULL,
+    prev_did_hash TEXT,
+    prev_hash TEXT,
+    entry_hash TEXT NOT NULL UNIQUE,
+    recorded_at INTEGER NOT NULL
+);
+
+CREATE INDEX idx_operation_log_did ON operation_log (did, seq);
+
+CREATE TRIGGER operation_log_no_update BEFORE UPDATE ON operation_log
+BEGIN
+    SELECT RAISE(ABORT, 'operation_log is append-only');
+END;
+
+CREATE TRIGGER operation_log_no_delete BEFORE DELETE ON operation_log
+BEGIN
+    SELECT RAISE(ABORT, 'operation_log is append-only');
+END;
@@ -0,0 +1,17 @@
+-- 操作日志记录可重放的操作内容和控制者签名
+ALTER TABLE operation_log ADD COLUMN payload TEXT;
+ALTER TABLE operation_log ADD COLUMN signer TEXT;
+ALTER TABLE operation_log ADD COLUMN signature TEXT;
+
+-- 重放快照，记录DID在某条日志之后的物化状态
+CREATE TABLE did_snapshots (
+    did TEXT PRIMARY KEY,
+    seq INTEGER NOT NULL,
+    entry_hash TEXT NOT NULL,
+    version_id INTEGER NOT NULL,
+    document TEXT NOT NULL,
+    is_active INTEGER NOT NULL,
+    created_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL,
+    taken_at INTEGER NOT NULL
+);
@@ -0,0 +1,12 @@
+-- 静态加密的数据加密密钥（由口令或密钥文件派生的包装密钥加密），最多一行
+CREATE TABLE encryption_key (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    kdf TEXT NOT NULL,
+    salt TEXT,
+    m_cost INTEGER,
+    t_cost INTEGER,
+    p_cost INTEGER,
+    wrapped_key TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    rotated_at INTEGER
+);
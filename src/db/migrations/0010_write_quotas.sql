@@ -0,0 +1,10 @@
+-- 按API密钥设置的每日写操作配额，为空时使用全局配置
+ALTER TABLE api_keys ADD COLUMN daily_write_quota INTEGER;
+
+-- 每个租户每天（UTC）成功的写操作数
+CREATE TABLE write_usage (
+    tenant TEXT NOT NULL,
+    day INTEGER NOT NULL,
+    writes INTEGER NOT NULL,
+    PRIMARY KEY (tenant, day)
+);
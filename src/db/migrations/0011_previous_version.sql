@@ -0,0 +1,2 @@
+-- 签名操作所基于的文档版本，参与签名，重放时用于校验签名
+ALTER TABLE operation_log ADD COLUMN previous_version_id INTEGER;
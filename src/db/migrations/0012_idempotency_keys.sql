@@ -0,0 +1,12 @@
+-- 带幂等键的写请求：请求指纹和保存的响应，响应为空表示请求仍在处理中；响应启用静态加密时加密
+CREATE TABLE idempotency_keys (
+    tenant TEXT NOT NULL,
+    idempotency_key TEXT NOT NULL,
+    fingerprint TEXT NOT NULL,
+    response TEXT,
+    created_at INTEGER NOT NULL,
+    expires_at INTEGER NOT NULL,
+    PRIMARY KEY (tenant, idempotency_key)
+);
+
+CREATE INDEX idx_idempotency_keys_expires_at ON idempotency_keys (expires_at);
@@ -0,0 +1,2 @@
+-- 按DID查找更早的未投递出站记录，保证同一个DID的操作按顺序上链
+CREATE INDEX IF NOT EXISTS idx_ledger_outbox_did ON ledger_outbox (did, id);
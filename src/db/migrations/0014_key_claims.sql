@@ -0,0 +1,13 @@
+-- 活跃DID登记的公钥，主键保证公钥唯一策略下同一公钥只属于一个活跃的DID
+CREATE TABLE IF NOT EXISTS key_claims (
+    public_key TEXT PRIMARY KEY,
+    did TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_key_claims_did ON key_claims (did);
+
+INSERT OR IGNORE INTO key_claims (public_key, did)
+    SELECT a.value, a.did
+    FROM did_attributes a JOIN did_documents d ON d.did = a.did
+    WHERE a.name = 'public_key' AND d.is_active = 1
+    ORDER BY d.created_at, a.did;
@@ -0,0 +1,8 @@
+-- 已锚定到账本的操作日志链头，校验时哈希链必须经过最新锚定的链头
+CREATE TABLE IF NOT EXISTS oplog_anchors (
+    seq INTEGER PRIMARY KEY,
+    entry_hash TEXT NOT NULL,
+    ledger TEXT NOT NULL,
+    tx_hash TEXT NOT NULL,
+    anchored_at INTEGER NOT NULL
+);
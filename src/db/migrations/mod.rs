@@ -0,0 +1,251 @@
+//! 数据库迁移模块 - 按版本顺序执行内嵌的SQL迁移
+//!
+//! 已执行的迁移记录在`schema_version`表中，每个迁移在独立的事务中执行。
+
+use rusqlite::{Connection, OptionalExtension, params};
+use crate::types::Error;
+use crate::utils;
+use super::datetime;
+
+/// 内嵌的SQL迁移
+#[derive(Debug, Clone, Copy)]
+pub struct Migration {
+    /// 版本号，从1开始连续递增
+    pub version: u32,
+    /// 迁移名称
+    pub name: &'static str,
+    /// 迁移SQL
+    pub sql: &'static str,
+}
+
+/// 已执行的迁移
+#[derive(Debug, Clone)]
+pub struct AppliedMigration {
+    pub version: u32,
+    pub name: String,
+    pub applied_at: u64,
+}
+
+/// 全部迁移，按版本号排序
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial",
+        sql: include_str!("0001_initial.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "anchoring",
+        sql: include_str!("0002_anchoring.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "history_nonces_transactions",
+        sql: include_str!("0003_history_nonces_transactions.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "did_index",
+        sql: include_str!("0004_did_index.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "reverse_lookup",
+        sql: include_str!("0005_reverse_lookup.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "operation_log",
+        sql: include_str!("0006_operation_log.sql"),
+    },
+    Migration {
+        version: 7,
+        name: "replayable_operations",
+        sql: include_str!("0007_replayable_operations.sql"),
+    },
+    Migration {
+        version: 8,
+        name: "encryption_key",
+        sql: include_str!("0008_encryption_key.sql"),
+    },
+    Migration {
+        version: 9,
+        name: "api_keys",
+        sql: include_str!("0009_api_keys.sql"),
+    },
+    Migration {
+        version: 10,
+        name: "write_quotas",
+        sql: include_str!("0010_write_quotas.sql"),
+    },
+    Migration {
+        version: 11,
+        name: "previous_version",
+        sql: include_str!("0011_previous_version.sql"),
+    },
+    Migration {
+        version: 12,
+        name: "idempotency_keys",
+        sql: include_str!("0012_idempotency_keys.sql"),
+    },
+    Migration {
+        version: 13,
+        name: "outbox_did_order",
+        sql: include_str!("0013_outbox_did_order.sql"),
+    },
+    Migration {
+        version: 14,
+        name: "key_claims",
+        sql: include_str!("0014_key_claims.sql"),
+    },
+    Migration {
+        version: 15,
+        name: "oplog_anchors",
+        sql: include_str!("0015_oplog_anchors.sql"),
+    },
+];
+
+/// 引入迁移之前的版本通过`ALTER TABLE`补充的列
+const LEGACY_COLUMNS: &[(&str, &str, &str)] = &[
+    ("did_documents", "version_id", "INTEGER NOT NULL DEFAULT 1"),
+    ("ledger_outbox", "version_id", "INTEGER"),
+    ("ledger_outbox", "batch_id", "INTEGER"),
+    ("ledger_outbox", "ledger", "TEXT NOT NULL DEFAULT 'default'"),
+    ("anchor_batches", "ledger", "TEXT NOT NULL DEFAULT 'default'"),
+];
+
+/// 本程序支持的最新架构版本
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map(|migration| migration.version).unwrap_or(0)
+}
+
+/// 执行所有未执行的迁移，返回本次执行的迁移
+///
+/// 数据库架构版本高于本程序支持的版本时拒绝启动。
+pub fn run(conn: &mut Connection) -> Result<Vec<AppliedMigration>, Error> {
+    let legacy = !table_exists(conn, "schema_version")? && table_exists(conn, "did_documents")?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS schema_version (
+            version INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            applied_at INTEGER NOT NULL
+        )",
+        [],
+    ).map_err(|e| Error::DatabaseError(format!("Failed to create schema_version table: {}", e)))?;
+
+    let current = current_version(conn)?;
+    if current > latest_version() {
+        return Err(Error::DatabaseError(format!(
+            "Database schema version {} is newer than supported version {}",
+            current,
+            latest_version()
+        )));
+    }
+
+    if legacy {
+        log::info!("检测到未记录架构版本的旧数据库，补充缺失的列后执行迁移");
+        adopt_legacy_schema(conn)?;
+    }
+
+    let mut applied = Vec::new();
+    for migration in MIGRATIONS.iter().filter(|migration| migration.version > current) {
+        applied.push(apply(conn, migration)?);
+    }
+
+    Ok(applied)
+}
+
+/// 在事务中执行单个迁移并记录版本
+fn apply(conn: &mut Connection, migration: &Migration) -> Result<AppliedMigration, Error> {
+    let tx = conn.transaction()
+        .map_err(|e| Error::DatabaseError(format!("Failed to begin transaction: {}", e)))?;
+    let applied_at = utils::current_timestamp();
+
+    tx.execute_batch(migration.sql)
+        .map_err(|e| Error::DatabaseError(format!(
+            "Failed to apply migration {:04}_{}: {}",
+            migration.version, migration.name, e
+        )))?;
+
+    tx.execute(
+        "INSERT INTO schema_version (version, name, applied_at) VALUES (?, ?, ?)",
+        params![migration.version, migration.name, applied_at],
+    ).map_err(|e| Error::DatabaseError(format!("Failed to record schema version: {}", e)))?;
+
+    tx.commit()
+        .map_err(|e| Error::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
+    log::info!(
+        "已执行数据库迁移 {:04}_{}（{}）",
+        migration.version,
+        migration.name,
+        datetime::to_rfc3339(applied_at)
+    );
+
+    Ok(AppliedMigration {
+        version: migration.version,
+        name: migration.name.to_string(),
+        applied_at,
+    })
+}
+
+/// 获取数据库当前的架构版本，未执行过迁移时为0
+pub fn current_version(conn: &Connection) -> Result<u32, Error> {
+    conn.query_row("SELECT MAX(version) FROM schema_version", [], |row| row.get::<_, Option<u32>>(0))
+        .map(|version| version.unwrap_or(0))
+        .map_err(|e| Error::DatabaseError(format!("Failed to read schema version: {}", e)))
+}
+
+/// 列出已执行的迁移
+pub fn applied_migrations(conn: &Connection) -> Result<Vec<AppliedMigration>, Error> {
+    let mut stmt = conn.prepare("SELECT version, name, applied_at FROM schema_version ORDER BY version")
+        .map_err(|e| Error::DatabaseError(format!("Failed to prepare statement: {}", e)))?;
+
+    let rows = stmt.query_map([], |row| {
+        Ok(AppliedMigration {
+            version: row.get(0)?,
+            name: row.get(1)?,
+            applied_at: row.get(2)?,
+        })
+    }).map_err(|e| Error::DatabaseError(format!("Failed to execute query: {}", e)))?;
+
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| Error::DatabaseError(format!("Failed to fetch row: {}", e)))
+}
+
+/// 为引入迁移之前创建的数据库补充缺失的列，使后续迁移可以在其上执行
+fn adopt_legacy_schema(conn: &Connection) -> Result<(), Error> {
+    for (table, column, definition) in LEGACY_COLUMNS {
+        if table_exists(conn, table)? && !column_exists(conn, table, column)? {
+            conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition), [])
+                .map_err(|e| Error::DatabaseError(format!("Failed to add column {}.{}: {}", table, column, e)))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// 检查表是否存在
+fn table_exists(conn: &Connection, table: &str) -> Result<bool, Error> {
+    conn.query_row(
+        "SELECT name FROM sqlite_master WHERE type = 'table' AND name = ?",
+        params![table],
+        |row| row.get::<_, String>(0),
+    ).optional()
+        .map(|name| name.is_some())
+        .map_err(|e| Error::DatabaseError(format!("Failed to read schema: {}", e)))
+}
+
+/// 检查表中是否存在指定列
+fn column_exists(conn: &Connection, table: &str, column: &str) -> Result<bool, Error> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))
+        .map_err(|e| Error::DatabaseError(format!("Failed to prepare statement: {}", e)))?;
+
+    let columns = stmt.query_map([], |row| row.get::<_, String>(1))
+        .map_err(|e| Error::DatabaseError(format!("Failed to read table info: {}", e)))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| Error::DatabaseError(format!("Failed to read table info: {}", e)))?;
+
+    Ok(columns.iter().any(|name| name == column))
+}
@@ -1,110 +1,389 @@
 //! 数据库模块 - 实现本地数据存储
+//!
+//! 所有存储操作都通过`DidStore`接口完成，提供基于连接池的SQLite后端和内存后端。
 
-use rusqlite::{Connection, params};
+pub mod conversions;
+pub mod datetime;
+pub mod encryption;
+pub mod memory;
+pub mod migrations;
+pub mod sqlite;
+
+use std::sync::Arc;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use crate::anchoring::{AnchorBatch, AnchorLeaf, AnchorProof, merkle::ProofStep};
+use crate::auth::ApiKey;
+use crate::backup::{ConflictPolicy, ImportReport, Registry};
+use crate::blockchain::LedgerCheckpoint;
 use crate::did::DIDDocument;
+use crate::idempotency::{IdempotencyRecord, StoredResponse};
+use crate::oplog::{HeadAnchor, OperationLogEntry, SignedOperation, replay::DidSnapshot};
+use crate::outbox::{OutboxEntry, OutboxStatus, PendingOperation};
 use crate::types::Error;
 
-/// 初始化数据库
-pub fn init_database() -> Result<(), Error> {
-    let conn = Connection::open("did.db")
-        .map_err(|e| Error::DatabaseError(format!("Failed to open database: {}", e)))?;
-
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS did_documents (
-            did TEXT PRIMARY KEY,
-            document TEXT NOT NULL,
-            is_active INTEGER NOT NULL DEFAULT 1,
-            created_at INTEGER NOT NULL,
-            updated_at INTEGER NOT NULL
-        )",
-        [],
-    ).map_err(|e| Error::DatabaseError(format!("Failed to create table: {}", e)))?;
-
-    Ok(())
+pub use self::encryption::KeySource;
+pub use self::memory::MemoryStore;
+pub use self::sqlite::SqliteStore;
+
+/// 使用内存后端时的数据库地址
+pub const MEMORY_DATABASE: &str = ":memory:";
+
+/// 共享的存储实例
+pub type SharedStore = Arc<dyn DidStore>;
+
+/// 本地DID记录（包含已停用的DID）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DidRecord {
+    pub did: String,
+    pub document: DIDDocument,
+    pub is_active: bool,
+    pub created_at: u64,
+    pub updated_at: u64,
+    pub version_id: u64,
+}
+
+/// DID状态筛选条件
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DidStatus {
+    Active,
+    Deactivated,
+}
+
+impl std::str::FromStr for DidStatus {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "active" => Ok(Self::Active),
+            "deactivated" => Ok(Self::Deactivated),
+            other => Err(Error::InvalidInput(format!("Unknown DID status: {}", other))),
+        }
+    }
 }
 
-/// 存储DID文档
-pub fn store_did_document(did: &str, document: &DIDDocument, is_update: bool) -> Result<(), Error> {
-    let conn = Connection::open("did.db")
-        .map_err(|e| Error::DatabaseError(format!("Failed to open database: {}", e)))?;
+/// 可用于查询的DID文档属性名
+pub const ATTRIBUTE_METHOD: &str = "method";
+pub const ATTRIBUTE_CONTROLLER: &str = "controller";
+pub const ATTRIBUTE_VERIFICATION_METHOD_TYPE: &str = "verification_method_type";
+pub const ATTRIBUTE_SERVICE_TYPE: &str = "service_type";
+pub const ATTRIBUTE_PUBLIC_KEY: &str = "public_key";
+pub const ATTRIBUTE_SERVICE_ENDPOINT: &str = "service_endpoint";
+
+/// DID查询条件
+///
+/// 时间范围为左闭右开区间；结果按DID排序，`after`为上一页最后一个DID。
+#[derive(Debug, Clone, Default)]
+pub struct DidQuery {
+    pub status: Option<DidStatus>,
+    pub method: Option<String>,
+    pub controller: Option<String>,
+    pub verification_method_type: Option<String>,
+    pub service_type: Option<String>,
+    /// Base58编码的公钥
+    pub public_key: Option<String>,
+    pub service_endpoint: Option<String>,
+    pub created_after: Option<u64>,
+    pub created_before: Option<u64>,
+    pub updated_after: Option<u64>,
+    pub updated_before: Option<u64>,
+    pub after: Option<String>,
+    pub limit: usize,
+}
 
-    // 检查DID是否存在
-    let mut stmt = conn.prepare(
-        "SELECT COUNT(*) FROM did_documents WHERE did = ?"
-    ).map_err(|e| Error::DatabaseError(format!("Failed to prepare statement: {}", e)))?;
+impl DidQuery {
+    /// 需要匹配的文档属性
+    pub fn attribute_filters(&self) -> Vec<(&'static str, &str)> {
+        [
+            (ATTRIBUTE_METHOD, &self.method),
+            (ATTRIBUTE_CONTROLLER, &self.controller),
+            (ATTRIBUTE_VERIFICATION_METHOD_TYPE, &self.verification_method_type),
+            (ATTRIBUTE_SERVICE_TYPE, &self.service_type),
+            (ATTRIBUTE_PUBLIC_KEY, &self.public_key),
+            (ATTRIBUTE_SERVICE_ENDPOINT, &self.service_endpoint),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| value.as_deref().map(|value| (name, value)))
+        .collect()
+    }
+}
 
-    let count: i64 = stmt.query_row(params![did], |row| row.get(0))
-        .map_err(|e| Error::DatabaseError(format!("Failed to check DID existence: {}", e)))?;
+/// 提取DID文档中用于查询的属性（方法、控制者、验证方法类型、服务类型、公钥和服务端点）
+pub fn did_attributes(did: &str, document: &DIDDocument) -> Vec<(&'static str, String)> {
+    let mut attributes = Vec::new();
 
-    // 如果是创建操作且DID已存在，返回错误
-    if !is_update && count > 0 {
-        return Err(Error::InvalidInput(format!("DID already exists: {}", did)));
+    if let Some(method) = did.strip_prefix("did:").and_then(|rest| rest.split(':').next()) {
+        attributes.push((ATTRIBUTE_METHOD, method.to_string()));
     }
-    // 如果是更新操作且DID不存在，返回错误
-    if is_update && count == 0 {
-        return Err(Error::NotFound(format!("DID not found: {}", did)));
+    for key in &document.public_keys {
+        attributes.push((ATTRIBUTE_CONTROLLER, key.controller.clone()));
+        attributes.push((ATTRIBUTE_VERIFICATION_METHOD_TYPE, key.type_.clone()));
+        attributes.push((ATTRIBUTE_PUBLIC_KEY, key.public_key_base58.clone()));
+    }
+    for service in &document.services {
+        attributes.push((ATTRIBUTE_SERVICE_TYPE, service.type_.clone()));
+        attributes.push((ATTRIBUTE_SERVICE_ENDPOINT, service.endpoint.clone()));
     }
 
-    let document_json = serde_json::to_string(document)
-        .map_err(|e| Error::SerializationError(e.to_string()))?;
-
-    // 根据操作类型选择SQL语句
-    let sql = if is_update {
-        "UPDATE did_documents SET document = ?, updated_at = ? WHERE did = ?"
-    } else {
-        "INSERT INTO did_documents (did, document, created_at, updated_at) VALUES (?, ?, ?, ?)"
-    };
-
-    if is_update {
-        conn.execute(
-            sql,
-            params![document_json, document.updated, did],
-        )
-    } else {
-        conn.execute(
-            sql,
-            params![did, document_json, document.created, document.updated],
-        )
-    }.map_err(|e| Error::DatabaseError(format!("Failed to store document: {}", e)))?;
-
-    Ok(())
+    attributes.sort();
+    attributes.dedup();
+    attributes
 }
 
-/// 获取DID文档
-pub fn get_did_document(did: &str) -> Result<Option<DIDDocument>, Error> {
-    let conn = Connection::open("did.db")
-        .map_err(|e| Error::DatabaseError(format!("Failed to open database: {}", e)))?;
+/// DID文档的历史版本
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DidVersion {
+    pub did: String,
+    pub version_id: u64,
+    pub document: DIDDocument,
+    pub is_active: bool,
+    pub recorded_at: u64,
+}
 
-    let mut stmt = conn.prepare(
-        "SELECT document FROM did_documents WHERE did = ? AND is_active = 1"
-    ).map_err(|e| Error::DatabaseError(format!("Failed to prepare statement: {}", e)))?;
+/// 待保存的包含证明
+#[derive(Debug, Clone)]
+pub struct NewAnchorProof {
+    pub outbox_id: i64,
+    pub leaf: AnchorLeaf,
+    pub leaf_index: u64,
+    pub proof: Vec<ProofStep>,
+}
 
-    let mut rows = stmt.query(params![did])
-        .map_err(|e| Error::DatabaseError(format!("Failed to execute query: {}", e)))?;
+/// 一次DID变更的存储写入：签名操作、变更后的文档和对应的区块链出站操作
+#[derive(Debug, Clone)]
+pub struct DidWrite {
+    pub did: String,
+    /// 变更后的文档；停用时为停用前的文档，不写入
+    pub document: DIDDocument,
+    /// 变更所基于的版本，含义与`DidStore::store_did_document`相同
+    pub base_version: Option<u64>,
+    pub change: SignedOperation,
+    pub operation: PendingOperation,
+}
 
-    if let Some(row) = rows.next()
-        .map_err(|e| Error::DatabaseError(format!("Failed to fetch row: {}", e)))? {
-        let document_json: String = row.get(0)
-            .map_err(|e| Error::DatabaseError(format!("Failed to get document: {}", e)))?;
+/// 已写入的变更
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WrittenChange {
+    /// 新的文档版本号
+    pub version_id: u64,
+    /// 对应的出站记录ID
+    pub outbox_id: i64,
+}
 
-        let document = serde_json::from_str(&document_json)
-            .map_err(|e| Error::SerializationError(e.to_string()))?;
+/// DID存储接口
+///
+/// 文档变更与对应的出站记录、操作日志必须在同一个原子操作中写入。
+#[async_trait]
+pub trait DidStore: Send + Sync {
+    /// 存储DID文档并写入对应的区块链出站记录，返回新的版本号；`change`为产生该文档的签名操作。
+    /// `base_version`为变更所基于的版本，文档已被修改到其他版本时返回`PreconditionFailed`
+    async fn store_did_document(
+        &self,
+        did: &str,
+        document: &DIDDocument,
+        base_version: Option<u64>,
+        change: &SignedOperation,
+        operation: &PendingOperation,
+    ) -> Result<u64, Error>;
 
-        Ok(Some(document))
-    } else {
-        Ok(None)
-    }
-}
+    /// 获取活跃的DID文档
+    async fn get_did_document(&self, did: &str) -> Result<Option<DIDDocument>, Error>;
+
+    /// 停用DID并写入区块链停用记录，`base_version`的含义与`store_did_document`相同
+    async fn deactivate_did(
+        &self,
+        did: &str,
+        base_version: Option<u64>,
+        change: &SignedOperation,
+        operation: &PendingOperation,
+    ) -> Result<(), Error>;
+
+    /// 在一个原子操作中按顺序应用一组变更，任一变更失败时全部回滚；创建、更新和停用由签名操作的类型决定
+    async fn apply_did_writes(&self, writes: &[DidWrite]) -> Result<Vec<WrittenChange>, Error>;
+
+    /// 获取单个DID记录（包含已停用的DID）
+    async fn get_did_record(&self, did: &str) -> Result<Option<DidRecord>, Error>;
+
+    /// 列出所有本地DID记录
+    async fn list_did_records(&self) -> Result<Vec<DidRecord>, Error>;
+
+    /// 按条件查询DID记录，按DID排序并最多返回`query.limit`条
+    async fn query_did_records(&self, query: &DidQuery) -> Result<Vec<DidRecord>, Error>;
+
+    /// 以区块链状态为准覆盖本地DID记录并产生一个新版本，不写入出站队列
+    async fn overwrite_did_document(&self, did: &str, document: &DIDDocument, is_active: bool) -> Result<(), Error>;
+
+    /// 以区块链状态为准设置本地DID的活跃状态并产生一个新版本，不写入出站队列
+    async fn set_did_active(&self, did: &str, is_active: bool) -> Result<(), Error>;
+
+    /// 按版本号顺序列出DID的历史版本
+    async fn list_did_versions(&self, did: &str) -> Result<Vec<DidVersion>, Error>;
+
+    /// 获取指定账本已到期的待投递出站记录；`skip_batched`为真时跳过由批量锚定处理的操作。
+    /// 同一个DID有更早的未投递记录（等待重试或死信）时跳过该DID，保证操作按顺序上链
+    async fn fetch_due_outbox_entries(
+        &self,
+        ledger: &str,
+        now: u64,
+        limit: usize,
+        skip_batched: bool,
+    ) -> Result<Vec<OutboxEntry>, Error>;
+
+    /// 按状态列出出站记录
+    async fn list_outbox_entries(&self, status: OutboxStatus) -> Result<Vec<OutboxEntry>, Error>;
+
+    /// 将出站记录标记为已投递，并推进所属账本的检查点
+    async fn mark_outbox_delivered(&self, id: i64, tx_hash: &str) -> Result<(), Error>;
+
+    /// 记录出站记录投递失败；`next_attempt_at`为空时进入死信状态
+    async fn mark_outbox_failed(
+        &self,
+        id: i64,
+        attempts: u32,
+        error: &str,
+        next_attempt_at: Option<u64>,
+    ) -> Result<(), Error>;
+
+    /// 将死信记录重新放回待投递队列
+    async fn requeue_outbox_entry(&self, id: i64) -> Result<OutboxEntry, Error>;
+
+    /// 列出出站队列中出现过但本地不存在文档的DID
+    async fn list_dids_missing_locally(&self) -> Result<Vec<String>, Error>;
 
-/// 停用DID
-pub fn deactivate_did(did: &str) -> Result<(), Error> {
-    let conn = Connection::open("did.db")
-        .map_err(|e| Error::DatabaseError(format!("Failed to open database: {}", e)))?;
+    /// 统计DID在出站队列中指定状态的记录数
+    async fn count_outbox_entries_for_did(&self, did: &str, status: OutboxStatus) -> Result<u64, Error>;
 
-    conn.execute(
-        "UPDATE did_documents SET is_active = 0 WHERE did = ?",
-        params![did],
-    ).map_err(|e| Error::DatabaseError(format!("Failed to deactivate DID: {}", e)))?;
+    /// 按写入顺序列出DID在出站队列中指定状态的记录
+    async fn list_outbox_entries_for_did(&self, did: &str, status: OutboxStatus) -> Result<Vec<OutboxEntry>, Error>;
 
-    Ok(())
-}
\ No newline at end of file
+    /// 列出所有账本的检查点
+    async fn list_ledger_checkpoints(&self) -> Result<Vec<LedgerCheckpoint>, Error>;
+
+    /// 按序号列出操作日志中`after`之后的条目
+    async fn list_operation_log(&self, after: i64, limit: usize) -> Result<Vec<OperationLogEntry>, Error>;
+
+    /// 获取操作日志的最后一条（链头）
+    async fn last_operation_log_entry(&self) -> Result<Option<OperationLogEntry>, Error>;
+
+    /// 记录已锚定到账本的操作日志链头
+    async fn record_head_anchor(&self, anchor: &HeadAnchor) -> Result<(), Error>;
+
+    /// 获取最新锚定的操作日志链头
+    async fn latest_head_anchor(&self) -> Result<Option<HeadAnchor>, Error>;
+
+    /// 按序号列出DID在`after`之后的全部操作日志
+    async fn list_did_operations(&self, did: &str, after: i64) -> Result<Vec<OperationLogEntry>, Error>;
+
+    /// 列出操作日志中出现过的所有DID，按DID排序
+    async fn list_logged_dids(&self) -> Result<Vec<String>, Error>;
+
+    /// 获取DID的重放快照
+    async fn get_did_snapshot(&self, did: &str) -> Result<Option<DidSnapshot>, Error>;
+
+    /// 保存DID的重放快照，覆盖已有快照
+    async fn save_did_snapshot(&self, snapshot: &DidSnapshot) -> Result<(), Error>;
+
+    /// 以重放结果覆盖本地DID记录，不写入操作日志和出站队列
+    async fn restore_did_record(&self, record: &DidRecord) -> Result<(), Error>;
+
+    /// 在同一读事务中读取全部DID记录、历史版本和操作日志
+    async fn export_registry(&self) -> Result<Registry, Error>;
+
+    /// 在一个原子操作中导入注册表数据，不写入出站队列
+    ///
+    /// 本地没有任何DID和操作日志时原样恢复归档中的操作日志；否则按`backup::plan_merge`
+    /// 追加归档中本地当前状态之后的签名操作。
+    async fn import_registry(&self, registry: &Registry, policy: ConflictPolicy) -> Result<ImportReport, Error>;
+
+    /// 获取指定账本尚未封装进批次的锚定和停用操作
+    async fn fetch_unbatched_anchor_entries(&self, ledger: &str, limit: usize) -> Result<Vec<OutboxEntry>, Error>;
+
+    /// 创建批次、保存包含证明并关联出站记录，返回批次ID
+    async fn create_anchor_batch(
+        &self,
+        ledger: &str,
+        root: &str,
+        idempotency_key: &str,
+        proofs: &[NewAnchorProof],
+    ) -> Result<i64, Error>;
+
+    /// 获取已到期的待上链批次
+    async fn fetch_due_anchor_batches(&self, now: u64, limit: usize) -> Result<Vec<AnchorBatch>, Error>;
+
+    /// 将批次及其包含的出站记录标记为已上链，并推进所属账本的检查点
+    async fn mark_anchor_batch_delivered(&self, batch_id: i64, tx_hash: &str) -> Result<(), Error>;
+
+    /// 记录批次上链失败；`next_attempt_at`为空时批次及其出站记录进入死信状态
+    async fn mark_anchor_batch_failed(
+        &self,
+        batch_id: i64,
+        attempts: u32,
+        error: &str,
+        next_attempt_at: Option<u64>,
+    ) -> Result<(), Error>;
+
+    /// 获取DID指定版本的最新包含证明
+    async fn get_anchor_proof(&self, did: &str, version_id: u64) -> Result<Option<AnchorProof>, Error>;
+
+    /// 保存一次性随机数，`subject`为随机数签发的对象
+    async fn store_nonce(&self, nonce: &str, subject: &str, expires_at: u64) -> Result<(), Error>;
+
+    /// 取出并删除未过期的一次性随机数，返回其签发对象；不存在或已过期时返回None
+    async fn consume_nonce(&self, nonce: &str) -> Result<Option<String>, Error>;
+
+    /// 保存新的API密钥
+    async fn create_api_key(&self, key: &ApiKey) -> Result<(), Error>;
+
+    /// 按ID获取API密钥
+    async fn get_api_key(&self, id: &str) -> Result<Option<ApiKey>, Error>;
+
+    /// 按创建时间列出全部API密钥，包括已撤销和已过期的密钥
+    async fn list_api_keys(&self) -> Result<Vec<ApiKey>, Error>;
+
+    /// 撤销API密钥，密钥不存在或已撤销时返回false
+    async fn revoke_api_key(&self, id: &str, revoked_at: u64) -> Result<bool, Error>;
+
+    /// 租户在指定UTC日（`ratelimit::day_of`）成功的写操作数
+    async fn get_write_usage(&self, tenant: &str, day: u64) -> Result<u64, Error>;
+
+    /// 占用后当天的写操作数不超过`quota`时原子地占用`count`次写操作，返回占用后的写操作数，
+    /// 配额不足时不占用并返回`None`；同时清理超过保留期的用量
+    async fn reserve_write(&self, tenant: &str, day: u64, quota: u64, count: u64) -> Result<Option<u64>, Error>;
+
+    /// 退还`reserve_write`占用的`count`次写操作
+    async fn release_write(&self, tenant: &str, day: u64, count: u64) -> Result<(), Error>;
+
+    /// 为租户占用幂等键，`expires_at`之后记录失效；同时清理已过期的记录
+    ///
+    /// 成功占用时返回`None`。键已被占用时返回已有的记录，由调用方比较指纹；
+    /// 处理中超过`idempotency::LOCK_TIMEOUT_SECS`的记录视为已放弃，重新被占用。
+    async fn claim_idempotency_key(
+        &self,
+        tenant: &str,
+        key: &str,
+        fingerprint: &str,
+        now: u64,
+        expires_at: u64,
+    ) -> Result<Option<IdempotencyRecord>, Error>;
+
+    /// 保存幂等键对应请求的响应
+    async fn complete_idempotency_key(&self, tenant: &str, key: &str, response: &StoredResponse) -> Result<(), Error>;
+
+    /// 释放仍在处理中的幂等键，之后同一个键的请求重新执行
+    async fn release_idempotency_key(&self, tenant: &str, key: &str) -> Result<(), Error>;
+}
+
+/// 按数据库地址打开存储，`:memory:`使用内存后端，其他值作为SQLite数据库文件路径
+///
+/// 指定`encryption`时SQLite后端加密敏感列；内存后端不落盘，忽略该参数。
+pub fn open_store(database: &str, encryption: Option<&KeySource>) -> Result<SharedStore, Error> {
+    if database == MEMORY_DATABASE {
+        log::info!("使用内存存储后端");
+        if encryption.is_some() {
+            log::warn!("内存存储后端不落盘，忽略静态加密设置");
+        }
+        return Ok(Arc::new(MemoryStore::new()));
+    }
+
+    Ok(Arc::new(SqliteStore::open(database, encryption)?))
+}
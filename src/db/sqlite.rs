@@ -0,0 +1,2036 @@
+//! SQLite存储后端 - 通过连接池在阻塞线程上执行数据库操作
+
+use std::collections::HashSet;
+use async_trait::async_trait;
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::{Connection, OptionalExtension, Transaction, params, params_from_iter};
+use rusqlite::types::Value;
+use crate::anchoring::{AnchorBatch, AnchorProof};
+use crate::auth::{self, ApiKey};
+use crate::backup::{self, ConflictPolicy, ImportReport, Registry};
+use crate::blockchain::LedgerCheckpoint;
+use crate::did::{self, DIDDocument, KeyPolicy};
+use crate::idempotency::{self, IdempotencyRecord, StoredResponse};
+use crate::oplog::{DidOperation, HeadAnchor, LoggedOperation, OperationLogEntry, SignedOperation, replay::DidSnapshot};
+use crate::outbox::{self, OutboxEntry, OutboxOperation, OutboxStatus, PendingOperation};
+use crate::ratelimit;
+use crate::types::Error;
+use crate::utils;
+use super::{DidQuery, DidRecord, DidStatus, DidStore, DidVersion, DidWrite, NewAnchorProof, WrittenChange, migrations};
+use super::encryption::{self, ColumnCipher, EncryptedColumn, KeySource};
+
+/// 连接池默认大小
+const DEFAULT_POOL_SIZE: u32 = 8;
+/// 等待数据库锁的超时时间（毫秒）
+const BUSY_TIMEOUT_MS: u32 = 5000;
+
+/// 基于SQLite连接池的存储后端
+#[derive(Clone)]
+pub struct SqliteStore {
+    pool: Pool<SqliteConnectionManager>,
+    /// 敏感列的加解密器
+    cipher: ColumnCipher,
+}
+
+impl SqliteStore {
+    /// 打开指定路径的数据库并执行未完成的迁移
+    ///
+    /// 数据库已加密时必须提供`encryption`；未加密的数据库提供`encryption`时启用加密并加密已有数据。
+    pub fn open(path: &str, encryption: Option<&KeySource>) -> Result<Self, Error> {
+        let manager = SqliteConnectionManager::file(path)
+            .with_init(|conn| conn.execute_batch(&format!("PRAGMA busy_timeout = {};", BUSY_TIMEOUT_MS)));
+        let pool = Pool::builder()
+            .max_size(DEFAULT_POOL_SIZE)
+            .build(manager)
+            .map_err(|e| Error::DatabaseError(format!("Failed to open database {}: {}", path, e)))?;
+
+        let mut conn = pool.get()
+            .map_err(|e| Error::DatabaseError(format!("Failed to get connection: {}", e)))?;
+        let applied = migrations::run(&mut conn)?;
+        let cipher = encryption::open_cipher(&mut conn, encryption)?;
+        drop(conn);
+
+        let store = Self { pool, cipher };
+        let seeded = seed_operation_log(&mut *store.connection()?, &store.cipher)?;
+        if seeded > 0 {
+            log::info!("已为{}个已有DID补充操作日志", seeded);
+        }
+        log::info!(
+            "已打开SQLite数据库: {}（架构版本 {}，本次执行 {} 个迁移，静态加密{}）",
+            path,
+            migrations::latest_version(),
+            applied.len(),
+            if store.cipher.is_enabled() { "已启用" } else { "未启用" }
+        );
+
+        Ok(store)
+    }
+
+    /// 使用新的包装密钥重新包装数据加密密钥
+    pub fn rotate_encryption_key(&self, current: &KeySource, new: &KeySource) -> Result<(), Error> {
+        encryption::rotate_wrapping_key(&*self.connection()?, current, new)
+    }
+
+    /// 从连接池获取连接
+    fn connection(&self) -> Result<r2d2::PooledConnection<SqliteConnectionManager>, Error> {
+        self.pool.get()
+            .map_err(|e| Error::DatabaseError(format!("Failed to get connection: {}", e)))
+    }
+
+    /// 在阻塞线程上使用池中的连接执行数据库操作
+    async fn run<T, F>(&self, f: F) -> Result<T, Error>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> Result<T, Error> + Send + 'static,
+    {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || f(&mut *store.connection()?))
+            .await
+            .map_err(|e| Error::InternalError(format!("Database task failed: {}", e)))?
+    }
+
+    /// 在阻塞线程上执行需要读写敏感列的数据库操作
+    async fn run_with_cipher<T, F>(&self, f: F) -> Result<T, Error>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection, &ColumnCipher) -> Result<T, Error> + Send + 'static,
+    {
+        let cipher = self.cipher.clone();
+        self.run(move |conn| f(conn, &cipher)).await
+    }
+}
+
+/// 序列化DID文档，启用静态加密时按所在的列和行加密
+fn encode_document(
+    cipher: &ColumnCipher,
+    column: &EncryptedColumn,
+    key: &[&str],
+    document: &DIDDocument,
+) -> Result<String, Error> {
+    let document_json = serde_json::to_string(document)
+        .map_err(|e| Error::SerializationError(e.to_string()))?;
+    cipher.encrypt(document_json, column, key)
+}
+
+/// 解密（如已加密）并反序列化DID文档
+fn decode_document(
+    cipher: &ColumnCipher,
+    column: &EncryptedColumn,
+    key: &[&str],
+    stored: String,
+) -> Result<DIDDocument, Error> {
+    serde_json::from_str(&cipher.decrypt(stored, column, key)?)
+        .map_err(|e| Error::SerializationError(e.to_string()))
+}
+
+/// 在事务中写入出站记录，返回记录ID
+fn enqueue_operation(
+    tx: &Transaction,
+    cipher: &ColumnCipher,
+    did: &str,
+    version_id: u64,
+    operation: &PendingOperation,
+) -> Result<i64, Error> {
+    let now = utils::current_timestamp();
+    let idempotency_key = outbox::new_idempotency_key();
+    let payload = cipher.encrypt_bytes(operation.payload.clone(), &encryption::OUTBOX_PAYLOADS, &[&idempotency_key])?;
+
+    tx.execute(
+        "INSERT INTO ledger_outbox
+            (did, ledger, operation, payload, idempotency_key, version_id, status, attempts, next_attempt_at, created_at, updated_at)
+         VALUES (?, ?, ?, ?, ?, ?, ?, 0, ?, ?, ?)",
+        params![
+            did,
+            operation.ledger,
+            operation.operation,
+            payload,
+            idempotency_key,
+            version_id,
+            OutboxStatus::Pending,
+            now,
+            now,
+            now,
+        ],
+    ).map_err(|e| Error::DatabaseError(format!("Failed to enqueue ledger operation: {}", e)))?;
+
+    Ok(tx.last_insert_rowid())
+}
+
+/// 在事务中读取DID文档的当前版本号
+fn current_version(tx: &Transaction, did: &str) -> Result<u64, Error> {
+    tx.query_row(
+        "SELECT version_id FROM did_documents WHERE did = ?",
+        params![did],
+        |row| row.get(0),
+    ).map_err(|e| Error::DatabaseError(format!("Failed to read version: {}", e)))
+}
+
+/// 文档的当前版本与变更所基于的版本不一致时的错误
+fn version_conflict(tx: &Transaction, did: &str, base_version: Option<u64>) -> Result<Error, Error> {
+    Ok(Error::PreconditionFailed(format!(
+        "DID {} is at version {}, not version {}",
+        did,
+        current_version(tx, did)?,
+        base_version.unwrap_or_default()
+    )))
+}
+
+/// 将DID文档的当前状态写入历史版本表
+///
+/// 密文绑定所在的行，文档按历史版本表的行重新加密，不能直接复制。
+fn record_version(conn: &Connection, cipher: &ColumnCipher, did: &str) -> Result<(), Error> {
+    let record = get_did_record(conn, cipher, did)?
+        .ok_or_else(|| Error::NotFound(format!("DID not found: {}", did)))?;
+    let version_id = record.version_id.to_string();
+
+    conn.execute(
+        "INSERT OR REPLACE INTO did_document_history (did, version_id, document, is_active, recorded_at)
+         VALUES (?, ?, ?, ?, ?)",
+        params![
+            did,
+            record.version_id,
+            encode_document(cipher, &encryption::DOCUMENT_HISTORY, &[did, &version_id], &record.document)?,
+            record.is_active,
+            utils::current_timestamp(),
+        ],
+    ).map_err(|e| Error::DatabaseError(format!("Failed to record document history: {}", e)))?;
+
+    Ok(())
+}
+
+/// 重建DID文档的查询属性
+fn index_did_attributes(conn: &Connection, cipher: &ColumnCipher, did: &str, document: &DIDDocument) -> Result<(), Error> {
+    conn.execute("DELETE FROM did_attributes WHERE did = ?", params![did])
+        .map_err(|e| Error::DatabaseError(format!("Failed to clear DID attributes: {}", e)))?;
+
+    for (name, value) in super::did_attributes(did, document) {
+        conn.execute(
+            "INSERT OR IGNORE INTO did_attributes (did, name, value) VALUES (?, ?, ?)",
+            params![did, name, cipher.index(&value)],
+        ).map_err(|e| Error::DatabaseError(format!("Failed to index DID attributes: {}", e)))?;
+    }
+
+    Ok(())
+}
+
+/// 重新登记DID的公钥，停用的DID释放其公钥
+///
+/// 公钥唯一策略下公钥已由其他活跃的DID登记时失败，登记和文档写入在同一事务中完成，
+/// 并发创建使用同一公钥的DID时只有一个能够提交。
+fn claim_public_keys(
+    conn: &Connection,
+    cipher: &ColumnCipher,
+    did: &str,
+    document: &DIDDocument,
+    is_active: bool,
+) -> Result<(), Error> {
+    conn.execute("DELETE FROM key_claims WHERE did = ?", params![did])
+        .map_err(|e| Error::DatabaseError(format!("Failed to release public keys: {}", e)))?;
+    if !is_active {
+        return Ok(());
+    }
+
+    for key in &document.public_keys {
+        let public_key = cipher.index(&key.public_key_base58);
+        let claimed = conn.execute(
+            "INSERT INTO key_claims (public_key, did) VALUES (?, ?) ON CONFLICT(public_key) DO NOTHING",
+            params![public_key, did],
+        ).map_err(|e| Error::DatabaseError(format!("Failed to claim public key: {}", e)))?;
+        if claimed > 0 || did::key_policy() != KeyPolicy::Unique {
+            continue;
+        }
+
+        let owner: String = conn.query_row(
+            "SELECT did FROM key_claims WHERE public_key = ?",
+            params![public_key],
+            |row| row.get(0),
+        ).map_err(|e| Error::DatabaseError(format!("Failed to query public key owner: {}", e)))?;
+        if owner != did {
+            return Err(Error::InvalidState(format!(
+                "Public key {} is already registered to {}",
+                key.public_key_base58, owner
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// 以DID文档的当前状态追加一条操作日志，链接到全局和该DID的上一条日志
+fn append_operation_log(
+    conn: &Connection,
+    cipher: &ColumnCipher,
+    did: &str,
+    operation: &SignedOperation,
+) -> Result<(), Error> {
+    let record = get_did_record(conn, cipher, did)?
+        .ok_or_else(|| Error::NotFound(format!("DID not found: {}", did)))?;
+
+    let prev_hash: Option<String> = conn.query_row(
+        "SELECT entry_hash FROM operation_log ORDER BY seq DESC LIMIT 1",
+        [],
+        |row| row.get(0),
+    ).optional()
+        .map_err(|e| Error::DatabaseError(format!("Failed to query operation log: {}", e)))?;
+    let prev_did_hash: Option<String> = conn.query_row(
+        "SELECT entry_hash FROM operation_log WHERE did = ? ORDER BY seq DESC LIMIT 1",
+        params![did],
+        |row| row.get(0),
+    ).optional()
+        .map_err(|e| Error::DatabaseError(format!("Failed to query operation log: {}", e)))?;
+
+    let entry = OperationLogEntry::new(
+        LoggedOperation::from_record(&record, operation)?,
+        prev_did_hash,
+        prev_hash,
+        utils::current_timestamp(),
+    )?;
+
+    insert_operation_log_entry(conn, cipher, &entry)
+}
+
+/// 写入一条操作日志；`entry.seq`为0时由数据库分配序号
+fn insert_operation_log_entry(conn: &Connection, cipher: &ColumnCipher, entry: &OperationLogEntry) -> Result<(), Error> {
+    let payload = entry.operation.payload.as_ref()
+        .map(serde_json::to_string)
+        .transpose()
+        .map_err(|e| Error::SerializationError(e.to_string()))?
+        .map(|payload| cipher.encrypt(payload, &encryption::OPERATION_PAYLOADS, &[&entry.entry_hash]))
+        .transpose()?;
+
+    conn.execute(
+        "INSERT INTO operation_log
+            (seq, did, version_id, operation, is_active, document_hash, payload, signer, signature,
+             prev_did_hash, prev_hash, entry_hash, recorded_at, previous_version_id)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        params![
+            (entry.seq > 0).then_some(entry.seq),
+            entry.operation.did,
+            entry.operation.version_id,
+            entry.operation.operation,
+            entry.operation.is_active,
+            entry.operation.document_hash,
+            payload,
+            entry.operation.signer,
+            entry.operation.signature,
+            entry.prev_did_hash,
+            entry.prev_hash,
+            entry.entry_hash,
+            entry.recorded_at,
+            entry.operation.previous_version_id,
+        ],
+    ).map_err(|e| Error::DatabaseError(format!("Failed to append operation log: {}", e)))?;
+
+    Ok(())
+}
+
+/// 为引入操作日志之前已存在的DID补充导入日志
+fn seed_operation_log(conn: &mut Connection, cipher: &ColumnCipher) -> Result<usize, Error> {
+    let tx = conn.transaction()
+        .map_err(|e| Error::DatabaseError(format!("Failed to begin transaction: {}", e)))?;
+
+    let dids = {
+        let mut stmt = tx.prepare(
+            "SELECT did FROM did_documents d
+             WHERE NOT EXISTS (SELECT 1 FROM operation_log l WHERE l.did = d.did)
+             ORDER BY did"
+        ).map_err(|e| Error::DatabaseError(format!("Failed to prepare statement: {}", e)))?;
+
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))
+            .map_err(|e| Error::DatabaseError(format!("Failed to execute query: {}", e)))?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| Error::DatabaseError(format!("Failed to fetch row: {}", e)))?
+    };
+
+    for did in &dids {
+        let record = get_did_record(&tx, cipher, did)?
+            .ok_or_else(|| Error::NotFound(format!("DID not found: {}", did)))?;
+        let import = SignedOperation::system(DidOperation::Import {
+            document: record.document,
+            is_active: record.is_active,
+            version_id: record.version_id,
+        });
+        append_operation_log(&tx, cipher, did, &import)?;
+    }
+
+    tx.commit()
+        .map_err(|e| Error::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
+    Ok(dids.len())
+}
+
+/// 执行操作日志查询
+fn query_operation_log(
+    conn: &Connection,
+    cipher: &ColumnCipher,
+    sql: &str,
+    params: impl rusqlite::Params,
+) -> Result<Vec<OperationLogEntry>, Error> {
+    let mut stmt = conn.prepare(sql)
+        .map_err(|e| Error::DatabaseError(format!("Failed to prepare statement: {}", e)))?;
+
+    let rows = stmt.query_map(params, |row| {
+        Ok((
+            OperationLogEntry {
+                seq: row.get(0)?,
+                operation: LoggedOperation {
+                    did: row.get(1)?,
+                    version_id: row.get(2)?,
+                    operation: row.get(3)?,
+                    is_active: row.get(4)?,
+                    document_hash: row.get(5)?,
+                    payload: None,
+                    signer: row.get(7)?,
+                    signature: row.get(8)?,
+                    previous_version_id: row.get(13)?,
+                },
+                prev_did_hash: row.get(9)?,
+                prev_hash: row.get(10)?,
+                entry_hash: row.get(11)?,
+                recorded_at: row.get(12)?,
+            },
+            row.get::<_, Option<String>>(6)?,
+        ))
+    }).map_err(|e| Error::DatabaseError(format!("Failed to execute query: {}", e)))?;
+
+    let mut entries = Vec::new();
+    for row in rows {
+        let (mut entry, payload) = row
+            .map_err(|e| Error::DatabaseError(format!("Failed to fetch row: {}", e)))?;
+        entry.operation.payload = payload
+            .map(|payload| cipher.decrypt(payload, &encryption::OPERATION_PAYLOADS, &[&entry.entry_hash]))
+            .transpose()?
+            .map(|payload| serde_json::from_str(&payload))
+            .transpose()
+            .map_err(|e| Error::SerializationError(e.to_string()))?;
+        entries.push(entry);
+    }
+
+    Ok(entries)
+}
+
+const OPERATION_LOG_COLUMNS: &str = "seq, did, version_id, operation, is_active, document_hash, \
+    payload, signer, signature, prev_did_hash, prev_hash, entry_hash, recorded_at, previous_version_id";
+
+/// 按序号列出操作日志
+fn list_operation_log(conn: &Connection, cipher: &ColumnCipher, after: i64, limit: usize) -> Result<Vec<OperationLogEntry>, Error> {
+    query_operation_log(
+        conn,
+        cipher,
+        &format!("SELECT {} FROM operation_log WHERE seq > ? ORDER BY seq LIMIT ?", OPERATION_LOG_COLUMNS),
+        params![after, limit as i64],
+    )
+}
+
+/// 获取操作日志的最后一条
+fn last_operation_log_entry(conn: &Connection, cipher: &ColumnCipher) -> Result<Option<OperationLogEntry>, Error> {
+    Ok(query_operation_log(
+        conn,
+        cipher,
+        &format!("SELECT {} FROM operation_log ORDER BY seq DESC LIMIT 1", OPERATION_LOG_COLUMNS),
+        [],
+    )?.pop())
+}
+
+/// 记录已锚定的操作日志链头
+fn record_head_anchor(conn: &Connection, anchor: &HeadAnchor) -> Result<(), Error> {
+    conn.execute(
+        "INSERT OR REPLACE INTO oplog_anchors (seq, entry_hash, ledger, tx_hash, anchored_at) VALUES (?, ?, ?, ?, ?)",
+        params![anchor.seq, anchor.entry_hash, anchor.ledger, anchor.tx_hash, utils::current_timestamp()],
+    ).map_err(|e| Error::DatabaseError(format!("Failed to record head anchor: {}", e)))?;
+
+    Ok(())
+}
+
+/// 获取最新锚定的操作日志链头
+fn latest_head_anchor(conn: &Connection) -> Result<Option<HeadAnchor>, Error> {
+    conn.query_row(
+        "SELECT seq, entry_hash, ledger, tx_hash FROM oplog_anchors ORDER BY seq DESC LIMIT 1",
+        [],
+        |row| Ok(HeadAnchor {
+            seq: row.get(0)?,
+            entry_hash: row.get(1)?,
+            ledger: row.get(2)?,
+            tx_hash: row.get(3)?,
+        }),
+    ).optional()
+        .map_err(|e| Error::DatabaseError(format!("Failed to query head anchor: {}", e)))
+}
+
+/// 按序号列出DID的操作日志
+fn list_did_operations(conn: &Connection, cipher: &ColumnCipher, did: &str, after: i64) -> Result<Vec<OperationLogEntry>, Error> {
+    query_operation_log(
+        conn,
+        cipher,
+        &format!("SELECT {} FROM operation_log WHERE did = ? AND seq > ? ORDER BY seq", OPERATION_LOG_COLUMNS),
+        params![did, after],
+    )
+}
+
+/// 列出操作日志中出现过的DID
+fn list_logged_dids(conn: &Connection) -> Result<Vec<String>, Error> {
+    let mut stmt = conn.prepare("SELECT DISTINCT did FROM operation_log ORDER BY did")
+        .map_err(|e| Error::DatabaseError(format!("Failed to prepare statement: {}", e)))?;
+
+    let rows = stmt.query_map([], |row| row.get(0))
+        .map_err(|e| Error::DatabaseError(format!("Failed to execute query: {}", e)))?;
+
+    rows.collect::<Result<Vec<String>, _>>()
+        .map_err(|e| Error::DatabaseError(format!("Failed to fetch row: {}", e)))
+}
+
+/// 获取DID的重放快照
+fn get_did_snapshot(conn: &Connection, cipher: &ColumnCipher, did: &str) -> Result<Option<DidSnapshot>, Error> {
+    let row = conn.query_row(
+        "SELECT seq, entry_hash, version_id, document, is_active, created_at, updated_at, taken_at
+         FROM did_snapshots WHERE did = ?",
+        params![did],
+        |row| Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, u64>(2)?,
+            row.get::<_, String>(3)?,
+            row.get::<_, bool>(4)?,
+            row.get::<_, u64>(5)?,
+            row.get::<_, u64>(6)?,
+            row.get::<_, u64>(7)?,
+        )),
+    ).optional()
+        .map_err(|e| Error::DatabaseError(format!("Failed to query snapshot: {}", e)))?;
+
+    let Some((seq, entry_hash, version_id, document_json, is_active, created_at, updated_at, taken_at)) = row else {
+        return Ok(None);
+    };
+    let document = decode_document(cipher, &encryption::SNAPSHOTS, &[did], document_json)?;
+
+    Ok(Some(DidSnapshot {
+        seq,
+        entry_hash,
+        record: DidRecord { did: did.to_string(), document, is_active, created_at, updated_at, version_id },
+        taken_at,
+    }))
+}
+
+/// 保存DID的重放快照
+fn save_did_snapshot(conn: &Connection, cipher: &ColumnCipher, snapshot: &DidSnapshot) -> Result<(), Error> {
+    let record = &snapshot.record;
+    let document_json = encode_document(cipher, &encryption::SNAPSHOTS, &[&record.did], &record.document)?;
+
+    conn.execute(
+        "INSERT OR REPLACE INTO did_snapshots
+            (did, seq, entry_hash, version_id, document, is_active, created_at, updated_at, taken_at)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        params![
+            record.did,
+            snapshot.seq,
+            snapshot.entry_hash,
+            record.version_id,
+            document_json,
+            record.is_active,
+            record.created_at,
+            record.updated_at,
+            snapshot.taken_at,
+        ],
+    ).map_err(|e| Error::DatabaseError(format!("Failed to save snapshot: {}", e)))?;
+
+    Ok(())
+}
+
+/// 以重放结果覆盖本地DID记录，并重建查询属性和当前版本的历史记录
+fn restore_did_record(conn: &mut Connection, cipher: &ColumnCipher, record: &DidRecord) -> Result<(), Error> {
+    let tx = conn.transaction()
+        .map_err(|e| Error::DatabaseError(format!("Failed to begin transaction: {}", e)))?;
+
+    upsert_did_record(&tx, cipher, record)?;
+    record_version(&tx, cipher, &record.did)?;
+
+    tx.commit()
+        .map_err(|e| Error::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
+    Ok(())
+}
+
+/// 写入或替换DID记录并重建其查询属性
+fn upsert_did_record(conn: &Connection, cipher: &ColumnCipher, record: &DidRecord) -> Result<(), Error> {
+    conn.execute(
+        "INSERT OR REPLACE INTO did_documents (did, document, is_active, created_at, updated_at, version_id)
+         VALUES (?, ?, ?, ?, ?, ?)",
+        params![
+            record.did,
+            encode_document(cipher, &encryption::DOCUMENTS, &[&record.did], &record.document)?,
+            record.is_active,
+            record.created_at,
+            record.updated_at,
+            record.version_id,
+        ],
+    ).map_err(|e| Error::DatabaseError(format!("Failed to store document: {}", e)))?;
+
+    index_did_attributes(conn, cipher, &record.did, &record.document)?;
+    claim_public_keys(conn, cipher, &record.did, &record.document, record.is_active)
+}
+
+/// 在同一读事务中读取全部DID记录、历史版本和操作日志
+fn export_registry(conn: &mut Connection, cipher: &ColumnCipher) -> Result<Registry, Error> {
+    let tx = conn.transaction()
+        .map_err(|e| Error::DatabaseError(format!("Failed to begin transaction: {}", e)))?;
+
+    let registry = Registry {
+        dids: list_did_records(&tx, cipher)?,
+        versions: query_did_versions(
+            &tx,
+            cipher,
+            "SELECT did, version_id, document, is_active, recorded_at FROM did_document_history ORDER BY did, version_id",
+            [],
+        )?,
+        operations: query_operation_log(
+            &tx,
+            cipher,
+            &format!("SELECT {} FROM operation_log ORDER BY seq", OPERATION_LOG_COLUMNS),
+            [],
+        )?,
+    };
+
+    tx.commit()
+        .map_err(|e| Error::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
+    Ok(registry)
+}
+
+/// 在一个事务中导入注册表数据
+fn import_registry(
+    conn: &mut Connection,
+    cipher: &ColumnCipher,
+    registry: &Registry,
+    policy: ConflictPolicy,
+) -> Result<ImportReport, Error> {
+    let tx = conn.transaction()
+        .map_err(|e| Error::DatabaseError(format!("Failed to begin transaction: {}", e)))?;
+
+    let count = |table: &str| -> Result<i64, Error> {
+        tx.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| row.get(0))
+            .map_err(|e| Error::DatabaseError(format!("Failed to count {}: {}", table, e)))
+    };
+    let restore_log = count("did_documents")? == 0 && count("operation_log")? == 0;
+
+    let mut existing = HashSet::new();
+    for record in &registry.dids {
+        if get_did_record(&tx, cipher, &record.did)?.is_some() {
+            existing.insert(record.did.as_str());
+        }
+    }
+    if policy == ConflictPolicy::Fail && !existing.is_empty() {
+        let mut conflicts: Vec<_> = existing.into_iter().collect();
+        conflicts.sort();
+        return Err(Error::InvalidState(format!("DIDs already exist: {}", conflicts.join(", "))));
+    }
+
+    let mut report = ImportReport::default();
+    if restore_log {
+        for entry in &registry.operations {
+            insert_operation_log_entry(&tx, cipher, entry)?;
+        }
+        report.operations_restored = registry.operations.len() as u64;
+    }
+
+    for record in &registry.dids {
+        let exists = existing.contains(record.did.as_str());
+        if exists && policy == ConflictPolicy::Skip {
+            report.skipped.push(record.did.clone());
+            continue;
+        }
+        if exists {
+            report.overwritten += 1;
+        } else {
+            report.imported += 1;
+        }
+
+        // 本地已有操作日志时不能插入另一条哈希链中的日志，改为依次追加归档中本地之后的签名操作
+        if !restore_log {
+            let local = get_did_record(&tx, cipher, &record.did)?;
+            for (state, change) in backup::plan_merge(registry, record, local.as_ref())? {
+                upsert_did_record(&tx, cipher, &state)?;
+                record_version(&tx, cipher, &record.did)?;
+                append_operation_log(&tx, cipher, &record.did, &change)?;
+                report.versions += 1;
+            }
+            continue;
+        }
+
+        upsert_did_record(&tx, cipher, record)?;
+        for version in registry.versions.iter().filter(|version| version.did == record.did) {
+            tx.execute(
+                "INSERT OR REPLACE INTO did_document_history (did, version_id, document, is_active, recorded_at)
+                 VALUES (?, ?, ?, ?, ?)",
+                params![
+                    version.did,
+                    version.version_id,
+                    encode_document(
+                        cipher,
+                        &encryption::DOCUMENT_HISTORY,
+                        &[&version.did, &version.version_id.to_string()],
+                        &version.document,
+                    )?,
+                    version.is_active,
+                    version.recorded_at,
+                ],
+            ).map_err(|e| Error::DatabaseError(format!("Failed to import document history: {}", e)))?;
+            report.versions += 1;
+        }
+    }
+
+    tx.commit()
+        .map_err(|e| Error::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
+    Ok(report)
+}
+
+/// 在事务中记录已上链的交易
+fn record_transaction(
+    tx: &Transaction,
+    ledger: &str,
+    tx_hash: &str,
+    outbox_id: Option<i64>,
+    batch_id: Option<i64>,
+) -> Result<(), Error> {
+    tx.execute(
+        "INSERT INTO ledger_transactions (ledger, tx_hash, outbox_id, batch_id, confirmed_at) VALUES (?, ?, ?, ?, ?)",
+        params![ledger, tx_hash, outbox_id, batch_id, utils::current_timestamp()],
+    ).map_err(|e| Error::DatabaseError(format!("Failed to record ledger transaction: {}", e)))?;
+
+    Ok(())
+}
+
+/// 存储DID文档，并在同一事务中写入对应的区块链出站记录，返回新的版本号
+fn store_did_document(
+    conn: &mut Connection,
+    cipher: &ColumnCipher,
+    did: &str,
+    document: &DIDDocument,
+    base_version: Option<u64>,
+    change: &SignedOperation,
+    operation: &PendingOperation,
+) -> Result<u64, Error> {
+    let tx = conn.transaction()
+        .map_err(|e| Error::DatabaseError(format!("Failed to begin transaction: {}", e)))?;
+
+    let written = write_did_document(&tx, cipher, did, document, base_version, change, operation)?;
+
+    tx.commit()
+        .map_err(|e| Error::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
+    Ok(written.version_id)
+}
+
+/// 在事务中存储DID文档并写入出站记录
+fn write_did_document(
+    tx: &Transaction,
+    cipher: &ColumnCipher,
+    did: &str,
+    document: &DIDDocument,
+    base_version: Option<u64>,
+    change: &SignedOperation,
+    operation: &PendingOperation,
+) -> Result<WrittenChange, Error> {
+    let is_update = !matches!(change.operation, DidOperation::Create { .. });
+
+    // 检查DID是否存在
+    let count: i64 = tx.query_row(
+        "SELECT COUNT(*) FROM did_documents WHERE did = ?",
+        params![did],
+        |row| row.get(0),
+    ).map_err(|e| Error::DatabaseError(format!("Failed to check DID existence: {}", e)))?;
+
+    // 如果是创建操作且DID已存在，返回错误
+    if !is_update && count > 0 {
+        return Err(Error::InvalidInput(format!("DID already exists: {}", did)));
+    }
+    // 如果是更新操作且DID不存在，返回错误
+    if is_update && count == 0 {
+        return Err(Error::NotFound(format!("DID not found: {}", did)));
+    }
+
+    let document_json = encode_document(cipher, &encryption::DOCUMENTS, &[did], document)?;
+
+    let stored = if is_update {
+        tx.execute(
+            "UPDATE did_documents SET document = ?1, updated_at = ?2, version_id = version_id + 1
+             WHERE did = ?3 AND (?4 IS NULL OR version_id = ?4)",
+            params![document_json, document.updated, did, base_version],
+        )
+    } else {
+        tx.execute(
+            "INSERT INTO did_documents (did, document, created_at, updated_at, version_id) VALUES (?, ?, ?, ?, 1)",
+            params![did, document_json, document.created, document.updated],
+        )
+    }.map_err(|e| Error::DatabaseError(format!("Failed to store document: {}", e)))?;
+    // 变更计算之后文档已被其他请求修改
+    if stored == 0 {
+        return Err(version_conflict(tx, did, base_version)?);
+    }
+    index_did_attributes(tx, cipher, did, document)?;
+    claim_public_keys(tx, cipher, did, document, true)?;
+
+    let version_id = current_version(tx, did)?;
+    record_version(tx, cipher, did)?;
+    append_operation_log(tx, cipher, did, change)?;
+    let outbox_id = enqueue_operation(tx, cipher, did, version_id, operation)?;
+
+    Ok(WrittenChange { version_id, outbox_id })
+}
+
+/// 获取DID文档
+fn get_did_document(conn: &Connection, cipher: &ColumnCipher, did: &str) -> Result<Option<DIDDocument>, Error> {
+    let mut stmt = conn.prepare(
+        "SELECT document FROM did_documents WHERE did = ? AND is_active = 1"
+    ).map_err(|e| Error::DatabaseError(format!("Failed to prepare statement: {}", e)))?;
+
+    let mut rows = stmt.query(params![did])
+        .map_err(|e| Error::DatabaseError(format!("Failed to execute query: {}", e)))?;
+
+    if let Some(row) = rows.next()
+        .map_err(|e| Error::DatabaseError(format!("Failed to fetch row: {}", e)))? {
+        let document_json: String = row.get(0)
+            .map_err(|e| Error::DatabaseError(format!("Failed to get document: {}", e)))?;
+
+        Ok(Some(decode_document(cipher, &encryption::DOCUMENTS, &[did], document_json)?))
+    } else {
+        Ok(None)
+    }
+}
+
+/// 停用DID，并在同一事务中写入区块链停用记录
+fn deactivate_did(
+    conn: &mut Connection,
+    cipher: &ColumnCipher,
+    did: &str,
+    base_version: Option<u64>,
+    change: &SignedOperation,
+    operation: &PendingOperation,
+) -> Result<(), Error> {
+    let tx = conn.transaction()
+        .map_err(|e| Error::DatabaseError(format!("Failed to begin transaction: {}", e)))?;
+
+    write_deactivation(&tx, cipher, did, base_version, change, operation)?;
+
+    tx.commit()
+        .map_err(|e| Error::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
+    Ok(())
+}
+
+/// 在事务中停用DID并写入出站记录
+fn write_deactivation(
+    tx: &Transaction,
+    cipher: &ColumnCipher,
+    did: &str,
+    base_version: Option<u64>,
+    change: &SignedOperation,
+    operation: &PendingOperation,
+) -> Result<WrittenChange, Error> {
+    let updated = tx.execute(
+        "UPDATE did_documents SET is_active = 0, updated_at = ?1, version_id = version_id + 1
+         WHERE did = ?2 AND is_active = 1 AND (?3 IS NULL OR version_id = ?3)",
+        params![utils::current_timestamp(), did, base_version],
+    ).map_err(|e| Error::DatabaseError(format!("Failed to deactivate DID: {}", e)))?;
+
+    if updated == 0 {
+        let active: Option<bool> = tx.query_row(
+            "SELECT is_active FROM did_documents WHERE did = ?",
+            params![did],
+            |row| row.get(0),
+        ).optional()
+            .map_err(|e| Error::DatabaseError(format!("Failed to check DID existence: {}", e)))?;
+        return Err(match active {
+            Some(true) => version_conflict(tx, did, base_version)?,
+            _ => Error::NotFound(format!("DID not found: {}", did)),
+        });
+    }
+    tx.execute("DELETE FROM key_claims WHERE did = ?", params![did])
+        .map_err(|e| Error::DatabaseError(format!("Failed to release public keys: {}", e)))?;
+
+    let version_id = current_version(tx, did)?;
+    record_version(tx, cipher, did)?;
+    append_operation_log(tx, cipher, did, change)?;
+    let outbox_id = enqueue_operation(tx, cipher, did, version_id, operation)?;
+
+    Ok(WrittenChange { version_id, outbox_id })
+}
+
+/// 在同一事务中按顺序应用一组变更
+fn apply_did_writes(conn: &mut Connection, cipher: &ColumnCipher, writes: &[DidWrite]) -> Result<Vec<WrittenChange>, Error> {
+    let tx = conn.transaction()
+        .map_err(|e| Error::DatabaseError(format!("Failed to begin transaction: {}", e)))?;
+
+    let written = writes.iter()
+        .map(|write| match write.change.operation {
+            DidOperation::Deactivate => {
+                write_deactivation(&tx, cipher, &write.did, write.base_version, &write.change, &write.operation)
+            }
+            _ => write_did_document(
+                &tx, cipher, &write.did, &write.document, write.base_version, &write.change, &write.operation,
+            ),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    tx.commit()
+        .map_err(|e| Error::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
+    Ok(written)
+}
+
+const OUTBOX_COLUMNS: &str = "id, did, operation, payload, idempotency_key, status, attempts, \
+    next_attempt_at, last_error, tx_hash, created_at, updated_at, version_id, ledger";
+
+/// 将查询结果行转换为出站记录
+fn outbox_entry_from_row(row: &rusqlite::Row) -> rusqlite::Result<OutboxEntry> {
+    Ok(OutboxEntry {
+        id: row.get(0)?,
+        did: row.get(1)?,
+        operation: row.get(2)?,
+        payload: row.get(3)?,
+        idempotency_key: row.get(4)?,
+        status: row.get(5)?,
+        attempts: row.get(6)?,
+        next_attempt_at: row.get(7)?,
+        last_error: row.get(8)?,
+        tx_hash: row.get(9)?,
+        created_at: row.get(10)?,
+        updated_at: row.get(11)?,
+        version_id: row.get(12)?,
+        ledger: row.get(13)?,
+    })
+}
+
+/// 执行出站记录查询，启用静态加密时解密载荷
+fn query_outbox(
+    conn: &Connection,
+    cipher: &ColumnCipher,
+    sql: &str,
+    params: impl rusqlite::Params,
+) -> Result<Vec<OutboxEntry>, Error> {
+    let mut stmt = conn.prepare(sql)
+        .map_err(|e| Error::DatabaseError(format!("Failed to prepare statement: {}", e)))?;
+
+    let rows = stmt.query_map(params, outbox_entry_from_row)
+        .map_err(|e| Error::DatabaseError(format!("Failed to execute query: {}", e)))?;
+
+    let mut entries = Vec::new();
+    for row in rows {
+        let mut entry = row
+            .map_err(|e| Error::DatabaseError(format!("Failed to fetch row: {}", e)))?;
+        let payload = std::mem::take(&mut entry.payload);
+        entry.payload = cipher.decrypt_bytes(payload, &encryption::OUTBOX_PAYLOADS, &[&entry.idempotency_key])?;
+        entries.push(entry);
+    }
+
+    Ok(entries)
+}
+
+/// 获取指定账本已到期的待投递出站记录；`skip_batched`为真时跳过由批量锚定处理的操作
+fn fetch_due_outbox_entries(
+    conn: &Connection,
+    cipher: &ColumnCipher,
+    ledger: &str,
+    now: u64,
+    limit: usize,
+    skip_batched: bool,
+) -> Result<Vec<OutboxEntry>, Error> {
+    let batched_filter = if skip_batched { BATCHED_OPERATIONS_FILTER } else { "" };
+
+    query_outbox(
+        conn,
+        cipher,
+        &format!(
+            "SELECT {} FROM ledger_outbox
+             WHERE ledger = ? AND status = ? AND next_attempt_at <= ? {}
+               AND NOT EXISTS (
+                   SELECT 1 FROM ledger_outbox earlier
+                   WHERE earlier.did = ledger_outbox.did AND earlier.id < ledger_outbox.id AND earlier.status != ?
+               )
+             ORDER BY id LIMIT ?",
+            OUTBOX_COLUMNS, batched_filter
+        ),
+        params![ledger, OutboxStatus::Pending, now, OutboxStatus::Delivered, limit as i64],
+    )
+}
+
+/// 由批量锚定处理的操作类型过滤条件
+const BATCHED_OPERATIONS_FILTER: &str = "AND operation NOT IN ('anchor', 'deactivate')";
+
+/// 按状态列出出站记录
+fn list_outbox_entries(conn: &Connection, cipher: &ColumnCipher, status: OutboxStatus) -> Result<Vec<OutboxEntry>, Error> {
+    query_outbox(
+        conn,
+        cipher,
+        &format!("SELECT {} FROM ledger_outbox WHERE status = ? ORDER BY id", OUTBOX_COLUMNS),
+        params![status],
+    )
+}
+
+/// 将出站记录标记为已投递，并推进所属账本的检查点
+fn mark_outbox_delivered(conn: &mut Connection, id: i64, tx_hash: &str) -> Result<(), Error> {
+    let tx = conn.transaction()
+        .map_err(|e| Error::DatabaseError(format!("Failed to begin transaction: {}", e)))?;
+
+    tx.execute(
+        "UPDATE ledger_outbox SET status = ?, tx_hash = ?, last_error = NULL, updated_at = ? WHERE id = ?",
+        params![OutboxStatus::Delivered, tx_hash, utils::current_timestamp(), id],
+    ).map_err(|e| Error::DatabaseError(format!("Failed to update outbox entry: {}", e)))?;
+
+    let ledger: String = tx.query_row(
+        "SELECT ledger FROM ledger_outbox WHERE id = ?",
+        params![id],
+        |row| row.get(0),
+    ).map_err(|e| Error::DatabaseError(format!("Failed to query outbox entry: {}", e)))?;
+    record_transaction(&tx, &ledger, tx_hash, Some(id), None)?;
+    advance_checkpoint(&tx, &ledger, Some(id), None, tx_hash, 1)?;
+
+    tx.commit()
+        .map_err(|e| Error::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
+    Ok(())
+}
+
+/// 在事务中推进账本检查点
+fn advance_checkpoint(
+    tx: &Transaction,
+    ledger: &str,
+    outbox_id: Option<i64>,
+    batch_id: Option<i64>,
+    tx_hash: &str,
+    delivered: u64,
+) -> Result<(), Error> {
+    tx.execute(
+        "INSERT INTO ledger_checkpoints (ledger, last_outbox_id, last_batch_id, last_tx_hash, delivered, updated_at)
+         VALUES (?, ?, ?, ?, ?, ?)
+         ON CONFLICT(ledger) DO UPDATE SET
+            last_outbox_id = MAX(COALESCE(last_outbox_id, 0), COALESCE(excluded.last_outbox_id, 0)),
+            last_batch_id = MAX(COALESCE(last_batch_id, 0), COALESCE(excluded.last_batch_id, 0)),
+            last_tx_hash = excluded.last_tx_hash,
+            delivered = delivered + excluded.delivered,
+            updated_at = excluded.updated_at",
+        params![ledger, outbox_id, batch_id, tx_hash, delivered, utils::current_timestamp()],
+    ).map_err(|e| Error::DatabaseError(format!("Failed to update ledger checkpoint: {}", e)))?;
+
+    Ok(())
+}
+
+/// 列出所有账本的检查点
+fn list_ledger_checkpoints(conn: &Connection) -> Result<Vec<LedgerCheckpoint>, Error> {
+    let mut stmt = conn.prepare(
+        "SELECT ledger, NULLIF(last_outbox_id, 0), NULLIF(last_batch_id, 0), last_tx_hash, delivered, updated_at
+         FROM ledger_checkpoints ORDER BY ledger"
+    ).map_err(|e| Error::DatabaseError(format!("Failed to prepare statement: {}", e)))?;
+
+    let rows = stmt.query_map([], |row| {
+        Ok(LedgerCheckpoint {
+            ledger: row.get(0)?,
+            last_outbox_id: row.get(1)?,
+            last_batch_id: row.get(2)?,
+            last_tx_hash: row.get(3)?,
+            delivered: row.get(4)?,
+            updated_at: row.get(5)?,
+        })
+    }).map_err(|e| Error::DatabaseError(format!("Failed to execute query: {}", e)))?;
+
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| Error::DatabaseError(format!("Failed to fetch row: {}", e)))
+}
+
+/// 记录出站记录投递失败；`next_attempt_at`为空时进入死信状态
+fn mark_outbox_failed(
+    conn: &Connection,
+    id: i64,
+    attempts: u32,
+    error: &str,
+    next_attempt_at: Option<u64>,
+) -> Result<(), Error> {
+    let now = utils::current_timestamp();
+    let status = if next_attempt_at.is_some() { OutboxStatus::Pending } else { OutboxStatus::Dead };
+
+    conn.execute(
+        "UPDATE ledger_outbox
+         SET status = ?, attempts = ?, last_error = ?, next_attempt_at = ?, updated_at = ?
+         WHERE id = ?",
+        params![status, attempts, error, next_attempt_at.unwrap_or(now), now, id],
+    ).map_err(|e| Error::DatabaseError(format!("Failed to update outbox entry: {}", e)))?;
+
+    Ok(())
+}
+
+/// 将死信记录重新放回待投递队列
+fn requeue_outbox_entry(conn: &Connection, cipher: &ColumnCipher, id: i64) -> Result<OutboxEntry, Error> {
+    let now = utils::current_timestamp();
+
+    let updated = conn.execute(
+        "UPDATE ledger_outbox SET status = ?, attempts = 0, next_attempt_at = ?, updated_at = ?, batch_id = NULL
+         WHERE id = ? AND status = ?",
+        params![OutboxStatus::Pending, now, now, id, OutboxStatus::Dead],
+    ).map_err(|e| Error::DatabaseError(format!("Failed to requeue outbox entry: {}", e)))?;
+
+    if updated == 0 {
+        let exists: Option<i64> = conn.query_row(
+            "SELECT id FROM ledger_outbox WHERE id = ?",
+            params![id],
+            |row| row.get(0),
+        ).optional()
+            .map_err(|e| Error::DatabaseError(format!("Failed to query outbox entry: {}", e)))?;
+
+        return Err(match exists {
+            Some(_) => Error::InvalidState(format!("Outbox entry {} is not dead-lettered", id)),
+            None => Error::NotFound(format!("Outbox entry not found: {}", id)),
+        });
+    }
+
+    query_outbox(
+        conn,
+        cipher,
+        &format!("SELECT {} FROM ledger_outbox WHERE id = ?", OUTBOX_COLUMNS),
+        params![id],
+    )?
+    .pop()
+    .ok_or_else(|| Error::NotFound(format!("Outbox entry not found: {}", id)))
+}
+
+/// 查询DID记录
+fn query_did_records(
+    conn: &Connection,
+    cipher: &ColumnCipher,
+    sql: &str,
+    params: impl rusqlite::Params,
+) -> Result<Vec<DidRecord>, Error> {
+    let mut stmt = conn.prepare(sql)
+        .map_err(|e| Error::DatabaseError(format!("Failed to prepare statement: {}", e)))?;
+
+    let rows = stmt.query_map(params, |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, bool>(2)?,
+            row.get::<_, u64>(3)?,
+            row.get::<_, u64>(4)?,
+            row.get::<_, u64>(5)?,
+        ))
+    }).map_err(|e| Error::DatabaseError(format!("Failed to execute query: {}", e)))?;
+
+    let mut records = Vec::new();
+    for row in rows {
+        let (did, document_json, is_active, created_at, updated_at, version_id) = row
+            .map_err(|e| Error::DatabaseError(format!("Failed to fetch row: {}", e)))?;
+        let document = decode_document(cipher, &encryption::DOCUMENTS, &[&did], document_json)?;
+
+        records.push(DidRecord { did, document, is_active, created_at, updated_at, version_id });
+    }
+
+    Ok(records)
+}
+
+/// 列出所有本地DID记录
+fn list_did_records(conn: &Connection, cipher: &ColumnCipher) -> Result<Vec<DidRecord>, Error> {
+    query_did_records(
+        conn,
+        cipher,
+        "SELECT did, document, is_active, created_at, updated_at, version_id FROM did_documents ORDER BY did",
+        [],
+    )
+}
+
+/// 获取单个DID记录（包含已停用的DID）
+fn get_did_record(conn: &Connection, cipher: &ColumnCipher, did: &str) -> Result<Option<DidRecord>, Error> {
+    Ok(query_did_records(
+        conn,
+        cipher,
+        "SELECT did, document, is_active, created_at, updated_at, version_id FROM did_documents WHERE did = ?",
+        params![did],
+    )?.pop())
+}
+
+/// 按条件查询DID记录
+fn query_dids(conn: &Connection, cipher: &ColumnCipher, query: &DidQuery) -> Result<Vec<DidRecord>, Error> {
+    let mut sql = String::from(
+        "SELECT did, document, is_active, created_at, updated_at, version_id FROM did_documents d WHERE 1 = 1"
+    );
+    let mut values: Vec<Value> = Vec::new();
+
+    if let Some(status) = query.status {
+        sql.push_str(" AND is_active = ?");
+        values.push(Value::Integer((status == DidStatus::Active) as i64));
+    }
+
+    let ranges = [
+        ("created_at >= ?", query.created_after),
+        ("created_at < ?", query.created_before),
+        ("updated_at >= ?", query.updated_after),
+        ("updated_at < ?", query.updated_before),
+    ];
+    for (condition, value) in ranges {
+        if let Some(value) = value {
+            sql.push_str(" AND ");
+            sql.push_str(condition);
+            values.push(Value::Integer(value as i64));
+        }
+    }
+
+    for (name, value) in query.attribute_filters() {
+        sql.push_str(" AND EXISTS (SELECT 1 FROM did_attributes a WHERE a.did = d.did AND a.name = ? AND a.value = ?)");
+        values.push(Value::Text(name.to_string()));
+        values.push(Value::Text(cipher.index(value)));
+    }
+
+    if let Some(after) = &query.after {
+        sql.push_str(" AND did > ?");
+        values.push(Value::Text(after.clone()));
+    }
+
+    sql.push_str(" ORDER BY did LIMIT ?");
+    values.push(Value::Integer(query.limit as i64));
+
+    query_did_records(conn, cipher, &sql, params_from_iter(values))
+}
+
+/// 列出出站队列中出现过但本地不存在文档的DID
+fn list_dids_missing_locally(conn: &Connection) -> Result<Vec<String>, Error> {
+    let mut stmt = conn.prepare(
+        "SELECT DISTINCT o.did FROM ledger_outbox o
+         LEFT JOIN did_documents d ON d.did = o.did
+         WHERE d.did IS NULL
+         ORDER BY o.did"
+    ).map_err(|e| Error::DatabaseError(format!("Failed to prepare statement: {}", e)))?;
+
+    let rows = stmt.query_map([], |row| row.get(0))
+        .map_err(|e| Error::DatabaseError(format!("Failed to execute query: {}", e)))?;
+
+    rows.collect::<Result<Vec<String>, _>>()
+        .map_err(|e| Error::DatabaseError(format!("Failed to fetch row: {}", e)))
+}
+
+/// 统计DID在出站队列中指定状态的记录数
+fn count_outbox_entries_for_did(conn: &Connection, did: &str, status: OutboxStatus) -> Result<u64, Error> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM ledger_outbox WHERE did = ? AND status = ?",
+        params![did, status],
+        |row| row.get(0),
+    ).map_err(|e| Error::DatabaseError(format!("Failed to count outbox entries: {}", e)))
+}
+
+/// 按写入顺序列出DID在出站队列中指定状态的记录
+fn list_outbox_entries_for_did(conn: &Connection, cipher: &ColumnCipher, did: &str, status: OutboxStatus) -> Result<Vec<OutboxEntry>, Error> {
+    query_outbox(
+        conn,
+        cipher,
+        &format!("SELECT {} FROM ledger_outbox WHERE did = ? AND status = ? ORDER BY id", OUTBOX_COLUMNS),
+        params![did, status],
+    )
+}
+
+/// 以区块链状态为准覆盖本地DID记录并产生一个新版本，不写入出站队列
+fn overwrite_did_document(
+    conn: &mut Connection,
+    cipher: &ColumnCipher,
+    did: &str,
+    document: &DIDDocument,
+    is_active: bool,
+) -> Result<(), Error> {
+    let document_json = encode_document(cipher, &encryption::DOCUMENTS, &[did], document)?;
+    let tx = conn.transaction()
+        .map_err(|e| Error::DatabaseError(format!("Failed to begin transaction: {}", e)))?;
+
+    tx.execute(
+        "INSERT INTO did_documents (did, document, is_active, created_at, updated_at)
+         VALUES (?, ?, ?, ?, ?)
+         ON CONFLICT(did) DO UPDATE SET
+            document = excluded.document,
+            is_active = excluded.is_active,
+            updated_at = excluded.updated_at,
+            version_id = version_id + 1",
+        params![did, document_json, is_active, document.created, document.updated],
+    ).map_err(|e| Error::DatabaseError(format!("Failed to overwrite document: {}", e)))?;
+    index_did_attributes(&tx, cipher, did, document)?;
+    claim_public_keys(&tx, cipher, did, document, is_active)?;
+    record_version(&tx, cipher, did)?;
+    let repair = SignedOperation::system(DidOperation::Repair { document: document.clone(), is_active });
+    append_operation_log(&tx, cipher, did, &repair)?;
+
+    tx.commit()
+        .map_err(|e| Error::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
+    Ok(())
+}
+
+/// 以区块链状态为准设置本地DID的活跃状态并产生一个新版本，不写入出站队列
+fn set_did_active(conn: &mut Connection, cipher: &ColumnCipher, did: &str, is_active: bool) -> Result<(), Error> {
+    let tx = conn.transaction()
+        .map_err(|e| Error::DatabaseError(format!("Failed to begin transaction: {}", e)))?;
+
+    let updated = tx.execute(
+        "UPDATE did_documents SET is_active = ?, updated_at = ?, version_id = version_id + 1 WHERE did = ?",
+        params![is_active, utils::current_timestamp(), did],
+    ).map_err(|e| Error::DatabaseError(format!("Failed to update DID status: {}", e)))?;
+
+    if updated == 0 {
+        return Err(Error::NotFound(format!("DID not found: {}", did)));
+    }
+    record_version(&tx, cipher, did)?;
+    let record = get_did_record(&tx, cipher, did)?
+        .ok_or_else(|| Error::NotFound(format!("DID not found: {}", did)))?;
+    claim_public_keys(&tx, cipher, did, &record.document, is_active)?;
+    let repair = SignedOperation::system(DidOperation::Repair { document: record.document, is_active });
+    append_operation_log(&tx, cipher, did, &repair)?;
+
+    tx.commit()
+        .map_err(|e| Error::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
+    Ok(())
+}
+
+/// 列出DID的历史版本
+fn list_did_versions(conn: &Connection, cipher: &ColumnCipher, did: &str) -> Result<Vec<DidVersion>, Error> {
+    query_did_versions(
+        conn,
+        cipher,
+        "SELECT did, version_id, document, is_active, recorded_at FROM did_document_history
+         WHERE did = ? ORDER BY version_id",
+        params![did],
+    )
+}
+
+/// 查询DID历史版本
+fn query_did_versions(
+    conn: &Connection,
+    cipher: &ColumnCipher,
+    sql: &str,
+    params: impl rusqlite::Params,
+) -> Result<Vec<DidVersion>, Error> {
+    let mut stmt = conn.prepare(sql)
+        .map_err(|e| Error::DatabaseError(format!("Failed to prepare statement: {}", e)))?;
+
+    let rows = stmt.query_map(params, |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, u64>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, bool>(3)?,
+            row.get::<_, u64>(4)?,
+        ))
+    }).map_err(|e| Error::DatabaseError(format!("Failed to execute query: {}", e)))?;
+
+    let mut versions = Vec::new();
+    for row in rows {
+        let (did, version_id, document_json, is_active, recorded_at) = row
+            .map_err(|e| Error::DatabaseError(format!("Failed to fetch row: {}", e)))?;
+        let document = decode_document(
+            cipher,
+            &encryption::DOCUMENT_HISTORY,
+            &[&did, &version_id.to_string()],
+            document_json,
+        )?;
+
+        versions.push(DidVersion { did, version_id, document, is_active, recorded_at });
+    }
+
+    Ok(versions)
+}
+
+/// 获取指定账本尚未封装进批次的锚定和停用操作
+fn fetch_unbatched_anchor_entries(conn: &Connection, cipher: &ColumnCipher, ledger: &str, limit: usize) -> Result<Vec<OutboxEntry>, Error> {
+    query_outbox(
+        conn,
+        cipher,
+        &format!(
+            "SELECT {} FROM ledger_outbox
+             WHERE ledger = ? AND status = ? AND batch_id IS NULL AND operation IN (?, ?)
+             ORDER BY id LIMIT ?",
+            OUTBOX_COLUMNS
+        ),
+        params![
+            ledger,
+            OutboxStatus::Pending,
+            OutboxOperation::Anchor,
+            OutboxOperation::Deactivate,
+            limit as i64,
+        ],
+    )
+}
+
+/// 在同一事务中创建批次、保存包含证明并关联出站记录，返回批次ID
+fn create_anchor_batch(
+    conn: &mut Connection,
+    ledger: &str,
+    root: &str,
+    idempotency_key: &str,
+    proofs: &[NewAnchorProof],
+) -> Result<i64, Error> {
+    let tx = conn.transaction()
+        .map_err(|e| Error::DatabaseError(format!("Failed to begin transaction: {}", e)))?;
+    let now = utils::current_timestamp();
+
+    tx.execute(
+        "INSERT INTO anchor_batches (ledger, root, size, idempotency_key, status, next_attempt_at, created_at, updated_at)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        params![ledger, root, proofs.len() as u64, idempotency_key, OutboxStatus::Pending, now, now, now],
+    ).map_err(|e| Error::DatabaseError(format!("Failed to create anchor batch: {}", e)))?;
+    let batch_id = tx.last_insert_rowid();
+
+    for proof in proofs {
+        let leaf_json = serde_json::to_string(&proof.leaf)
+            .map_err(|e| Error::SerializationError(e.to_string()))?;
+        let proof_json = serde_json::to_string(&proof.proof)
+            .map_err(|e| Error::SerializationError(e.to_string()))?;
+
+        tx.execute(
+            "INSERT OR REPLACE INTO anchor_proofs
+                (outbox_id, batch_id, did, version_id, leaf, leaf_index, proof, created_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+            params![
+                proof.outbox_id,
+                batch_id,
+                proof.leaf.did,
+                proof.leaf.version_id,
+                leaf_json,
+                proof.leaf_index,
+                proof_json,
+                now,
+            ],
+        ).map_err(|e| Error::DatabaseError(format!("Failed to store anchor proof: {}", e)))?;
+
+        let updated = tx.execute(
+            "UPDATE ledger_outbox SET batch_id = ?, updated_at = ? WHERE id = ? AND batch_id IS NULL",
+            params![batch_id, now, proof.outbox_id],
+        ).map_err(|e| Error::DatabaseError(format!("Failed to update outbox entry: {}", e)))?;
+        // 同一个操作不能出现在两个批次中
+        if updated == 0 {
+            return Err(Error::InvalidState(format!("Outbox entry {} is missing or already batched", proof.outbox_id)));
+        }
+    }
+
+    tx.commit()
+        .map_err(|e| Error::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
+    Ok(batch_id)
+}
+
+/// 获取已到期的待上链批次
+fn fetch_due_anchor_batches(conn: &Connection, now: u64, limit: usize) -> Result<Vec<AnchorBatch>, Error> {
+    let mut stmt = conn.prepare(
+        "SELECT id, ledger, root, size, idempotency_key, attempts FROM anchor_batches
+         WHERE status = ? AND next_attempt_at <= ?
+         ORDER BY id LIMIT ?"
+    ).map_err(|e| Error::DatabaseError(format!("Failed to prepare statement: {}", e)))?;
+
+    let rows = stmt.query_map(params![OutboxStatus::Pending, now, limit as i64], |row| {
+        Ok(AnchorBatch {
+            id: row.get(0)?,
+            ledger: row.get(1)?,
+            root: row.get(2)?,
+            size: row.get(3)?,
+            idempotency_key: row.get(4)?,
+            attempts: row.get(5)?,
+        })
+    }).map_err(|e| Error::DatabaseError(format!("Failed to execute query: {}", e)))?;
+
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| Error::DatabaseError(format!("Failed to fetch row: {}", e)))
+}
+
+/// 将批次及其包含的出站记录标记为已上链，并推进所属账本的检查点
+fn mark_anchor_batch_delivered(conn: &mut Connection, batch_id: i64, tx_hash: &str) -> Result<(), Error> {
+    let tx = conn.transaction()
+        .map_err(|e| Error::DatabaseError(format!("Failed to begin transaction: {}", e)))?;
+    let now = utils::current_timestamp();
+
+    tx.execute(
+        "UPDATE anchor_batches SET status = ?, tx_hash = ?, last_error = NULL, updated_at = ? WHERE id = ?",
+        params![OutboxStatus::Delivered, tx_hash, now, batch_id],
+    ).map_err(|e| Error::DatabaseError(format!("Failed to update anchor batch: {}", e)))?;
+
+    let delivered = tx.execute(
+        "UPDATE ledger_outbox SET status = ?, tx_hash = ?, last_error = NULL, updated_at = ? WHERE batch_id = ?",
+        params![OutboxStatus::Delivered, tx_hash, now, batch_id],
+    ).map_err(|e| Error::DatabaseError(format!("Failed to update outbox entries: {}", e)))?;
+
+    let ledger: String = tx.query_row(
+        "SELECT ledger FROM anchor_batches WHERE id = ?",
+        params![batch_id],
+        |row| row.get(0),
+    ).map_err(|e| Error::DatabaseError(format!("Failed to query anchor batch: {}", e)))?;
+    record_transaction(&tx, &ledger, tx_hash, None, Some(batch_id))?;
+    advance_checkpoint(&tx, &ledger, None, Some(batch_id), tx_hash, delivered as u64)?;
+
+    tx.commit()
+        .map_err(|e| Error::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
+    Ok(())
+}
+
+/// 记录批次上链失败；`next_attempt_at`为空时批次及其出站记录进入死信状态
+fn mark_anchor_batch_failed(
+    conn: &mut Connection,
+    batch_id: i64,
+    attempts: u32,
+    error: &str,
+    next_attempt_at: Option<u64>,
+) -> Result<(), Error> {
+    let tx = conn.transaction()
+        .map_err(|e| Error::DatabaseError(format!("Failed to begin transaction: {}", e)))?;
+    let now = utils::current_timestamp();
+    let status = if next_attempt_at.is_some() { OutboxStatus::Pending } else { OutboxStatus::Dead };
+
+    tx.execute(
+        "UPDATE anchor_batches
+         SET status = ?, attempts = ?, last_error = ?, next_attempt_at = ?, updated_at = ?
+         WHERE id = ?",
+        params![status, attempts, error, next_attempt_at.unwrap_or(now), now, batch_id],
+    ).map_err(|e| Error::DatabaseError(format!("Failed to update anchor batch: {}", e)))?;
+
+    tx.execute(
+        "UPDATE ledger_outbox SET status = ?, attempts = ?, last_error = ?, updated_at = ? WHERE batch_id = ?",
+        params![status, attempts, error, now, batch_id],
+    ).map_err(|e| Error::DatabaseError(format!("Failed to update outbox entries: {}", e)))?;
+
+    tx.commit()
+        .map_err(|e| Error::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
+    Ok(())
+}
+
+/// 获取DID指定版本的最新包含证明
+fn get_anchor_proof(conn: &Connection, did: &str, version_id: u64) -> Result<Option<AnchorProof>, Error> {
+    let row = conn.query_row(
+        "SELECT p.leaf, p.leaf_index, p.proof, p.batch_id, b.root, b.status, b.tx_hash, b.ledger
+         FROM anchor_proofs p JOIN anchor_batches b ON b.id = p.batch_id
+         WHERE p.did = ? AND p.version_id = ?
+         ORDER BY p.batch_id DESC LIMIT 1",
+        params![did, version_id],
+        |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, u64>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, i64>(3)?,
+                row.get::<_, String>(4)?,
+                row.get::<_, OutboxStatus>(5)?,
+                row.get::<_, Option<String>>(6)?,
+                row.get::<_, String>(7)?,
+            ))
+        },
+    ).optional()
+        .map_err(|e| Error::DatabaseError(format!("Failed to query anchor proof: {}", e)))?;
+
+    let (leaf_json, leaf_index, proof_json, batch_id, root, status, tx_hash, ledger) = match row {
+        Some(row) => row,
+        None => return Ok(None),
+    };
+
+    Ok(Some(AnchorProof {
+        did: did.to_string(),
+        version_id,
+        leaf: serde_json::from_str(&leaf_json)
+            .map_err(|e| Error::SerializationError(e.to_string()))?,
+        leaf_index,
+        proof: serde_json::from_str(&proof_json)
+            .map_err(|e| Error::SerializationError(e.to_string()))?,
+        batch_id,
+        ledger,
+        root,
+        status,
+        tx_hash,
+    }))
+}
+
+/// 保存一次性随机数
+fn store_nonce(conn: &Connection, nonce: &str, subject: &str, expires_at: u64) -> Result<(), Error> {
+    conn.execute(
+        "INSERT INTO nonces (nonce, subject, expires_at, created_at) VALUES (?, ?, ?, ?)",
+        params![nonce, subject, expires_at, utils::current_timestamp()],
+    ).map_err(|e| Error::DatabaseError(format!("Failed to store nonce: {}", e)))?;
+
+    Ok(())
+}
+
+/// 取出并删除未过期的一次性随机数，同时清理已过期的随机数
+fn consume_nonce(conn: &mut Connection, nonce: &str) -> Result<Option<String>, Error> {
+    let tx = conn.transaction()
+        .map_err(|e| Error::DatabaseError(format!("Failed to begin transaction: {}", e)))?;
+    let now = utils::current_timestamp();
+
+    let subject: Option<String> = tx.query_row(
+        "SELECT subject FROM nonces WHERE nonce = ? AND expires_at > ?",
+        params![nonce, now],
+        |row| row.get(0),
+    ).optional()
+        .map_err(|e| Error::DatabaseError(format!("Failed to query nonce: {}", e)))?;
+
+    tx.execute(
+        "DELETE FROM nonces WHERE nonce = ? OR expires_at <= ?",
+        params![nonce, now],
+    ).map_err(|e| Error::DatabaseError(format!("Failed to delete nonce: {}", e)))?;
+
+    tx.commit()
+        .map_err(|e| Error::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
+    Ok(subject)
+}
+
+/// API密钥的查询列
+const API_KEY_COLUMNS: &str = "id, name, role, scopes, secret_hash, created_at, expires_at, revoked_at, daily_write_quota";
+
+/// 将查询结果行转换为API密钥
+fn api_key_from_row(row: &rusqlite::Row) -> rusqlite::Result<ApiKey> {
+    let scopes: String = row.get(3)?;
+
+    Ok(ApiKey {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        role: row.get(2)?,
+        scopes: auth::parse_scopes(&scopes)
+            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(3, rusqlite::types::Type::Text, Box::new(e)))?,
+        secret_hash: row.get(4)?,
+        created_at: row.get(5)?,
+        expires_at: row.get(6)?,
+        revoked_at: row.get(7)?,
+        daily_write_quota: row.get(8)?,
+    })
+}
+
+/// 保存新的API密钥
+fn create_api_key(conn: &Connection, key: &ApiKey) -> Result<(), Error> {
+    conn.execute(
+        &format!("INSERT INTO api_keys ({}) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)", API_KEY_COLUMNS),
+        params![
+            key.id,
+            key.name,
+            key.role,
+            auth::format_scopes(&key.scopes),
+            key.secret_hash,
+            key.created_at,
+            key.expires_at,
+            key.revoked_at,
+            key.daily_write_quota,
+        ],
+    ).map_err(|e| Error::DatabaseError(format!("Failed to store API key: {}", e)))?;
+
+    Ok(())
+}
+
+/// 按ID获取API密钥
+fn get_api_key(conn: &Connection, id: &str) -> Result<Option<ApiKey>, Error> {
+    conn.query_row(
+        &format!("SELECT {} FROM api_keys WHERE id = ?", API_KEY_COLUMNS),
+        params![id],
+        api_key_from_row,
+    ).optional()
+        .map_err(|e| Error::DatabaseError(format!("Failed to query API key: {}", e)))
+}
+
+/// 按创建时间列出全部API密钥
+fn list_api_keys(conn: &Connection) -> Result<Vec<ApiKey>, Error> {
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM api_keys ORDER BY created_at, id", API_KEY_COLUMNS))
+        .map_err(|e| Error::DatabaseError(format!("Failed to prepare statement: {}", e)))?;
+
+    let rows = stmt.query_map([], api_key_from_row)
+        .map_err(|e| Error::DatabaseError(format!("Failed to execute query: {}", e)))?;
+
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| Error::DatabaseError(format!("Failed to fetch row: {}", e)))
+}
+
+/// 撤销未撤销的API密钥
+fn revoke_api_key(conn: &Connection, id: &str, revoked_at: u64) -> Result<bool, Error> {
+    let updated = conn.execute(
+        "UPDATE api_keys SET revoked_at = ? WHERE id = ? AND revoked_at IS NULL",
+        params![revoked_at, id],
+    ).map_err(|e| Error::DatabaseError(format!("Failed to revoke API key: {}", e)))?;
+
+    Ok(updated > 0)
+}
+
+/// 租户当天成功的写操作数
+fn get_write_usage(conn: &Connection, tenant: &str, day: u64) -> Result<u64, Error> {
+    conn.query_row(
+        "SELECT writes FROM write_usage WHERE tenant = ? AND day = ?",
+        params![tenant, day],
+        |row| row.get(0),
+    ).optional()
+        .map(Option::unwrap_or_default)
+        .map_err(|e| Error::DatabaseError(format!("Failed to query write usage: {}", e)))
+}
+
+/// 配额足够时占用`count`次写操作并清理过期的用量，返回占用后的写操作数
+fn reserve_write(conn: &mut Connection, tenant: &str, day: u64, quota: u64, count: u64) -> Result<Option<u64>, Error> {
+    let tx = conn.transaction()
+        .map_err(|e| Error::DatabaseError(format!("Failed to begin transaction: {}", e)))?;
+
+    // 计数和判断在同一条语句中完成，并发请求不会超出配额
+    let reserved = tx.execute(
+        "INSERT INTO write_usage (tenant, day, writes) SELECT ?1, ?2, ?4 WHERE ?4 <= ?3
+         ON CONFLICT (tenant, day) DO UPDATE SET writes = writes + ?4 WHERE writes + ?4 <= ?3",
+        params![tenant, day, quota, count],
+    ).map_err(|e| Error::DatabaseError(format!("Failed to record write usage: {}", e)))? > 0;
+    tx.execute(
+        "DELETE FROM write_usage WHERE day < ?",
+        params![day.saturating_sub(ratelimit::USAGE_RETENTION_DAYS)],
+    ).map_err(|e| Error::DatabaseError(format!("Failed to prune write usage: {}", e)))?;
+    let writes = get_write_usage(&tx, tenant, day)?;
+
+    tx.commit()
+        .map_err(|e| Error::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
+    Ok(reserved.then_some(writes))
+}
+
+/// 退还占用的写操作
+fn release_write(conn: &Connection, tenant: &str, day: u64, count: u64) -> Result<(), Error> {
+    conn.execute(
+        "UPDATE write_usage SET writes = MAX(writes - ?, 0) WHERE tenant = ? AND day = ?",
+        params![count, tenant, day],
+    ).map_err(|e| Error::DatabaseError(format!("Failed to release write usage: {}", e)))?;
+
+    Ok(())
+}
+
+/// 占用幂等键，键已被占用时返回已有的记录
+fn claim_idempotency_key(
+    conn: &mut Connection,
+    cipher: &ColumnCipher,
+    tenant: &str,
+    key: &str,
+    fingerprint: &str,
+    now: u64,
+    expires_at: u64,
+) -> Result<Option<IdempotencyRecord>, Error> {
+    let tx = conn.transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)
+        .map_err(|e| Error::DatabaseError(format!("Failed to begin transaction: {}", e)))?;
+
+    tx.execute("DELETE FROM idempotency_keys WHERE expires_at <= ?", params![now])
+        .map_err(|e| Error::DatabaseError(format!("Failed to prune idempotency keys: {}", e)))?;
+    let existing = tx.query_row(
+        "SELECT fingerprint, created_at, response FROM idempotency_keys WHERE tenant = ? AND idempotency_key = ?",
+        params![tenant, key],
+        |row| Ok((row.get::<_, String>(0)?, row.get::<_, u64>(1)?, row.get::<_, Option<String>>(2)?)),
+    ).optional()
+        .map_err(|e| Error::DatabaseError(format!("Failed to query idempotency key: {}", e)))?;
+
+    if let Some((existing_fingerprint, created_at, response)) = existing {
+        if response.is_some() || created_at + idempotency::LOCK_TIMEOUT_SECS > now {
+            let response = response
+                .map(|response| serde_json::from_str(&cipher.decrypt(response, &encryption::IDEMPOTENT_RESPONSES, &[tenant, key])?)
+                    .map_err(|e| Error::SerializationError(e.to_string())))
+                .transpose()?;
+            return Ok(Some(IdempotencyRecord { fingerprint: existing_fingerprint, created_at, response }));
+        }
+    }
+
+    tx.execute(
+        "INSERT OR REPLACE INTO idempotency_keys (tenant, idempotency_key, fingerprint, response, created_at, expires_at)
+         VALUES (?, ?, ?, NULL, ?, ?)",
+        params![tenant, key, fingerprint, now, expires_at],
+    ).map_err(|e| Error::DatabaseError(format!("Failed to claim idempotency key: {}", e)))?;
+
+    tx.commit()
+        .map_err(|e| Error::DatabaseError(format!("Failed to commit transaction: {}", e)))?;
+
+    Ok(None)
+}
+
+/// 保存幂等键对应请求的响应，启用静态加密时加密
+fn complete_idempotency_key(
+    conn: &Connection,
+    cipher: &ColumnCipher,
+    tenant: &str,
+    key: &str,
+    response: &StoredResponse,
+) -> Result<(), Error> {
+    let response = serde_json::to_string(response)
+        .map_err(|e| Error::SerializationError(e.to_string()))?;
+    conn.execute(
+        "UPDATE idempotency_keys SET response = ? WHERE tenant = ? AND idempotency_key = ?",
+        params![cipher.encrypt(response, &encryption::IDEMPOTENT_RESPONSES, &[tenant, key])?, tenant, key],
+    ).map_err(|e| Error::DatabaseError(format!("Failed to store idempotent response: {}", e)))?;
+    Ok(())
+}
+
+#[async_trait]
+impl DidStore for SqliteStore {
+    async fn store_did_document(
+        &self,
+        did: &str,
+        document: &DIDDocument,
+        base_version: Option<u64>,
+        change: &SignedOperation,
+        operation: &PendingOperation,
+    ) -> Result<u64, Error> {
+        let (did, document, change, operation) = (did.to_string(), document.clone(), change.clone(), operation.clone());
+        self.run_with_cipher(move |conn, cipher| {
+            store_did_document(conn, cipher, &did, &document, base_version, &change, &operation)
+        }).await
+    }
+
+    async fn get_did_document(&self, did: &str) -> Result<Option<DIDDocument>, Error> {
+        let did = did.to_string();
+        self.run_with_cipher(move |conn, cipher| get_did_document(conn, cipher, &did)).await
+    }
+
+    async fn deactivate_did(
+        &self,
+        did: &str,
+        base_version: Option<u64>,
+        change: &SignedOperation,
+        operation: &PendingOperation,
+    ) -> Result<(), Error> {
+        let (did, change, operation) = (did.to_string(), change.clone(), operation.clone());
+        self.run_with_cipher(move |conn, cipher| deactivate_did(conn, cipher, &did, base_version, &change, &operation)).await
+    }
+
+    async fn apply_did_writes(&self, writes: &[DidWrite]) -> Result<Vec<WrittenChange>, Error> {
+        let writes = writes.to_vec();
+        self.run_with_cipher(move |conn, cipher| apply_did_writes(conn, cipher, &writes)).await
+    }
+
+    async fn get_did_record(&self, did: &str) -> Result<Option<DidRecord>, Error> {
+        let did = did.to_string();
+        self.run_with_cipher(move |conn, cipher| get_did_record(conn, cipher, &did)).await
+    }
+
+    async fn list_did_records(&self) -> Result<Vec<DidRecord>, Error> {
+        self.run_with_cipher(|conn, cipher| list_did_records(conn, cipher)).await
+    }
+
+    async fn query_did_records(&self, query: &DidQuery) -> Result<Vec<DidRecord>, Error> {
+        let query = query.clone();
+        self.run_with_cipher(move |conn, cipher| query_dids(conn, cipher, &query)).await
+    }
+
+    async fn overwrite_did_document(&self, did: &str, document: &DIDDocument, is_active: bool) -> Result<(), Error> {
+        let (did, document) = (did.to_string(), document.clone());
+        self.run_with_cipher(move |conn, cipher| overwrite_did_document(conn, cipher, &did, &document, is_active)).await
+    }
+
+    async fn set_did_active(&self, did: &str, is_active: bool) -> Result<(), Error> {
+        let did = did.to_string();
+        self.run_with_cipher(move |conn, cipher| set_did_active(conn, cipher, &did, is_active)).await
+    }
+
+    async fn list_did_versions(&self, did: &str) -> Result<Vec<DidVersion>, Error> {
+        let did = did.to_string();
+        self.run_with_cipher(move |conn, cipher| list_did_versions(conn, cipher, &did)).await
+    }
+
+    async fn fetch_due_outbox_entries(
+        &self,
+        ledger: &str,
+        now: u64,
+        limit: usize,
+        skip_batched: bool,
+    ) -> Result<Vec<OutboxEntry>, Error> {
+        let ledger = ledger.to_string();
+        self.run_with_cipher(move |conn, cipher| fetch_due_outbox_entries(conn, cipher, &ledger, now, limit, skip_batched)).await
+    }
+
+    async fn list_outbox_entries(&self, status: OutboxStatus) -> Result<Vec<OutboxEntry>, Error> {
+        self.run_with_cipher(move |conn, cipher| list_outbox_entries(conn, cipher, status)).await
+    }
+
+    async fn mark_outbox_delivered(&self, id: i64, tx_hash: &str) -> Result<(), Error> {
+        let tx_hash = tx_hash.to_string();
+        self.run(move |conn| mark_outbox_delivered(conn, id, &tx_hash)).await
+    }
+
+    async fn mark_outbox_failed(
+        &self,
+        id: i64,
+        attempts: u32,
+        error: &str,
+        next_attempt_at: Option<u64>,
+    ) -> Result<(), Error> {
+        let error = error.to_string();
+        self.run(move |conn| mark_outbox_failed(conn, id, attempts, &error, next_attempt_at)).await
+    }
+
+    async fn requeue_outbox_entry(&self, id: i64) -> Result<OutboxEntry, Error> {
+        self.run_with_cipher(move |conn, cipher| requeue_outbox_entry(conn, cipher, id)).await
+    }
+
+    async fn list_dids_missing_locally(&self) -> Result<Vec<String>, Error> {
+        self.run(|conn| list_dids_missing_locally(conn)).await
+    }
+
+    async fn count_outbox_entries_for_did(&self, did: &str, status: OutboxStatus) -> Result<u64, Error> {
+        let did = did.to_string();
+        self.run(move |conn| count_outbox_entries_for_did(conn, &did, status)).await
+    }
+
+    async fn list_outbox_entries_for_did(&self, did: &str, status: OutboxStatus) -> Result<Vec<OutboxEntry>, Error> {
+        let did = did.to_string();
+        self.run_with_cipher(move |conn, cipher| list_outbox_entries_for_did(conn, cipher, &did, status)).await
+    }
+
+    async fn list_ledger_checkpoints(&self) -> Result<Vec<LedgerCheckpoint>, Error> {
+        self.run(|conn| list_ledger_checkpoints(conn)).await
+    }
+
+    async fn list_operation_log(&self, after: i64, limit: usize) -> Result<Vec<OperationLogEntry>, Error> {
+        self.run_with_cipher(move |conn, cipher| list_operation_log(conn, cipher, after, limit)).await
+    }
+
+    async fn last_operation_log_entry(&self) -> Result<Option<OperationLogEntry>, Error> {
+        self.run_with_cipher(|conn, cipher| last_operation_log_entry(conn, cipher)).await
+    }
+
+    async fn record_head_anchor(&self, anchor: &HeadAnchor) -> Result<(), Error> {
+        let anchor = anchor.clone();
+        self.run(move |conn| record_head_anchor(conn, &anchor)).await
+    }
+
+    async fn latest_head_anchor(&self) -> Result<Option<HeadAnchor>, Error> {
+        self.run(|conn| latest_head_anchor(conn)).await
+    }
+
+    async fn list_did_operations(&self, did: &str, after: i64) -> Result<Vec<OperationLogEntry>, Error> {
+        let did = did.to_string();
+        self.run_with_cipher(move |conn, cipher| list_did_operations(conn, cipher, &did, after)).await
+    }
+
+    async fn list_logged_dids(&self) -> Result<Vec<String>, Error> {
+        self.run(|conn| list_logged_dids(conn)).await
+    }
+
+    async fn get_did_snapshot(&self, did: &str) -> Result<Option<DidSnapshot>, Error> {
+        let did = did.to_string();
+        self.run_with_cipher(move |conn, cipher| get_did_snapshot(conn, cipher, &did)).await
+    }
+
+    async fn save_did_snapshot(&self, snapshot: &DidSnapshot) -> Result<(), Error> {
+        let snapshot = snapshot.clone();
+        self.run_with_cipher(move |conn, cipher| save_did_snapshot(conn, cipher, &snapshot)).await
+    }
+
+    async fn restore_did_record(&self, record: &DidRecord) -> Result<(), Error> {
+        let record = record.clone();
+        self.run_with_cipher(move |conn, cipher| restore_did_record(conn, cipher, &record)).await
+    }
+
+    async fn export_registry(&self) -> Result<Registry, Error> {
+        self.run_with_cipher(export_registry).await
+    }
+
+    async fn import_registry(&self, registry: &Registry, policy: ConflictPolicy) -> Result<ImportReport, Error> {
+        let registry = registry.clone();
+        self.run_with_cipher(move |conn, cipher| import_registry(conn, cipher, &registry, policy)).await
+    }
+
+    async fn fetch_unbatched_anchor_entries(&self, ledger: &str, limit: usize) -> Result<Vec<OutboxEntry>, Error> {
+        let ledger = ledger.to_string();
+        self.run_with_cipher(move |conn, cipher| fetch_unbatched_anchor_entries(conn, cipher, &ledger, limit)).await
+    }
+
+    async fn create_anchor_batch(
+        &self,
+        ledger: &str,
+        root: &str,
+        idempotency_key: &str,
+        proofs: &[NewAnchorProof],
+    ) -> Result<i64, Error> {
+        let (ledger, root, key, proofs) = (ledger.to_string(), root.to_string(), idempotency_key.to_string(), proofs.to_vec());
+        self.run(move |conn| create_anchor_batch(conn, &ledger, &root, &key, &proofs)).await
+    }
+
+    async fn fetch_due_anchor_batches(&self, now: u64, limit: usize) -> Result<Vec<AnchorBatch>, Error> {
+        self.run(move |conn| fetch_due_anchor_batches(conn, now, limit)).await
+    }
+
+    async fn mark_anchor_batch_delivered(&self, batch_id: i64, tx_hash: &str) -> Result<(), Error> {
+        let tx_hash = tx_hash.to_string();
+        self.run(move |conn| mark_anchor_batch_delivered(conn, batch_id, &tx_hash)).await
+    }
+
+    async fn mark_anchor_batch_failed(
+        &self,
+        batch_id: i64,
+        attempts: u32,
+        error: &str,
+        next_attempt_at: Option<u64>,
+    ) -> Result<(), Error> {
+        let error = error.to_string();
+        self.run(move |conn| mark_anchor_batch_failed(conn, batch_id, attempts, &error, next_attempt_at)).await
+    }
+
+    async fn get_anchor_proof(&self, did: &str, version_id: u64) -> Result<Option<AnchorProof>, Error> {
+        let did = did.to_string();
+        self.run(move |conn| get_anchor_proof(conn, &did, version_id)).await
+    }
+
+    async fn store_nonce(&self, nonce: &str, subject: &str, expires_at: u64) -> Result<(), Error> {
+        let (nonce, subject) = (nonce.to_string(), subject.to_string());
+        self.run(move |conn| store_nonce(conn, &nonce, &subject, expires_at)).await
+    }
+
+    async fn consume_nonce(&self, nonce: &str) -> Result<Option<String>, Error> {
+        let nonce = nonce.to_string();
+        self.run(move |conn| consume_nonce(conn, &nonce)).await
+    }
+
+    async fn create_api_key(&self, key: &ApiKey) -> Result<(), Error> {
+        let key = key.clone();
+        self.run(move |conn| create_api_key(conn, &key)).await
+    }
+
+    async fn get_api_key(&self, id: &str) -> Result<Option<ApiKey>, Error> {
+        let id = id.to_string();
+        self.run(move |conn| get_api_key(conn, &id)).await
+    }
+
+    async fn list_api_keys(&self) -> Result<Vec<ApiKey>, Error> {
+        self.run(|conn| list_api_keys(conn)).await
+    }
+
+    async fn revoke_api_key(&self, id: &str, revoked_at: u64) -> Result<bool, Error> {
+        let id = id.to_string();
+        self.run(move |conn| revoke_api_key(conn, &id, revoked_at)).await
+    }
+
+    async fn get_write_usage(&self, tenant: &str, day: u64) -> Result<u64, Error> {
+        let tenant = tenant.to_string();
+        self.run(move |conn| get_write_usage(conn, &tenant, day)).await
+    }
+
+    async fn reserve_write(&self, tenant: &str, day: u64, quota: u64, count: u64) -> Result<Option<u64>, Error> {
+        let tenant = tenant.to_string();
+        self.run(move |conn| reserve_write(conn, &tenant, day, quota, count)).await
+    }
+
+    async fn release_write(&self, tenant: &str, day: u64, count: u64) -> Result<(), Error> {
+        let tenant = tenant.to_string();
+        self.run(move |conn| release_write(conn, &tenant, day, count)).await
+    }
+
+    async fn claim_idempotency_key(
+        &self,
+        tenant: &str,
+        key: &str,
+        fingerprint: &str,
+        now: u64,
+        expires_at: u64,
+    ) -> Result<Option<IdempotencyRecord>, Error> {
+        let (tenant, key, fingerprint) = (tenant.to_string(), key.to_string(), fingerprint.to_string());
+        self.run_with_cipher(move |conn, cipher| {
+            claim_idempotency_key(conn, cipher, &tenant, &key, &fingerprint, now, expires_at)
+        }).await
+    }
+
+    async fn complete_idempotency_key(&self, tenant: &str, key: &str, response: &StoredResponse) -> Result<(), Error> {
+        let (tenant, key, response) = (tenant.to_string(), key.to_string(), response.clone());
+        self.run_with_cipher(move |conn, cipher| complete_idempotency_key(conn, cipher, &tenant, &key, &response)).await
+    }
+
+    async fn release_idempotency_key(&self, tenant: &str, key: &str) -> Result<(), Error> {
+        let (tenant, key) = (tenant.to_string(), key.to_string());
+        self.run(move |conn| {
+            conn.execute(
+                "DELETE FROM idempotency_keys WHERE tenant = ? AND idempotency_key = ? AND response IS NULL",
+                params![tenant, key],
+            ).map_err(|e| Error::DatabaseError(format!("Failed to release idempotency key: {}", e)))?;
+            Ok(())
+        }).await
+    }
+}
@@ -0,0 +1,218 @@
+//! 批量操作模块 - 在一个请求中提交多个DID的签名操作
+//!
+//! 每个操作的校验和状态计算与单个操作相同。默认逐个写入，失败的操作不影响其他操作；
+//! 原子模式下全部操作在同一个事务中写入，任何一个操作失败时都不写入。
+//! 写入后批量锚定模式账本上的操作立即封装为一个批次，不等待后台批量锚定任务；
+//! 完整模式和锚定模式账本上的操作仍各自写入发件箱，由发件箱任务逐个上链。
+
+use std::collections::{BTreeMap, HashMap};
+use serde::Deserialize;
+use utoipa::ToSchema;
+use crate::anchoring::{self, AnchorLeaf};
+use crate::blockchain::{self, LedgerMode};
+use crate::db::{DidRecord, DidStore, DidWrite, WrittenChange};
+use crate::did::{cache, check_submission, prepare};
+use crate::oplog::{DidOperation, SignedOperation};
+use crate::outbox::OutboxOperation;
+use crate::types::Error;
+
+/// 一个批次的最大操作数
+pub const MAX_BATCH_OPERATIONS: usize = 1000;
+
+/// 批次中的一个签名操作
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct BatchOperation {
+    /// 操作的DID
+    pub did: String,
+    #[serde(flatten)]
+    pub change: SignedOperation,
+}
+
+impl BatchOperation {
+    /// 是否为创建操作
+    pub fn is_create(&self) -> bool {
+        matches!(self.change.operation, DidOperation::Create { .. })
+    }
+}
+
+/// 单个操作的结果
+#[derive(Debug)]
+pub enum BatchOutcome {
+    /// 已写入，附带变更后的记录
+    Applied(DidRecord),
+    /// 操作失败
+    Failed(Error),
+    /// 原子模式下因其他操作失败而未写入
+    Aborted,
+}
+
+/// 批次的执行结果
+#[derive(Debug, Clone, Default)]
+pub struct BatchSummary {
+    pub applied: usize,
+    pub failed: usize,
+    pub aborted: usize,
+    /// 为批次中的操作封装的锚定批次ID
+    pub anchor_batches: Vec<i64>,
+}
+
+impl BatchSummary {
+    fn record(&mut self, outcome: &BatchOutcome) {
+        match outcome {
+            BatchOutcome::Applied(_) => self.applied += 1,
+            BatchOutcome::Failed(_) => self.failed += 1,
+            BatchOutcome::Aborted => self.aborted += 1,
+        }
+    }
+}
+
+/// 校验批次的操作数
+pub fn validate(operations: &[BatchOperation]) -> Result<(), Error> {
+    if operations.is_empty() || operations.len() > MAX_BATCH_OPERATIONS {
+        return Err(Error::validation(
+            "operations",
+            format!("A batch must contain 1 to {} operations", MAX_BATCH_OPERATIONS),
+        ));
+    }
+    Ok(())
+}
+
+/// 按顺序执行批次中的操作，每个操作有结果时调用`report`（操作序号、DID和结果）
+///
+/// 非原子模式下每个操作单独写入，操作完成后立即报告；原子模式下所有操作校验通过后在同一个事务中写入，
+/// 存储写入失败时所有操作报告为未写入并返回错误。
+pub async fn execute<F>(
+    store: &dyn DidStore,
+    operations: Vec<BatchOperation>,
+    atomic: bool,
+    mut report: F,
+) -> Result<BatchSummary, Error>
+where
+    F: FnMut(usize, &str, BatchOutcome) + Send,
+{
+    validate(&operations)?;
+    let mut summary = BatchSummary::default();
+    let mut written = Vec::new();
+
+    if atomic {
+        // 已校验的操作之后的状态，同一个DID的后续操作基于之前的操作
+        let mut overlay: HashMap<String, Option<DidRecord>> = HashMap::new();
+        let mut prepared = Vec::with_capacity(operations.len());
+        let mut failure = None;
+        for (index, item) in operations.iter().enumerate() {
+            let current = match overlay.get(&item.did) {
+                Some(state) => Ok(state.clone()),
+                None => store.get_did_record(&item.did).await,
+            };
+            let result = match current {
+                Ok(current) => stage(store, &item.did, item.change.clone(), current).await,
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok((next, write)) => {
+                    overlay.insert(item.did.clone(), Some(next.clone()));
+                    prepared.push((next, write));
+                }
+                Err(e) => {
+                    failure = Some((index, e));
+                    break;
+                }
+            }
+        }
+
+        if let Some((failed, error)) = failure {
+            let mut error = Some(error);
+            for (index, item) in operations.iter().enumerate() {
+                let outcome = match error.take_if(|_| index == failed) {
+                    Some(e) => BatchOutcome::Failed(e),
+                    None => BatchOutcome::Aborted,
+                };
+                summary.record(&outcome);
+                report(index, &item.did, outcome);
+            }
+            return Ok(summary);
+        }
+
+        let (records, writes): (Vec<_>, Vec<_>) = prepared.into_iter().unzip();
+        let changes = match store.apply_did_writes(&writes).await {
+            Ok(changes) => changes,
+            Err(e) => {
+                for (index, item) in operations.iter().enumerate() {
+                    report(index, &item.did, BatchOutcome::Aborted);
+                }
+                return Err(e);
+            }
+        };
+        for (index, (record, write)) in records.into_iter().zip(writes).enumerate() {
+            cache::invalidate(&write.did);
+            let outcome = BatchOutcome::Applied(record);
+            summary.record(&outcome);
+            report(index, &write.did, outcome);
+            written.push((write, changes[index]));
+        }
+    } else {
+        for (index, item) in operations.into_iter().enumerate() {
+            let result = match store.get_did_record(&item.did).await {
+                Ok(current) => stage(store, &item.did, item.change, current).await,
+                Err(e) => Err(e),
+            };
+            let result = match result {
+                Ok((record, write)) => store.apply_did_writes(std::slice::from_ref(&write)).await
+                    .map(|changes| (record, write, changes[0])),
+                Err(e) => Err(e),
+            };
+            let outcome = match result {
+                Ok((record, write, change)) => {
+                    cache::invalidate(&item.did);
+                    written.push((write, change));
+                    BatchOutcome::Applied(record)
+                }
+                Err(e) => BatchOutcome::Failed(e),
+            };
+            summary.record(&outcome);
+            report(index, &item.did, outcome);
+        }
+    }
+
+    summary.anchor_batches = seal_anchors(store, &written).await;
+    Ok(summary)
+}
+
+/// 校验操作并计算写入内容
+async fn stage(
+    store: &dyn DidStore,
+    did: &str,
+    change: SignedOperation,
+    current: Option<DidRecord>,
+) -> Result<(DidRecord, DidWrite), Error> {
+    let state = check_submission(did, &change, current, None)?;
+    prepare(store, did, state.as_ref(), change).await
+}
+
+/// 将写入批量锚定模式账本的操作按账本各封装为一个批次，返回批次ID
+///
+/// 封装失败时记录警告，出站记录留给后台批量锚定任务封装。
+async fn seal_anchors(store: &dyn DidStore, written: &[(DidWrite, WrittenChange)]) -> Vec<i64> {
+    let mut ledgers: BTreeMap<&str, Vec<(i64, AnchorLeaf)>> = BTreeMap::new();
+    for (write, change) in written {
+        let operation = &write.operation;
+        let batched = blockchain::ledger(&operation.ledger)
+            .is_ok_and(|ledger| ledger.mode() == LedgerMode::Batch);
+        if !batched || !matches!(operation.operation, OutboxOperation::Anchor | OutboxOperation::Deactivate) {
+            continue;
+        }
+        match AnchorLeaf::from_operation(&write.did, change.version_id, operation.operation, &operation.payload) {
+            Ok(leaf) => ledgers.entry(&operation.ledger).or_default().push((change.outbox_id, leaf)),
+            Err(e) => log::warn!("无法为{}构造锚定叶子: {}", write.did, e),
+        }
+    }
+
+    let mut batches = Vec::new();
+    for (ledger, entries) in ledgers {
+        match anchoring::seal_leaves(store, ledger, entries).await {
+            Ok(batch_id) => batches.push(batch_id),
+            Err(e) => log::warn!("封装账本{}的批量操作失败，留给后台批量锚定任务: {}", ledger, e),
+        }
+    }
+    batches
+}
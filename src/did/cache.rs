@@ -0,0 +1,362 @@
+//! 解析缓存 - 在`resolve_record`之前缓存解析结果
+//!
+//! 解析结果只取决于DID，缓存以DID URL中的DID部分为键，路径、查询和片段不会产生新的条目；
+//! 按DID方法配置有效期，`notFound`结果按单独的有效期缓存。
+//! 条目过期后的宽限期内仍返回旧结果，同时在后台重新解析（stale-while-revalidate）。
+//! 本地变更DID后需调用`invalidate`，后台刷新不会写回失效之前开始的解析结果。
+//! 失效只作用于本进程：多个实例共用一个数据库时，其他实例写入的变更要到条目过期后才可见，
+//! 此时应将本系统DID方法的有效期设为0，只缓存外部方法。
+
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use crate::db::{DidRecord, SharedStore};
+use crate::types::Error;
+use utoipa::ToSchema;
+
+/// 默认有效期（秒）
+const DEFAULT_TTL_SECS: u64 = 300;
+/// `notFound`结果的默认有效期（秒）
+const DEFAULT_NEGATIVE_TTL_SECS: u64 = 30;
+/// 过期后仍可返回旧结果的默认宽限期（秒）
+const DEFAULT_STALE_SECS: u64 = 60;
+/// 默认最多缓存的条目数
+const DEFAULT_CAPACITY: usize = 10_000;
+
+/// 解析缓存配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    /// 没有单独配置的DID方法使用的有效期（秒）
+    pub ttl_secs: u64,
+    /// 按DID方法配置的有效期（秒），为0时不缓存该方法
+    pub method_ttls: HashMap<String, u64>,
+    /// `notFound`结果的有效期（秒），为0时不做否定缓存
+    pub negative_ttl_secs: u64,
+    /// 过期后仍可返回旧结果并在后台刷新的宽限期（秒）
+    pub stale_secs: u64,
+    /// 最多缓存的条目数
+    pub capacity: usize,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            ttl_secs: DEFAULT_TTL_SECS,
+            method_ttls: HashMap::new(),
+            negative_ttl_secs: DEFAULT_NEGATIVE_TTL_SECS,
+            stale_secs: DEFAULT_STALE_SECS,
+            capacity: DEFAULT_CAPACITY,
+        }
+    }
+}
+
+impl CacheConfig {
+    /// DID方法对应的有效期
+    fn ttl_for(&self, method: &str) -> Duration {
+        Duration::from_secs(self.method_ttls.get(method).copied().unwrap_or(self.ttl_secs))
+    }
+
+    fn negative_ttl(&self) -> Duration {
+        Duration::from_secs(self.negative_ttl_secs)
+    }
+
+    fn stale_ttl(&self) -> Duration {
+        Duration::from_secs(self.stale_secs)
+    }
+}
+
+/// 解析`method=秒数`列表，如`web=3600,example=60`
+pub fn parse_method_ttls(value: &str) -> Result<HashMap<String, u64>, Error> {
+    value.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| {
+            let (method, secs) = item.split_once('=')
+                .ok_or_else(|| Error::InvalidInput(format!("Invalid method TTL entry: {}", item)))?;
+            let secs = secs.trim().parse()
+                .map_err(|_| Error::InvalidInput(format!("Invalid method TTL entry: {}", item)))?;
+            Ok((method.trim().to_string(), secs))
+        })
+        .collect()
+}
+
+/// 缓存的解析结果
+#[derive(Debug, Clone)]
+enum Resolution {
+    Found(DidRecord),
+    NotFound(String),
+}
+
+impl Resolution {
+    fn into_result(self) -> Result<DidRecord, Error> {
+        match self {
+            Self::Found(record) => Ok(record),
+            Self::NotFound(message) => Err(Error::NotFound(message)),
+        }
+    }
+}
+
+/// 缓存条目
+#[derive(Debug)]
+struct CacheEntry {
+    resolution: Resolution,
+    expires_at: Instant,
+    /// 超过该时间后不再返回旧结果
+    stale_until: Instant,
+    /// 是否已有后台刷新在进行
+    refreshing: bool,
+}
+
+/// 缓存统计
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct CacheStats {
+    /// 命中未过期的文档
+    pub hits: u64,
+    /// 命中未过期的`notFound`结果
+    pub negative_hits: u64,
+    /// 在宽限期内返回了旧结果
+    pub stale_hits: u64,
+    /// 未命中，重新解析
+    pub misses: u64,
+    /// 后台刷新次数
+    pub revalidations: u64,
+    /// 因本地变更而失效的条目数
+    pub invalidations: u64,
+    /// 因容量不足而淘汰的条目数
+    pub evictions: u64,
+    /// 当前缓存的条目数
+    pub entries: usize,
+    pub capacity: usize,
+}
+
+/// 缓存内容和统计
+#[derive(Debug, Default)]
+struct CacheState {
+    entries: HashMap<String, CacheEntry>,
+    /// 每次失效时递增，解析开始后发生过失效的结果不写回缓存
+    generation: u64,
+    stats: CacheStats,
+}
+
+/// 查找缓存的结果
+enum Lookup {
+    Fresh(Resolution),
+    /// 旧结果，`refresh`表示由本次调用负责后台刷新
+    Stale { resolution: Resolution, refresh: bool },
+    Miss,
+}
+
+/// DID解析缓存
+#[derive(Debug)]
+pub struct ResolverCache {
+    config: CacheConfig,
+    state: Mutex<CacheState>,
+}
+
+impl ResolverCache {
+    pub fn new(config: CacheConfig) -> Self {
+        Self { config, state: Mutex::new(CacheState::default()) }
+    }
+
+    fn state(&self) -> MutexGuard<'_, CacheState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// 解析DID URL，优先返回缓存的结果
+    pub async fn resolve(&'static self, store: &SharedStore, did_url: &str) -> Result<DidRecord, Error> {
+        let did = did_of(did_url);
+        let Some(method) = did_method(did) else {
+            return super::resolve_record(store.as_ref(), did).await;
+        };
+        if self.config.ttl_for(method).is_zero() {
+            return super::resolve_record(store.as_ref(), did).await;
+        }
+
+        match self.lookup(did) {
+            Lookup::Fresh(resolution) => resolution.into_result(),
+            Lookup::Stale { resolution, refresh } => {
+                if refresh {
+                    let store = store.clone();
+                    let did = did.to_string();
+                    tokio::spawn(async move {
+                        log::debug!("后台刷新解析缓存: {}", did);
+                        if let Err(e) = self.load(&store, &did).await {
+                            log::warn!("后台刷新解析缓存失败: {} ({})", did, e);
+                        }
+                    });
+                }
+                resolution.into_result()
+            }
+            Lookup::Miss => self.load(store, did).await,
+        }
+    }
+
+    /// 查找缓存并更新统计
+    fn lookup(&self, did: &str) -> Lookup {
+        let now = Instant::now();
+        let mut state = self.state();
+        let state = &mut *state;
+
+        let lookup = match state.entries.get_mut(did) {
+            Some(entry) if now < entry.expires_at => {
+                match entry.resolution {
+                    Resolution::Found(_) => state.stats.hits += 1,
+                    Resolution::NotFound(_) => state.stats.negative_hits += 1,
+                }
+                Lookup::Fresh(entry.resolution.clone())
+            }
+            Some(entry) if now < entry.stale_until => {
+                state.stats.stale_hits += 1;
+                let refresh = !entry.refreshing;
+                entry.refreshing = true;
+                if refresh {
+                    state.stats.revalidations += 1;
+                }
+                Lookup::Stale { resolution: entry.resolution.clone(), refresh }
+            }
+            _ => {
+                state.stats.misses += 1;
+                Lookup::Miss
+            }
+        };
+
+        if matches!(lookup, Lookup::Miss) {
+            state.entries.remove(did);
+        }
+        lookup
+    }
+
+    /// 重新解析DID并写入缓存；只缓存文档和`notFound`结果
+    async fn load(&self, store: &SharedStore, did: &str) -> Result<DidRecord, Error> {
+        let generation = self.state().generation;
+        let result = super::resolve_record(store.as_ref(), did).await;
+
+        let now = Instant::now();
+        let (resolution, ttl) = match &result {
+            Ok(record) => (Resolution::Found(record.clone()), self.config.ttl_for(did_method(did).unwrap_or_default())),
+            Err(Error::NotFound(message)) => (Resolution::NotFound(message.clone()), self.config.negative_ttl()),
+            Err(_) => {
+                if let Some(entry) = self.state().entries.get_mut(did) {
+                    entry.refreshing = false;
+                }
+                return result;
+            }
+        };
+
+        let mut state = self.state();
+        if state.generation != generation || ttl.is_zero() {
+            state.entries.remove(did);
+            return result;
+        }
+        if !state.entries.contains_key(did) {
+            self.make_room(&mut state, now);
+        }
+        state.entries.insert(did.to_string(), CacheEntry {
+            resolution,
+            expires_at: now + ttl,
+            stale_until: now + ttl + self.config.stale_ttl(),
+            refreshing: false,
+        });
+
+        result
+    }
+
+    /// 缓存已满时先淘汰超过宽限期的条目，仍然不足时淘汰最早过期的条目
+    fn make_room(&self, state: &mut CacheState, now: Instant) {
+        if state.entries.len() < self.config.capacity {
+            return;
+        }
+
+        let before = state.entries.len();
+        state.entries.retain(|_, entry| now < entry.stale_until);
+        while !state.entries.is_empty() && state.entries.len() >= self.config.capacity {
+            let oldest = state.entries.iter()
+                .min_by_key(|(_, entry)| entry.expires_at)
+                .map(|(key, _)| key.clone());
+            if let Some(key) = oldest {
+                state.entries.remove(&key);
+            }
+        }
+        state.stats.evictions += (before - state.entries.len()) as u64;
+    }
+
+    /// 使DID的缓存条目失效
+    pub fn invalidate(&self, did: &str) {
+        let mut state = self.state();
+        state.generation += 1;
+
+        if state.entries.remove(did_of(did)).is_some() {
+            state.stats.invalidations += 1;
+        }
+    }
+
+    /// 清空缓存
+    pub fn clear(&self) {
+        let mut state = self.state();
+        state.generation += 1;
+        state.stats.invalidations += state.entries.len() as u64;
+        state.entries.clear();
+    }
+
+    /// 当前的缓存统计
+    pub fn stats(&self) -> CacheStats {
+        let state = self.state();
+        CacheStats {
+            entries: state.entries.len(),
+            capacity: self.config.capacity,
+            ..state.stats.clone()
+        }
+    }
+}
+
+/// DID URL中的DID部分
+fn did_of(did_url: &str) -> &str {
+    did_url.split(['/', '?', '#']).next().unwrap_or(did_url)
+}
+
+/// DID URL的方法名
+fn did_method(did_url: &str) -> Option<&str> {
+    did_url.strip_prefix("did:")?.split(':').next().filter(|method| !method.is_empty())
+}
+
+static CACHE: OnceLock<ResolverCache> = OnceLock::new();
+
+/// 初始化全局解析缓存
+pub fn init(config: CacheConfig) -> Result<(), Error> {
+    log::info!(
+        "初始化解析缓存，有效期: {}秒，否定缓存有效期: {}秒，宽限期: {}秒，容量: {}",
+        config.ttl_secs, config.negative_ttl_secs, config.stale_secs, config.capacity
+    );
+    CACHE
+        .set(ResolverCache::new(config))
+        .map_err(|_| Error::InternalError("Resolver cache already initialized".to_string()))
+}
+
+/// 全局解析缓存，未初始化时为空
+pub fn global() -> Option<&'static ResolverCache> {
+    CACHE.get()
+}
+
+/// 经全局缓存解析DID URL；缓存未初始化时直接解析
+pub async fn resolve(store: &SharedStore, did_url: &str) -> Result<DidRecord, Error> {
+    match global() {
+        Some(cache) => cache.resolve(store, did_url).await,
+        None => super::resolve_record(store.as_ref(), did_of(did_url)).await,
+    }
+}
+
+/// 本地变更DID后使其缓存失效
+pub fn invalidate(did: &str) {
+    if let Some(cache) = global() {
+        cache.invalidate(did);
+    }
+}
+
+/// 清空全局缓存
+pub fn clear() {
+    if let Some(cache) = global() {
+        cache.clear();
+    }
+}
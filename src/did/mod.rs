@@ -1,14 +1,25 @@
 //! DID模块 - 实现DID的核心功能
 
+use std::sync::OnceLock;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
 use ed25519_dalek::SigningKey;
 use serde::{Deserialize, Serialize};
-use crate::blockchain;
-use crate::db;
+use crate::anchoring::{self, InclusionStatus};
+use crate::blockchain::{self, LedgerMode};
+use crate::db::{DidQuery, DidRecord, DidStatus, DidStore, DidWrite};
+use crate::oplog::{DidOperation, LogOperation, OperationLogEntry, SignedOperation, replay};
+use crate::outbox::{OutboxOperation, OutboxStatus, PendingOperation};
 use crate::types::Error;
 use crate::utils;
+use utoipa::ToSchema;
+
+pub mod batch;
+pub mod cache;
+pub mod representation;
 
 /// DID文档结构
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct DIDDocument {
     /// DID标识符
     pub id: String,
@@ -25,7 +36,7 @@ pub struct DIDDocument {
 }
 
 /// 公钥信息
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct PublicKeyInfo {
     pub id: String,
     pub type_: String,
@@ -34,22 +45,96 @@ pub struct PublicKeyInfo {
 }
 
 /// 服务端点
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct Service {
     pub id: String,
     pub type_: String,
     pub endpoint: String,
 }
 
-/// 创建新的DID
-pub async fn create_did(signing_key: &SigningKey) -> Result<DIDDocument, Error> {
+/// DID列表的一页结果
+#[derive(Debug, Clone)]
+pub struct DidPage {
+    pub records: Vec<DidRecord>,
+    /// 下一页的游标，没有更多结果时为None
+    pub next_cursor: Option<String>,
+}
+
+/// 每页默认返回的DID数量
+pub const DEFAULT_PAGE_SIZE: usize = 50;
+/// 每页最多返回的DID数量
+pub const MAX_PAGE_SIZE: usize = 500;
+
+/// 本服务创建的DID使用的方法名
+pub const DID_METHOD: &str = "web";
+/// 客户端签名操作的时间戳允许超前服务器时间的秒数
+const MAX_CLOCK_SKEW_SECS: u64 = 300;
+
+/// Ed25519公钥的multicodec前缀
+const ED25519_MULTICODEC: [u8; 2] = [0xed, 0x01];
+
+/// 公钥使用策略
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyPolicy {
+    /// 同一公钥可以出现在多个DID中
+    #[default]
+    Shared,
+    /// 同一公钥只能属于一个活跃的DID
+    Unique,
+}
+
+impl std::str::FromStr for KeyPolicy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "shared" => Ok(Self::Shared),
+            "unique" => Ok(Self::Unique),
+            other => Err(Error::InvalidInput(format!("Unknown key policy: {}", other))),
+        }
+    }
+}
+
+static KEY_POLICY: OnceLock<KeyPolicy> = OnceLock::new();
+
+/// 按配置设置公钥使用策略，只能在打开存储之前设置一次
+pub fn init_key_policy(policy: KeyPolicy) -> Result<(), Error> {
+    log::info!("公钥使用策略: {:?}", policy);
+    KEY_POLICY
+        .set(policy)
+        .map_err(|_| Error::InternalError("Key policy already initialized".to_string()))
+}
+
+/// 当前的公钥使用策略，未设置时为shared
+pub fn key_policy() -> KeyPolicy {
+    KEY_POLICY.get().copied().unwrap_or_default()
+}
+
+/// 由公钥生成本服务的DID，指定网络段时必须已为其配置账本
+pub fn did_for_key(public_key: &[u8], network: Option<&str>) -> Result<String, Error> {
+    let public_key_base58 = utils::encode_base58(public_key);
+
+    match network {
+        Some(network) => {
+            if !blockchain::registry()?.has_network(DID_METHOD, network) {
+                return Err(Error::validation("network", format!("Unknown network: {}", network)));
+            }
+            Ok(format!("did:{}:{}:{}", DID_METHOD, network, public_key_base58))
+        }
+        None => Ok(format!("did:{}:{}", DID_METHOD, public_key_base58)),
+    }
+}
+
+/// 创建新的DID，`network`为网络段（如`testnet`），为空时使用默认账本
+pub async fn create_did(store: &dyn DidStore, signing_key: &SigningKey, network: Option<&str>) -> Result<DIDDocument, Error> {
     // 获取验证密钥（公钥）
-    let verifying_key = signing_key.verifying_key();
-    let public_key_bytes = verifying_key.to_bytes();
-    
+    let public_key_bytes = signing_key.verifying_key().to_bytes();
+
     // 生成DID标识符
-    let did = format!("did:example:{}", utils::encode_base58(&public_key_bytes));
-    
+    let did = did_for_key(&public_key_bytes, network)?;
+    log::debug!("生成的DID: {}", did);
+
     // 创建公钥信息
     let key_id = format!("{}{}", did, "#keys-1");
     let public_key_info = PublicKeyInfo {
@@ -58,35 +143,45 @@ pub async fn create_did(signing_key: &SigningKey) -> Result<DIDDocument, Error>
         controller: did.clone(),
         public_key_base58: utils::encode_base58(&public_key_bytes),
     };
-    
+
     // 创建DID文档
     let timestamp = utils::current_timestamp();
     let document = DIDDocument {
         id: did.clone(),
         public_keys: vec![public_key_info],
-        authentication: vec![key_id],
+        authentication: vec![key_id.clone()],
         services: Vec::new(),
         created: timestamp,
         updated: timestamp,
     };
-    
-    // 将DID文档保存到数据库，指定为创建操作（is_update = false）
-    db::store_did_document(&did, &document, false)?;
-    
-    // 将DID注册到区块链
-    blockchain::register_did(&did, &public_key_bytes).await?;
-    
-    Ok(document)
+
+    // 将DID文档保存到数据库，区块链注册记录在同一事务中写入出站队列
+    let change = SignedOperation::sign(&did, DidOperation::Create { document }, None, &key_id, signing_key)?;
+    let record = commit(store, &did, None, change).await?;
+
+    Ok(record.document)
 }
 
 /// 解析DID
-pub async fn resolve_did(did: &str) -> Result<DIDDocument, Error> {
+pub async fn resolve_did(store: &dyn DidStore, did: &str) -> Result<DIDDocument, Error> {
+    Ok(resolve_record(store, did).await?.document)
+}
+
+/// 解析DID，返回包含版本号的记录
+pub async fn resolve_record(store: &dyn DidStore, did: &str) -> Result<DidRecord, Error> {
     log::debug!("开始解析DID: {}", did);
     
     // 从数据库中获取DID文档
-    match db::get_did_document(did)? {
-        Some(document) => {
+    match store.get_did_record(did).await?.filter(|record| record.is_active) {
+        Some(record) => {
             log::debug!("从数据库中找到DID文档");
+
+            // 锚定模式下校验本地文档与链上锚定的哈希一致
+            match blockchain::ledger_mode(did) {
+                LedgerMode::Anchor => verify_anchor(store, &record).await?,
+                LedgerMode::Batch => verify_batch_inclusion(store, &record).await?,
+                LedgerMode::Full => {}
+            }
             
             // 暂时跳过区块链状态检查，因为我们还没有完全实现区块链集成
             // TODO: 在区块链集成完成后恢复状态检查
@@ -95,7 +190,7 @@ pub async fn resolve_did(did: &str) -> Result<DIDDocument, Error> {
             //     return Err(Error::InvalidState("DID is deactivated".to_string()));
             // }
             
-            Ok(document)
+            Ok(record)
         },
         None => {
             log::error!("DID不存在: {}", did);
@@ -104,45 +199,386 @@ pub async fn resolve_did(did: &str) -> Result<DIDDocument, Error> {
     }
 }
 
+/// 按条件分页列出DID，`cursor`为上一页返回的游标
+pub async fn list_dids(store: &dyn DidStore, mut query: DidQuery, cursor: Option<&str>) -> Result<DidPage, Error> {
+    if query.limit == 0 || query.limit > MAX_PAGE_SIZE {
+        return Err(Error::validation("limit", format!("limit must be between 1 and {}", MAX_PAGE_SIZE)));
+    }
+    if let Some(cursor) = cursor {
+        query.after = Some(decode_cursor(cursor)?);
+    }
+
+    // 多取一条用于判断是否还有下一页
+    let limit = query.limit;
+    query.limit += 1;
+    let mut records = store.query_did_records(&query).await?;
+
+    let next_cursor = if records.len() > limit {
+        records.truncate(limit);
+        records.last().map(|record| encode_cursor(&record.did))
+    } else {
+        None
+    };
+
+    Ok(DidPage { records, next_cursor })
+}
+
+/// 按公钥反向查询DID，公钥为multibase编码（base58btc，可带Ed25519 multicodec前缀）
+pub async fn lookup_by_key(store: &dyn DidStore, multibase: &str, query: DidQuery, cursor: Option<&str>) -> Result<DidPage, Error> {
+    let public_key = decode_multibase_key(multibase)?;
+    list_dids(store, DidQuery { public_key: Some(public_key), ..query }, cursor).await
+}
+
+/// 按服务端点反向查询DID
+pub async fn lookup_by_service(store: &dyn DidStore, endpoint: &str, query: DidQuery, cursor: Option<&str>) -> Result<DidPage, Error> {
+    list_dids(store, DidQuery { service_endpoint: Some(endpoint.to_string()), ..query }, cursor).await
+}
+
+/// 将multibase编码的公钥转换为文档中使用的Base58编码
+pub fn decode_multibase_key(multibase: &str) -> Result<String, Error> {
+    let encoded = multibase.strip_prefix('z')
+        .ok_or_else(|| Error::InvalidInput("Only base58btc multibase keys (prefix 'z') are supported".to_string()))?;
+    let bytes = utils::decode_base58(encoded)
+        .map_err(|e| Error::InvalidInput(format!("Invalid multibase key: {}", e)))?;
+
+    let key = match bytes.strip_prefix(&ED25519_MULTICODEC) {
+        Some(key) if bytes.len() == ED25519_MULTICODEC.len() + 32 => key,
+        _ => &bytes[..],
+    };
+
+    Ok(utils::encode_base58(key))
+}
+
+/// 公钥唯一策略下，检查文档中的公钥没有被其他活跃的DID使用
+pub async fn ensure_keys_available(store: &dyn DidStore, did: &str, document: &DIDDocument) -> Result<(), Error> {
+    if key_policy() != KeyPolicy::Unique {
+        return Ok(());
+    }
+
+    for key in &document.public_keys {
+        let query = DidQuery {
+            status: Some(DidStatus::Active),
+            public_key: Some(key.public_key_base58.clone()),
+            limit: 2,
+            ..DidQuery::default()
+        };
+
+        if let Some(owner) = store.query_did_records(&query).await?.into_iter().find(|record| record.did != did) {
+            return Err(Error::InvalidState(format!(
+                "Public key {} is already registered to {}",
+                key.public_key_base58, owner.did
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// 将DID编码为分页游标
+fn encode_cursor(did: &str) -> String {
+    URL_SAFE_NO_PAD.encode(did)
+}
+
+/// 解码分页游标
+fn decode_cursor(cursor: &str) -> Result<String, Error> {
+    URL_SAFE_NO_PAD.decode(cursor)
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .ok_or_else(|| Error::validation("cursor", format!("Invalid cursor: {}", cursor)))
+}
+
 /// 更新DID文档
+///
+/// 本函数及以下的变更函数中，`expected_version`为客户端所基于的文档版本（`If-Match`），
+/// 与当前版本不一致时返回`PreconditionFailed`；为空时基于读取到的当前版本。
 pub async fn update_did(
+    store: &dyn DidStore,
     did: &str,
     signing_key: &SigningKey,
     mut document: DIDDocument,
-) -> Result<DIDDocument, Error> {
-    // 验证DID所有权
-    verify_did_ownership(did, signing_key)?;
-    
-    // 验证DID在区块链上的状态
-    let is_active = blockchain::verify_did(did).await?;
-    if !is_active {
-        return Err(Error::InvalidState("DID is deactivated".to_string()));
-    }
-    
+    expected_version: Option<u64>,
+) -> Result<DidRecord, Error> {
     // 更新时间戳
     document.updated = utils::current_timestamp();
-    
-    // 更新数据库中的DID文档，指定为更新操作（is_update = true）
-    db::store_did_document(did, &document, true)?;
-    
-    Ok(document)
+
+    apply_change(store, did, signing_key, DidOperation::Update { document }, expected_version).await
 }
 
-/// 停用DID
-pub async fn deactivate_did(did: &str, signing_key: &SigningKey) -> Result<(), Error> {
+/// 轮换验证方法的公钥，`key_id`为空时轮换签名密钥对应的验证方法
+pub async fn rotate_key(
+    store: &dyn DidStore,
+    did: &str,
+    signing_key: &SigningKey,
+    key_id: Option<&str>,
+    new_public_key: &str,
+    expected_version: Option<u64>,
+) -> Result<DidRecord, Error> {
+    let public_key = utils::decode_base58(new_public_key)
+        .map_err(|e| Error::validation("new_public_key", format!("Invalid public key encoding: {}", e)))?;
+    if public_key.len() != 32 {
+        return Err(Error::validation("new_public_key", format!("Ed25519公钥长度必须为32字节，实际为{}字节", public_key.len())));
+    }
+
+    let key_id = match key_id {
+        Some(key_id) => key_id.to_string(),
+        None => verify_did_ownership(store, did, signing_key).await?,
+    };
+    let operation = DidOperation::Rotate {
+        key_id,
+        public_key_base58: utils::encode_base58(&public_key),
+        updated: utils::current_timestamp(),
+    };
+
+    apply_change(store, did, signing_key, operation, expected_version).await
+}
+
+/// 添加服务端点
+pub async fn add_service(
+    store: &dyn DidStore,
+    did: &str,
+    signing_key: &SigningKey,
+    service: Service,
+    expected_version: Option<u64>,
+) -> Result<DidRecord, Error> {
+    let operation = DidOperation::AddService { service, updated: utils::current_timestamp() };
+    apply_change(store, did, signing_key, operation, expected_version).await
+}
+
+/// 删除服务端点
+pub async fn remove_service(
+    store: &dyn DidStore,
+    did: &str,
+    signing_key: &SigningKey,
+    service_id: &str,
+    expected_version: Option<u64>,
+) -> Result<DidRecord, Error> {
+    let operation = DidOperation::RemoveService {
+        service_id: service_id.to_string(),
+        updated: utils::current_timestamp(),
+    };
+    apply_change(store, did, signing_key, operation, expected_version).await
+}
+
+/// 以控制者身份签名并应用一次文档变更，返回变更后的记录；签名操作记录所基于的版本
+async fn apply_change(
+    store: &dyn DidStore,
+    did: &str,
+    signing_key: &SigningKey,
+    operation: DidOperation,
+    expected_version: Option<u64>,
+) -> Result<DidRecord, Error> {
     // 验证DID所有权
-    verify_did_ownership(did, signing_key)?;
-    
-    // 在区块链上停用DID
-    blockchain::deactivate_did(did).await?;
-    
+    let key_id = verify_did_ownership(store, did, signing_key).await?;
+
+    let record = store.get_did_record(did).await?
+        .ok_or_else(|| Error::NotFound(format!("DID not found: {}", did)))?;
+    check_version(&record, expected_version)?;
+    let change = SignedOperation::sign(did, operation, Some(record.version_id), &key_id, signing_key)?;
+
+    commit(store, did, Some(record), change).await
+}
+
+/// 停用DID
+pub async fn deactivate_did(
+    store: &dyn DidStore,
+    did: &str,
+    signing_key: &SigningKey,
+    expected_version: Option<u64>,
+) -> Result<(), Error> {
+    apply_change(store, did, signing_key, DidOperation::Deactivate, expected_version).await?;
+    Ok(())
+}
+
+/// 检查记录的当前版本是否为客户端所基于的版本
+fn check_version(record: &DidRecord, expected_version: Option<u64>) -> Result<(), Error> {
+    match expected_version {
+        Some(expected) if expected != record.version_id => Err(Error::PreconditionFailed(format!(
+            "DID {} is at version {}, not version {}",
+            record.did, record.version_id, expected
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// 提交由客户端签名的操作，返回变更后的记录
+///
+/// 签名内容与操作日志相同，服务端不接触私钥。创建操作由文档自身中的验证方法签名，且DID必须由签名公钥生成；
+/// 其他操作由当前文档中的验证方法签名，时间戳必须晚于当前版本，防止重放旧的签名操作。
+/// 操作中的`previousVersionId`和`expected_version`（`If-Match`）都必须与当前版本一致。
+pub async fn submit_operation(
+    store: &dyn DidStore,
+    did: &str,
+    change: SignedOperation,
+    expected_version: Option<u64>,
+) -> Result<DidRecord, Error> {
+    let current = match change.operation {
+        DidOperation::Create { .. } => None,
+        _ => Some(store.get_did_record(did).await?
+            .ok_or_else(|| Error::NotFound(format!("DID not found: {}", did)))?),
+    };
+    let state = check_submission(did, &change, current, expected_version)?;
+
+    commit(store, did, state, change).await
+}
+
+/// 校验客户端签名的操作，返回操作所基于的状态；`current`为DID的当前记录
+fn check_submission(
+    did: &str,
+    change: &SignedOperation,
+    current: Option<DidRecord>,
+    expected_version: Option<u64>,
+) -> Result<Option<DidRecord>, Error> {
+    if matches!(change.operation, DidOperation::Repair { .. } | DidOperation::Import { .. }) {
+        return Err(Error::validation("operation.type", format!(
+            "{} operations cannot be submitted by clients",
+            change.operation.kind().as_str()
+        )));
+    }
+    let Some(signer) = change.signer.as_deref().filter(|_| change.signature.is_some()) else {
+        return Err(Error::validation("signature", "Operation must be signed by a verification method of the DID"));
+    };
+
+    let now = utils::current_timestamp();
+    if let Some(updated) = change.operation.updated() {
+        if updated > now + MAX_CLOCK_SKEW_SECS {
+            return Err(Error::validation("operation.updated", "Operation timestamp is in the future"));
+        }
+    }
+
+    match &change.operation {
+        DidOperation::Create { document } => {
+            if document.id != did {
+                return Err(Error::validation("operation.document.id", "Document id does not match the DID"));
+            }
+            let public_key = signer_public_key(document, signer)?;
+            let network = did.strip_prefix(&format!("did:{}:", DID_METHOD))
+                .and_then(|rest| rest.rsplit_once(':'))
+                .map(|(network, _)| network);
+            if did_for_key(&public_key, network)? != did {
+                return Err(Error::validation("did", "DID must be derived from the public key of the signer"));
+            }
+            if change.previous_version_id.is_some() {
+                return Err(Error::validation("previousVersionId", "Create operations have no previous version"));
+            }
+            if expected_version.is_some() {
+                return Err(Error::PreconditionFailed(format!("DID {} does not exist yet", did)));
+            }
+            if current.is_some() {
+                return Err(Error::InvalidInput(format!("DID already exists: {}", did)));
+            }
+            change.verify(did, document)?;
+            Ok(None)
+        }
+        operation => {
+            let record = current.ok_or_else(|| Error::NotFound(format!("DID not found: {}", did)))?;
+            check_version(&record, expected_version)?;
+            change.verify(did, &record.document)?;
+            if operation.updated().is_some_and(|updated| updated <= record.updated_at) {
+                return Err(Error::InvalidState(format!(
+                    "Operation timestamp must be later than the current version (updated {})",
+                    record.updated_at
+                )));
+            }
+            Ok(Some(record))
+        }
+    }
+}
+
+/// 应用已签名的操作：与重放使用同一套规则计算新状态，区块链变更记录在同一事务中写入出站队列
+async fn commit(store: &dyn DidStore, did: &str, state: Option<DidRecord>, change: SignedOperation) -> Result<DidRecord, Error> {
+    let (next, write) = prepare(store, did, state.as_ref(), change).await?;
+
+    match write.change.operation {
+        DidOperation::Deactivate => {
+            store.deactivate_did(did, write.base_version, &write.change, &write.operation).await?;
+        }
+        _ => {
+            store.store_did_document(did, &write.document, write.base_version, &write.change, &write.operation).await?;
+        }
+    }
+    cache::invalidate(did);
+
+    Ok(next)
+}
+
+/// 计算签名操作应用后的新状态和对应的存储写入，不修改存储
+async fn prepare(
+    store: &dyn DidStore,
+    did: &str,
+    state: Option<&DidRecord>,
+    change: SignedOperation,
+) -> Result<(DidRecord, DidWrite), Error> {
+    // DID是否已停用以本地状态为准（由`replay::apply`检查）：链上写入经出站队列异步投递，
+    // 链上状态落后于本地，批量锚定模式下链上也不记录DID状态
+    if let Some(state) = state {
+        check_version(state, change.previous_version_id)?;
+        if let DidOperation::Update { document } = &change.operation {
+            check_updated_document(did, &state.document, document)?;
+        }
+    }
+    let next = replay::apply(state, did, &change.operation, utils::current_timestamp())?;
+
+    let operation = match &change.operation {
+        DidOperation::Create { document } => {
+            ensure_keys_available(store, did, document).await?;
+            let public_key = signer_public_key(document, change.signer.as_deref().unwrap_or_default())?;
+            PendingOperation::create(did, document, &public_key)?
+        }
+        DidOperation::Deactivate => PendingOperation::deactivate(did)?,
+        _ => {
+            ensure_keys_available(store, did, &next.document).await?;
+            PendingOperation::update(did, &next.document)?
+        }
+    };
+
+    // 以读取到的版本作为存储的前提条件，并发的变更不会互相覆盖
+    let write = DidWrite {
+        did: did.to_string(),
+        document: next.document.clone(),
+        base_version: state.map(|state| state.version_id),
+        change,
+        operation,
+    };
+    Ok((next, write))
+}
+
+/// 校验整体替换的文档：标识符和创建时间不能改变，并且至少保留一个验证方法，否则DID将无法再被修改
+fn check_updated_document(did: &str, current: &DIDDocument, document: &DIDDocument) -> Result<(), Error> {
+    if document.id != did {
+        return Err(Error::validation("operation.document.id", "Document id does not match the DID"));
+    }
+    if document.created != current.created {
+        return Err(Error::validation("operation.document.created", "Document creation time cannot be changed"));
+    }
+    if document.public_keys.is_empty() {
+        return Err(Error::validation("operation.document.public_keys", "Document must keep at least one verification method"));
+    }
     Ok(())
 }
 
-/// 验证DID所有权
-fn verify_did_ownership(did: &str, signing_key: &SigningKey) -> Result<(), Error> {
+/// 文档中签名验证方法的公钥
+fn signer_public_key(document: &DIDDocument, signer: &str) -> Result<Vec<u8>, Error> {
+    let key = document.public_keys.iter()
+        .find(|key| key.id == signer)
+        .ok_or_else(|| Error::Unauthorized(format!("Unknown signer: {}", signer)))?;
+
+    utils::decode_base58(&key.public_key_base58).map_err(Error::CryptoError)
+}
+
+/// 按顺序列出DID的全部操作日志，用于审计文档的变更过程
+pub async fn list_operations(store: &dyn DidStore, did: &str) -> Result<Vec<OperationLogEntry>, Error> {
+    let entries = store.list_did_operations(did, 0).await?;
+    if entries.is_empty() {
+        return Err(Error::NotFound(format!("DID not found: {}", did)));
+    }
+
+    Ok(entries)
+}
+
+/// 验证DID所有权，返回与签名密钥对应的验证方法ID
+async fn verify_did_ownership(store: &dyn DidStore, did: &str, signing_key: &SigningKey) -> Result<String, Error> {
     // 获取DID文档
-    let document = db::get_did_document(did)?
+    let document = store.get_did_document(did).await?
         .ok_or_else(|| Error::NotFound(format!("DID not found: {}", did)))?;
     
     // 获取验证密钥
@@ -151,9 +587,110 @@ fn verify_did_ownership(did: &str, signing_key: &SigningKey) -> Result<(), Error
     let public_key_base58 = utils::encode_base58(&public_key_bytes);
     
     // 验证公钥是否匹配
-    if !document.public_keys.iter().any(|key| key.public_key_base58 == public_key_base58) {
-        return Err(Error::Unauthorized("Invalid signing key".to_string()));
+    document.public_keys.iter()
+        .find(|key| key.public_key_base58 == public_key_base58)
+        .map(|key| key.id.clone())
+        .ok_or_else(|| Error::Unauthorized("Invalid signing key".to_string()))
+}
+
+/// DID文档的规范化序列化（按键名排序的紧凑JSON）
+pub fn canonical_bytes(document: &DIDDocument) -> Result<Vec<u8>, Error> {
+    let value = serde_json::to_value(document)
+        .map_err(|e| Error::SerializationError(e.to_string()))?;
+
+    serde_json::to_vec(&value)
+        .map_err(|e| Error::SerializationError(e.to_string()))
+}
+
+/// 计算DID文档的规范化哈希（规范化序列化结果的SHA-256十六进制）
+pub fn document_hash(document: &DIDDocument) -> Result<String, Error> {
+    Ok(utils::to_hex(&utils::sha256(&canonical_bytes(document)?)))
+}
+
+/// 校验本地DID文档与区块链上锚定的哈希一致
+async fn verify_anchor(store: &dyn DidStore, record: &DidRecord) -> Result<(), Error> {
+    let anchor = blockchain::get_anchor(&record.did).await?;
+
+    match anchor {
+        Some(anchor) if anchor.version_id == record.version_id => {
+            if anchor.hash != document_hash(&record.document)? {
+                log::error!("DID文档与链上锚定哈希不一致: {}", record.did);
+                return Err(Error::InvalidState(format!(
+                    "DID document does not match anchored hash (version {})",
+                    record.version_id
+                )));
+            }
+            Ok(())
+        }
+        Some(anchor) if anchor.version_id > record.version_id => {
+            Err(Error::InvalidState(format!(
+                "Local DID document version {} is older than anchored version {}",
+                record.version_id, anchor.version_id
+            )))
+        }
+        // 链上版本落后于本地时，只有当前版本的锚定操作仍在出站队列中时才视为正常
+        anchor => {
+            let expected = document_hash(&record.document)?;
+            // 对账修复以链上状态为准产生新版本，内容与锚定的哈希一致
+            if anchor.is_some_and(|anchor| anchor.hash == expected) && is_repaired(store, &record.did).await? {
+                return Ok(());
+            }
+            let pending = store.list_outbox_entries_for_did(&record.did, OutboxStatus::Pending).await?
+                .into_iter()
+                .any(|entry| entry.version_id == Some(record.version_id) && match entry.operation {
+                    OutboxOperation::Anchor => entry.payload == expected.as_bytes(),
+                    OutboxOperation::Update => serde_json::from_slice::<DIDDocument>(&entry.payload).ok()
+                        .and_then(|document| document_hash(&document).ok())
+                        .is_some_and(|hash| hash == expected),
+                    _ => false,
+                });
+            if pending {
+                log::debug!("DID文档锚定尚未完成: {}", record.did);
+                Ok(())
+            } else {
+                Err(Error::InvalidState(format!(
+                    "DID document version {} is not anchored",
+                    record.version_id
+                )))
+            }
+        }
     }
-    
-    Ok(())
-}
\ No newline at end of file
+}
+
+/// DID的最后一条操作日志是否为对账修复
+async fn is_repaired(store: &dyn DidStore, did: &str) -> Result<bool, Error> {
+    Ok(store.list_did_operations(did, 0).await?
+        .last()
+        .is_some_and(|entry| entry.operation.operation == LogOperation::Repair))
+}
+
+/// 校验本地DID文档已被批量锚定
+async fn verify_batch_inclusion(store: &dyn DidStore, record: &DidRecord) -> Result<(), Error> {
+    match anchoring::verify_inclusion(store, record).await? {
+        InclusionStatus::Verified { .. } => Ok(()),
+        InclusionStatus::Pending { .. } => {
+            log::debug!("DID文档批量锚定尚未完成: {}", record.did);
+            Ok(())
+        }
+        InclusionStatus::Missing => Err(Error::InvalidState(format!(
+            "DID document version {} is not anchored",
+            record.version_id
+        ))),
+        InclusionStatus::Mismatch { reason, .. } => {
+            log::error!("DID文档批量锚定校验失败: {} ({})", record.did, reason);
+            Err(Error::InvalidState(reason))
+        }
+    }
+}
+
+/// 获取DID当前版本的批量锚定包含证明及其验证结果
+pub async fn get_inclusion_proof(store: &dyn DidStore, did: &str) -> Result<InclusionStatus, Error> {
+    if blockchain::ledger_mode(did) != LedgerMode::Batch {
+        return Err(Error::InvalidState(format!("Ledger of {} is not in batch anchoring mode", did)));
+    }
+
+    let record = store.get_did_record(did).await?
+        .ok_or_else(|| Error::NotFound(format!("DID not found: {}", did)))?;
+
+    anchoring::verify_inclusion(store, &record).await
+}
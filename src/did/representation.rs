@@ -0,0 +1,251 @@
+//! DID文档表示模块 - 按DID Core规范生成和读取DID文档的各种表示
+//!
+//! 支持`application/did+json`、`application/did+ld+json`（带`@context`）和`application/did+cbor`。
+//! 三种表示共用同一数据模型：验证方法写入`verificationMethod`，服务端点写入`service`，
+//! `Ed25519VerificationKey2020`类型的公钥以`publicKeyMultibase`表示。
+//! 创建和更新时间属于文档元数据，不包含在表示中。
+
+use std::fmt;
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use crate::types::Error;
+use crate::utils;
+use super::{DIDDocument, PublicKeyInfo, Service, ED25519_MULTICODEC};
+
+/// DID Core v1上下文
+pub const DID_CONTEXT: &str = "https://www.w3.org/ns/did/v1";
+/// Ed25519VerificationKey2020的上下文
+pub const ED25519_2020_CONTEXT: &str = "https://w3id.org/security/suites/ed25519-2020/v1";
+/// 以`publicKeyMultibase`表示公钥的验证方法类型
+const ED25519_2020_TYPE: &str = "Ed25519VerificationKey2020";
+
+/// DID文档表示
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Representation {
+    /// `application/did+json`
+    Json,
+    /// `application/did+ld+json`
+    JsonLd,
+    /// `application/did+cbor`
+    Cbor,
+}
+
+impl Representation {
+    /// 全部支持的表示
+    pub const ALL: [Representation; 3] = [Representation::Json, Representation::JsonLd, Representation::Cbor];
+
+    /// 表示对应的媒体类型
+    pub fn media_type(self) -> &'static str {
+        match self {
+            Representation::Json => "application/did+json",
+            Representation::JsonLd => "application/did+ld+json",
+            Representation::Cbor => "application/did+cbor",
+        }
+    }
+
+    /// 按媒体类型查找表示
+    pub fn from_media_type(media_type: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|representation| representation.media_type().eq_ignore_ascii_case(media_type))
+    }
+}
+
+impl fmt::Display for Representation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.media_type())
+    }
+}
+
+impl FromStr for Representation {
+    type Err = Error;
+
+    /// 接受完整媒体类型或省略`application/`前缀的形式，如`did+cbor`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let media_type = if s.contains('/') { s.to_string() } else { format!("application/{}", s) };
+        Self::from_media_type(&media_type)
+            .ok_or_else(|| Error::RepresentationNotSupported(format!("Unsupported DID document representation: {}", s)))
+    }
+}
+
+/// `@context`可以是单个URI或URI列表
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(untagged)]
+pub enum Context {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Context {
+    fn first(&self) -> Option<&str> {
+        match self {
+            Context::One(uri) => Some(uri),
+            Context::Many(uris) => uris.first().map(String::as_str),
+        }
+    }
+}
+
+/// DID Core数据模型中的DID文档
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CoreDocument {
+    #[serde(rename = "@context", default, skip_serializing_if = "Option::is_none")]
+    context: Option<Context>,
+    id: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    verification_method: Vec<CoreVerificationMethod>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    authentication: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    service: Vec<CoreService>,
+}
+
+/// DID Core数据模型中的验证方法
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CoreVerificationMethod {
+    id: String,
+    #[serde(rename = "type")]
+    type_: String,
+    controller: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    public_key_multibase: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    public_key_base58: Option<String>,
+}
+
+/// DID Core数据模型中的服务端点
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CoreService {
+    id: String,
+    #[serde(rename = "type")]
+    type_: String,
+    service_endpoint: String,
+}
+
+impl CoreDocument {
+    /// 按指定表示的规则将DID文档转换为DID Core数据模型，只有JSON-LD表示带`@context`
+    pub fn from_document(document: &DIDDocument, representation: Representation) -> Result<Self, Error> {
+        let verification_method = document.public_keys.iter()
+            .map(CoreVerificationMethod::from_key)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let context = match representation {
+            Representation::JsonLd => {
+                let mut contexts = vec![DID_CONTEXT.to_string()];
+                if document.public_keys.iter().any(|key| key.type_ == ED25519_2020_TYPE) {
+                    contexts.push(ED25519_2020_CONTEXT.to_string());
+                }
+                Some(Context::Many(contexts))
+            }
+            Representation::Json | Representation::Cbor => None,
+        };
+
+        Ok(Self {
+            context,
+            id: document.id.clone(),
+            verification_method,
+            authentication: document.authentication.clone(),
+            service: document.services.iter()
+                .map(|service| CoreService {
+                    id: service.id.clone(),
+                    type_: service.type_.clone(),
+                    service_endpoint: service.endpoint.clone(),
+                })
+                .collect(),
+        })
+    }
+
+    fn into_document(self, representation: Representation) -> Result<DIDDocument, Error> {
+        if representation == Representation::JsonLd
+            && self.context.as_ref().and_then(Context::first) != Some(DID_CONTEXT)
+        {
+            return Err(Error::validation("@context", format!("The first context must be {}", DID_CONTEXT)));
+        }
+
+        let public_keys = self.verification_method.into_iter()
+            .enumerate()
+            .map(|(index, method)| method.into_key(index))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(DIDDocument {
+            id: self.id,
+            public_keys,
+            authentication: self.authentication,
+            services: self.service.into_iter()
+                .map(|service| Service {
+                    id: service.id,
+                    type_: service.type_,
+                    endpoint: service.service_endpoint,
+                })
+                .collect(),
+            created: 0,
+            updated: 0,
+        })
+    }
+}
+
+impl CoreVerificationMethod {
+    fn from_key(key: &PublicKeyInfo) -> Result<Self, Error> {
+        let (public_key_multibase, public_key_base58) = if key.type_ == ED25519_2020_TYPE {
+            let bytes = utils::decode_base58(&key.public_key_base58)
+                .map_err(|e| Error::SerializationError(format!("Invalid public key {}: {}", key.id, e)))?;
+            (Some(format!("z{}", utils::encode_base58(&[&ED25519_MULTICODEC[..], &bytes].concat()))), None)
+        } else {
+            (None, Some(key.public_key_base58.clone()))
+        };
+
+        Ok(Self {
+            id: key.id.clone(),
+            type_: key.type_.clone(),
+            controller: key.controller.clone(),
+            public_key_multibase,
+            public_key_base58,
+        })
+    }
+
+    fn into_key(self, index: usize) -> Result<PublicKeyInfo, Error> {
+        let field = format!("verificationMethod[{}]", index);
+        let public_key_base58 = match (self.public_key_multibase, self.public_key_base58) {
+            (Some(multibase), _) => super::decode_multibase_key(&multibase)
+                .map_err(|e| Error::validation(format!("{}.publicKeyMultibase", field), e.to_string()))?,
+            (None, Some(base58)) => base58,
+            (None, None) => return Err(Error::validation(field, "Missing publicKeyMultibase or publicKeyBase58")),
+        };
+
+        Ok(PublicKeyInfo {
+            id: self.id,
+            type_: self.type_,
+            controller: self.controller,
+            public_key_base58,
+        })
+    }
+}
+
+/// 生成DID文档的指定表示
+pub fn produce(document: &DIDDocument, representation: Representation) -> Result<Vec<u8>, Error> {
+    let core = CoreDocument::from_document(document, representation)?;
+
+    match representation {
+        Representation::Json | Representation::JsonLd => serde_json::to_vec_pretty(&core)
+            .map_err(|e| Error::SerializationError(e.to_string())),
+        Representation::Cbor => {
+            let mut bytes = Vec::new();
+            ciborium::into_writer(&core, &mut bytes)
+                .map_err(|e| Error::SerializationError(format!("Failed to encode CBOR: {}", e)))?;
+            Ok(bytes)
+        }
+    }
+}
+
+/// 读取指定表示的DID文档，返回的文档时间戳为0，由调用方按元数据填写
+pub fn consume(bytes: &[u8], representation: Representation) -> Result<DIDDocument, Error> {
+    let core: CoreDocument = match representation {
+        Representation::Json | Representation::JsonLd => serde_json::from_slice(bytes)
+            .map_err(|e| Error::InvalidInput(format!("Invalid {} document: {}", representation, e)))?,
+        Representation::Cbor => ciborium::from_reader(bytes)
+            .map_err(|e| Error::InvalidInput(format!("Invalid {} document: {}", representation, e)))?,
+    };
+
+    core.into_document(representation)
+}
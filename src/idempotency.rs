@@ -0,0 +1,83 @@
+//! 幂等模块 - 带`Idempotency-Key`头的写请求只执行一次
+//!
+//! 第一次请求时按租户和幂等键占用一条记录，保存请求指纹（方法、路径和请求体的SHA-256），
+//! 完成后保存响应；保留期内相同的重试直接重放保存的响应，不再执行，也不会重复提交账本交易。
+//! 同一个键用于不同的请求时拒绝。服务端错误和限流的响应不保存，客户端可以用同一个键重试。
+
+use serde::{Deserialize, Serialize};
+use crate::types::Error;
+use crate::utils;
+
+/// 默认的保留期（秒）
+const DEFAULT_RETENTION_SECS: u64 = 86_400;
+/// 幂等键的最大长度
+pub const MAX_KEY_LENGTH: usize = 255;
+/// 处理中的记录超过该时间（秒）仍未完成时视为已放弃，可以被重新占用
+pub const LOCK_TIMEOUT_SECS: u64 = 300;
+
+/// 幂等配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IdempotencyConfig {
+    /// 是否处理`Idempotency-Key`头，关闭时忽略该头
+    pub enabled: bool,
+    /// 保存的响应的保留期（秒），过期后同一个键视为新请求
+    pub retention_secs: u64,
+}
+
+impl Default for IdempotencyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            retention_secs: DEFAULT_RETENTION_SECS,
+        }
+    }
+}
+
+/// 保存的响应
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoredResponse {
+    /// 状态码
+    pub status: u16,
+    /// 响应头
+    pub headers: Vec<(String, String)>,
+    /// 响应体
+    pub body: String,
+}
+
+/// 幂等键对应的记录
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdempotencyRecord {
+    /// 第一次请求的指纹
+    pub fingerprint: String,
+    /// 占用时间
+    pub created_at: u64,
+    /// 保存的响应，请求仍在处理中时为`None`
+    pub response: Option<StoredResponse>,
+}
+
+/// 校验幂等键：1到255个可见ASCII字符
+pub fn validate_key(key: &str) -> Result<(), Error> {
+    if key.is_empty() || key.len() > MAX_KEY_LENGTH || !key.bytes().all(|b| b.is_ascii_graphic()) {
+        return Err(Error::InvalidInput(format!(
+            "Idempotency-Key must be 1 to {} visible ASCII characters", MAX_KEY_LENGTH
+        )));
+    }
+    Ok(())
+}
+
+/// 请求指纹：方法、路径（含查询参数）和请求体的SHA-256
+pub fn fingerprint(method: &str, uri: &str, body: &[u8]) -> String {
+    let mut input = Vec::with_capacity(method.len() + uri.len() + body.len() + 2);
+    input.extend_from_slice(method.as_bytes());
+    input.push(b'\n');
+    input.extend_from_slice(uri.as_bytes());
+    input.push(b'\n');
+    input.extend_from_slice(body);
+    utils::to_hex(&utils::sha256(&input))
+}
+
+/// 响应是否应当保存：服务端错误和限流的响应不保存，客户端可以用同一个键重试
+pub fn is_storable(status: u16) -> bool {
+    status < 500 && status != 429
+}
@@ -0,0 +1,17 @@
+//! DID系统库 - 导出各功能模块供主程序和示例程序使用
+
+pub mod anchoring;
+pub mod api;
+pub mod auth;
+pub mod backup;
+pub mod blockchain;
+pub mod config;
+pub mod db;
+pub mod did;
+pub mod idempotency;
+pub mod oplog;
+pub mod outbox;
+pub mod ratelimit;
+pub mod reconcile;
+pub mod types;
+pub mod utils;
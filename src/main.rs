@@ -1,41 +1,395 @@
 //! DID系统主程序
 
-mod api;
-mod blockchain;
-mod db;
-mod did;
-mod types;
-mod utils;
-
+use clap::{Parser, Subcommand};
+use did_system::{anchoring, api, auth, backup, blockchain, db, did, oplog, outbox, reconcile, utils};
+use did_system::auth::{Authenticator, Role, Scope};
+use did_system::backup::ConflictPolicy;
+use did_system::blockchain::LedgerMode;
+use did_system::config::{Config, LogConfig, LogFormat, Overrides};
+use did_system::db::{KeySource, SqliteStore};
+use did_system::did::representation::{self, Representation};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, Read, Write};
 use std::net::SocketAddr;
-use tokio;
+use std::path::PathBuf;
+
+/// 命令行参数
+#[derive(Debug, Parser)]
+#[command(name = "did-system", about = "去中心化身份管理系统")]
+struct Cli {
+    /// TOML配置文件路径
+    #[arg(long, global = true, env = "DID_CONFIG")]
+    config: Option<PathBuf>,
+
+    #[command(flatten)]
+    overrides: Overrides,
+
+    /// 静态加密口令，经Argon2id派生包装密钥
+    #[arg(long, global = true, env = "DID_ENCRYPTION_PASSPHRASE", hide_env_values = true)]
+    encryption_passphrase: Option<String>,
+
+    /// 允许在未启用认证时启动HTTP服务，任何能访问端口的客户端都可以调用包括管理接口在内的全部接口
+    #[arg(long, global = true, env = "DID_INSECURE_NO_AUTH")]
+    insecure_no_auth: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// 子命令
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// 启动HTTP服务（默认）
+    Serve,
+    /// 比较本地数据库与区块链状态并输出漂移报告
+    Reconcile {
+        /// 以区块链为准修复本地状态
+        #[arg(long)]
+        repair: bool,
+    },
+    /// 校验操作日志哈希链，并检查本地文档与日志一致
+    VerifyLog,
+    /// 重放操作日志，以重放结果修复本地DID文档
+    Rebuild {
+        /// 只重建指定的DID
+        #[arg(long)]
+        did: Option<String>,
+        /// 忽略快照，从第一条日志开始重放
+        #[arg(long)]
+        full: bool,
+    },
+    /// 使用新的口令或密钥文件重新包装数据加密密钥
+    RotateEncryptionKey {
+        /// 新的加密口令
+        #[arg(long, env = "DID_NEW_ENCRYPTION_PASSPHRASE", hide_env_values = true)]
+        new_passphrase: Option<String>,
+        /// 新的密钥文件
+        #[arg(long)]
+        new_key_file: Option<PathBuf>,
+    },
+    /// 校验配置并输出合并后的结果
+    ShowConfig,
+    /// 输出HTTP接口的OpenAPI文档
+    Openapi,
+    /// 以JSON Lines格式导出全部DID、历史版本和操作日志
+    Export {
+        /// 归档文件路径，`-`表示标准输出
+        #[arg(long, default_value = "-")]
+        output: String,
+    },
+    /// 从导出的归档导入DID
+    Import {
+        /// 归档文件路径，`-`表示标准输入
+        input: String,
+        /// DID已存在时的处理方式：skip、overwrite或fail
+        #[arg(long, default_value = "fail")]
+        on_conflict: ConflictPolicy,
+    },
+    /// 解析DID并输出指定表示的DID文档
+    Resolve {
+        did: String,
+        /// 表示的媒体类型：did+json、did+ld+json或did+cbor
+        #[arg(long, default_value = "did+json")]
+        accept: Representation,
+    },
+    /// 在DID文档的各种表示之间转换
+    Convert {
+        /// 输入文件路径，`-`表示标准输入
+        #[arg(default_value = "-")]
+        input: String,
+        /// 输入表示的媒体类型
+        #[arg(long)]
+        from: Representation,
+        /// 输出表示的媒体类型
+        #[arg(long)]
+        to: Representation,
+    },
+    /// 管理API密钥和令牌
+    Auth {
+        #[command(subcommand)]
+        command: AuthCommand,
+    },
+}
+
+/// 认证子命令
+#[derive(Debug, Subcommand)]
+enum AuthCommand {
+    /// 创建API密钥，完整密钥只输出这一次
+    CreateKey {
+        /// 密钥名称，作为认证主体
+        #[arg(long)]
+        name: String,
+        /// 角色：reader、writer或admin
+        #[arg(long)]
+        role: Option<Role>,
+        /// 角色之外单独授予的权限范围，可重复
+        #[arg(long = "scope")]
+        scopes: Vec<Scope>,
+        /// 有效期（秒），默认永不过期
+        #[arg(long)]
+        expires_in: Option<u64>,
+        /// 每日写操作配额，默认使用rate_limit.daily_write_quota，0表示不限制
+        #[arg(long)]
+        daily_write_quota: Option<u64>,
+    },
+    /// 列出全部API密钥
+    ListKeys,
+    /// 撤销API密钥
+    RevokeKey {
+        id: String,
+    },
+    /// 使用签发者私钥签发令牌
+    IssueToken {
+        /// 令牌主体
+        #[arg(long)]
+        subject: String,
+        /// 角色：reader、writer或admin
+        #[arg(long)]
+        role: Option<Role>,
+        /// 角色之外单独授予的权限范围，可重复
+        #[arg(long = "scope")]
+        scopes: Vec<Scope>,
+        /// 有效期（秒），默认使用auth.token_ttl_secs
+        #[arg(long)]
+        ttl: Option<u64>,
+    },
+    /// 生成签发者私钥文件，文件已存在时不覆盖
+    InitIssuerKey {
+        /// 私钥文件路径
+        #[arg(long)]
+        output: PathBuf,
+    },
+}
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let cli = Cli::parse();
+
+    // 加载配置，配置错误时列出全部问题后退出
+    let config = match Config::load(cli.config.as_deref(), &cli.overrides) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+
     // 初始化日志
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("debug"))
-        .format_timestamp_secs()
-        .init();
-    
-    println!("Starting DID System...");
-    
-    // 初始化数据库
-    db::init_database()?;
-    println!("Database initialized");
-    
+    init_logging(&config.log);
+
+    if let Some(Command::ShowConfig) = cli.command {
+        print!("{}", toml::to_string_pretty(&config)?);
+        return Ok(());
+    }
+
+    if let Some(Command::Openapi) = cli.command {
+        println!("{}", api::openapi().to_pretty_json()?);
+        return Ok(());
+    }
+
+    // 表示转换不需要数据库
+    if let Some(Command::Convert { input, from, to }) = &cli.command {
+        let bytes = if input == "-" {
+            let mut bytes = Vec::new();
+            io::stdin().lock().read_to_end(&mut bytes)?;
+            bytes
+        } else {
+            std::fs::read(input)?
+        };
+        let document = representation::consume(&bytes, *from)?;
+        io::stdout().lock().write_all(&representation::produce(&document, *to)?)?;
+        return Ok(());
+    }
+
+    // 签发令牌和生成签发者私钥不需要数据库
+    match &cli.command {
+        Some(Command::Auth { command: AuthCommand::IssueToken { subject, role, scopes, ttl } }) => {
+            let token = Authenticator::from_config(&config.auth)?.issue_token(subject, *role, scopes, *ttl)?;
+            println!("{}", token);
+            return Ok(());
+        }
+        Some(Command::Auth { command: AuthCommand::InitIssuerKey { output } }) => {
+            let key = utils::generate_keypair();
+            let mut file = OpenOptions::new().write(true).create_new(true).open(output)
+                .map_err(|e| format!("Failed to create issuer key file {}: {}", output.display(), e))?;
+            writeln!(file, "{}", utils::encode_base58(&key.to_bytes()))?;
+            println!("Issuer public key: {}", utils::encode_base58(key.verifying_key().as_bytes()));
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    let encryption = KeySource::from_options(cli.encryption_passphrase, config.database.encryption_key_file.clone())?;
+
+    // 轮换包装密钥只涉及数据库本身
+    if let Some(Command::RotateEncryptionKey { new_passphrase, new_key_file }) = cli.command {
+        let current = encryption.ok_or("The current encryption passphrase or key file is required")?;
+        let new = KeySource::from_options(new_passphrase, new_key_file)?
+            .ok_or("A new encryption passphrase or key file is required")?;
+        if config.database.path == db::MEMORY_DATABASE {
+            return Err("The in-memory store is not encrypted".into());
+        }
+
+        SqliteStore::open(&config.database.path, Some(&current))?.rotate_encryption_key(&current, &new)?;
+        println!("Encryption key rotated");
+        return Ok(());
+    }
+
+    // 公钥使用策略在存储写入时检查，需在打开数据库之前设置
+    did::init_key_policy(config.did.key_policy)?;
+
+    // 打开数据库
+    let store = db::open_store(&config.database.path, encryption.as_ref())?;
+
     // 初始化区块链连接
-    blockchain::init().await?;
-    println!("Blockchain connection initialized");
-    
+    blockchain::init(config.ledger.clone()).await?;
+
+    match cli.command.unwrap_or(Command::Serve) {
+        Command::Serve => serve(store, config, cli.insecure_no_auth).await,
+        Command::Reconcile { repair } => {
+            let report = reconcile::reconcile(store.as_ref(), repair).await?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            Ok(())
+        }
+        Command::VerifyLog => {
+            let report = oplog::verify_chain(store.as_ref()).await?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            if !report.intact {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Command::Rebuild { did, full } => {
+            let options = oplog::replay::RebuildOptions { did, full };
+            let report = oplog::replay::rebuild(store.as_ref(), &options).await?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            if !report.failures.is_empty() {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Command::Export { output } => {
+            let manifest = if output == "-" {
+                backup::export(store.as_ref(), &mut io::stdout().lock()).await?
+            } else {
+                backup::export(store.as_ref(), &mut File::create(&output)?).await?
+            };
+            // 归档写入标准输出时，清单只输出到标准错误
+            let manifest = serde_json::to_string_pretty(&manifest)?;
+            if output == "-" {
+                eprintln!("{}", manifest);
+            } else {
+                println!("{}", manifest);
+            }
+            Ok(())
+        }
+        Command::Import { input, on_conflict } => {
+            let report = if input == "-" {
+                backup::import(store.as_ref(), &mut io::stdin().lock(), on_conflict).await?
+            } else {
+                backup::import(store.as_ref(), &mut BufReader::new(File::open(&input)?), on_conflict).await?
+            };
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            Ok(())
+        }
+        Command::Resolve { did, accept } => {
+            let document = did::resolve_did(store.as_ref(), &did).await?;
+            io::stdout().lock().write_all(&representation::produce(&document, accept)?)?;
+            Ok(())
+        }
+        Command::Auth { command } => manage_auth(store.as_ref(), command).await,
+        Command::RotateEncryptionKey { .. } | Command::ShowConfig | Command::Openapi | Command::Convert { .. } => {
+            unreachable!("handled before the store is opened")
+        }
+    }
+}
+
+/// 管理数据库中的API密钥
+async fn manage_auth(store: &dyn db::DidStore, command: AuthCommand) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        AuthCommand::CreateKey { name, role, scopes, expires_in, daily_write_quota } => {
+            let (key, secret) = auth::create_api_key(store, &name, role, scopes, expires_in, daily_write_quota).await?;
+            let mut output = serde_json::to_value(&key)?;
+            output["key"] = secret.into();
+            println!("{}", serde_json::to_string_pretty(&output)?);
+        }
+        AuthCommand::ListKeys => {
+            println!("{}", serde_json::to_string_pretty(&store.list_api_keys().await?)?);
+        }
+        AuthCommand::RevokeKey { id } => {
+            if !store.revoke_api_key(&id, utils::current_timestamp()).await? {
+                return Err(format!("API key not found or already revoked: {}", id).into());
+            }
+            println!("API key {} revoked", id);
+        }
+        AuthCommand::IssueToken { .. } | AuthCommand::InitIssuerKey { .. } => {
+            unreachable!("handled before the store is opened")
+        }
+    }
+    Ok(())
+}
+
+/// 启动HTTP服务
+async fn serve(store: db::SharedStore, config: Config, insecure_no_auth: bool) -> Result<(), Box<dyn std::error::Error>> {
+    // 未启用认证时默认拒绝启动
+    if !config.auth.enabled {
+        if !insecure_no_auth {
+            return Err("Authentication is disabled; set auth.enabled = true, or pass --insecure-no-auth \
+                to serve every endpoint, including /admin, without credentials".into());
+        }
+        log::warn!("未启用认证，任何能访问服务端口的客户端都可以调用全部接口");
+    }
+
+    println!("Starting DID System...");
+
+    // 初始化解析缓存
+    did::cache::init(config.cache.clone())?;
+
+    // 启动出站队列投递任务
+    outbox::spawn_worker(store.clone(), config.outbox.clone());
+    println!("Ledger outbox worker started");
+
+    // 存在批量锚定模式的账本时启动批处理任务
+    if blockchain::ledgers().iter().any(|ledger| ledger.mode() == LedgerMode::Batch) {
+        anchoring::spawn_batcher(store.clone(), config.batch.clone(), config.outbox.clone());
+        println!("Anchor batcher started");
+    }
+
     // 创建API路由
-    let app = api::create_router();
-    
+    let app = api::create_router(store, &config)?;
+
     // 启动服务器
-    let addr = SocketAddr::from(([127, 0, 0, 1], 3000));
+    let addr = config.server.bind;
     println!("DID System running on http://{}", addr);
-    
+
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
-    
+    // 限流需要客户端地址
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
+
     Ok(())
 }
+
+/// 按配置初始化日志
+fn init_logging(config: &LogConfig) {
+    let mut builder = env_logger::Builder::new();
+    builder.parse_filters(&config.level);
+
+    match config.format {
+        LogFormat::Text => {
+            builder.format_timestamp_secs();
+        }
+        LogFormat::Json => {
+            builder.format(|buf, record| {
+                let line = serde_json::json!({
+                    "timestamp": chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+                    "level": record.level().as_str(),
+                    "target": record.target(),
+                    "message": record.args().to_string(),
+                });
+                writeln!(buf, "{}", line)
+            });
+        }
+    }
+
+    builder.init();
+}
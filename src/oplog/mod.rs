@@ -0,0 +1,529 @@
+//! 操作日志模块 - 以哈希链记录所有DID变更，用于发现对数据库文件的篡改
+//!
+//! 每条日志包含规范化的操作内容、变更后文档的哈希、同一DID上一条日志的哈希以及全局上一条日志的哈希。
+//! 校验时沿哈希链逐条重新计算，并将链上每个DID的最新状态与本地文档比对；
+//! 链头锚定到账本后，哈希链必须经过最新锚定的链头。
+//! 日志中的操作带有控制者签名，可通过`replay`模块重放得到DID的当前状态。
+
+pub mod replay;
+
+use std::collections::HashMap;
+use ed25519_dalek::{Signer, SigningKey};
+use serde::{Deserialize, Serialize};
+use crate::blockchain;
+use crate::db::{DidRecord, DidStore};
+use crate::did::{self, DIDDocument, Service};
+use crate::types::Error;
+use crate::utils;
+use utoipa::ToSchema;
+
+/// 校验时每次读取的日志条数
+const VERIFY_PAGE_SIZE: usize = 1000;
+
+/// 日志记录的操作类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LogOperation {
+    /// 创建DID
+    Create,
+    /// 更新DID文档
+    Update,
+    /// 轮换验证方法的公钥
+    Rotate,
+    /// 添加服务端点
+    AddService,
+    /// 删除服务端点
+    RemoveService,
+    /// 停用DID
+    Deactivate,
+    /// 对账时以区块链状态修复本地记录
+    Repair,
+    /// 引入操作日志之前已存在的DID
+    Import,
+}
+
+impl LogOperation {
+    /// 数据库中存储的名称
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Create => "create",
+            Self::Update => "update",
+            Self::Rotate => "rotate",
+            Self::AddService => "add_service",
+            Self::RemoveService => "remove_service",
+            Self::Deactivate => "deactivate",
+            Self::Repair => "repair",
+            Self::Import => "import",
+        }
+    }
+}
+
+impl std::str::FromStr for LogOperation {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "create" => Ok(Self::Create),
+            "update" => Ok(Self::Update),
+            "rotate" => Ok(Self::Rotate),
+            "add_service" => Ok(Self::AddService),
+            "remove_service" => Ok(Self::RemoveService),
+            "deactivate" => Ok(Self::Deactivate),
+            "repair" => Ok(Self::Repair),
+            "import" => Ok(Self::Import),
+            other => Err(Error::InvalidInput(format!("Unknown log operation: {}", other))),
+        }
+    }
+}
+
+/// 可重放的DID操作
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DidOperation {
+    Create {
+        document: DIDDocument,
+    },
+    Update {
+        document: DIDDocument,
+    },
+    Rotate {
+        key_id: String,
+        public_key_base58: String,
+        updated: u64,
+    },
+    AddService {
+        service: Service,
+        updated: u64,
+    },
+    RemoveService {
+        service_id: String,
+        updated: u64,
+    },
+    Deactivate,
+    Repair {
+        document: DIDDocument,
+        is_active: bool,
+    },
+    Import {
+        document: DIDDocument,
+        is_active: bool,
+        version_id: u64,
+    },
+}
+
+impl DidOperation {
+    /// 操作类型
+    pub fn kind(&self) -> LogOperation {
+        match self {
+            Self::Create { .. } => LogOperation::Create,
+            Self::Update { .. } => LogOperation::Update,
+            Self::Rotate { .. } => LogOperation::Rotate,
+            Self::AddService { .. } => LogOperation::AddService,
+            Self::RemoveService { .. } => LogOperation::RemoveService,
+            Self::Deactivate => LogOperation::Deactivate,
+            Self::Repair { .. } => LogOperation::Repair,
+            Self::Import { .. } => LogOperation::Import,
+        }
+    }
+
+    /// 操作携带的更新时间；停用、修复和导入操作没有时间戳
+    pub fn updated(&self) -> Option<u64> {
+        match self {
+            Self::Create { document } | Self::Update { document } => Some(document.updated),
+            Self::Rotate { updated, .. } | Self::AddService { updated, .. } | Self::RemoveService { updated, .. } => Some(*updated),
+            Self::Deactivate | Self::Repair { .. } | Self::Import { .. } => None,
+        }
+    }
+}
+
+/// 参与签名的内容
+#[derive(Serialize)]
+struct SigningInput<'a> {
+    did: &'a str,
+    operation: &'a DidOperation,
+    #[serde(rename = "previousVersionId", skip_serializing_if = "Option::is_none")]
+    previous_version_id: Option<u64>,
+}
+
+/// 带控制者签名的操作；对账修复和导入等系统操作没有签名
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SignedOperation {
+    pub operation: DidOperation,
+    /// 签名所用验证方法的ID
+    pub signer: Option<String>,
+    /// Base58编码的Ed25519签名
+    pub signature: Option<String>,
+    /// 操作所基于的文档版本，参与签名；与当前版本不一致时拒绝操作
+    #[serde(default, rename = "previousVersionId", skip_serializing_if = "Option::is_none")]
+    pub previous_version_id: Option<u64>,
+}
+
+impl SignedOperation {
+    /// 使用控制者的签名密钥对操作签名，`previous_version_id`为操作所基于的文档版本
+    pub fn sign(
+        did: &str,
+        operation: DidOperation,
+        previous_version_id: Option<u64>,
+        key_id: &str,
+        signing_key: &SigningKey,
+    ) -> Result<Self, Error> {
+        let signature = signing_key.sign(&signing_bytes(did, &operation, previous_version_id)?);
+
+        Ok(Self {
+            operation,
+            signer: Some(key_id.to_string()),
+            signature: Some(utils::encode_base58(&signature.to_bytes())),
+            previous_version_id,
+        })
+    }
+
+    /// 不带签名的系统操作
+    pub fn system(operation: DidOperation) -> Self {
+        Self { operation, signer: None, signature: None, previous_version_id: None }
+    }
+
+    /// 使用文档中的验证方法校验签名；系统操作直接通过
+    pub fn verify(&self, did: &str, document: &DIDDocument) -> Result<(), Error> {
+        let (signer, signature) = match (&self.signer, &self.signature) {
+            (Some(signer), Some(signature)) => (signer, signature),
+            (None, None) => return Ok(()),
+            _ => return Err(Error::InvalidState("Operation has an incomplete signature".to_string())),
+        };
+
+        let key = document.public_keys.iter()
+            .find(|key| &key.id == signer)
+            .ok_or_else(|| Error::Unauthorized(format!("Unknown signer: {}", signer)))?;
+        let public_key = utils::decode_base58(&key.public_key_base58).map_err(Error::CryptoError)?;
+        let signature = utils::decode_base58(signature).map_err(Error::CryptoError)?;
+
+        if !utils::verify_signature(&signing_bytes(did, &self.operation, self.previous_version_id)?, &signature, &public_key)
+            .map_err(Error::CryptoError)?
+        {
+            return Err(Error::Unauthorized(format!("Invalid signature by {}", signer)));
+        }
+
+        Ok(())
+    }
+}
+
+/// 操作签名的规范化序列化
+fn signing_bytes(did: &str, operation: &DidOperation, previous_version_id: Option<u64>) -> Result<Vec<u8>, Error> {
+    serde_json::to_vec(&SigningInput { did, operation, previous_version_id })
+        .map_err(|e| Error::SerializationError(e.to_string()))
+}
+
+/// 规范化的操作内容
+///
+/// 引入可重放操作之前写入的日志没有`payload`和签名，这些字段为空时不参与哈希计算。
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct LoggedOperation {
+    pub did: String,
+    pub version_id: u64,
+    pub operation: LogOperation,
+    pub is_active: bool,
+    /// 变更后文档的规范化哈希
+    pub document_hash: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payload: Option<DidOperation>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signer: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    /// 签名操作所基于的文档版本
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_version_id: Option<u64>,
+}
+
+impl LoggedOperation {
+    /// 由变更后的DID记录和产生该变更的操作构造日志内容
+    pub fn from_record(record: &DidRecord, operation: &SignedOperation) -> Result<Self, Error> {
+        Ok(Self {
+            did: record.did.clone(),
+            version_id: record.version_id,
+            operation: operation.operation.kind(),
+            is_active: record.is_active,
+            document_hash: did::document_hash(&record.document)?,
+            payload: Some(operation.operation.clone()),
+            signer: operation.signer.clone(),
+            signature: operation.signature.clone(),
+            previous_version_id: operation.previous_version_id,
+        })
+    }
+
+    /// 日志中记录的签名操作，旧日志没有操作内容时为None
+    pub fn signed_operation(&self) -> Option<SignedOperation> {
+        self.payload.as_ref().map(|payload| SignedOperation {
+            operation: payload.clone(),
+            signer: self.signer.clone(),
+            signature: self.signature.clone(),
+            previous_version_id: self.previous_version_id,
+        })
+    }
+}
+
+/// 参与哈希计算的日志内容
+#[derive(Serialize)]
+struct HashInput<'a> {
+    operation: &'a LoggedOperation,
+    prev_did_hash: Option<&'a str>,
+    prev_hash: Option<&'a str>,
+    recorded_at: u64,
+}
+
+/// 操作日志条目
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct OperationLogEntry {
+    pub seq: i64,
+    #[serde(flatten)]
+    pub operation: LoggedOperation,
+    /// 同一DID上一条日志的哈希
+    pub prev_did_hash: Option<String>,
+    /// 全局上一条日志的哈希
+    pub prev_hash: Option<String>,
+    pub entry_hash: String,
+    pub recorded_at: u64,
+}
+
+impl OperationLogEntry {
+    /// 构造新的日志条目，序号由存储分配
+    pub fn new(
+        operation: LoggedOperation,
+        prev_did_hash: Option<String>,
+        prev_hash: Option<String>,
+        recorded_at: u64,
+    ) -> Result<Self, Error> {
+        let entry_hash = entry_hash(&operation, prev_did_hash.as_deref(), prev_hash.as_deref(), recorded_at)?;
+
+        Ok(Self {
+            seq: 0,
+            operation,
+            prev_did_hash,
+            prev_hash,
+            entry_hash,
+            recorded_at,
+        })
+    }
+
+    /// 重新计算条目哈希
+    pub fn compute_hash(&self) -> Result<String, Error> {
+        entry_hash(&self.operation, self.prev_did_hash.as_deref(), self.prev_hash.as_deref(), self.recorded_at)
+    }
+}
+
+/// 计算日志条目哈希（规范化序列化结果的SHA-256十六进制）
+fn entry_hash(
+    operation: &LoggedOperation,
+    prev_did_hash: Option<&str>,
+    prev_hash: Option<&str>,
+    recorded_at: u64,
+) -> Result<String, Error> {
+    let input = HashInput { operation, prev_did_hash, prev_hash, recorded_at };
+    let bytes = serde_json::to_vec(&input)
+        .map_err(|e| Error::SerializationError(e.to_string()))?;
+
+    Ok(utils::to_hex(&utils::sha256(&bytes)))
+}
+
+/// 哈希链中第一处断裂
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct BrokenLink {
+    pub seq: i64,
+    pub did: String,
+    pub reason: String,
+}
+
+/// 本地文档与日志记录的最新状态不一致
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct DocumentMismatch {
+    pub did: String,
+    pub reason: String,
+}
+
+/// 操作日志校验报告
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct ChainReport {
+    /// 已校验的日志条数
+    pub entries: u64,
+    /// 最后一条完好日志的哈希
+    pub head: Option<String>,
+    /// 哈希链与本地文档均未发现问题
+    pub intact: bool,
+    /// 哈希链中第一处断裂
+    pub broken_link: Option<BrokenLink>,
+    /// 与日志不一致的本地文档
+    pub document_mismatches: Vec<DocumentMismatch>,
+    /// 比对过的最新锚定链头
+    pub anchored_head: Option<HeadAnchor>,
+}
+
+/// 已锚定的日志链头
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct HeadAnchor {
+    pub seq: i64,
+    pub entry_hash: String,
+    pub ledger: String,
+    pub tx_hash: String,
+}
+
+/// 沿哈希链校验操作日志，并检查本地文档与日志中的最新状态一致
+///
+/// 链头锚定过时，先确认最新锚定的链头记录在账本上，再要求哈希链经过该链头，
+/// 防止整条哈希链被重新计算或末尾被截断。
+pub async fn verify_chain(store: &dyn DidStore) -> Result<ChainReport, Error> {
+    let mut report = ChainReport::default();
+    let mut did_heads: HashMap<String, OperationLogEntry> = HashMap::new();
+    let mut after = 0;
+    let mut prev_seq = 0;
+
+    let anchored = store.latest_head_anchor().await?;
+    if let Some(anchor) = &anchored {
+        let recorded = blockchain::get_log_head(&anchor.ledger, &anchor.entry_hash).await?;
+        if recorded.is_none_or(|recorded| i64::try_from(recorded.seq) != Ok(anchor.seq)) {
+            let reason = format!("anchored head is not recorded on ledger {}", anchor.ledger);
+            log::error!("操作日志链头锚定无效: 序号{} ({})", anchor.seq, reason);
+            report.broken_link = Some(BrokenLink { seq: anchor.seq, did: String::new(), reason });
+        }
+    }
+
+    'pages: while report.broken_link.is_none() {
+        let entries = store.list_operation_log(after, VERIFY_PAGE_SIZE).await?;
+        let Some(last) = entries.last() else { break };
+        after = last.seq;
+
+        for entry in entries {
+            let reason = match check_link(&entry, report.head.as_deref(), did_heads.get(&entry.operation.did))? {
+                Some(reason) => Some(reason),
+                None => anchored.as_ref().and_then(|anchor| check_anchored_head(&entry, prev_seq, anchor)),
+            };
+            if let Some(reason) = reason {
+                log::error!("操作日志哈希链断裂: 序号{} ({})", entry.seq, reason);
+                report.broken_link = Some(BrokenLink { seq: entry.seq, did: entry.operation.did.clone(), reason });
+                break 'pages;
+            }
+
+            report.entries += 1;
+            report.head = Some(entry.entry_hash.clone());
+            prev_seq = entry.seq;
+            did_heads.insert(entry.operation.did.clone(), entry);
+        }
+    }
+
+    // 哈希链在锚定的链头之前结束
+    if let Some(anchor) = anchored.as_ref().filter(|_| report.broken_link.is_none()) {
+        if prev_seq < anchor.seq {
+            let reason = "operation log ends before the anchored head".to_string();
+            log::error!("操作日志哈希链断裂: 序号{} ({})", anchor.seq, reason);
+            report.broken_link = Some(BrokenLink { seq: anchor.seq, did: String::new(), reason });
+        }
+    }
+    report.anchored_head = anchored;
+
+    // 哈希链断裂时无法确定各DID的可信状态，只报告断裂位置
+    if report.broken_link.is_none() {
+        report.document_mismatches = check_documents(store, &mut did_heads).await?;
+    }
+    report.intact = report.broken_link.is_none() && report.document_mismatches.is_empty();
+
+    Ok(report)
+}
+
+/// 校验单条日志与前序日志的链接，返回断裂原因
+pub fn check_link(
+    entry: &OperationLogEntry,
+    prev_hash: Option<&str>,
+    prev_did_entry: Option<&OperationLogEntry>,
+) -> Result<Option<String>, Error> {
+    if entry.compute_hash()? != entry.entry_hash {
+        return Ok(Some("entry hash does not match its contents".to_string()));
+    }
+    if entry.prev_hash.as_deref() != prev_hash {
+        return Ok(Some("previous hash does not match the preceding entry".to_string()));
+    }
+    if entry.prev_did_hash.as_deref() != prev_did_entry.map(|prev| prev.entry_hash.as_str()) {
+        return Ok(Some(format!("previous hash for {} does not match its preceding entry", entry.operation.did)));
+    }
+
+    Ok(None)
+}
+
+/// 校验日志与锚定的链头一致，锚定序号处的日志缺失时同样视为断裂
+fn check_anchored_head(entry: &OperationLogEntry, prev_seq: i64, anchor: &HeadAnchor) -> Option<String> {
+    if entry.seq == anchor.seq && entry.entry_hash != anchor.entry_hash {
+        return Some("entry hash does not match the anchored head".to_string());
+    }
+    if prev_seq < anchor.seq && anchor.seq < entry.seq {
+        return Some("anchored head is missing from the operation log".to_string());
+    }
+    None
+}
+
+/// 比较本地文档与日志中各DID的最新状态
+async fn check_documents(
+    store: &dyn DidStore,
+    did_heads: &mut HashMap<String, OperationLogEntry>,
+) -> Result<Vec<DocumentMismatch>, Error> {
+    let mut mismatches = Vec::new();
+
+    for record in store.list_did_records().await? {
+        let expected = match did_heads.remove(&record.did) {
+            Some(entry) => entry.operation,
+            None => {
+                mismatches.push(DocumentMismatch {
+                    did: record.did,
+                    reason: "document has no operation log entry".to_string(),
+                });
+                continue;
+            }
+        };
+
+        let reason = if record.version_id != expected.version_id {
+            Some(format!("version {} does not match logged version {}", record.version_id, expected.version_id))
+        } else if did::document_hash(&record.document)? != expected.document_hash {
+            Some("document hash does not match the operation log".to_string())
+        } else if record.is_active != expected.is_active {
+            Some("active status does not match the operation log".to_string())
+        } else {
+            None
+        };
+
+        if let Some(reason) = reason {
+            mismatches.push(DocumentMismatch { did: record.did, reason });
+        }
+    }
+
+    // 日志中存在但本地已被删除的DID
+    let mut missing: Vec<_> = did_heads.drain().map(|(did, _)| did).collect();
+    missing.sort();
+    mismatches.extend(missing.into_iter().map(|did| DocumentMismatch {
+        did,
+        reason: "document is missing from the database".to_string(),
+    }));
+
+    Ok(mismatches)
+}
+
+/// 将当前的日志链头锚定到默认账本
+pub async fn anchor_head(store: &dyn DidStore) -> Result<HeadAnchor, Error> {
+    let head = store.last_operation_log_entry().await?
+        .ok_or_else(|| Error::InvalidState("Operation log is empty".to_string()))?;
+    let seq = u64::try_from(head.seq)
+        .map_err(|_| Error::InvalidState(format!("Invalid operation log sequence: {}", head.seq)))?;
+    let ledger = blockchain::registry()?.default_ledger().name().to_string();
+
+    let tx_hash = blockchain::anchor_log_head(
+        &ledger,
+        &head.entry_hash,
+        seq,
+        &format!("oplog-{}", head.entry_hash),
+    ).await?;
+    log::info!("已将操作日志链头（序号{}）锚定到账本{}: {}", head.seq, ledger, tx_hash);
+
+    let anchor = HeadAnchor {
+        seq: head.seq,
+        entry_hash: head.entry_hash,
+        ledger,
+        tx_hash,
+    };
+    store.record_head_anchor(&anchor).await?;
+    Ok(anchor)
+}
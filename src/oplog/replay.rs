@@ -0,0 +1,308 @@
+//! 重放引擎 - 按操作日志重放签名操作，重建DID的物化状态
+//!
+//! 写入路径与重放共用`apply`，保证重放结果与写入时的状态一致。
+//! 每个DID重放完成后保存快照，下次重建时从快照对应的日志条目之后继续。
+
+use std::collections::HashMap;
+use serde::Serialize;
+use crate::db::{DidRecord, DidStore, DidVersion};
+use crate::did::{self, DIDDocument};
+use crate::types::Error;
+use crate::utils;
+use super::{DidOperation, OperationLogEntry};
+
+/// DID在某条日志之后的物化状态快照
+#[derive(Debug, Clone)]
+pub struct DidSnapshot {
+    /// 快照对应的日志序号
+    pub seq: i64,
+    /// 快照对应的日志哈希
+    pub entry_hash: String,
+    pub record: DidRecord,
+    pub taken_at: u64,
+}
+
+/// 重建选项
+#[derive(Debug, Clone, Default)]
+pub struct RebuildOptions {
+    /// 只重建指定的DID
+    pub did: Option<String>,
+    /// 忽略已有快照，从第一条日志开始重放
+    pub full: bool,
+}
+
+/// 重放失败的DID
+#[derive(Debug, Clone, Serialize)]
+pub struct RebuildFailure {
+    pub did: String,
+    pub message: String,
+}
+
+/// 重建报告
+#[derive(Debug, Default, Serialize)]
+pub struct RebuildReport {
+    /// 已重放的DID数量
+    pub replayed: usize,
+    /// 重放的日志条数
+    pub operations: usize,
+    /// 从快照开始重放的DID数量
+    pub from_snapshot: usize,
+    /// 物化状态与重放结果不一致并已被覆盖的DID
+    pub restored: Vec<String>,
+    /// 本地存在但没有任何操作日志的DID
+    pub unlogged: Vec<String>,
+    /// 重放失败的DID
+    pub failures: Vec<RebuildFailure>,
+}
+
+/// 将操作应用到DID的当前状态，返回新状态；`recorded_at`为操作发生的时间
+pub fn apply(state: Option<&DidRecord>, did: &str, operation: &DidOperation, recorded_at: u64) -> Result<DidRecord, Error> {
+    let state = match (operation, state) {
+        (DidOperation::Create { document }, None) => return Ok(new_record(did, document.clone(), true, 1)),
+        (DidOperation::Create { .. }, Some(_)) => {
+            return Err(Error::InvalidInput(format!("DID already exists: {}", did)));
+        }
+        (DidOperation::Import { document, is_active, version_id }, _) => {
+            return Ok(new_record(did, document.clone(), *is_active, *version_id));
+        }
+        // 修复改变了文档或状态，产生新版本，已缓存的ETag和历史版本不会与修复后的内容混淆
+        (DidOperation::Repair { document, is_active }, state) => {
+            let mut record = new_record(did, document.clone(), *is_active, 1);
+            if let Some(state) = state {
+                record.version_id = state.version_id + 1;
+                record.created_at = state.created_at;
+            }
+            return Ok(record);
+        }
+        (_, None) => return Err(Error::NotFound(format!("DID not found: {}", did))),
+        (_, Some(state)) => state,
+    };
+
+    if !state.is_active {
+        return Err(Error::InvalidState("DID is deactivated".to_string()));
+    }
+
+    let mut next = state.clone();
+    next.version_id += 1;
+
+    match operation {
+        DidOperation::Update { document } => {
+            next.document = document.clone();
+            next.updated_at = document.updated;
+        }
+        DidOperation::Rotate { key_id, public_key_base58, updated } => {
+            let key = next.document.public_keys.iter_mut()
+                .find(|key| &key.id == key_id)
+                .ok_or_else(|| Error::NotFound(format!("Verification method not found: {}", key_id)))?;
+            key.public_key_base58 = public_key_base58.clone();
+            next.document.updated = *updated;
+            next.updated_at = *updated;
+        }
+        DidOperation::AddService { service, updated } => {
+            if next.document.services.iter().any(|existing| existing.id == service.id) {
+                return Err(Error::InvalidInput(format!("Service already exists: {}", service.id)));
+            }
+            next.document.services.push(service.clone());
+            next.document.updated = *updated;
+            next.updated_at = *updated;
+        }
+        DidOperation::RemoveService { service_id, updated } => {
+            let index = next.document.services.iter()
+                .position(|service| &service.id == service_id)
+                .ok_or_else(|| Error::NotFound(format!("Service not found: {}", service_id)))?;
+            next.document.services.remove(index);
+            next.document.updated = *updated;
+            next.updated_at = *updated;
+        }
+        DidOperation::Deactivate => {
+            next.is_active = false;
+            next.updated_at = recorded_at;
+        }
+        DidOperation::Create { .. } | DidOperation::Import { .. } | DidOperation::Repair { .. } => unreachable!(),
+    }
+
+    Ok(next)
+}
+
+/// 构造新的DID记录
+fn new_record(did: &str, document: DIDDocument, is_active: bool, version_id: u64) -> DidRecord {
+    DidRecord {
+        did: did.to_string(),
+        created_at: document.created,
+        updated_at: document.updated,
+        document,
+        is_active,
+        version_id,
+    }
+}
+
+/// 按操作日志重建DID的物化状态，并为每个DID保存新的快照
+pub async fn rebuild(store: &dyn DidStore, options: &RebuildOptions) -> Result<RebuildReport, Error> {
+    let mut report = RebuildReport::default();
+
+    let dids = match &options.did {
+        Some(did) => vec![did.clone()],
+        None => store.list_logged_dids().await?,
+    };
+
+    for did in &dids {
+        match rebuild_did(store, did, options.full, &mut report).await {
+            Ok(()) => report.replayed += 1,
+            Err(e) => {
+                log::error!("重放DID失败: {} ({})", did, e);
+                report.failures.push(RebuildFailure { did: did.clone(), message: e.to_string() });
+            }
+        }
+    }
+
+    if options.did.is_none() {
+        report.unlogged = store.list_did_records().await?
+            .into_iter()
+            .map(|record| record.did)
+            .filter(|did| dids.binary_search(did).is_err())
+            .collect();
+    }
+
+    Ok(report)
+}
+
+/// 重放单个DID并在物化状态不一致时覆盖
+async fn rebuild_did(store: &dyn DidStore, did: &str, full: bool, report: &mut RebuildReport) -> Result<(), Error> {
+    let snapshot = if full { None } else { store.get_did_snapshot(did).await? };
+    if snapshot.is_some() {
+        report.from_snapshot += 1;
+    }
+
+    let (record, last, applied) = replay_did(store, did, snapshot).await?;
+    report.operations += applied;
+
+    let current = store.get_did_record(did).await?;
+    if !same_state(current.as_ref(), &record)? {
+        log::warn!("DID物化状态与操作日志不一致，已按重放结果覆盖: {}", did);
+        store.restore_did_record(&record).await?;
+        did::cache::invalidate(did);
+        report.restored.push(did.to_string());
+    }
+
+    store.save_did_snapshot(&DidSnapshot {
+        seq: last.seq,
+        entry_hash: last.entry_hash,
+        record,
+        taken_at: utils::current_timestamp(),
+    }).await
+}
+
+/// 比较物化状态与重放结果
+pub fn same_state(current: Option<&DidRecord>, replayed: &DidRecord) -> Result<bool, Error> {
+    Ok(match current {
+        Some(current) => current.version_id == replayed.version_id
+            && current.is_active == replayed.is_active
+            && did::document_hash(&current.document)? == did::document_hash(&replayed.document)?,
+        None => false,
+    })
+}
+
+/// 从快照（或第一条日志）开始重放DID的操作，返回最终状态、最后一条日志和重放的条数
+async fn replay_did(
+    store: &dyn DidStore,
+    did: &str,
+    snapshot: Option<DidSnapshot>,
+) -> Result<(DidRecord, OperationLogEntry, usize), Error> {
+    // 快照必须与其对应的日志条目一致
+    let after = snapshot.as_ref().map(|snapshot| snapshot.seq - 1).unwrap_or(0);
+    let mut entries = store.list_did_operations(did, after).await?.into_iter();
+
+    let (mut state, mut last) = match snapshot {
+        Some(snapshot) => {
+            let entry = entries.next()
+                .filter(|entry| entry.seq == snapshot.seq && entry.entry_hash == snapshot.entry_hash)
+                .ok_or_else(|| Error::InvalidState(format!("Snapshot at entry {} does not match the operation log", snapshot.seq)))?;
+            check_state(&entry, &snapshot.record)?;
+            (Some(snapshot.record), Some(entry))
+        }
+        None => (None, None),
+    };
+
+    let mut history = HashMap::new();
+    let mut applied = 0;
+
+    for entry in entries {
+        let prev_hash = last.as_ref().map(|last| last.entry_hash.as_str());
+        if entry.compute_hash()? != entry.entry_hash || entry.prev_did_hash.as_deref() != prev_hash {
+            return Err(Error::InvalidState(format!("Operation log is broken at entry {}", entry.seq)));
+        }
+
+        if entry.operation.payload.is_none() && history.is_empty() {
+            history = store.list_did_versions(did).await?
+                .into_iter()
+                .map(|version| (version.version_id, version))
+                .collect();
+        }
+        state = Some(replay_entry(state.as_ref(), did, &entry, &history)?);
+        last = Some(entry);
+        applied += 1;
+    }
+
+    match (state, last) {
+        (Some(state), Some(last)) => Ok((state, last, applied)),
+        _ => Err(Error::NotFound(format!("No operation log entries for {}", did))),
+    }
+}
+
+/// 在DID的当前状态上重放一条日志，校验签名和结果状态，返回新状态
+///
+/// 引入可重放操作之前的日志只记录了文档哈希，从`history`（按版本号索引的历史版本）中取回文档。
+pub fn replay_entry(
+    state: Option<&DidRecord>,
+    did: &str,
+    entry: &OperationLogEntry,
+    history: &HashMap<u64, DidVersion>,
+) -> Result<DidRecord, Error> {
+    let next = match entry.operation.signed_operation() {
+        Some(signed) => {
+            if signed.previous_version_id.is_some() && signed.previous_version_id != state.map(|state| state.version_id) {
+                return Err(Error::InvalidState(format!("Entry {} was not based on the preceding version", entry.seq)));
+            }
+            // 创建操作由新文档中的密钥签名，其他操作由变更前文档中的密钥签名
+            let next = apply(state, did, &signed.operation, entry.recorded_at)
+                .map_err(|e| Error::InvalidState(format!("Entry {} cannot be applied: {}", entry.seq, e)))?;
+            let authority = match &signed.operation {
+                DidOperation::Create { .. } => &next.document,
+                _ => &state.unwrap_or(&next).document,
+            };
+            signed.verify(did, authority)
+                .map_err(|e| Error::InvalidState(format!("Entry {} has an invalid signature: {}", entry.seq, e)))?;
+            next
+        }
+        None => {
+            let version = history.get(&entry.operation.version_id)
+                .ok_or_else(|| Error::InvalidState(format!(
+                    "Entry {} has no payload and version {} is missing from history",
+                    entry.seq, entry.operation.version_id
+                )))?;
+            DidRecord {
+                did: did.to_string(),
+                document: version.document.clone(),
+                is_active: entry.operation.is_active,
+                created_at: state.map(|state| state.created_at).unwrap_or(version.document.created),
+                updated_at: version.recorded_at,
+                version_id: entry.operation.version_id,
+            }
+        }
+    };
+
+    check_state(entry, &next)?;
+    Ok(next)
+}
+
+/// 校验重放得到的状态与日志记录的结果一致
+fn check_state(entry: &OperationLogEntry, record: &DidRecord) -> Result<(), Error> {
+    if record.version_id != entry.operation.version_id
+        || record.is_active != entry.operation.is_active
+        || did::document_hash(&record.document)? != entry.operation.document_hash
+    {
+        return Err(Error::InvalidState(format!("Replayed state diverges from the operation log at entry {}", entry.seq)));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,319 @@
+//! 出站队列模块 - 以事务性发件箱的方式将DID状态变更可靠地投递到区块链
+//!
+//! 每次DID状态变更都会在同一个SQLite事务中写入一条出站记录，
+//! 后台任务按指数退避策略将记录投递到区块链，超过最大重试次数后进入死信状态。
+
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use crate::blockchain::{self, Anchor, BlockchainClient, LedgerMode};
+use crate::db::{DidStore, SharedStore};
+use crate::did::{self, DIDDocument};
+use crate::types::Error;
+use crate::utils;
+use utoipa::ToSchema;
+
+/// 出站操作类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum OutboxOperation {
+    /// 注册DID
+    Register,
+    /// 更新DID文档
+    Update,
+    /// 停用DID
+    Deactivate,
+    /// 锚定DID文档哈希
+    Anchor,
+}
+
+impl OutboxOperation {
+    /// 转换为数据库中存储的字符串
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OutboxOperation::Register => "register",
+            OutboxOperation::Update => "update",
+            OutboxOperation::Deactivate => "deactivate",
+            OutboxOperation::Anchor => "anchor",
+        }
+    }
+
+    /// 对应的区块链API端点
+    pub fn endpoint(&self) -> &'static str {
+        match self {
+            OutboxOperation::Register => "/did/register",
+            OutboxOperation::Update => "/did/store",
+            OutboxOperation::Deactivate => "/did/deactivate",
+            OutboxOperation::Anchor => "/did/anchor",
+        }
+    }
+}
+
+impl std::str::FromStr for OutboxOperation {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "register" => Ok(OutboxOperation::Register),
+            "update" => Ok(OutboxOperation::Update),
+            "deactivate" => Ok(OutboxOperation::Deactivate),
+            "anchor" => Ok(OutboxOperation::Anchor),
+            _ => Err(Error::InvalidInput(format!("Invalid outbox operation: {}", s))),
+        }
+    }
+}
+
+/// 出站记录状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum OutboxStatus {
+    /// 等待投递
+    Pending,
+    /// 已投递
+    Delivered,
+    /// 死信（超过最大重试次数）
+    Dead,
+}
+
+impl OutboxStatus {
+    /// 转换为数据库中存储的字符串
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OutboxStatus::Pending => "pending",
+            OutboxStatus::Delivered => "delivered",
+            OutboxStatus::Dead => "dead",
+        }
+    }
+}
+
+impl std::str::FromStr for OutboxStatus {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "pending" => Ok(OutboxStatus::Pending),
+            "delivered" => Ok(OutboxStatus::Delivered),
+            "dead" => Ok(OutboxStatus::Dead),
+            _ => Err(Error::InvalidInput(format!("Invalid outbox status: {}", s))),
+        }
+    }
+}
+
+/// 待写入出站队列的区块链操作
+#[derive(Debug, Clone)]
+pub struct PendingOperation {
+    /// 目标账本名称
+    pub ledger: String,
+    /// 操作类型
+    pub operation: OutboxOperation,
+    /// 发送到区块链的原始数据
+    pub payload: Vec<u8>,
+}
+
+impl PendingOperation {
+    /// 创建DID时的区块链操作，按目标账本的上链模式选择注册或锚定
+    pub fn create(did: &str, document: &DIDDocument, public_key: &[u8]) -> Result<Self, Error> {
+        let ledger = blockchain::ledger_for_did(did)?;
+        match ledger.mode() {
+            LedgerMode::Full => Ok(Self::register(ledger, did, public_key)),
+            LedgerMode::Anchor | LedgerMode::Batch => Self::anchor(ledger, document),
+        }
+    }
+
+    /// 更新DID文档操作，锚定模式下只上链文档哈希
+    pub fn update(did: &str, document: &DIDDocument) -> Result<Self, Error> {
+        let ledger = blockchain::ledger_for_did(did)?;
+        if ledger.mode().is_anchoring() {
+            return Self::anchor(ledger, document);
+        }
+
+        let payload = serde_json::to_vec(document)
+            .map_err(|e| Error::SerializationError(e.to_string()))?;
+
+        Ok(Self {
+            ledger: ledger.name().to_string(),
+            operation: OutboxOperation::Update,
+            payload,
+        })
+    }
+
+    /// 停用DID操作
+    pub fn deactivate(did: &str) -> Result<Self, Error> {
+        Ok(Self {
+            ledger: blockchain::ledger_for_did(did)?.name().to_string(),
+            operation: OutboxOperation::Deactivate,
+            payload: did.as_bytes().to_vec(),
+        })
+    }
+
+    /// 注册DID操作
+    fn register(ledger: &BlockchainClient, did: &str, public_key: &[u8]) -> Self {
+        Self {
+            ledger: ledger.name().to_string(),
+            operation: OutboxOperation::Register,
+            payload: [did.as_bytes(), public_key].concat(),
+        }
+    }
+
+    /// 锚定DID文档哈希操作，版本号在写入出站队列时确定
+    fn anchor(ledger: &BlockchainClient, document: &DIDDocument) -> Result<Self, Error> {
+        Ok(Self {
+            ledger: ledger.name().to_string(),
+            operation: OutboxOperation::Anchor,
+            payload: did::document_hash(document)?.into_bytes(),
+        })
+    }
+}
+
+/// 出站记录
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct OutboxEntry {
+    pub id: i64,
+    pub did: String,
+    /// 目标账本名称
+    pub ledger: String,
+    pub operation: OutboxOperation,
+    #[serde(skip)]
+    pub payload: Vec<u8>,
+    pub idempotency_key: String,
+    /// 操作对应的DID文档版本号
+    pub version_id: Option<u64>,
+    pub status: OutboxStatus,
+    pub attempts: u32,
+    pub next_attempt_at: u64,
+    pub last_error: Option<String>,
+    pub tx_hash: Option<String>,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+/// 出站队列投递配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutboxConfig {
+    /// 轮询间隔（秒）
+    pub poll_interval_secs: u64,
+    /// 每轮最多投递的记录数
+    pub batch_size: usize,
+    /// 首次重试的退避时间（秒）
+    pub base_delay_secs: u64,
+    /// 退避时间上限（秒）
+    pub max_delay_secs: u64,
+    /// 进入死信前的最大尝试次数
+    pub max_attempts: u32,
+}
+
+impl Default for OutboxConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval_secs: 2,
+            batch_size: 32,
+            base_delay_secs: 2,
+            max_delay_secs: 600,
+            max_attempts: 10,
+        }
+    }
+}
+
+impl OutboxConfig {
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_secs(self.poll_interval_secs)
+    }
+
+    /// 计算第`attempts`次失败后的退避时间（秒）
+    pub fn backoff_delay(&self, attempts: u32) -> u64 {
+        let exponent = attempts.saturating_sub(1).min(32);
+        self.base_delay_secs
+            .saturating_mul(1u64 << exponent)
+            .min(self.max_delay_secs)
+    }
+}
+
+/// 生成出站记录的幂等键
+pub fn new_idempotency_key() -> String {
+    utils::to_hex(&utils::generate_random_bytes(16))
+}
+
+/// 启动后台投递任务
+pub fn spawn_worker(store: SharedStore, config: OutboxConfig) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        log::info!("出站队列投递任务已启动，轮询间隔: {}秒", config.poll_interval_secs);
+        loop {
+            match process_due_entries(store.as_ref(), &config).await {
+                Ok(0) => {}
+                Ok(count) => log::debug!("本轮处理了{}条出站记录", count),
+                Err(e) => log::error!("处理出站队列失败: {}", e),
+            }
+            tokio::time::sleep(config.poll_interval()).await;
+        }
+    })
+}
+
+/// 按账本投递所有已到期的出站记录，返回处理的记录数
+///
+/// 批量锚定模式的账本上，锚定和停用操作由批处理任务统一上链，这里不单独投递。
+/// 同一个DID的操作按写入顺序投递，更早的记录未投递前不投递后续记录。
+pub async fn process_due_entries(store: &dyn DidStore, config: &OutboxConfig) -> Result<usize, Error> {
+    let mut count = 0;
+
+    for ledger in blockchain::ledgers() {
+        let skip_batched = ledger.mode() == LedgerMode::Batch;
+        let entries = store.fetch_due_outbox_entries(
+            ledger.name(),
+            utils::current_timestamp(),
+            config.batch_size,
+            skip_batched,
+        ).await?;
+        count += entries.len();
+
+        // 单条记录的存储错误不影响其他记录，下一轮重新获取
+        for entry in entries {
+            if let Err(e) = deliver_entry(store, config, &entry).await {
+                log::error!("记录出站记录{}的投递结果失败: {}", entry.id, e);
+            }
+        }
+    }
+
+    Ok(count)
+}
+
+/// 投递单条出站记录并记录结果
+async fn deliver_entry(store: &dyn DidStore, config: &OutboxConfig, entry: &OutboxEntry) -> Result<(), Error> {
+    let result = match entry.operation {
+        OutboxOperation::Anchor => deliver_anchor(entry).await,
+        operation => blockchain::submit_operation(
+            &entry.ledger,
+            operation.endpoint(),
+            &entry.payload,
+            &entry.idempotency_key,
+        ).await,
+    };
+
+    match result {
+        Ok(tx_hash) => {
+            log::info!("出站记录{}已投递到账本{}: {} ({})", entry.id, entry.ledger, entry.did, tx_hash);
+            store.mark_outbox_delivered(entry.id, &tx_hash).await
+        }
+        Err(e) => {
+            let attempts = entry.attempts + 1;
+            if attempts >= config.max_attempts {
+                log::error!("出站记录{}超过最大重试次数，进入死信状态: {}", entry.id, e);
+                store.mark_outbox_failed(entry.id, attempts, &e.to_string(), None).await
+            } else {
+                let next_attempt_at = utils::current_timestamp() + config.backoff_delay(attempts);
+                log::warn!("出站记录{}投递失败（第{}次），将于{}重试: {}", entry.id, attempts, next_attempt_at, e);
+                store.mark_outbox_failed(entry.id, attempts, &e.to_string(), Some(next_attempt_at)).await
+            }
+        }
+    }
+}
+
+/// 投递锚定记录
+async fn deliver_anchor(entry: &OutboxEntry) -> Result<String, Error> {
+    let version_id = entry.version_id.ok_or_else(|| {
+        Error::InvalidState(format!("Anchor entry {} has no version id", entry.id))
+    })?;
+    let hash = String::from_utf8(entry.payload.clone())
+        .map_err(|e| Error::SerializationError(e.to_string()))?;
+
+    blockchain::anchor_document(&entry.ledger, &entry.did, &Anchor { hash, version_id }, &entry.idempotency_key).await
+}
@@ -0,0 +1,200 @@
+//! 限流模块 - 按客户端的令牌桶限流和按租户的每日写操作配额
+//!
+//! 令牌桶保存在进程内，读操作和写操作使用各自的桶；每日写操作配额的用量保存在数据库中，
+//! 进程重启后仍然有效。租户是API密钥、令牌主体、DID会话的DID或客户端IP。
+
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+use std::time::Instant;
+use serde::{Deserialize, Serialize};
+
+/// 默认每分钟的读请求数
+const DEFAULT_READ_PER_MINUTE: u32 = 600;
+/// 默认的读请求突发量
+const DEFAULT_READ_BURST: u32 = 100;
+/// 默认每分钟的写请求数
+const DEFAULT_WRITE_PER_MINUTE: u32 = 60;
+/// 默认的写请求突发量
+const DEFAULT_WRITE_BURST: u32 = 10;
+/// 默认的每日写操作配额
+const DEFAULT_DAILY_WRITE_QUOTA: u64 = 1000;
+/// 默认的可信反向代理层数
+const DEFAULT_TRUSTED_PROXY_HOPS: usize = 1;
+/// 令牌桶数量超过该值时清理已经回满的桶
+const MAX_IDLE_BUCKETS: usize = 10_000;
+/// 一天的秒数
+pub const SECONDS_PER_DAY: u64 = 86_400;
+/// 写操作用量的保留天数
+pub const USAGE_RETENTION_DAYS: u64 = 7;
+
+/// 限流配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    /// 是否启用限流和配额
+    pub enabled: bool,
+    /// 每个客户端每分钟的读请求数
+    pub read_per_minute: u32,
+    /// 读请求的突发量，即读令牌桶的容量
+    pub read_burst: u32,
+    /// 每个客户端每分钟的写请求数
+    pub write_per_minute: u32,
+    /// 写请求的突发量，即写令牌桶的容量
+    pub write_burst: u32,
+    /// 每个租户每天（UTC）成功的写操作数，为0时不限制；API密钥可以单独设置
+    pub daily_write_quota: u64,
+    /// 是否按`X-Forwarded-For`确定客户端IP，只应在可信的反向代理之后启用
+    pub trust_forwarded_for: bool,
+    /// 服务之前的可信反向代理层数；客户端IP取`X-Forwarded-For`中从右数第该数量个地址，
+    /// 即最外层的可信代理追加的地址，客户端自己填写的地址不会被采用
+    pub trusted_proxy_hops: usize,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            read_per_minute: DEFAULT_READ_PER_MINUTE,
+            read_burst: DEFAULT_READ_BURST,
+            write_per_minute: DEFAULT_WRITE_PER_MINUTE,
+            write_burst: DEFAULT_WRITE_BURST,
+            daily_write_quota: DEFAULT_DAILY_WRITE_QUOTA,
+            trust_forwarded_for: false,
+            trusted_proxy_hops: DEFAULT_TRUSTED_PROXY_HOPS,
+        }
+    }
+}
+
+/// 请求类别，各自使用独立的令牌桶
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Class {
+    Read,
+    Write,
+}
+
+/// 令牌桶参数
+#[derive(Debug, Clone, Copy)]
+struct Policy {
+    /// 桶容量
+    burst: u32,
+    /// 每秒补充的令牌数
+    rate: f64,
+    /// 每分钟的请求数，写入`RateLimit-Policy`
+    per_minute: u32,
+}
+
+impl Policy {
+    fn new(per_minute: u32, burst: u32) -> Self {
+        Self { burst, rate: f64::from(per_minute) / 60.0, per_minute }
+    }
+}
+
+/// 令牌桶
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// 一次限流判断的结果
+#[derive(Debug, Clone, Copy)]
+pub struct Decision {
+    pub allowed: bool,
+    /// 桶容量
+    pub limit: u32,
+    /// 剩余令牌数
+    pub remaining: u32,
+    /// 桶回满所需的秒数
+    pub reset_secs: u64,
+    /// 被拒绝时下一个令牌可用的秒数
+    pub retry_after_secs: u64,
+    /// 每分钟的请求数
+    pub per_minute: u32,
+}
+
+/// 按客户端和请求类别划分的令牌桶
+#[derive(Debug)]
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    read: Policy,
+    write: Policy,
+    buckets: Mutex<HashMap<(String, Class), Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        Self {
+            config: config.clone(),
+            read: Policy::new(config.read_per_minute, config.read_burst),
+            write: Policy::new(config.write_per_minute, config.write_burst),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn config(&self) -> &RateLimitConfig {
+        &self.config
+    }
+
+    fn buckets(&self) -> MutexGuard<'_, HashMap<(String, Class), Bucket>> {
+        self.buckets.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// 从客户端的令牌桶中取出一个令牌
+    pub fn check(&self, client: &str, class: Class) -> Decision {
+        let now = Instant::now();
+        let policy = match class {
+            Class::Read => self.read,
+            Class::Write => self.write,
+        };
+        let mut buckets = self.buckets();
+        if buckets.len() > MAX_IDLE_BUCKETS {
+            buckets.retain(|(_, class), bucket| {
+                let policy = if *class == Class::Read { self.read } else { self.write };
+                refill(bucket, policy, now) < f64::from(policy.burst)
+            });
+        }
+
+        let bucket = buckets.entry((client.to_string(), class))
+            .or_insert(Bucket { tokens: f64::from(policy.burst), updated: now });
+        let tokens = refill(bucket, policy, now);
+        let allowed = tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+        }
+
+        Decision {
+            allowed,
+            limit: policy.burst,
+            remaining: bucket.tokens.floor() as u32,
+            reset_secs: seconds_until(f64::from(policy.burst) - bucket.tokens, policy.rate),
+            retry_after_secs: if allowed { 0 } else { seconds_until(1.0 - bucket.tokens, policy.rate).max(1) },
+            per_minute: policy.per_minute,
+        }
+    }
+}
+
+/// 按经过的时间补充令牌，返回当前令牌数
+fn refill(bucket: &mut Bucket, policy: Policy, now: Instant) -> f64 {
+    let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+    bucket.tokens = (bucket.tokens + elapsed * policy.rate).min(f64::from(policy.burst));
+    bucket.updated = now;
+    bucket.tokens
+}
+
+/// 补充`missing`个令牌所需的整秒数
+fn seconds_until(missing: f64, rate: f64) -> u64 {
+    if missing <= 0.0 {
+        return 0;
+    }
+    (missing / rate).ceil() as u64
+}
+
+/// 时间戳所在的UTC日，作为配额用量的键
+pub fn day_of(timestamp: u64) -> u64 {
+    timestamp / SECONDS_PER_DAY
+}
+
+/// 到下一个UTC日开始的秒数
+pub fn seconds_until_next_day(timestamp: u64) -> u64 {
+    SECONDS_PER_DAY - timestamp % SECONDS_PER_DAY
+}
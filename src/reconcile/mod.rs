@@ -0,0 +1,254 @@
+//! 对账模块 - 比较本地数据库与区块链上的DID状态并生成漂移报告
+
+use serde::Serialize;
+use crate::anchoring::{self, InclusionStatus};
+use crate::blockchain::{self, LedgerMode};
+use crate::db::{DidRecord, DidStore};
+use crate::did;
+use crate::outbox::OutboxStatus;
+use crate::types::Error;
+use utoipa::ToSchema;
+
+/// 漂移类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DriftKind {
+    /// 本地存在但区块链上不存在
+    MissingOnChain,
+    /// 区块链上存在但本地不存在
+    MissingLocally,
+    /// 活跃状态不一致
+    StatusMismatch,
+    /// 文档内容不一致
+    ContentMismatch,
+}
+
+/// 单个DID的漂移记录
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Drift {
+    pub did: String,
+    pub kind: DriftKind,
+    pub local_active: Option<bool>,
+    pub chain_active: Option<bool>,
+    pub local_hash: Option<String>,
+    pub chain_hash: Option<String>,
+    /// 是否已按区块链状态修复
+    pub repaired: bool,
+}
+
+/// 对账过程中无法检查的DID
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ReconcileFailure {
+    pub did: String,
+    pub message: String,
+}
+
+/// 漂移报告
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct DriftReport {
+    /// 已检查的DID数量
+    pub checked: usize,
+    /// 仍有未投递出站记录而跳过的DID数量
+    pub skipped_pending: usize,
+    /// 发现的漂移
+    pub drifts: Vec<Drift>,
+    /// 检查失败的DID
+    pub failures: Vec<ReconcileFailure>,
+    /// 是否启用了修复模式
+    pub repair: bool,
+}
+
+/// 执行对账；`repair`为真时以区块链为准修复本地状态
+pub async fn reconcile(store: &dyn DidStore, repair: bool) -> Result<DriftReport, Error> {
+    let mut report = DriftReport {
+        repair,
+        ..Default::default()
+    };
+
+    for record in store.list_did_records().await? {
+        // 仍在出站队列中的变更尚未上链，此时的差异不算漂移
+        if has_undelivered_operations(store, &record.did).await? {
+            report.skipped_pending += 1;
+            continue;
+        }
+
+        report.checked += 1;
+        match check_local_record(store, &record, repair).await {
+            Ok(Some(drift)) => report.drifts.push(drift),
+            Ok(None) => {}
+            Err(e) => report.failures.push(ReconcileFailure {
+                did: record.did.clone(),
+                message: e.to_string(),
+            }),
+        }
+    }
+
+    for did in store.list_dids_missing_locally().await? {
+        report.checked += 1;
+        match check_missing_locally(store, &did, repair).await {
+            Ok(Some(drift)) => report.drifts.push(drift),
+            Ok(None) => {}
+            Err(e) => report.failures.push(ReconcileFailure {
+                did,
+                message: e.to_string(),
+            }),
+        }
+    }
+
+    log::info!(
+        "对账完成: 检查{}个DID，发现{}处漂移，{}个检查失败，{}个待投递跳过",
+        report.checked,
+        report.drifts.len(),
+        report.failures.len(),
+        report.skipped_pending
+    );
+
+    Ok(report)
+}
+
+/// DID是否还有待投递或死信状态的出站记录
+async fn has_undelivered_operations(store: &dyn DidStore, did: &str) -> Result<bool, Error> {
+    Ok(store.count_outbox_entries_for_did(did, OutboxStatus::Pending).await? > 0
+        || store.count_outbox_entries_for_did(did, OutboxStatus::Dead).await? > 0)
+}
+
+/// 从区块链获取DID文档；链上尚未存储文档内容时返回None
+async fn fetch_chain_document(did: &str) -> Result<Option<did::DIDDocument>, Error> {
+    match blockchain::get_did_document(did).await {
+        Ok(document) => Ok(Some(document)),
+        Err(Error::NotFound(_)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// 检查本地存在的DID
+async fn check_local_record(store: &dyn DidStore, record: &DidRecord, repair: bool) -> Result<Option<Drift>, Error> {
+    let mode = blockchain::ledger_mode(&record.did);
+    if mode == LedgerMode::Batch {
+        return check_batched_record(store, record).await;
+    }
+
+    let chain_active = blockchain::verify_did(&record.did).await?;
+    let local_hash = did::document_hash(&record.document)?;
+
+    let mut drift = Drift {
+        did: record.did.clone(),
+        kind: DriftKind::StatusMismatch,
+        local_active: Some(record.is_active),
+        chain_active: Some(chain_active),
+        local_hash: Some(local_hash.clone()),
+        chain_hash: None,
+        repaired: false,
+    };
+
+    match (record.is_active, chain_active) {
+        (false, false) => return Ok(None),
+        (false, true) => {
+            if repair {
+                store.set_did_active(&record.did, true).await?;
+                did::cache::invalidate(&record.did);
+                drift.repaired = true;
+            }
+        }
+        (true, false) => {
+            // 曾经成功上链过的DID在链上不活跃说明已在链上停用，否则说明从未上链
+            if store.count_outbox_entries_for_did(&record.did, OutboxStatus::Delivered).await? == 0 {
+                drift.kind = DriftKind::MissingOnChain;
+            }
+            if repair {
+                store.set_did_active(&record.did, false).await?;
+                did::cache::invalidate(&record.did);
+                drift.repaired = true;
+            }
+        }
+        (true, true) if mode == LedgerMode::Anchor => {
+            // 锚定模式下链上只有文档哈希，无法按链上内容修复
+            let anchor = match blockchain::get_anchor(&record.did).await? {
+                Some(anchor) => anchor,
+                None => return Ok(None),
+            };
+            if anchor.hash == local_hash {
+                return Ok(None);
+            }
+
+            drift.kind = DriftKind::ContentMismatch;
+            drift.chain_hash = Some(anchor.hash);
+            if repair {
+                log::warn!("锚定模式下无法从区块链恢复文档内容: {}", record.did);
+            }
+        }
+        (true, true) => {
+            let chain_document = match fetch_chain_document(&record.did).await? {
+                Some(document) => document,
+                None => return Ok(None),
+            };
+            let chain_hash = did::document_hash(&chain_document)?;
+            if chain_hash == local_hash {
+                return Ok(None);
+            }
+
+            drift.kind = DriftKind::ContentMismatch;
+            drift.chain_hash = Some(chain_hash);
+            if repair {
+                store.overwrite_did_document(&record.did, &chain_document, true).await?;
+                did::cache::invalidate(&record.did);
+                drift.repaired = true;
+            }
+        }
+    }
+
+    log::warn!("发现DID漂移: {} {:?}", record.did, drift.kind);
+    Ok(Some(drift))
+}
+
+/// 批量锚定模式下通过包含证明检查本地DID；链上只有批次根哈希，无法修复
+async fn check_batched_record(store: &dyn DidStore, record: &DidRecord) -> Result<Option<Drift>, Error> {
+    let (kind, chain_hash) = match anchoring::verify_inclusion(store, record).await? {
+        InclusionStatus::Verified { .. } | InclusionStatus::Pending { .. } => return Ok(None),
+        InclusionStatus::Missing => (DriftKind::MissingOnChain, None),
+        InclusionStatus::Mismatch { proof, .. } => (DriftKind::ContentMismatch, proof.leaf.hash),
+    };
+
+    log::warn!("发现DID漂移: {} {:?}", record.did, kind);
+    Ok(Some(Drift {
+        did: record.did.clone(),
+        kind,
+        local_active: Some(record.is_active),
+        chain_active: None,
+        local_hash: Some(did::document_hash(&record.document)?),
+        chain_hash,
+        repaired: false,
+    }))
+}
+
+/// 检查只出现在出站队列中、本地已不存在的DID
+async fn check_missing_locally(store: &dyn DidStore, did: &str, repair: bool) -> Result<Option<Drift>, Error> {
+    if !blockchain::verify_did(did).await? {
+        return Ok(None);
+    }
+
+    let chain_document = fetch_chain_document(did).await?;
+    let mut drift = Drift {
+        did: did.to_string(),
+        kind: DriftKind::MissingLocally,
+        local_active: None,
+        chain_active: Some(true),
+        local_hash: None,
+        chain_hash: chain_document.as_ref().map(did::document_hash).transpose()?,
+        repaired: false,
+    };
+
+    if repair {
+        match &chain_document {
+            Some(document) => {
+                store.overwrite_did_document(did, document, true).await?;
+                did::cache::invalidate(did);
+                drift.repaired = true;
+            }
+            None => log::warn!("区块链上没有DID文档内容，无法恢复本地记录: {}", did),
+        }
+    }
+
+    log::warn!("发现DID漂移: {} {:?}", did, drift.kind);
+    Ok(Some(drift))
+}
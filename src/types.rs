@@ -1,9 +1,12 @@
 //! 系统错误类型定义
 
+use serde::Serialize;
 use thiserror::Error;
+use utoipa::ToSchema;
 
 /// 系统错误类型
 #[derive(Debug, Error)]
+#[allow(clippy::enum_variant_names)]
 pub enum Error {
     /// 序列化错误
     #[error("Serialization error: {0}")]
@@ -25,6 +28,34 @@ pub enum Error {
     #[error("Unauthorized: {0}")]
     Unauthorized(String),
 
+    /// 凭据有效但权限不足
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
+    /// 请求过于频繁
+    #[error("Rate limited: {0}")]
+    RateLimited(String),
+
+    /// 超出每日配额
+    #[error("Quota exceeded: {0}")]
+    QuotaExceeded(String),
+
+    /// 请求的前提条件（如`If-Match`）不成立
+    #[error("Precondition failed: {0}")]
+    PreconditionFailed(String),
+
+    /// 缺少要求的前提条件
+    #[error("Precondition required: {0}")]
+    PreconditionRequired(String),
+
+    /// 相同幂等键的请求仍在处理中
+    #[error("Idempotency key in use: {0}")]
+    IdempotencyKeyInUse(String),
+
+    /// 幂等键被用于不同的请求
+    #[error("Idempotency key mismatch: {0}")]
+    IdempotencyKeyMismatch(String),
+
     /// 无效输入
     #[error("Invalid input: {0}")]
     InvalidInput(String),
@@ -44,23 +75,74 @@ pub enum Error {
     /// 无效状态
     #[error("Invalid state: {0}")]
     InvalidState(String),
+
+    /// 不支持请求的DID文档表示
+    #[error("Representation not supported: {0}")]
+    RepresentationNotSupported(String),
+
+    /// 请求字段校验失败
+    #[error("Validation failed: {}", describe_fields(.0))]
+    Validation(Vec<FieldError>),
+}
+
+impl Error {
+    /// 单个字段校验失败
+    pub fn validation(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Error::Validation(vec![FieldError { field: field.into(), message: message.into() }])
+    }
+
+    /// 稳定的机器可读错误码
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::SerializationError(_) => "serialization_error",
+            Error::DatabaseError(_) => "database_error",
+            Error::BlockchainError(_) => "blockchain_error",
+            Error::NotFound(_) => "not_found",
+            Error::Unauthorized(_) => "unauthorized",
+            Error::Forbidden(_) => "forbidden",
+            Error::InvalidInput(_) => "invalid_input",
+            Error::InternalError(_) => "internal_error",
+            Error::CryptoError(_) => "crypto_error",
+            Error::NetworkError(_) => "network_error",
+            Error::InvalidState(_) => "invalid_state",
+            Error::RateLimited(_) => "rate_limited",
+            Error::QuotaExceeded(_) => "quota_exceeded",
+            Error::PreconditionFailed(_) => "precondition_failed",
+            Error::PreconditionRequired(_) => "precondition_required",
+            Error::IdempotencyKeyInUse(_) => "idempotency_key_in_use",
+            Error::IdempotencyKeyMismatch(_) => "idempotency_key_mismatch",
+            Error::Validation(_) => "validation_failed",
+            Error::RepresentationNotSupported(_) => "representationNotSupported",
+        }
+    }
+}
+
+/// 校验失败的字段
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct FieldError {
+    /// 字段路径，如`service.endpoint`
+    pub field: String,
+    pub message: String,
+}
+
+/// 将字段错误拼接为一行
+fn describe_fields(fields: &[FieldError]) -> String {
+    fields.iter()
+        .map(|field| format!("{}: {}", field.field, field.message))
+        .collect::<Vec<_>>()
+        .join("; ")
 }
 
 /// DID状态
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Default)]
 pub enum DIDStatus {
     /// 活跃
+    #[default]
     Active,
     /// 已停用
     Deactivated,
 }
 
-impl Default for DIDStatus {
-    fn default() -> Self {
-        Self::Active
-    }
-}
-
 /// 从字符串转换为DID状态
 impl std::str::FromStr for DIDStatus {
     type Err = Error;
@@ -82,6 +82,11 @@ pub fn sha256(data: &[u8]) -> Vec<u8> {
     hasher.finalize().to_vec()
 }
 
+/// 以固定时间比较两个字节串，用于比较密钥哈希等机密数据
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
 /// 计算RIPEMD-160哈希
 pub fn ripemd160(data: &[u8]) -> Vec<u8> {
     ripemd::Ripemd160::digest(data).to_vec()
@@ -109,7 +114,7 @@ pub fn is_valid_base58(encoded: &str) -> bool {
 
 /// 检查字符串是否为有效的十六进制编码
 pub fn is_valid_hex(hex: &str) -> bool {
-    hex.len() % 2 == 0 && hex.chars().all(|c| c.is_ascii_hexdigit())
+    hex.len().is_multiple_of(2) && hex.chars().all(|c| c.is_ascii_hexdigit())
 }
 
 /// 将字节数组转换为十六进制字符串
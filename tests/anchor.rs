@@ -0,0 +1,114 @@
+//! 锚定校验测试：锚定模式下解析DID时，本地文档必须与链上锚定的哈希和版本一致，
+//! 或者当前版本的锚定操作仍在出站队列中；其他待投递记录不能让未锚定的版本通过校验；
+//! 对账修复产生的新版本在内容与锚定一致时通过校验
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use did_system::blockchain::{self, Anchor, BlockchainConfig, LedgerMode, LedgersConfig};
+use did_system::db::{DidRecord, MemoryStore, SharedStore};
+use did_system::did::{self, Service};
+use did_system::oplog::{DidOperation, SignedOperation};
+use did_system::outbox::OutboxStatus;
+use did_system::utils;
+
+type Anchors = Arc<Mutex<HashMap<String, Anchor>>>;
+
+/// 只提供锚定查询的模拟节点，修改DID不查询链上状态
+async fn start_node() -> (String, Anchors) {
+    async fn get_anchor(State(anchors): State<Anchors>, Path(did): Path<String>) -> Response {
+        match anchors.lock().unwrap().get(&did) {
+            Some(anchor) => Json(anchor.clone()).into_response(),
+            None => StatusCode::NOT_FOUND.into_response(),
+        }
+    }
+
+    let anchors = Anchors::default();
+    let app = Router::new()
+        .route("/did/:did/anchor", get(get_anchor))
+        .with_state(anchors.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (format!("http://{}", address), anchors)
+}
+
+#[tokio::test]
+async fn resolution_checks_local_version_against_anchor() {
+    let (node_url, anchors) = start_node().await;
+    blockchain::init(LedgersConfig {
+        ledgers: vec![BlockchainConfig { node_url, mode: LedgerMode::Anchor, ..Default::default() }],
+        ..Default::default()
+    }).await.unwrap();
+    let store: SharedStore = Arc::new(MemoryStore::new());
+
+    // 当前版本的锚定仍在出站队列中
+    let key = utils::generate_keypair();
+    let document = did::create_did(store.as_ref(), &key, None).await.unwrap();
+    let did = document.id.clone();
+    assert_eq!(did::resolve_record(store.as_ref(), &did).await.unwrap().version_id, 1);
+
+    // 本地文档被改写，待投递的锚定操作与改写后的文档不一致
+    let mut tampered = document.clone();
+    tampered.services.push(Service {
+        id: format!("{}#evil", did),
+        type_: "LinkedDomains".to_string(),
+        endpoint: "https://evil.example.com".to_string(),
+    });
+    let original = store.get_did_record(&did).await.unwrap().unwrap();
+    store.restore_did_record(&DidRecord { document: tampered, ..original.clone() }).await.unwrap();
+    let error = did::resolve_record(store.as_ref(), &did).await.unwrap_err();
+    assert!(error.to_string().contains("not anchored"), "{}", error);
+
+    // 只有其他版本的锚定操作待投递；创建的锚定尚未投递、节点不提供状态查询时仍可更新
+    store.restore_did_record(&original).await.unwrap();
+    assert!(did::resolve_record(store.as_ref(), &did).await.is_ok());
+    let mut updated = document.clone();
+    updated.updated = document.updated + 1;
+    let key_id = format!("{}#keys-1", did);
+    let update = SignedOperation::sign(&did, DidOperation::Update { document: updated }, Some(1), &key_id, &key).unwrap();
+    assert_eq!(did::submit_operation(store.as_ref(), &did, update, None).await.unwrap().version_id, 2);
+    let pending = store.list_outbox_entries_for_did(&did, OutboxStatus::Pending).await.unwrap();
+    assert_eq!(pending.iter().map(|entry| entry.version_id).collect::<Vec<_>>(), [Some(1), Some(2)]);
+    store.mark_outbox_delivered(pending[1].id, "0xabc").await.unwrap();
+    let error = did::resolve_record(store.as_ref(), &did).await.unwrap_err();
+    assert!(error.to_string().contains("not anchored"), "{}", error);
+
+    // 锚定已投递：链上哈希和版本一致时通过
+    let document = did::create_did(store.as_ref(), &utils::generate_keypair(), None).await.unwrap();
+    let did = document.id.clone();
+    for entry in store.list_outbox_entries_for_did(&did, OutboxStatus::Pending).await.unwrap() {
+        store.mark_outbox_delivered(entry.id, "0xabc").await.unwrap();
+    }
+    let error = did::resolve_record(store.as_ref(), &did).await.unwrap_err();
+    assert!(error.to_string().contains("not anchored"), "{}", error);
+
+    let hash = did::document_hash(&document).unwrap();
+    anchors.lock().unwrap().insert(did.clone(), Anchor { hash: hash.clone(), version_id: 1 });
+    assert!(did::resolve_record(store.as_ref(), &did).await.is_ok());
+
+    // 哈希不一致
+    anchors.lock().unwrap().insert(did.clone(), Anchor { hash: "00".repeat(32), version_id: 1 });
+    let error = did::resolve_record(store.as_ref(), &did).await.unwrap_err();
+    assert!(error.to_string().contains("does not match anchored hash"), "{}", error);
+
+    // 本地版本落后于链上版本
+    anchors.lock().unwrap().insert(did.clone(), Anchor { hash: hash.clone(), version_id: 3 });
+    let error = did::resolve_record(store.as_ref(), &did).await.unwrap_err();
+    assert!(error.to_string().contains("older than anchored version"), "{}", error);
+
+    // 对账修复产生新版本，内容仍与锚定的哈希一致
+    anchors.lock().unwrap().insert(did.clone(), Anchor { hash, version_id: 1 });
+    store.set_did_active(&did, false).await.unwrap();
+    store.set_did_active(&did, true).await.unwrap();
+    assert_eq!(did::resolve_record(store.as_ref(), &did).await.unwrap().version_id, 3);
+    let mut repaired = document.clone();
+    repaired.updated += 1;
+    store.overwrite_did_document(&did, &repaired, true).await.unwrap();
+    let error = did::resolve_record(store.as_ref(), &did).await.unwrap_err();
+    assert!(error.to_string().contains("not anchored"), "{}", error);
+}
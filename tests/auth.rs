@@ -0,0 +1,228 @@
+//! 认证测试：API密钥和令牌解析为带权限范围的主体，受保护接口按权限范围放行；
+//! DID Auth登录得到的会话令牌只能操作自己的DID
+
+use std::sync::Arc;
+use axum::body::{to_bytes, Body};
+use axum::http::{header, Request, StatusCode};
+use axum::Router;
+use did_system::api;
+use did_system::auth::{self, token, Authenticator, Role, Scope};
+use did_system::config::Config;
+use did_system::db::{MemoryStore, SharedStore};
+use did_system::blockchain::{self, LedgersConfig};
+use did_system::did;
+use did_system::oplog::{DidOperation, SignedOperation};
+use did_system::utils;
+use ed25519_dalek::{Signer, SigningKey};
+use tower::ServiceExt;
+
+fn auth_config() -> Config {
+    let mut config = Config::default();
+    config.auth.enabled = true;
+    config
+}
+
+/// 写入临时签发者私钥文件并启用令牌，`name`区分并行运行的测试
+fn token_config(name: &str) -> Config {
+    let path = std::env::temp_dir().join(format!("did-system-issuer-{}-{}.key", name, std::process::id()));
+    std::fs::write(&path, utils::encode_base58(&utils::generate_keypair().to_bytes())).unwrap();
+
+    let mut config = auth_config();
+    config.auth.issuer_key_file = Some(path);
+    config
+}
+
+async fn send(router: &Router, method: &str, uri: &str, credential: Option<&str>) -> axum::response::Response {
+    send_json(router, method, uri, credential, None).await
+}
+
+async fn send_json(
+    router: &Router,
+    method: &str,
+    uri: &str,
+    credential: Option<&str>,
+    body: Option<serde_json::Value>,
+) -> axum::response::Response {
+    let mut request = Request::builder().method(method).uri(uri);
+    if let Some(credential) = credential {
+        request = request.header(header::AUTHORIZATION, format!("Bearer {}", credential));
+    }
+    let body = match body {
+        Some(body) => {
+            request = request.header(header::CONTENT_TYPE, "application/json");
+            Body::from(body.to_string())
+        }
+        None => Body::empty(),
+    };
+    router.clone().oneshot(request.body(body).unwrap()).await.unwrap()
+}
+
+#[tokio::test]
+async fn anonymous_requests_only_reach_public_routes() {
+    let store: SharedStore = Arc::new(MemoryStore::new());
+    let router = api::create_router(store, &auth_config()).unwrap();
+
+    let response = send(&router, "GET", "/v2/dids", None).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert!(response.headers()[header::WWW_AUTHENTICATE].to_str().unwrap().starts_with("Bearer"));
+
+    let response = send(&router, "GET", "/v2/dids/did:web:missing", None).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // 匿名请求在解析请求体之前被拒绝
+    let response = send(&router, "POST", "/v1/did", None).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = send(&router, "GET", "/v2/dids", Some("didk_0000000000000000_unknown")).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn api_key_scopes_are_enforced() {
+    let store: SharedStore = Arc::new(MemoryStore::new());
+    let router = api::create_router(store.clone(), &auth_config()).unwrap();
+    let (reader, reader_key) = auth::create_api_key(store.as_ref(), "reader", Some(Role::Reader), vec![], None, None).await.unwrap();
+    let (_, admin_key) = auth::create_api_key(store.as_ref(), "ops", None, vec![Scope::DidAdmin], None, None).await.unwrap();
+
+    assert_eq!(send(&router, "GET", "/v2/dids", Some(&reader_key)).await.status(), StatusCode::OK);
+    assert_eq!(send(&router, "GET", "/admin/cache", Some(&reader_key)).await.status(), StatusCode::FORBIDDEN);
+    let create = serde_json::json!({ "signing_key": utils::encode_base58(&utils::generate_keypair().to_bytes()) });
+    let response = send_json(&router, "POST", "/v1/did", Some(&reader_key), Some(create)).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(send(&router, "GET", "/admin/outbox", Some(&admin_key)).await.status(), StatusCode::OK);
+
+    // 篡改密钥部分
+    let tampered = format!("{}x", reader_key);
+    assert_eq!(send(&router, "GET", "/v2/dids", Some(&tampered)).await.status(), StatusCode::UNAUTHORIZED);
+
+    assert!(store.revoke_api_key(&reader.id, utils::current_timestamp()).await.unwrap());
+    assert_eq!(send(&router, "GET", "/v2/dids", Some(&reader_key)).await.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn tokens_signed_by_the_issuer_are_accepted() {
+    let config = token_config("tokens");
+    let store: SharedStore = Arc::new(MemoryStore::new());
+    let router = api::create_router(store, &config).unwrap();
+    let authenticator = Authenticator::from_config(&config.auth).unwrap();
+
+    let admin = authenticator.issue_token("operator", Some(Role::Admin), &[], None).unwrap();
+    assert_eq!(send(&router, "GET", "/admin/outbox", Some(&admin)).await.status(), StatusCode::OK);
+
+    let reader = authenticator.issue_token("dashboard", None, &[Scope::DidRead], None).unwrap();
+    assert_eq!(send(&router, "GET", "/v1/dids", Some(&reader)).await.status(), StatusCode::OK);
+    assert_eq!(send(&router, "GET", "/admin/cache", Some(&reader)).await.status(), StatusCode::FORBIDDEN);
+
+    // 其他私钥签名的令牌
+    let claims = token::Claims {
+        iss: config.auth.token_issuer.clone(),
+        sub: "intruder".to_string(),
+        iat: utils::current_timestamp(),
+        exp: utils::current_timestamp() + 60,
+        role: Some(Role::Admin),
+        scope: String::new(),
+        did: None,
+    };
+    let forged = token::sign(&claims, &utils::generate_keypair()).unwrap();
+    assert_eq!(send(&router, "GET", "/admin/cache", Some(&forged)).await.status(), StatusCode::UNAUTHORIZED);
+
+    let expired = authenticator.issue_token("operator", Some(Role::Admin), &[], Some(0)).unwrap();
+    assert_eq!(send(&router, "GET", "/admin/cache", Some(&expired)).await.status(), StatusCode::UNAUTHORIZED);
+
+    std::fs::remove_file(config.auth.issuer_key_file.unwrap()).unwrap();
+}
+
+async fn json(response: axum::response::Response) -> serde_json::Value {
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    serde_json::from_slice(&body).unwrap()
+}
+
+/// 以`key`签名的停用操作
+fn deactivate(did: &str, key: &SigningKey) -> serde_json::Value {
+    let operation = SignedOperation::sign(did, DidOperation::Deactivate, None, &format!("{}#keys-1", did), key).unwrap();
+    serde_json::to_value(operation).unwrap()
+}
+
+#[tokio::test]
+async fn did_auth_sessions_only_act_on_their_own_did() {
+    let config = token_config("did-auth");
+    let store: SharedStore = Arc::new(MemoryStore::new());
+    let router = api::create_router(store.clone(), &config).unwrap();
+    // 创建DID需要账本路由，操作只进入出站队列，不访问节点
+    let _ = blockchain::init(LedgersConfig::default()).await;
+    let (key, other_key) = (utils::generate_keypair(), utils::generate_keypair());
+    let document = did::create_did(store.as_ref(), &key, None).await.unwrap();
+    let other = did::create_did(store.as_ref(), &other_key, None).await.unwrap();
+    let method = document.authentication[0].clone();
+
+    let login = |message: &str, key: &SigningKey| serde_json::json!({
+        "did": document.id,
+        "message": message,
+        "verification_method": method,
+        "signature": utils::encode_base58(&key.sign(message.as_bytes()).to_bytes()),
+    });
+    let challenge = |did: &str| send_json(&router, "POST", "/auth/challenge", None, Some(serde_json::json!({ "did": did })));
+    let message = |issued: &serde_json::Value| issued["message"].as_str().unwrap().to_string();
+
+    // 质询消息写明受众、DID和过期时间
+    let issued = json(challenge(&document.id).await).await;
+    let fields: serde_json::Value = serde_json::from_str(&message(&issued)).unwrap();
+    assert_eq!(fields["type"], "did-auth");
+    assert_eq!(fields["audience"], config.auth.token_issuer.as_str());
+    assert_eq!(fields["did"], document.id.as_str());
+    assert_eq!(fields["expires_at"], issued["expires_at"]);
+
+    // 其他密钥的签名无效，且质询随之作废
+    let response = send_json(&router, "POST", "/auth/verify", None, Some(login(&message(&issued), &other_key))).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = send_json(&router, "POST", "/auth/verify", None, Some(login(&message(&issued), &key))).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // 签发给其他DID的质询
+    let issued = json(challenge(&other.id).await).await;
+    let response = send_json(&router, "POST", "/auth/verify", None, Some(login(&message(&issued), &key))).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // 改写受众后重新签名
+    let issued = json(challenge(&document.id).await).await;
+    let mut fields: serde_json::Value = serde_json::from_str(&message(&issued)).unwrap();
+    fields["audience"] = "another-service".into();
+    let response = send_json(&router, "POST", "/auth/verify", None, Some(login(&fields.to_string(), &key))).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let issued = json(challenge(&document.id).await).await;
+    let response = send_json(&router, "POST", "/auth/verify", None, Some(login(&message(&issued), &key))).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let session = json(response).await;
+    let session = session["access_token"].as_str().unwrap();
+
+    let uri = format!("/v2/dids/{}/operations", other.id);
+    let response = send_json(&router, "POST", &uri, Some(session), Some(deactivate(&other.id, &other_key))).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let uri = format!("/v2/dids/{}/operations", document.id);
+    let response = send_json(&router, "POST", &uri, Some(session), Some(deactivate(&document.id, &key))).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(send(&router, "GET", "/v2/dids", Some(session)).await.status(), StatusCode::FORBIDDEN);
+
+    std::fs::remove_file(config.auth.issuer_key_file.unwrap()).unwrap();
+}
+
+#[tokio::test]
+async fn challenges_are_rate_limited_as_writes() {
+    let mut config = token_config("challenge-limit");
+    config.rate_limit.enabled = true;
+    config.rate_limit.write_burst = 1;
+    let store: SharedStore = Arc::new(MemoryStore::new());
+    let router = api::create_router(store.clone(), &config).unwrap();
+    let _ = blockchain::init(LedgersConfig::default()).await;
+    let document = did::create_did(store.as_ref(), &utils::generate_keypair(), None).await.unwrap();
+
+    let body = serde_json::json!({ "did": document.id });
+    let response = send_json(&router, "POST", "/auth/challenge", None, Some(body.clone())).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = send_json(&router, "POST", "/auth/challenge", None, Some(body)).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    std::fs::remove_file(config.auth.issuer_key_file.unwrap()).unwrap();
+}
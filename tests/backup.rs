@@ -0,0 +1,178 @@
+//! 备份导入测试：导入前重放归档中的操作并校验签名，重新计算校验和的篡改归档无法导入；
+//! 覆盖本地DID时归档必须在本地当前状态之后继续，本地之后的签名操作逐条追加到本地日志
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use did_system::backup::{self, ConflictPolicy, Registry};
+use did_system::blockchain::{self, LedgersConfig};
+use did_system::db::{MemoryStore, SharedStore, SqliteStore};
+use did_system::did::{self, DIDDocument, Service};
+use did_system::oplog::{self, DidOperation, SignedOperation};
+use did_system::utils;
+use ed25519_dalek::SigningKey;
+use serde::Serialize;
+
+async fn export(store: &SharedStore) -> Vec<u8> {
+    let mut archive = Vec::new();
+    backup::export(store.as_ref(), &mut archive).await.unwrap();
+    archive
+}
+
+async fn import(store: &SharedStore, archive: &[u8], policy: ConflictPolicy) -> Result<backup::ImportReport, did_system::types::Error> {
+    backup::import(store.as_ref(), &mut &archive[..], policy).await
+}
+
+/// 以当前版本为基础提交一次签名的更新，文档中增加一个服务端点
+async fn update(store: &SharedStore, key: &SigningKey, did: &str, service: &str) -> DIDDocument {
+    let record = store.get_did_record(did).await.unwrap().unwrap();
+    let mut document = record.document.clone();
+    document.services.push(Service {
+        id: format!("{}#{}", did, service),
+        type_: "LinkedDomains".to_string(),
+        endpoint: format!("https://{}.example.com", service),
+    });
+    document.updated = record.document.updated + 1;
+    let update = SignedOperation::sign(
+        did,
+        DidOperation::Update { document: document.clone() },
+        Some(record.version_id),
+        &format!("{}#keys-1", did),
+        key,
+    ).unwrap();
+    did::submit_operation(store.as_ref(), did, update, None).await.unwrap();
+    document
+}
+
+/// 修改归档内容后重新链接哈希链并重新计算校验和，模拟知道归档格式的篡改者
+fn rewrite(archive: &[u8], edit: impl FnOnce(&mut Registry)) -> Vec<u8> {
+    fn line(kind: &str, value: impl Serialize) -> String {
+        let mut value = serde_json::to_value(value).unwrap();
+        value["kind"] = kind.into();
+        format!("{}\n", value)
+    }
+
+    let (mut manifest, mut registry) = backup::read_archive(&mut &archive[..]).unwrap();
+    edit(&mut registry);
+
+    let mut prev_hash = None;
+    let mut did_heads = HashMap::new();
+    for entry in &mut registry.operations {
+        entry.prev_hash = prev_hash.clone();
+        entry.prev_did_hash = did_heads.get(&entry.operation.did).cloned();
+        entry.entry_hash = entry.compute_hash().unwrap();
+        prev_hash = Some(entry.entry_hash.clone());
+        did_heads.insert(entry.operation.did.clone(), entry.entry_hash.clone());
+    }
+
+    let mut body = String::new();
+    registry.dids.iter().for_each(|record| body.push_str(&line("did", record)));
+    registry.versions.iter().for_each(|version| body.push_str(&line("version", version)));
+    registry.operations.iter().for_each(|entry| body.push_str(&line("operation", entry)));
+    manifest.sha256 = utils::to_hex(&utils::sha256(body.as_bytes()));
+    format!("{}{}", line("manifest", &manifest), body).into_bytes()
+}
+
+#[tokio::test]
+async fn forged_operations_are_rejected() {
+    let _ = blockchain::init(LedgersConfig::default()).await;
+    let source: SharedStore = Arc::new(MemoryStore::new());
+    let key = utils::generate_keypair();
+    let document = did::create_did(source.as_ref(), &key, None).await.unwrap();
+    update(&source, &key, &document.id, "home").await;
+    let archive = export(&source).await;
+
+    // 未修改的归档可以恢复到空的注册表
+    let target: SharedStore = Arc::new(MemoryStore::new());
+    let report = import(&target, &rewrite(&archive, |_| {}), ConflictPolicy::Fail).await.unwrap();
+    assert_eq!((report.imported, report.operations_restored), (1, 2));
+
+    // 替换更新操作中的文档，并让DID记录和历史版本与之一致
+    let forged = rewrite(&archive, |registry| {
+        let mut forged = registry.dids[0].document.clone();
+        forged.services[0].endpoint = "https://evil.example.com".to_string();
+        let hash = did::document_hash(&forged).unwrap();
+        let entry = registry.operations.iter_mut().find(|entry| entry.operation.version_id == 2).unwrap();
+        entry.operation.payload = Some(DidOperation::Update { document: forged.clone() });
+        entry.operation.document_hash = hash;
+        registry.versions.iter_mut().find(|version| version.version_id == 2).unwrap().document = forged.clone();
+        registry.dids[0].document = forged;
+    });
+    let target: SharedStore = Arc::new(MemoryStore::new());
+    let error = import(&target, &forged, ConflictPolicy::Fail).await.unwrap_err();
+    assert!(error.to_string().contains("invalid signature"), "{}", error);
+    assert!(target.get_did_record(&document.id).await.unwrap().is_none());
+
+    // DID记录与日志不一致
+    let forged = rewrite(&archive, |registry| registry.dids[0].is_active = false);
+    let error = import(&target, &forged, ConflictPolicy::Fail).await.unwrap_err();
+    assert!(error.to_string().contains("does not match its operation log"), "{}", error);
+}
+
+#[tokio::test]
+async fn overwrite_requires_history_extending_the_local_head() {
+    let _ = blockchain::init(LedgersConfig::default()).await;
+    let source: SharedStore = Arc::new(MemoryStore::new());
+    let key = utils::generate_keypair();
+    let document = did::create_did(source.as_ref(), &key, None).await.unwrap();
+    let first = export(&source).await;
+    update(&source, &key, &document.id, "home").await;
+    let second = export(&source).await;
+
+    // 归档版本不高于本地版本
+    let target: SharedStore = Arc::new(MemoryStore::new());
+    import(&target, &second, ConflictPolicy::Fail).await.unwrap();
+    let error = import(&target, &first, ConflictPolicy::Overwrite).await.unwrap_err();
+    assert!(error.to_string().contains("does not advance"), "{}", error);
+    assert_eq!(target.get_did_record(&document.id).await.unwrap().unwrap().version_id, 2);
+
+    // 本地在同一版本上有不同的更新
+    let target: SharedStore = Arc::new(MemoryStore::new());
+    import(&target, &first, ConflictPolicy::Fail).await.unwrap();
+    let local = update(&target, &key, &document.id, "local").await;
+    update(&source, &key, &document.id, "office").await;
+    let error = import(&target, &export(&source).await, ConflictPolicy::Overwrite).await.unwrap_err();
+    assert!(error.to_string().contains("does not contain local version"), "{}", error);
+    let record = target.get_did_record(&document.id).await.unwrap().unwrap();
+    assert_eq!(did::document_hash(&record.document).unwrap(), did::document_hash(&local).unwrap());
+}
+
+async fn check_merge(target: SharedStore) {
+    let _ = blockchain::init(LedgersConfig::default()).await;
+    let source: SharedStore = Arc::new(MemoryStore::new());
+    let key = utils::generate_keypair();
+    let document = did::create_did(source.as_ref(), &key, None).await.unwrap();
+    import(&target, &export(&source).await, ConflictPolicy::Fail).await.unwrap();
+
+    let updated = update(&source, &key, &document.id, "home").await;
+    let other = did::create_did(source.as_ref(), &utils::generate_keypair(), None).await.unwrap();
+    let report = import(&target, &export(&source).await, ConflictPolicy::Overwrite).await.unwrap();
+    assert_eq!((report.imported, report.overwritten, report.operations_restored), (1, 1, 0));
+    assert_eq!(report.versions, 2);
+
+    let record = target.get_did_record(&document.id).await.unwrap().unwrap();
+    assert_eq!(record.version_id, 2);
+    assert_eq!(did::document_hash(&record.document).unwrap(), did::document_hash(&updated).unwrap());
+    assert!(target.get_did_record(&other.id).await.unwrap().is_some());
+
+    // 追加的是归档中的签名操作，本地日志仍可重放
+    let entries = target.list_operation_log(0, 10).await.unwrap();
+    assert_eq!(entries.len(), 3);
+    assert!(entries.iter().all(|entry| entry.operation.signature.is_some()));
+    let report = oplog::verify_chain(target.as_ref()).await.unwrap();
+    assert!(report.intact, "{:?}", report);
+    let report = oplog::replay::rebuild(target.as_ref(), &Default::default()).await.unwrap();
+    assert!(report.failures.is_empty() && report.restored.is_empty(), "{:?}", report);
+}
+
+#[tokio::test]
+async fn memory_store_merges_signed_operations() {
+    check_merge(Arc::new(MemoryStore::new())).await;
+}
+
+#[tokio::test]
+async fn sqlite_store_merges_signed_operations() {
+    let path = std::env::temp_dir().join(format!("did-system-backup-{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    check_merge(Arc::new(SqliteStore::open(path.to_str().unwrap(), None).unwrap())).await;
+    std::fs::remove_file(path).unwrap();
+}
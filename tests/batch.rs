@@ -0,0 +1,268 @@
+//! 批量操作测试：逐个写入时每个操作单独成功或失败，原子模式下一个操作失败时都不写入；
+//! 批量锚定模式账本上的操作封装为一个锚定批次；逐行输出每个操作的结果和汇总，并可按幂等键重放，
+//! 汇总带有错误的逐行输出不保存；每个操作计入每日写操作配额，未写入的操作退还配额
+
+use std::sync::Arc;
+use axum::body::{to_bytes, Body};
+use axum::http::{header, Request, StatusCode};
+use axum::Router;
+use did_system::api;
+use did_system::blockchain::{self, BlockchainConfig, LedgerMode, LedgersConfig};
+use did_system::config::Config;
+use did_system::db::{MemoryStore, SharedStore, SqliteStore};
+use did_system::did::{self, DIDDocument, PublicKeyInfo};
+use did_system::oplog::{DidOperation, SignedOperation};
+use did_system::ratelimit;
+use did_system::utils;
+use ed25519_dalek::SigningKey;
+use tower::ServiceExt;
+
+/// 所有DID都由批量锚定模式的账本锚定；创建和停用只进入出站队列，不访问节点
+async fn router(store: &SharedStore) -> Router {
+    let _ = blockchain::init(LedgersConfig {
+        ledgers: vec![BlockchainConfig { mode: LedgerMode::Batch, ..Default::default() }],
+        ..Default::default()
+    }).await;
+    api::create_router(store.clone(), &Config::default()).unwrap()
+}
+
+async fn send(router: &Router, accept: &str, key: Option<&str>, body: serde_json::Value) -> (StatusCode, axum::http::HeaderMap, String) {
+    let mut request = Request::builder()
+        .method("POST")
+        .uri("/v2/batch")
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::ACCEPT, accept);
+    if let Some(key) = key {
+        request = request.header("idempotency-key", key);
+    }
+    let response = router.clone().oneshot(request.body(Body::from(body.to_string())).unwrap()).await.unwrap();
+    let (parts, body) = response.into_parts();
+    let body = to_bytes(body, usize::MAX).await.unwrap();
+    (parts.status, parts.headers, String::from_utf8(body.to_vec()).unwrap())
+}
+
+/// 新密钥和以其签名的创建操作
+fn create() -> (String, SigningKey, serde_json::Value) {
+    let key = utils::generate_keypair();
+    let public_key = key.verifying_key().to_bytes();
+    let did = did::did_for_key(&public_key, None).unwrap();
+    let key_id = format!("{}#keys-1", did);
+    let now = utils::current_timestamp();
+    let document = DIDDocument {
+        id: did.clone(),
+        public_keys: vec![PublicKeyInfo {
+            id: key_id.clone(),
+            type_: "Ed25519VerificationKey2020".to_string(),
+            controller: did.clone(),
+            public_key_base58: utils::encode_base58(&public_key),
+        }],
+        authentication: vec![key_id.clone()],
+        services: Vec::new(),
+        created: now,
+        updated: now,
+    };
+    let operation = SignedOperation::sign(&did, DidOperation::Create { document }, None, &key_id, &key).unwrap();
+    let item = item(&did, operation);
+    (did, key, item)
+}
+
+fn deactivate(did: &str, key: &SigningKey, previous_version_id: Option<u64>) -> serde_json::Value {
+    let operation = SignedOperation::sign(did, DidOperation::Deactivate, previous_version_id, &format!("{}#keys-1", did), key).unwrap();
+    item(did, operation)
+}
+
+fn item(did: &str, operation: SignedOperation) -> serde_json::Value {
+    let mut item = serde_json::to_value(operation).unwrap();
+    item["did"] = did.into();
+    item
+}
+
+#[tokio::test]
+async fn operations_are_applied_individually_and_anchored_in_one_batch() {
+    let store: SharedStore = Arc::new(MemoryStore::new());
+    let router = router(&store).await;
+    let (first, first_key, create_first) = create();
+    let (second, _, create_second) = create();
+    // 用其他DID的密钥签名
+    let mut forged = create().2;
+    forged["did"] = create().0.into();
+
+    let body = serde_json::json!({ "operations": [
+        create_first,
+        forged,
+        create_second,
+        deactivate(&first, &first_key, Some(1)),
+    ] });
+    let (status, _, body) = send(&router, "application/json", None, body).await;
+    assert_eq!(status, StatusCode::OK);
+    let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+
+    let statuses: Vec<_> = body["items"].as_array().unwrap().iter().map(|item| item["status"].clone()).collect();
+    assert_eq!(statuses, ["applied", "failed", "applied", "applied"]);
+    assert!(body["items"][1]["error"]["code"].is_string());
+    assert_eq!(body["items"][3]["result"]["didDocumentMetadata"]["deactivated"], true);
+    assert_eq!(body["summary"]["applied"], 3);
+    assert_eq!(body["summary"]["failed"], 1);
+
+    // 三个操作封装在同一个锚定批次中
+    let batches = body["summary"]["anchorBatches"].as_array().unwrap();
+    assert_eq!(batches.len(), 1);
+    let proofs = [
+        store.get_anchor_proof(&first, 1).await.unwrap().unwrap(),
+        store.get_anchor_proof(&first, 2).await.unwrap().unwrap(),
+        store.get_anchor_proof(&second, 1).await.unwrap().unwrap(),
+    ];
+    assert!(proofs.iter().all(|proof| proof.batch_id == batches[0].as_i64().unwrap()));
+    assert!(!store.get_did_record(&first).await.unwrap().unwrap().is_active);
+}
+
+#[tokio::test]
+async fn atomic_batches_are_all_or_nothing() {
+    let path = std::env::temp_dir().join(format!("did-system-batch-{}.db", std::process::id()));
+    let store: SharedStore = Arc::new(SqliteStore::open(path.to_str().unwrap(), None).unwrap());
+    let router = router(&store).await;
+    let (first, first_key, create_first) = create();
+    let (second, second_key, create_second) = create();
+
+    // 第二个DID尚不存在，停用失败，第一个DID也不写入
+    let body = serde_json::json!({ "atomic": true, "operations": [
+        create_first.clone(),
+        deactivate(&second, &second_key, Some(1)),
+    ] });
+    let (status, _, body) = send(&router, "application/json", None, body).await;
+    assert_eq!(status, StatusCode::OK);
+    let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(body["items"][0]["status"], "aborted");
+    assert_eq!(body["items"][1]["status"], "failed");
+    assert_eq!(body["items"][1]["error"]["code"], "not_found");
+    assert_eq!((body["summary"]["aborted"].as_u64(), body["summary"]["failed"].as_u64()), (Some(1), Some(1)));
+    assert!(body["summary"]["anchorBatches"].as_array().unwrap().is_empty());
+    assert!(store.get_did_record(&first).await.unwrap().is_none());
+
+    // 后续操作基于同一批次中之前的操作
+    let body = serde_json::json!({ "atomic": true, "operations": [
+        create_first,
+        create_second,
+        deactivate(&first, &first_key, Some(1)),
+    ] });
+    let (_, _, body) = send(&router, "application/json", None, body).await;
+    let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(body["summary"]["applied"], 3);
+    assert_eq!(body["summary"]["anchorBatches"].as_array().unwrap().len(), 1);
+    assert!(!store.get_did_record(&first).await.unwrap().unwrap().is_active);
+    assert!(store.get_did_record(&second).await.unwrap().unwrap().is_active);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn results_are_streamed_as_json_lines() {
+    let store: SharedStore = Arc::new(MemoryStore::new());
+    let router = router(&store).await;
+    let operations: Vec<_> = (0..5).map(|_| create().2).collect();
+    let body = serde_json::json!({ "operations": operations });
+
+    let (status, headers, lines) = send(&router, "application/x-ndjson", Some("batch-1"), body.clone()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers[header::CONTENT_TYPE], "application/x-ndjson");
+    let events: Vec<serde_json::Value> = lines.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    assert_eq!(events.len(), 6);
+    for (index, event) in events[..5].iter().enumerate() {
+        assert_eq!(event["type"], "item");
+        assert_eq!(event["index"], index);
+        assert_eq!(event["status"], "applied");
+    }
+    assert_eq!(events[5]["type"], "summary");
+    assert_eq!(events[5]["applied"], 5);
+
+    // 流式响应同样按幂等键重放
+    let (status, headers, replayed) = send(&router, "application/x-ndjson", Some("batch-1"), body).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers["idempotency-replayed"], "true");
+    assert_eq!(replayed, lines);
+    assert_eq!(store.list_did_records().await.unwrap().len(), 5);
+}
+
+#[tokio::test]
+async fn failed_streamed_batches_release_the_idempotency_key() {
+    let path = std::env::temp_dir().join(format!("did-system-batch-stream-{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let store: SharedStore = Arc::new(SqliteStore::open(path.to_str().unwrap(), None).unwrap());
+    let router = router(&store).await;
+    let body = serde_json::json!({ "atomic": true, "operations": [create().2, create().2] });
+
+    // 校验通过后事务写入失败，汇总行带有错误
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    conn.execute_batch("CREATE TRIGGER fail_writes BEFORE INSERT ON did_documents BEGIN SELECT RAISE(ABORT, 'disk full'); END;").unwrap();
+    let (status, _, lines) = send(&router, "application/x-ndjson", Some("batch-2"), body.clone()).await;
+    assert_eq!(status, StatusCode::OK);
+    let summary: serde_json::Value = serde_json::from_str(lines.lines().last().unwrap()).unwrap();
+    assert_eq!(summary["type"], "summary");
+    assert_eq!(summary["aborted"], 2);
+    assert!(summary["error"].is_object(), "{}", summary);
+
+    // 同一个键的重试重新执行批次，而不是重放失败的输出
+    conn.execute_batch("DROP TRIGGER fail_writes;").unwrap();
+    let (status, headers, lines) = send(&router, "application/x-ndjson", Some("batch-2"), body).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(headers.get("idempotency-replayed").is_none());
+    let summary: serde_json::Value = serde_json::from_str(lines.lines().last().unwrap()).unwrap();
+    assert_eq!(summary["applied"], 2);
+    assert_eq!(store.list_did_records().await.unwrap().len(), 2);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn operations_count_against_the_daily_write_quota() {
+    let store: SharedStore = Arc::new(MemoryStore::new());
+    let _ = router(&store).await;
+    let mut config = Config::default();
+    config.rate_limit.daily_write_quota = 6;
+    let router = api::create_router(store.clone(), &config).unwrap();
+    let day = ratelimit::day_of(utils::current_timestamp());
+
+    let created: Vec<_> = (0..3).map(|_| create().2).collect();
+    let (status, _, _) = send(&router, "application/json", None, serde_json::json!({ "operations": created })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(store.get_write_usage("ip:unknown", day).await.unwrap(), 3);
+
+    // 剩余配额不足以执行整个批次
+    let operations: Vec<_> = (0..4).map(|_| create().2).collect();
+    let (status, headers, body) = send(&router, "application/json", None, serde_json::json!({ "operations": operations })).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert!(headers.contains_key(header::RETRY_AFTER));
+    assert!(body.contains("quota_exceeded"), "{}", body);
+    assert_eq!(store.get_write_usage("ip:unknown", day).await.unwrap(), 3);
+    assert_eq!(store.list_did_records().await.unwrap().len(), 3);
+
+    // 失败的操作退还配额，逐行输出同样按操作计入
+    let body = serde_json::json!({ "operations": [created[0].clone(), create().2] });
+    let (status, _, response) = send(&router, "application/json", None, body).await;
+    assert_eq!(status, StatusCode::OK);
+    let response: serde_json::Value = serde_json::from_str(&response).unwrap();
+    assert_eq!((response["summary"]["applied"].as_u64(), response["summary"]["failed"].as_u64()), (Some(1), Some(1)));
+    assert_eq!(store.get_write_usage("ip:unknown", day).await.unwrap(), 4);
+    let body = serde_json::json!({ "operations": [created[1].clone(), create().2] });
+    let (status, _, _) = send(&router, "application/x-ndjson", None, body).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(store.get_write_usage("ip:unknown", day).await.unwrap(), 5);
+    assert_eq!(store.list_did_records().await.unwrap().len(), 5);
+}
+
+#[tokio::test]
+async fn invalid_batches_are_rejected() {
+    let store: SharedStore = Arc::new(MemoryStore::new());
+    let router = router(&store).await;
+
+    let (status, _, body) = send(&router, "application/json", None, serde_json::json!({ "operations": [] })).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(body.contains("operations"));
+
+    let mut config = Config::default();
+    config.api.require_preconditions = true;
+    let strict = api::create_router(store.clone(), &config).unwrap();
+    let (did, key, create) = create();
+    let body = serde_json::json!({ "operations": [create, deactivate(&did, &key, None)] });
+    let (status, _, _) = send(&strict, "application/json", None, body).await;
+    assert_eq!(status, StatusCode::PRECONDITION_REQUIRED);
+    assert!(store.get_did_record(&did).await.unwrap().is_none());
+}
@@ -0,0 +1,40 @@
+//! 解析缓存测试：同一个DID的不同DID URL共用一个缓存条目，本地变更后条目失效
+
+use std::sync::Arc;
+use did_system::blockchain::{self, LedgersConfig};
+use did_system::db::{MemoryStore, SharedStore};
+use did_system::did::{self, cache::{CacheConfig, ResolverCache}};
+use did_system::utils;
+
+#[tokio::test]
+async fn did_urls_share_one_entry() {
+    let _ = blockchain::init(LedgersConfig::default()).await;
+    let store: SharedStore = Arc::new(MemoryStore::new());
+    let cache: &'static ResolverCache = Box::leak(Box::new(ResolverCache::new(CacheConfig::default())));
+    let document = did::create_did(store.as_ref(), &utils::generate_keypair(), None).await.unwrap();
+
+    let urls = [
+        document.id.clone(),
+        format!("{}?versionId=1", document.id),
+        format!("{}?nonce={}", document.id, utils::current_timestamp()),
+        format!("{}#keys-1", document.id),
+        format!("{}/path", document.id),
+    ];
+    for url in &urls {
+        assert_eq!(cache.resolve(&store, url).await.unwrap().did, document.id);
+    }
+    let stats = cache.stats();
+    assert_eq!((stats.entries, stats.misses, stats.hits), (1, 1, 4));
+
+    // 查询参数不同的未知DID同样只占一个否定缓存条目
+    let missing = format!("{}x", document.id);
+    for nonce in 0..3 {
+        assert!(cache.resolve(&store, &format!("{}?nonce={}", missing, nonce)).await.is_err());
+    }
+    let stats = cache.stats();
+    assert_eq!((stats.entries, stats.negative_hits), (2, 2));
+
+    cache.invalidate(&document.id);
+    let stats = cache.stats();
+    assert_eq!((stats.entries, stats.invalidations), (1, 1));
+}
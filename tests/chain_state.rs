@@ -0,0 +1,76 @@
+//! 链上状态测试：修改DID只依据本地状态，不查询链上状态；批量锚定的DID在链上没有状态记录，
+//! 出站队列尚未投递或节点不可用时仍可修改，本地停用的DID不能再修改
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use did_system::blockchain::{self, BlockchainConfig, LedgerMode, LedgersConfig};
+use did_system::db::{MemoryStore, SharedStore};
+use did_system::did::{self, Service};
+use did_system::oplog::{DidOperation, SignedOperation};
+use did_system::utils;
+
+/// 与合约一致的模拟节点：`anchorBatch`不登记DID，状态查询总是返回未激活；下线时所有请求返回503
+async fn start_node() -> (String, Arc<AtomicBool>) {
+    async fn status(State(online): State<Arc<AtomicBool>>) -> Response {
+        if online.load(Ordering::SeqCst) {
+            Json(serde_json::json!({ "active": false })).into_response()
+        } else {
+            StatusCode::SERVICE_UNAVAILABLE.into_response()
+        }
+    }
+
+    let online = Arc::new(AtomicBool::new(true));
+    let app = Router::new()
+        .route("/did/:did/status", get(status))
+        .with_state(online.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (format!("http://{}", address), online)
+}
+
+#[tokio::test]
+async fn batch_anchored_dids_can_be_modified() {
+    let (node_url, online) = start_node().await;
+    blockchain::init(LedgersConfig {
+        ledgers: vec![BlockchainConfig { node_url, mode: LedgerMode::Batch, ..Default::default() }],
+        ..Default::default()
+    }).await.unwrap();
+    let store: SharedStore = Arc::new(MemoryStore::new());
+    let key = utils::generate_keypair();
+    let document = did::create_did(store.as_ref(), &key, None).await.unwrap();
+    let did = document.id.clone();
+    let key_id = format!("{}#keys-1", did);
+    assert!(!blockchain::verify_did(&did).await.unwrap());
+
+    // 创建操作仍在出站队列中，链上状态为未激活
+    let mut updated = document.clone();
+    updated.updated = document.updated + 1;
+    let update = SignedOperation::sign(&did, DidOperation::Update { document: updated.clone() }, Some(1), &key_id, &key).unwrap();
+    assert_eq!(did::submit_operation(store.as_ref(), &did, update, None).await.unwrap().version_id, 2);
+
+    // 节点不可用
+    online.store(false, Ordering::SeqCst);
+    let service = Service {
+        id: format!("{}#home", did),
+        type_: "LinkedDomains".to_string(),
+        endpoint: "https://home.example.com".to_string(),
+    };
+    let add = DidOperation::AddService { service, updated: updated.updated + 1 };
+    let add = SignedOperation::sign(&did, add, Some(2), &key_id, &key).unwrap();
+    assert_eq!(did::submit_operation(store.as_ref(), &did, add, None).await.unwrap().version_id, 3);
+    assert_eq!(did::resolve_record(store.as_ref(), &did).await.unwrap().document.services.len(), 1);
+
+    // 本地停用后不能再修改
+    let deactivate = SignedOperation::sign(&did, DidOperation::Deactivate, Some(3), &key_id, &key).unwrap();
+    did::submit_operation(store.as_ref(), &did, deactivate, None).await.unwrap();
+    let remove = DidOperation::RemoveService { service_id: format!("{}#home", did), updated: updated.updated + 2 };
+    let remove = SignedOperation::sign(&did, remove, Some(4), &key_id, &key).unwrap();
+    let error = did::submit_operation(store.as_ref(), &did, remove, None).await.unwrap_err();
+    assert!(error.to_string().contains("deactivated"), "{}", error);
+}
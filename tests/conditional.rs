@@ -0,0 +1,239 @@
+//! 条件请求测试：解析结果带有版本号ETag，If-None-Match命中时返回304；
+//! 修改DID时If-Match或签名操作中的previousVersionId与当前版本不一致返回412，存储层拒绝基于旧版本的写入；
+//! 对账修复产生新版本，修复前的ETag不再匹配，历史版本保留修复前的内容
+
+use std::sync::Arc;
+use axum::body::{to_bytes, Body};
+use axum::http::{header, Request, StatusCode};
+use axum::Router;
+use did_system::api;
+use did_system::blockchain::{self, LedgersConfig};
+use did_system::config::Config;
+use did_system::db::{MemoryStore, SharedStore, SqliteStore};
+use did_system::did::{self, DIDDocument, Service};
+use did_system::oplog::replay::{self, RebuildOptions};
+use did_system::oplog::{DidOperation, SignedOperation};
+use did_system::outbox::PendingOperation;
+use did_system::types::Error;
+use did_system::utils;
+use ed25519_dalek::SigningKey;
+use tower::ServiceExt;
+
+async fn send(
+    router: &Router,
+    method: &str,
+    uri: &str,
+    headers: &[(header::HeaderName, &str)],
+    body: Option<serde_json::Value>,
+) -> axum::response::Response {
+    let mut request = Request::builder().method(method).uri(uri);
+    for (name, value) in headers {
+        request = request.header(name, *value);
+    }
+    let body = match body {
+        Some(body) => {
+            request = request.header(header::CONTENT_TYPE, "application/json");
+            Body::from(body.to_string())
+        }
+        None => Body::empty(),
+    };
+    router.clone().oneshot(request.body(body).unwrap()).await.unwrap()
+}
+
+/// 在存储中创建DID；创建只进入出站队列，不访问节点
+async fn create(store: &SharedStore) -> (DIDDocument, SigningKey) {
+    let _ = blockchain::init(LedgersConfig::default()).await;
+    let key = utils::generate_keypair();
+    (did::create_did(store.as_ref(), &key, None).await.unwrap(), key)
+}
+
+/// 以`key`签名、基于`previous_version_id`的停用操作
+fn deactivate(did: &str, key: &SigningKey, previous_version_id: Option<u64>) -> serde_json::Value {
+    let operation = SignedOperation::sign(did, DidOperation::Deactivate, previous_version_id, &format!("{}#keys-1", did), key).unwrap();
+    serde_json::to_value(operation).unwrap()
+}
+
+#[tokio::test]
+async fn resolve_returns_etag_and_honours_if_none_match() {
+    let store: SharedStore = Arc::new(MemoryStore::new());
+    let router = api::create_router(store.clone(), &Config::default()).unwrap();
+    let (document, _) = create(&store).await;
+
+    for uri in [format!("/v1/did/{}", document.id), format!("/v2/dids/{}", document.id)] {
+        let response = send(&router, "GET", &uri, &[], None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::ETAG], "\"1\"");
+
+        for tag in ["\"1\"", "W/\"1\"", "\"7\", \"1\"", "*"] {
+            let response = send(&router, "GET", &uri, &[(header::IF_NONE_MATCH, tag)], None).await;
+            assert_eq!(response.status(), StatusCode::NOT_MODIFIED, "{} with {}", uri, tag);
+            assert_eq!(response.headers()[header::ETAG], "\"1\"");
+            assert!(to_bytes(response.into_body(), usize::MAX).await.unwrap().is_empty());
+        }
+
+        let response = send(&router, "GET", &uri, &[(header::IF_NONE_MATCH, "\"2\"")], None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    // DID Core表示同样带有ETag
+    let uri = format!("/v2/dids/{}?accept=application/did%2Bjson", document.id);
+    assert_eq!(send(&router, "GET", &uri, &[], None).await.headers()[header::ETAG], "\"1\"");
+}
+
+#[tokio::test]
+async fn stale_versions_are_rejected_with_412() {
+    let store: SharedStore = Arc::new(MemoryStore::new());
+    let router = api::create_router(store.clone(), &Config::default()).unwrap();
+    let (document, key) = create(&store).await;
+    let uri = format!("/v2/dids/{}/operations", document.id);
+
+    let response = send(&router, "POST", &uri, &[], Some(deactivate(&document.id, &key, Some(3)))).await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+    for tag in ["\"3\"", "W/\"1\"", "\"abc\""] {
+        let response = send(&router, "POST", &uri, &[(header::IF_MATCH, tag)], Some(deactivate(&document.id, &key, None))).await;
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED, "If-Match {}", tag);
+    }
+    let response = send(&router, "POST", &uri, &[(header::IF_MATCH, "\"1\", \"2\"")], Some(deactivate(&document.id, &key, None))).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // previousVersionId参与签名，篡改后签名无效
+    let mut tampered = deactivate(&document.id, &key, Some(3));
+    tampered["previousVersionId"] = 1.into();
+    let response = send(&router, "POST", &uri, &[], Some(tampered)).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let v1 = format!("/v1/did/{}", document.id);
+    let body = serde_json::json!({ "signing_key": utils::encode_base58(&key.to_bytes()) });
+    let response = send(&router, "DELETE", &v1, &[(header::IF_MATCH, "\"2\"")], Some(body)).await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+
+    let response = send(&router, "POST", &uri, &[(header::IF_MATCH, "\"1\"")], Some(deactivate(&document.id, &key, Some(1)))).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::ETAG], "\"2\"");
+
+    let resolve = format!("/v2/dids/{}", document.id);
+    let response = send(&router, "GET", &resolve, &[], None).await;
+    assert_eq!(response.status(), StatusCode::GONE);
+    assert_eq!(response.headers()[header::ETAG], "\"2\"");
+
+    // 停用的DID同样支持条件请求
+    let response = send(&router, "GET", &resolve, &[(header::IF_NONE_MATCH, "\"2\"")], None).await;
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(response.headers()[header::ETAG], "\"2\"");
+    let response = send(&router, "GET", &resolve, &[(header::IF_NONE_MATCH, "\"1\"")], None).await;
+    assert_eq!(response.status(), StatusCode::GONE);
+    assert_replays(&store, &document.id).await;
+}
+
+/// 操作日志可以重放，签名（包括`previousVersionId`）有效且结果与本地记录一致
+async fn assert_replays(store: &SharedStore, did: &str) {
+    let options = RebuildOptions { did: Some(did.to_string()), full: true };
+    let report = replay::rebuild(store.as_ref(), &options).await.unwrap();
+    assert!(report.failures.is_empty(), "{:?}", report.failures);
+    assert!(report.restored.is_empty());
+}
+
+#[tokio::test]
+async fn preconditions_can_be_required() {
+    let mut config = Config::default();
+    config.api.require_preconditions = true;
+    let store: SharedStore = Arc::new(MemoryStore::new());
+    let router = api::create_router(store.clone(), &config).unwrap();
+    let (document, key) = create(&store).await;
+    let uri = format!("/v2/dids/{}/operations", document.id);
+
+    let response = send(&router, "POST", &uri, &[], Some(deactivate(&document.id, &key, None))).await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_REQUIRED);
+
+    let v1 = format!("/v1/did/{}", document.id);
+    let update = serde_json::json!({ "signing_key": utils::encode_base58(&key.to_bytes()), "document": document });
+    let response = send(&router, "PUT", &v1, &[], Some(update)).await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_REQUIRED);
+
+    let response = send(&router, "POST", &uri, &[], Some(deactivate(&document.id, &key, Some(1)))).await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+/// 基于旧版本的写入在存储层被拒绝，文档保持不变
+async fn check_store_rejects_stale_writes(store: SharedStore) {
+    let (document, key) = create(&store).await;
+    let did = document.id.clone();
+    let key_id = format!("{}#keys-1", did);
+
+    let mut updated = document.clone();
+    updated.updated += 1;
+    let change = SignedOperation::sign(&did, DidOperation::Update { document: updated.clone() }, Some(1), &key_id, &key).unwrap();
+    let pending = PendingOperation::update(&did, &updated).unwrap();
+    assert_eq!(store.store_did_document(&did, &updated, Some(1), &change, &pending).await.unwrap(), 2);
+
+    // 另一个同样基于版本1的写入
+    let result = store.store_did_document(&did, &updated, Some(1), &change, &pending).await;
+    assert!(matches!(result, Err(Error::PreconditionFailed(_))), "{:?}", result);
+    let deactivate = SignedOperation::sign(&did, DidOperation::Deactivate, Some(1), &key_id, &key).unwrap();
+    let result = store.deactivate_did(&did, Some(1), &deactivate, &PendingOperation::deactivate(&did).unwrap()).await;
+    assert!(matches!(result, Err(Error::PreconditionFailed(_))), "{:?}", result);
+
+    let record = store.get_did_record(&did).await.unwrap().unwrap();
+    assert_eq!(record.version_id, 2);
+    assert!(record.is_active);
+    assert_replays(&store, &did).await;
+}
+
+#[tokio::test]
+async fn memory_store_rejects_stale_writes() {
+    check_store_rejects_stale_writes(Arc::new(MemoryStore::new())).await;
+}
+
+#[tokio::test]
+async fn sqlite_store_rejects_stale_writes() {
+    let path = std::env::temp_dir().join(format!("did-system-conditional-{}.db", std::process::id()));
+    let store = SqliteStore::open(path.to_str().unwrap(), None).unwrap();
+    check_store_rejects_stale_writes(Arc::new(store)).await;
+    std::fs::remove_file(path).unwrap();
+}
+
+/// 以区块链状态修复后ETag改变，修复前的版本仍在历史版本中
+async fn check_repair_creates_version(store: SharedStore) {
+    let router = api::create_router(store.clone(), &Config::default()).unwrap();
+    let (document, key) = create(&store).await;
+    let uri = format!("/v2/dids/{}", document.id);
+
+    let mut repaired = document.clone();
+    repaired.services.push(Service {
+        id: format!("{}#chain", document.id),
+        type_: "LinkedDomains".to_string(),
+        endpoint: "https://chain.example.com".to_string(),
+    });
+    store.overwrite_did_document(&document.id, &repaired, true).await.unwrap();
+
+    let response = send(&router, "GET", &uri, &[(header::IF_NONE_MATCH, "\"1\"")], None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::ETAG], "\"2\"");
+    let operations = format!("{}/operations", uri);
+    let response = send(&router, "POST", &operations, &[(header::IF_MATCH, "\"1\"")], Some(deactivate(&document.id, &key, None))).await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+
+    // 只修复状态同样产生新版本
+    store.set_did_active(&document.id, false).await.unwrap();
+    let versions = store.list_did_versions(&document.id).await.unwrap();
+    let summary: Vec<_> = versions.iter().map(|version| (version.version_id, version.document.services.len(), version.is_active)).collect();
+    assert_eq!(summary, [(1, 0, true), (2, 1, true), (3, 1, false)]);
+    let response = send(&router, "GET", &uri, &[], None).await;
+    assert_eq!(response.status(), StatusCode::GONE);
+    assert_eq!(response.headers()[header::ETAG], "\"3\"");
+    assert_replays(&store, &document.id).await;
+}
+
+#[tokio::test]
+async fn memory_store_repairs_create_versions() {
+    check_repair_creates_version(Arc::new(MemoryStore::new())).await;
+}
+
+#[tokio::test]
+async fn sqlite_store_repairs_create_versions() {
+    let path = std::env::temp_dir().join(format!("did-system-conditional-repair-{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let store = SqliteStore::open(path.to_str().unwrap(), None).unwrap();
+    check_repair_creates_version(Arc::new(store)).await;
+    std::fs::remove_file(path).unwrap();
+}
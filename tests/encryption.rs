@@ -0,0 +1,158 @@
+//! 静态加密测试：通过存储读写的数据在数据库文件中只以密文或带密钥哈希保存，仍可按属性查询；
+//! 密文绑定所在的行，移动或替换为明文时拒绝读取；轮换包装密钥后数据可读，错误的密钥无法打开数据库；
+//! 加密已有数据后操作日志的只追加触发器恢复
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use did_system::blockchain::{self, LedgersConfig};
+use did_system::db::{DidQuery, KeySource, SharedStore, SqliteStore};
+use did_system::did;
+use did_system::outbox::OutboxStatus;
+use did_system::utils;
+use rusqlite::{Connection, params};
+
+fn database(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("did-system-encryption-{}-{}.db", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+fn key_file(name: &str) -> KeySource {
+    let path = std::env::temp_dir().join(format!("did-system-encryption-{}-{}.key", name, std::process::id()));
+    let mut key = [0u8; 32];
+    rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut key);
+    std::fs::write(&path, STANDARD.encode(key)).unwrap();
+    KeySource::KeyFile(path)
+}
+
+fn remove(path: &Path, source: &KeySource) {
+    std::fs::remove_file(path).unwrap();
+    if let KeySource::KeyFile(key_file) = source {
+        std::fs::remove_file(key_file).unwrap();
+    }
+}
+
+fn open(path: &Path, source: Option<&KeySource>) -> Result<SharedStore, did_system::types::Error> {
+    Ok(Arc::new(SqliteStore::open(path.to_str().unwrap(), source)?))
+}
+
+/// 数据库文件中指定列的全部值
+fn column(path: &Path, table: &str, column: &str) -> Vec<Vec<u8>> {
+    let conn = Connection::open(path).unwrap();
+    let mut stmt = conn.prepare(&format!("SELECT CAST({} AS BLOB) FROM {} WHERE {} IS NOT NULL", column, table, column)).unwrap();
+    let rows = stmt.query_map([], |row| row.get(0)).unwrap();
+    rows.collect::<Result<_, _>>().unwrap()
+}
+
+fn all_start_with(values: &[Vec<u8>], prefix: &str) -> bool {
+    !values.is_empty() && values.iter().all(|value| value.starts_with(prefix.as_bytes()))
+}
+
+fn by_public_key(public_key: &str) -> DidQuery {
+    DidQuery { public_key: Some(public_key.to_string()), limit: 10, ..DidQuery::default() }
+}
+
+#[tokio::test]
+async fn data_round_trips_through_the_store_encrypted() {
+    let _ = blockchain::init(LedgersConfig::default()).await;
+    let path = database("round-trip");
+    let source = key_file("round-trip");
+    let store = open(&path, Some(&source)).unwrap();
+
+    let document = did::create_did(store.as_ref(), &utils::generate_keypair(), None).await.unwrap();
+    let public_key = document.public_keys[0].public_key_base58.clone();
+    let record = store.get_did_record(&document.id).await.unwrap().unwrap();
+    assert_eq!(did::document_hash(&record.document).unwrap(), did::document_hash(&document).unwrap());
+    let found = store.query_did_records(&by_public_key(&public_key)).await.unwrap();
+    assert_eq!(found.iter().map(|record| record.did.as_str()).collect::<Vec<_>>(), [document.id.as_str()]);
+    let pending = store.list_outbox_entries(OutboxStatus::Pending).await.unwrap();
+    assert!(String::from_utf8_lossy(&pending[0].payload).contains(&document.id));
+
+    // 数据库文件中没有明文
+    assert!(all_start_with(&column(&path, "did_documents", "document"), "enc:v2:"));
+    assert!(all_start_with(&column(&path, "did_document_history", "document"), "enc:v2:"));
+    assert!(all_start_with(&column(&path, "operation_log", "payload"), "enc:v2:"));
+    assert!(all_start_with(&column(&path, "ledger_outbox", "payload"), "enc:v2:"));
+    assert!(all_start_with(&column(&path, "did_attributes", "value"), "idx:v1:"));
+    assert!(all_start_with(&column(&path, "key_claims", "public_key"), "idx:v1:"));
+
+    // 密文不能移动到其他行
+    let other = did::create_did(store.as_ref(), &utils::generate_keypair(), None).await.unwrap();
+    let conn = Connection::open(&path).unwrap();
+    conn.execute(
+        "UPDATE did_documents SET document = (SELECT document FROM did_documents WHERE did = ?) WHERE did = ?",
+        params![document.id, other.id],
+    ).unwrap();
+    let error = store.get_did_record(&other.id).await.unwrap_err();
+    assert!(error.to_string().contains("Failed to decrypt"), "{}", error);
+
+    // 启用加密后拒绝明文
+    conn.execute(
+        "UPDATE did_documents SET document = ? WHERE did = ?",
+        params![serde_json::to_string(&other).unwrap(), other.id],
+    ).unwrap();
+    let error = store.get_did_record(&other.id).await.unwrap_err();
+    assert!(error.to_string().contains("Unencrypted value"), "{}", error);
+
+    remove(&path, &source);
+}
+
+#[tokio::test]
+async fn rotated_keys_open_the_database_and_wrong_keys_do_not() {
+    let _ = blockchain::init(LedgersConfig::default()).await;
+    let path = database("rotation");
+    let passphrase = KeySource::Passphrase("correct horse".to_string());
+    let store = open(&path, Some(&passphrase)).unwrap();
+    let document = did::create_did(store.as_ref(), &utils::generate_keypair(), None).await.unwrap();
+    drop(store);
+
+    let error = open(&path, Some(&KeySource::Passphrase("wrong horse".to_string()))).err().unwrap();
+    assert!(error.to_string().contains("wrong passphrase or key file"), "{}", error);
+    let error = open(&path, None).err().unwrap();
+    assert!(error.to_string().contains("no encryption key was provided"), "{}", error);
+
+    let new_key = key_file("rotation");
+    SqliteStore::open(path.to_str().unwrap(), Some(&passphrase)).unwrap()
+        .rotate_encryption_key(&passphrase, &new_key).unwrap();
+    let error = open(&path, Some(&passphrase)).err().unwrap();
+    assert!(error.to_string().contains("wrapped with key_file"), "{}", error);
+
+    // 数据加密密钥不变，已有的密文和查询属性仍然可用
+    let store = open(&path, Some(&new_key)).unwrap();
+    assert!(store.get_did_record(&document.id).await.unwrap().is_some());
+    let found = store.query_did_records(&by_public_key(&document.public_keys[0].public_key_base58)).await.unwrap();
+    assert_eq!(found.len(), 1);
+
+    remove(&path, &new_key);
+}
+
+#[tokio::test]
+async fn enabling_encryption_keeps_the_operation_log_append_only() {
+    let _ = blockchain::init(LedgersConfig::default()).await;
+    let path = database("enable");
+    let store = open(&path, None).unwrap();
+    let document = did::create_did(store.as_ref(), &utils::generate_keypair(), None).await.unwrap();
+    drop(store);
+    assert!(!all_start_with(&column(&path, "operation_log", "payload"), "enc:"));
+
+    let source = key_file("enable");
+    let store = open(&path, Some(&source)).unwrap();
+    assert!(all_start_with(&column(&path, "operation_log", "payload"), "enc:v2:"));
+    assert!(all_start_with(&column(&path, "did_attributes", "value"), "idx:v1:"));
+    assert!(store.get_did_record(&document.id).await.unwrap().is_some());
+    assert!(did_system::oplog::verify_chain(store.as_ref()).await.unwrap().intact);
+
+    let conn = Connection::open(&path).unwrap();
+    let triggers: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'trigger' AND tbl_name = 'operation_log'",
+        [],
+        |row| row.get(0),
+    ).unwrap();
+    assert_eq!(triggers, 2);
+    let error = conn.execute("DELETE FROM operation_log", []).unwrap_err();
+    assert!(error.to_string().contains("append-only"), "{}", error);
+
+    remove(&path, &source);
+}
@@ -0,0 +1,143 @@
+//! 幂等请求测试：带`Idempotency-Key`的重试重放第一次的响应，不重复执行也不计入配额；
+//! 同一个键用于不同的请求返回422，处理中返回409；存储层的占用、完成、释放和过期
+
+use std::sync::Arc;
+use axum::body::{to_bytes, Body};
+use axum::http::{header, Request, StatusCode};
+use axum::Router;
+use did_system::api;
+use did_system::blockchain::{self, LedgersConfig};
+use did_system::config::Config;
+use did_system::db::{MemoryStore, SharedStore, SqliteStore};
+use did_system::idempotency::{self, StoredResponse};
+use did_system::ratelimit;
+use did_system::utils;
+use tower::ServiceExt;
+
+async fn send(router: &Router, method: &str, uri: &str, key: Option<&str>, body: serde_json::Value) -> (StatusCode, axum::http::HeaderMap, serde_json::Value) {
+    let mut request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::ACCEPT, "application/problem+json");
+    if let Some(key) = key {
+        request = request.header("idempotency-key", key);
+    }
+    let response = router.clone().oneshot(request.body(Body::from(body.to_string())).unwrap()).await.unwrap();
+    let (parts, body) = response.into_parts();
+    let body = to_bytes(body, usize::MAX).await.unwrap();
+    (parts.status, parts.headers, serde_json::from_slice(&body).unwrap_or_default())
+}
+
+fn create_request() -> serde_json::Value {
+    serde_json::json!({ "signing_key": utils::encode_base58(&utils::generate_keypair().to_bytes()) })
+}
+
+#[tokio::test]
+async fn retries_replay_the_original_response() {
+    let _ = blockchain::init(LedgersConfig::default()).await;
+    let mut config = Config::default();
+    config.rate_limit.enabled = true;
+    let store: SharedStore = Arc::new(MemoryStore::new());
+    let router = api::create_router(store.clone(), &config).unwrap();
+    let request = create_request();
+
+    let (status, headers, created) = send(&router, "POST", "/v1/did", Some("create-1"), request.clone()).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert!(headers.get("idempotency-replayed").is_none());
+
+    let (status, headers, replayed) = send(&router, "POST", "/v1/did", Some("create-1"), request.clone()).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(headers["idempotency-replayed"], "true");
+    assert_eq!(headers[header::CONTENT_TYPE], "application/json");
+    assert_eq!(replayed, created);
+    assert_eq!(store.list_did_records().await.unwrap().len(), 1);
+
+    // 重放不计入每日写操作配额
+    let day = ratelimit::day_of(utils::current_timestamp());
+    assert_eq!(store.get_write_usage("ip:unknown", day).await.unwrap(), 1);
+
+    // 不带幂等键的重试再次执行
+    let (status, _, problem) = send(&router, "POST", "/v1/did", None, request.clone()).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(problem["detail"].as_str().unwrap().contains("already exists"));
+
+    // 同一个键用于不同的请求
+    let (status, _, problem) = send(&router, "POST", "/v1/did", Some("create-1"), create_request()).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(problem["code"], "idempotency_key_mismatch");
+    let (status, _, _) = send(&router, "POST", "/v2/dids/did:web:x/operations", Some("create-1"), request).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn client_errors_are_replayed_and_keys_are_validated() {
+    let store: SharedStore = Arc::new(MemoryStore::new());
+    let router = api::create_router(store.clone(), &Config::default()).unwrap();
+    let body = serde_json::json!({ "signing_key": utils::encode_base58(&[7u8; 32]) });
+
+    let (status, headers, first) = send(&router, "DELETE", "/v1/did/did:web:missing", Some("delete-1"), body.clone()).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert!(headers.get("idempotency-replayed").is_none());
+    let (status, headers, replayed) = send(&router, "DELETE", "/v1/did/did:web:missing", Some("delete-1"), body.clone()).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(headers["idempotency-replayed"], "true");
+    assert_eq!(replayed["code"], first["code"]);
+
+    for key in ["", "has space", &"k".repeat(idempotency::MAX_KEY_LENGTH + 1)] {
+        let (status, _, _) = send(&router, "DELETE", "/v1/did/did:web:missing", Some(key), body.clone()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "key {:?}", key);
+    }
+
+    // 第一次请求仍在处理中
+    let now = utils::current_timestamp();
+    let fingerprint = idempotency::fingerprint("DELETE", "/v1/did/did:web:other", body.to_string().as_bytes());
+    store.claim_idempotency_key("anonymous", "delete-2", &fingerprint, now, now + 60).await.unwrap();
+    let (status, _, problem) = send(&router, "DELETE", "/v1/did/did:web:other", Some("delete-2"), body).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(problem["code"], "idempotency_key_in_use");
+}
+
+/// 占用、重放、释放、处理超时和过期
+async fn check_store_idempotency_keys(store: SharedStore) {
+    let now = 1_000_000;
+    let response = StoredResponse {
+        status: 201,
+        headers: vec![("content-type".to_string(), "application/json".to_string())],
+        body: "{\"success\":true}".to_string(),
+    };
+
+    assert!(store.claim_idempotency_key("key:a", "k1", "f1", now, now + 100).await.unwrap().is_none());
+    let record = store.claim_idempotency_key("key:a", "k1", "f2", now + 1, now + 101).await.unwrap().unwrap();
+    assert_eq!((record.fingerprint.as_str(), record.response.as_ref()), ("f1", None));
+    // 幂等键按租户隔离
+    assert!(store.claim_idempotency_key("key:b", "k1", "f2", now, now + 100).await.unwrap().is_none());
+
+    store.complete_idempotency_key("key:a", "k1", &response).await.unwrap();
+    store.release_idempotency_key("key:a", "k1").await.unwrap();
+    let record = store.claim_idempotency_key("key:a", "k1", "f1", now + 2, now + 102).await.unwrap().unwrap();
+    assert_eq!(record.response, Some(response));
+
+    // 释放的键和处理超时的键可以重新占用
+    store.release_idempotency_key("key:b", "k1").await.unwrap();
+    assert!(store.claim_idempotency_key("key:b", "k1", "f3", now + 3, now + 103).await.unwrap().is_none());
+    assert!(store.claim_idempotency_key("key:a", "k2", "f1", now, now + 1000).await.unwrap().is_none());
+    let abandoned = now + idempotency::LOCK_TIMEOUT_SECS;
+    assert!(store.claim_idempotency_key("key:a", "k2", "f4", abandoned, abandoned + 1000).await.unwrap().is_none());
+
+    // 过期后同一个键视为新请求
+    assert!(store.claim_idempotency_key("key:a", "k1", "f5", now + 100, now + 200).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn memory_store_idempotency_keys() {
+    check_store_idempotency_keys(Arc::new(MemoryStore::new())).await;
+}
+
+#[tokio::test]
+async fn sqlite_store_idempotency_keys() {
+    let path = std::env::temp_dir().join(format!("did-system-idempotency-{}.db", std::process::id()));
+    let store = SqliteStore::open(path.to_str().unwrap(), None).unwrap();
+    check_store_idempotency_keys(Arc::new(store)).await;
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,58 @@
+//! 公钥唯一策略测试：存储在写入事务中登记活跃DID的公钥，公钥已属于其他活跃的DID时拒绝写入，
+//! DID停用后释放其公钥
+
+use std::sync::Arc;
+use did_system::blockchain::{self, LedgersConfig};
+use did_system::db::{MemoryStore, SharedStore, SqliteStore};
+use did_system::did::{self, KeyPolicy, PublicKeyInfo};
+use did_system::oplog::{DidOperation, SignedOperation};
+use did_system::utils;
+
+async fn check_unique_keys(store: SharedStore) {
+    // 两个测试共用一个进程，策略只能设置一次
+    let _ = did::init_key_policy(KeyPolicy::Unique);
+    assert_eq!(did::key_policy(), KeyPolicy::Unique);
+    let _ = blockchain::init(LedgersConfig::default()).await;
+
+    let first_key = utils::generate_keypair();
+    let first = did::create_did(store.as_ref(), &first_key, None).await.unwrap();
+    let second = did::create_did(store.as_ref(), &utils::generate_keypair(), None).await.unwrap();
+
+    // 修复路径不经过提交前的检查，由存储拒绝
+    let mut borrowed = second.clone();
+    borrowed.public_keys.push(PublicKeyInfo {
+        id: format!("{}#keys-2", second.id),
+        ..first.public_keys[0].clone()
+    });
+    let error = store.overwrite_did_document(&second.id, &borrowed, true).await.unwrap_err();
+    assert!(error.to_string().contains("already registered"), "{}", error);
+    assert_eq!(store.get_did_record(&second.id).await.unwrap().unwrap().document.public_keys.len(), 1);
+
+    // 停用后公钥可以由其他DID使用
+    let deactivate = SignedOperation::sign(
+        &first.id,
+        DidOperation::Deactivate,
+        Some(1),
+        &format!("{}#keys-1", first.id),
+        &first_key,
+    ).unwrap();
+    did::submit_operation(store.as_ref(), &first.id, deactivate, None).await.unwrap();
+    store.overwrite_did_document(&second.id, &borrowed, true).await.unwrap();
+
+    // 重新激活时公钥已属于其他DID
+    let error = store.set_did_active(&first.id, true).await.unwrap_err();
+    assert!(error.to_string().contains("already registered"), "{}", error);
+}
+
+#[tokio::test]
+async fn memory_store_enforces_unique_keys() {
+    check_unique_keys(Arc::new(MemoryStore::new())).await;
+}
+
+#[tokio::test]
+async fn sqlite_store_enforces_unique_keys() {
+    let path = std::env::temp_dir().join(format!("did-system-key-policy-{}.db", std::process::id()));
+    let store = SqliteStore::open(path.to_str().unwrap(), None).unwrap();
+    check_unique_keys(Arc::new(store)).await;
+    std::fs::remove_file(path).unwrap();
+}
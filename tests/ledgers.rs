@@ -0,0 +1,154 @@
+//! 多账本测试：按DID方法和网络段选择账本，没有匹配路由时使用默认账本，未配置的网络段被拒绝，
+//! 出站队列按账本分别投递
+
+use std::sync::{Arc, Mutex};
+use axum::extract::State;
+use axum::routing::post;
+use axum::{Json, Router};
+use did_system::blockchain::{self, BlockchainConfig, LedgerRegistry, LedgerRoute, LedgersConfig};
+use did_system::db::{MemoryStore, SharedStore};
+use did_system::did;
+use did_system::outbox::{self, OutboxConfig, OutboxStatus};
+use did_system::types::Error;
+use did_system::utils;
+
+fn ledger(name: &str) -> BlockchainConfig {
+    BlockchainConfig { name: name.to_string(), ..Default::default() }
+}
+
+fn route(method: &str, network: Option<&str>, ledger: &str) -> LedgerRoute {
+    LedgerRoute {
+        method: method.to_string(),
+        network: network.map(str::to_string),
+        ledger: ledger.to_string(),
+    }
+}
+
+#[test]
+fn dids_are_routed_by_method_and_network() {
+    let registry = LedgerRegistry::new(LedgersConfig {
+        default: Some("fallback".to_string()),
+        ledgers: vec![ledger("main"), ledger("test"), ledger("fallback")],
+        routes: vec![
+            route("web", Some("testnet"), "test"),
+            route("web", None, "main"),
+            route("example", Some("dev"), "test"),
+        ],
+    }).unwrap();
+
+    for (did, expected) in [
+        ("did:web:abc", "main"),
+        // 网络段精确匹配优先于只匹配方法的路由
+        ("did:web:testnet:abc", "test"),
+        ("did:web:mainnet:abc", "main"),
+        ("did:example:dev:abc", "test"),
+        // 没有匹配的路由时使用默认账本
+        ("did:example:abc", "fallback"),
+        ("did:example:prod:abc", "fallback"),
+        ("did:key:abc", "fallback"),
+    ] {
+        assert_eq!(registry.ledger_for_did(did).unwrap().name(), expected, "{}", did);
+    }
+
+    for did in ["web:abc", "did:web", "did::abc", "did:web:", "urn:web:abc"] {
+        assert!(matches!(registry.ledger_for_did(did), Err(Error::InvalidInput(_))), "{}", did);
+    }
+
+    assert!(registry.has_network("web", "testnet"));
+    assert!(!registry.has_network("web", "mainnet"));
+    assert!(!registry.has_network("example", "testnet"));
+    assert_eq!(registry.default_ledger().name(), "fallback");
+    assert!(registry.ledger("unknown").is_err());
+}
+
+#[test]
+fn first_ledger_is_the_default() {
+    let registry = LedgerRegistry::new(LedgersConfig {
+        default: None,
+        ledgers: vec![ledger("main"), ledger("test")],
+        routes: vec![route("web", Some("testnet"), "test")],
+    }).unwrap();
+    assert_eq!(registry.default_ledger().name(), "main");
+    assert_eq!(registry.ledger_for_did("did:web:abc").unwrap().name(), "main");
+}
+
+#[test]
+fn unknown_ledgers_are_rejected() {
+    for config in [
+        LedgersConfig {
+            default: None,
+            ledgers: vec![ledger("main")],
+            routes: vec![route("web", Some("testnet"), "test")],
+        },
+        LedgersConfig {
+            default: Some("test".to_string()),
+            ledgers: vec![ledger("main")],
+            routes: Vec::new(),
+        },
+        LedgersConfig {
+            default: None,
+            ledgers: vec![ledger("main"), ledger("main")],
+            routes: Vec::new(),
+        },
+        LedgersConfig {
+            default: None,
+            ledgers: Vec::new(),
+            routes: Vec::new(),
+        },
+    ] {
+        assert!(LedgerRegistry::new(config).is_err());
+    }
+}
+
+/// 模拟节点，记录收到的注册交易数，返回带账本名称的交易哈希
+async fn start_node(name: &'static str) -> (String, Arc<Mutex<usize>>) {
+    async fn register(State((name, count)): State<(&'static str, Arc<Mutex<usize>>)>) -> Json<serde_json::Value> {
+        *count.lock().unwrap() += 1;
+        Json(serde_json::json!({ "hash": format!("0x{}", name), "status": "success" }))
+    }
+
+    let count = Arc::new(Mutex::new(0));
+    let app = Router::new()
+        .route("/did/register", post(register))
+        .with_state((name, count.clone()));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (format!("http://{}", address), count)
+}
+
+#[tokio::test]
+async fn outbox_entries_are_delivered_to_their_ledger() {
+    let (main_url, main_count) = start_node("main").await;
+    let (test_url, test_count) = start_node("test").await;
+    blockchain::init(LedgersConfig {
+        default: None,
+        ledgers: vec![
+            BlockchainConfig { node_url: main_url, ..ledger("main") },
+            BlockchainConfig { node_url: test_url, ..ledger("test") },
+        ],
+        routes: vec![route("web", Some("testnet"), "test")],
+    }).await.unwrap();
+    let store: SharedStore = Arc::new(MemoryStore::new());
+
+    // 未配置的网络段
+    let error = did::create_did(store.as_ref(), &utils::generate_keypair(), Some("devnet")).await.unwrap_err();
+    let Error::Validation(errors) = error else {
+        panic!("unexpected error: {}", error);
+    };
+    assert_eq!(errors[0].field, "network");
+
+    let main = did::create_did(store.as_ref(), &utils::generate_keypair(), None).await.unwrap();
+    let test = did::create_did(store.as_ref(), &utils::generate_keypair(), Some("testnet")).await.unwrap();
+    assert!(test.id.starts_with("did:web:testnet:"), "{}", test.id);
+
+    let pending = store.list_outbox_entries(OutboxStatus::Pending).await.unwrap();
+    let ledgers: Vec<_> = pending.iter().map(|entry| (entry.did.as_str(), entry.ledger.as_str())).collect();
+    assert_eq!(ledgers, [(main.id.as_str(), "main"), (test.id.as_str(), "test")]);
+
+    assert_eq!(outbox::process_due_entries(store.as_ref(), &OutboxConfig::default()).await.unwrap(), 2);
+    assert_eq!((*main_count.lock().unwrap(), *test_count.lock().unwrap()), (1, 1));
+    let delivered = store.list_outbox_entries(OutboxStatus::Delivered).await.unwrap();
+    let hashes: Vec<_> = delivered.iter().map(|entry| (entry.did.as_str(), entry.tx_hash.as_deref())).collect();
+    assert_eq!(hashes, [(main.id.as_str(), Some("0xmain")), (test.id.as_str(), Some("0xtest"))]);
+}
@@ -0,0 +1,153 @@
+//! 反向查询测试：按multibase编码的公钥或服务端点查询引用它们的DID，结果按DID排序分页，
+//! 默认不返回已停用的DID
+
+use std::sync::Arc;
+use axum::body::{to_bytes, Body};
+use axum::http::{Request, StatusCode};
+use axum::Router;
+use did_system::api;
+use did_system::blockchain::{self, LedgersConfig};
+use did_system::config::Config;
+use did_system::db::{MemoryStore, SharedStore, SqliteStore};
+use did_system::did::{self, DIDDocument, PublicKeyInfo, Service};
+use did_system::oplog::{DidOperation, SignedOperation};
+use did_system::utils;
+use ed25519_dalek::SigningKey;
+use tower::ServiceExt;
+
+const ENDPOINT: &str = "https://hub.example.com/inbox";
+
+async fn get(router: &Router, uri: &str) -> (StatusCode, serde_json::Value) {
+    let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+    let response = router.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+/// 一页v2查询结果中的DID和下一页的游标
+async fn page(router: &Router, uri: &str) -> (Vec<String>, Option<String>) {
+    let (status, body) = get(router, uri).await;
+    assert_eq!(status, StatusCode::OK, "{}: {}", uri, body);
+    let dids = body["items"].as_array().unwrap().iter()
+        .map(|item| item["didDocument"]["id"].as_str().unwrap().to_string())
+        .collect();
+    (dids, body["nextCursor"].as_str().map(str::to_string))
+}
+
+async fn dids(router: &Router, uri: &str) -> Vec<String> {
+    page(router, uri).await.0
+}
+
+/// 创建第二个公钥为`shared`、带共享服务端点的DID
+async fn create_sharing(store: &SharedStore, shared: &SigningKey) -> (String, SigningKey) {
+    let key = utils::generate_keypair();
+    let public_key = key.verifying_key().to_bytes();
+    let did = did::did_for_key(&public_key, None).unwrap();
+    let key_id = format!("{}#keys-1", did);
+    let now = utils::current_timestamp();
+    let document = DIDDocument {
+        id: did.clone(),
+        public_keys: vec![
+            PublicKeyInfo {
+                id: key_id.clone(),
+                type_: "Ed25519VerificationKey2020".to_string(),
+                controller: did.clone(),
+                public_key_base58: utils::encode_base58(&public_key),
+            },
+            PublicKeyInfo {
+                id: format!("{}#keys-2", did),
+                type_: "Ed25519VerificationKey2020".to_string(),
+                controller: did.clone(),
+                public_key_base58: utils::encode_base58(&shared.verifying_key().to_bytes()),
+            },
+        ],
+        authentication: vec![key_id.clone()],
+        services: vec![Service {
+            id: format!("{}#hub", did),
+            type_: "DIDCommMessaging".to_string(),
+            endpoint: ENDPOINT.to_string(),
+        }],
+        created: now,
+        updated: now,
+    };
+    let operation = SignedOperation::sign(&did, DidOperation::Create { document }, None, &key_id, &key).unwrap();
+    did::submit_operation(store.as_ref(), &did, operation, None).await.unwrap();
+    (did, key)
+}
+
+fn sorted(mut dids: Vec<String>) -> Vec<String> {
+    dids.sort();
+    dids
+}
+
+async fn check_lookup(store: SharedStore) {
+    let _ = blockchain::init(LedgersConfig::default()).await;
+    let router = api::create_router(store.clone(), &Config::default()).unwrap();
+
+    let shared = utils::generate_keypair();
+    let owner = did::create_did(store.as_ref(), &shared, None).await.unwrap();
+    let service = Service {
+        id: format!("{}#hub", owner.id),
+        type_: "DIDCommMessaging".to_string(),
+        endpoint: ENDPOINT.to_string(),
+    };
+    did::add_service(store.as_ref(), &owner.id, &shared, service, None).await.unwrap();
+    let (sharing, _) = create_sharing(&store, &shared).await;
+    let (deactivated, key) = create_sharing(&store, &shared).await;
+    let deactivate = SignedOperation::sign(&deactivated, DidOperation::Deactivate, Some(1), &format!("{}#keys-1", deactivated), &key).unwrap();
+    did::submit_operation(store.as_ref(), &deactivated, deactivate, None).await.unwrap();
+    let active = sorted(vec![owner.id.clone(), sharing.clone()]);
+
+    // 带Ed25519 multicodec前缀和不带前缀的multibase编码查询结果相同
+    let public_key = shared.verifying_key().to_bytes();
+    let raw = format!("z{}", utils::encode_base58(&public_key));
+    let multicodec = format!("z{}", utils::encode_base58(&[&[0xed, 0x01][..], &public_key[..]].concat()));
+    for multibase in [&raw, &multicodec] {
+        assert_eq!(dids(&router, &format!("/v2/lookup/key/{}", multibase)).await, active, "{}", multibase);
+    }
+    assert_eq!(dids(&router, &format!("/v2/lookup/key/{}?status=deactivated", raw)).await, [deactivated.as_str()]);
+    let other = format!("z{}", utils::encode_base58(&utils::generate_keypair().verifying_key().to_bytes()));
+    assert!(dids(&router, &format!("/v2/lookup/key/{}", other)).await.is_empty());
+
+    let by_service = "/v2/lookup/service?endpoint=https%3A%2F%2Fhub.example.com%2Finbox";
+    assert_eq!(dids(&router, by_service).await, active);
+    assert_eq!(dids(&router, &format!("{}&status=deactivated", by_service)).await, [deactivated.as_str()]);
+    assert!(dids(&router, "/v2/lookup/service?endpoint=https%3A%2F%2Fother.example.com").await.is_empty());
+
+    // 逐页读取
+    let (first, cursor) = page(&router, &format!("/v2/lookup/key/{}?limit=1", raw)).await;
+    let (second, next) = page(&router, &format!("/v2/lookup/key/{}?limit=1&cursor={}", raw, cursor.unwrap())).await;
+    assert_eq!(([first, second].concat(), next), (active.clone(), None));
+    let (first, cursor) = page(&router, &format!("{}&limit=1", by_service)).await;
+    let (second, next) = page(&router, &format!("{}&limit=1&cursor={}", by_service, cursor.unwrap())).await;
+    assert_eq!(([first, second].concat(), next), (active.clone(), None));
+
+    // v1返回同样的结果
+    let (status, body) = get(&router, &format!("/v1/lookup/key/{}", multicodec)).await;
+    assert_eq!(status, StatusCode::OK);
+    let items: Vec<_> = body["data"]["items"].as_array().unwrap().iter()
+        .map(|item| item["did"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(items, active);
+
+    // 不是base58btc multibase编码的公钥、缺少服务端点
+    let (status, _) = get(&router, &format!("/v2/lookup/key/{}", utils::encode_base58(&public_key))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, problem) = get(&router, "/v2/lookup/service").await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(problem["errors"][0]["field"], "endpoint");
+}
+
+#[tokio::test]
+async fn memory_store_looks_up_keys_and_services() {
+    check_lookup(Arc::new(MemoryStore::new())).await;
+}
+
+#[tokio::test]
+async fn sqlite_store_looks_up_keys_and_services() {
+    let path = std::env::temp_dir().join(format!("did-system-lookup-{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    check_lookup(Arc::new(SqliteStore::open(path.to_str().unwrap(), None).unwrap())).await;
+    std::fs::remove_file(path).unwrap();
+}
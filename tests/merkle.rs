@@ -0,0 +1,90 @@
+//! Merkle树测试：根哈希按带前缀的节点哈希计算，奇数个节点时最后一个节点直接提升；
+//! 每个叶子的包含证明都能得到根哈希，篡改证明、叶子或根哈希时验证失败
+
+use did_system::anchoring::merkle::{self, MerkleTree, Position, ProofStep};
+use did_system::utils;
+
+fn leaves(count: usize) -> Vec<Vec<u8>> {
+    (0..count).map(|index| merkle::leaf_hash(format!("leaf-{}", index).as_bytes())).collect()
+}
+
+/// 与实现无关地计算内部节点哈希
+fn node(left: &[u8], right: &[u8]) -> Vec<u8> {
+    utils::sha256(&[&[0x01], left, right].concat())
+}
+
+#[test]
+fn empty_tree_has_no_root() {
+    let tree = MerkleTree::new(Vec::new());
+    assert!(tree.root().is_none());
+    assert!(tree.proof(0).is_none());
+}
+
+#[test]
+fn single_leaf_is_the_root() {
+    let leaves = leaves(1);
+    let tree = MerkleTree::new(leaves.clone());
+    assert_eq!(tree.root(), Some(leaves[0].as_slice()));
+
+    let proof = tree.proof(0).unwrap();
+    assert!(proof.is_empty());
+    assert!(merkle::verify_proof(&leaves[0], &proof, &leaves[0]));
+    assert!(tree.proof(1).is_none());
+}
+
+#[test]
+fn odd_leaf_is_promoted() {
+    let leaves = leaves(3);
+    let tree = MerkleTree::new(leaves.clone());
+    let expected = node(&node(&leaves[0], &leaves[1]), &leaves[2]);
+    assert_eq!(tree.root(), Some(expected.as_slice()));
+
+    // 提升的叶子在第一层没有兄弟节点
+    let proof = tree.proof(2).unwrap();
+    assert_eq!(proof, [ProofStep { hash: utils::to_hex(&node(&leaves[0], &leaves[1])), position: Position::Left }]);
+}
+
+#[test]
+fn every_leaf_of_a_five_leaf_tree_verifies() {
+    let leaves = leaves(5);
+    let tree = MerkleTree::new(leaves.clone());
+    let left = node(&node(&leaves[0], &leaves[1]), &node(&leaves[2], &leaves[3]));
+    let expected = node(&left, &leaves[4]);
+    let root = tree.root().unwrap().to_vec();
+    assert_eq!(root, expected);
+
+    for (index, leaf) in leaves.iter().enumerate() {
+        let proof = tree.proof(index).unwrap();
+        assert!(merkle::verify_proof(leaf, &proof, &root), "leaf {}", index);
+        // 证明不能用于其他叶子
+        let other = &leaves[(index + 1) % leaves.len()];
+        assert!(!merkle::verify_proof(other, &proof, &root), "leaf {} with proof of {}", (index + 1) % 5, index);
+    }
+    assert!(tree.proof(5).is_none());
+}
+
+#[test]
+fn tampered_proofs_and_roots_are_rejected() {
+    let leaves = leaves(5);
+    let tree = MerkleTree::new(leaves.clone());
+    let root = tree.root().unwrap().to_vec();
+    let proof = tree.proof(1).unwrap();
+
+    let mut tampered = proof.clone();
+    tampered[0].hash = utils::to_hex(&merkle::leaf_hash(b"forged"));
+    assert!(!merkle::verify_proof(&leaves[1], &tampered, &root));
+
+    let mut swapped = proof.clone();
+    swapped[0].position = Position::Right;
+    assert!(!merkle::verify_proof(&leaves[1], &swapped, &root));
+
+    let mut invalid = proof.clone();
+    invalid[1].hash = "not hex".to_string();
+    assert!(!merkle::verify_proof(&leaves[1], &invalid, &root));
+
+    assert!(!merkle::verify_proof(&leaves[1], &proof[..proof.len() - 1], &root));
+
+    let mut wrong_root = root.clone();
+    wrong_root[0] ^= 0xff;
+    assert!(!merkle::verify_proof(&leaves[1], &proof, &wrong_root));
+}
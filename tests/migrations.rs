@@ -0,0 +1,121 @@
+//! 数据库迁移测试：新数据库按顺序执行全部迁移，引入迁移之前的旧数据库补充缺失的列后被接管，
+//! 架构版本高于本程序支持的版本时拒绝打开
+
+use std::path::PathBuf;
+use did_system::blockchain::{self, LedgersConfig};
+use did_system::db::migrations::{self, MIGRATIONS};
+use did_system::db::{DidStore, MemoryStore, SqliteStore};
+use did_system::did;
+use did_system::utils;
+use rusqlite::{params, Connection};
+
+fn temp_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("did-system-migrations-{}-{}.db", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+fn columns(conn: &Connection, table: &str) -> Vec<String> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table)).unwrap();
+    let rows = stmt.query_map([], |row| row.get::<_, String>(1)).unwrap();
+    rows.collect::<Result<Vec<_>, _>>().unwrap()
+}
+
+#[tokio::test]
+async fn fresh_database_applies_every_migration() {
+    let path = temp_path("fresh");
+    SqliteStore::open(path.to_str().unwrap(), None).unwrap();
+
+    let mut conn = Connection::open(&path).unwrap();
+    assert_eq!(migrations::current_version(&conn).unwrap(), migrations::latest_version());
+    let applied = migrations::applied_migrations(&conn).unwrap();
+    let expected: Vec<_> = MIGRATIONS.iter().map(|migration| (migration.version, migration.name.to_string())).collect();
+    let actual: Vec<_> = applied.iter().map(|migration| (migration.version, migration.name.clone())).collect();
+    assert_eq!(actual, expected);
+    assert_eq!(actual.iter().map(|(version, _)| *version).collect::<Vec<_>>(), (1..=migrations::latest_version()).collect::<Vec<_>>());
+
+    // 再次执行时没有需要执行的迁移
+    assert!(migrations::run(&mut conn).unwrap().is_empty());
+    drop(conn);
+    SqliteStore::open(path.to_str().unwrap(), None).unwrap();
+    std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn legacy_database_is_adopted() {
+    let _ = blockchain::init(LedgersConfig::default()).await;
+    let key = utils::generate_keypair();
+    let document = did::create_did(&MemoryStore::new(), &key, None).await.unwrap();
+    let path = temp_path("legacy");
+
+    // 引入迁移之前的表结构：没有`schema_version`表，也没有后来补充的列
+    let conn = Connection::open(&path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE did_documents (
+            did TEXT PRIMARY KEY,
+            document TEXT NOT NULL,
+            is_active INTEGER NOT NULL DEFAULT 1,
+            created_at INTEGER NOT NULL,
+            updated_at INTEGER NOT NULL
+        );
+        CREATE TABLE ledger_outbox (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            did TEXT NOT NULL,
+            operation TEXT NOT NULL,
+            payload BLOB NOT NULL,
+            idempotency_key TEXT NOT NULL UNIQUE,
+            status TEXT NOT NULL DEFAULT 'pending',
+            attempts INTEGER NOT NULL DEFAULT 0,
+            next_attempt_at INTEGER NOT NULL,
+            last_error TEXT,
+            tx_hash TEXT,
+            created_at INTEGER NOT NULL,
+            updated_at INTEGER NOT NULL
+        );"
+    ).unwrap();
+    conn.execute(
+        "INSERT INTO did_documents (did, document, is_active, created_at, updated_at) VALUES (?, ?, 1, ?, ?)",
+        params![document.id, serde_json::to_string(&document).unwrap(), document.created, document.updated],
+    ).unwrap();
+    drop(conn);
+
+    let store = SqliteStore::open(path.to_str().unwrap(), None).unwrap();
+    let record = store.get_did_record(&document.id).await.unwrap().unwrap();
+    assert_eq!(serde_json::to_value(&record.document).unwrap(), serde_json::to_value(&document).unwrap());
+    assert_eq!(record.version_id, 1);
+    assert!(record.is_active);
+    // 已有的DID补充了导入操作
+    assert_eq!(store.list_did_operations(&document.id, 0).await.unwrap().len(), 1);
+    drop(store);
+
+    let conn = Connection::open(&path).unwrap();
+    assert_eq!(migrations::current_version(&conn).unwrap(), migrations::latest_version());
+    assert!(columns(&conn, "did_documents").contains(&"version_id".to_string()));
+    let outbox = columns(&conn, "ledger_outbox");
+    for column in ["version_id", "batch_id", "ledger"] {
+        assert!(outbox.contains(&column.to_string()), "{}", column);
+    }
+    drop(conn);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn newer_schema_version_is_refused() {
+    let path = temp_path("newer");
+    SqliteStore::open(path.to_str().unwrap(), None).unwrap();
+
+    let conn = Connection::open(&path).unwrap();
+    conn.execute(
+        "INSERT INTO schema_version (version, name, applied_at) VALUES (?, 'future', 0)",
+        params![migrations::latest_version() + 1],
+    ).unwrap();
+    drop(conn);
+
+    let error = SqliteStore::open(path.to_str().unwrap(), None).err().unwrap();
+    assert!(error.to_string().contains("newer than supported version"), "{}", error);
+    let mut conn = Connection::open(&path).unwrap();
+    assert!(migrations::run(&mut conn).is_err());
+    assert_eq!(migrations::current_version(&conn).unwrap(), migrations::latest_version() + 1);
+    drop(conn);
+    std::fs::remove_file(path).unwrap();
+}
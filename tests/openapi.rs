@@ -0,0 +1,138 @@
+//! 路由与OpenAPI文档一致性测试
+//!
+//! 对文档中的每个路径发送所有常用方法的请求：文档中声明的方法必须命中处理函数，
+//! 未声明的方法必须返回405，否则说明路由与文档不一致。
+
+use std::sync::Arc;
+use axum::body::{to_bytes, Body};
+use axum::http::{Method, Request, StatusCode};
+use axum::Router;
+use did_system::api;
+use did_system::config::Config;
+use did_system::db::{MemoryStore, SharedStore};
+use tower::ServiceExt;
+use utoipa::openapi::path::{Operation, ParameterIn, PathItem};
+
+/// 逐一探测的HTTP方法
+const METHODS: [Method; 5] = [Method::GET, Method::POST, Method::PUT, Method::DELETE, Method::PATCH];
+
+fn router() -> Router {
+    let store: SharedStore = Arc::new(MemoryStore::new());
+    // 逐个探测所有方法，关闭限流以免请求被429拒绝
+    let mut config = Config::default();
+    config.rate_limit.enabled = false;
+    api::create_router(store, &config).expect("router")
+}
+
+/// 文档中声明的方法及其操作
+fn operations(item: &PathItem) -> Vec<(Method, &Operation)> {
+    [
+        (Method::GET, &item.get),
+        (Method::POST, &item.post),
+        (Method::PUT, &item.put),
+        (Method::DELETE, &item.delete),
+        (Method::PATCH, &item.patch),
+    ]
+    .into_iter()
+    .filter_map(|(method, operation)| operation.as_ref().map(|operation| (method, operation)))
+    .collect()
+}
+
+/// 将路径模板中的参数替换为示例值
+fn sample_uri(path: &str) -> String {
+    path.split('/')
+        .map(|segment| match segment.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+            Some("did") => "did:web:openapi-test",
+            Some("id") => "1",
+            Some("multibase") => "z6MkopenapiTest",
+            Some(_) => "sample",
+            None => segment,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// 请求是否没有命中任何路由
+async fn is_unrouted(router: &Router, method: Method, uri: &str) -> bool {
+    let request = Request::builder().method(method).uri(uri).body(Body::empty()).unwrap();
+    let response = router.clone().oneshot(request).await.unwrap();
+
+    match response.status() {
+        StatusCode::METHOD_NOT_ALLOWED => true,
+        StatusCode::NOT_FOUND => {
+            let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            let problem: serde_json::Value = serde_json::from_slice(&body).unwrap_or_default();
+            problem["code"] == "route_not_found"
+        }
+        _ => false,
+    }
+}
+
+#[tokio::test]
+async fn every_documented_operation_is_routed() {
+    let router = router();
+    let spec = api::openapi();
+    assert!(!spec.paths.paths.is_empty(), "OpenAPI document has no paths");
+
+    for (path, item) in &spec.paths.paths {
+        let uri = sample_uri(path);
+        let documented: Vec<Method> = operations(item).into_iter().map(|(method, _)| method).collect();
+
+        for method in METHODS {
+            let unrouted = is_unrouted(&router, method.clone(), &uri).await;
+            if documented.contains(&method) {
+                assert!(!unrouted, "{} {} is documented but not routed", method, path);
+            } else {
+                assert!(unrouted, "{} {} is routed but not documented", method, path);
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn path_parameters_match_path_templates() {
+    let spec = api::openapi();
+
+    for (path, item) in &spec.paths.paths {
+        let mut templated: Vec<&str> = path.split('/')
+            .filter_map(|segment| segment.strip_prefix('{').and_then(|s| s.strip_suffix('}')))
+            .collect();
+        templated.sort_unstable();
+
+        for (method, operation) in operations(item) {
+            let mut declared: Vec<&str> = operation.parameters.iter()
+                .flatten()
+                .filter(|parameter| matches!(parameter.parameter_in, ParameterIn::Path))
+                .map(|parameter| parameter.name.as_str())
+                .collect();
+            declared.sort_unstable();
+            assert_eq!(declared, templated, "{} {} declares different path parameters", method, path);
+        }
+    }
+}
+
+#[tokio::test]
+async fn served_document_matches_generated_document() {
+    let request = Request::builder().uri("/openapi.json").body(Body::empty()).unwrap();
+    let response = router().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let served: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let generated = serde_json::to_value(api::openapi()).unwrap();
+    assert_eq!(served, generated);
+    assert!(served["openapi"].as_str().is_some_and(|version| version.starts_with("3.1")));
+}
+
+#[tokio::test]
+async fn reconcile_repair_requires_post() {
+    let spec = api::openapi();
+    let report = &spec.paths.paths["/admin/reconcile"];
+    let methods: Vec<Method> = operations(report).into_iter().map(|(method, _)| method).collect();
+    assert_eq!(methods, [Method::GET]);
+    assert!(report.get.as_ref().unwrap().parameters.as_ref().is_none_or(|parameters| parameters.is_empty()));
+
+    let repair = &spec.paths.paths["/admin/reconcile/repair"];
+    let methods: Vec<Method> = operations(repair).into_iter().map(|(method, _)| method).collect();
+    assert_eq!(methods, [Method::POST]);
+}
@@ -0,0 +1,76 @@
+//! 签名操作测试：整体替换文档的更新不能改变文档的标识符和创建时间，并且至少保留一个验证方法
+
+use std::sync::Arc;
+use axum::body::{to_bytes, Body};
+use axum::http::{header, Request, StatusCode};
+use axum::Router;
+use did_system::api;
+use did_system::blockchain::{self, LedgersConfig};
+use did_system::config::Config;
+use did_system::db::{MemoryStore, SharedStore};
+use did_system::did::{self, DIDDocument};
+use did_system::oplog::{DidOperation, SignedOperation};
+use did_system::utils;
+use ed25519_dalek::SigningKey;
+use tower::ServiceExt;
+
+/// 提交基于版本1的签名更新，返回状态码和问题详情中的字段
+async fn submit_update(router: &Router, did: &str, key: &SigningKey, document: DIDDocument) -> (StatusCode, serde_json::Value) {
+    let operation = SignedOperation::sign(did, DidOperation::Update { document }, Some(1), &format!("{}#keys-1", did), key).unwrap();
+    let request = Request::builder()
+        .method("POST")
+        .uri(format!("/v2/dids/{}/operations", did))
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(serde_json::to_string(&operation).unwrap()))
+        .unwrap();
+    let response = router.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+fn invalid_field(problem: &serde_json::Value) -> &str {
+    problem["errors"][0]["field"].as_str().unwrap_or_default()
+}
+
+#[tokio::test]
+async fn updates_keep_identity_and_verification_methods() {
+    let _ = blockchain::init(LedgersConfig::default()).await;
+    let store: SharedStore = Arc::new(MemoryStore::new());
+    let router = api::create_router(store.clone(), &Config::default()).unwrap();
+    let key = utils::generate_keypair();
+    let document = did::create_did(store.as_ref(), &key, None).await.unwrap();
+    let did = document.id.clone();
+    let other = did::create_did(store.as_ref(), &utils::generate_keypair(), None).await.unwrap();
+
+    let mut moved = document.clone();
+    moved.id = other.id.clone();
+    moved.updated += 1;
+    let (status, problem) = submit_update(&router, &did, &key, moved).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(invalid_field(&problem), "operation.document.id");
+
+    let mut backdated = document.clone();
+    backdated.created -= 1;
+    backdated.updated += 1;
+    let (status, problem) = submit_update(&router, &did, &key, backdated).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(invalid_field(&problem), "operation.document.created");
+
+    let mut locked = document.clone();
+    locked.public_keys.clear();
+    locked.authentication.clear();
+    locked.updated += 1;
+    let (status, problem) = submit_update(&router, &did, &key, locked).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(invalid_field(&problem), "operation.document.public_keys");
+
+    let record = store.get_did_record(&did).await.unwrap().unwrap();
+    assert_eq!(record.version_id, 1);
+
+    let mut updated = document.clone();
+    updated.updated += 1;
+    let (status, result) = submit_update(&router, &did, &key, updated).await;
+    assert_eq!(status, StatusCode::OK, "{}", result);
+    assert_eq!(store.get_did_record(&did).await.unwrap().unwrap().version_id, 2);
+}
@@ -0,0 +1,174 @@
+//! 操作日志校验测试：篡改日志内容或链接时哈希链断裂；链头锚定后，重新计算、截断或替换锚定链头的日志
+//! 同样被发现，锚定记录必须作为链头登记在账本上
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use axum::body::Bytes;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use did_system::blockchain::{self, BatchAnchor, BlockchainConfig, LedgersConfig, LogHeadAnchor};
+use did_system::db::{SharedStore, SqliteStore};
+use did_system::did;
+use did_system::oplog::{self, DidOperation, HeadAnchor, SignedOperation};
+use did_system::utils;
+use rusqlite::{Connection, params};
+
+/// 模拟节点上分开登记的批次根哈希和日志链头
+#[derive(Default)]
+struct Anchors {
+    batches: HashMap<String, BatchAnchor>,
+    heads: HashMap<String, LogHeadAnchor>,
+}
+
+type Node = Arc<Mutex<Anchors>>;
+
+/// 提供批次锚定和日志链头锚定的模拟节点
+async fn start_node() -> String {
+    // 客户端发送的交易没有Content-Type
+    async fn anchor_batch(State(node): State<Node>, body: Bytes) -> Json<serde_json::Value> {
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let root = body["root"].as_str().unwrap().to_string();
+        let size = body["size"].as_u64().unwrap();
+        node.lock().unwrap().batches.insert(root.clone(), BatchAnchor { root, size, timestamp: utils::current_timestamp() });
+        Json(serde_json::json!({ "hash": "0xabc", "status": "success" }))
+    }
+
+    async fn get_batch(State(node): State<Node>, Path(root): Path<String>) -> Response {
+        match node.lock().unwrap().batches.get(&root) {
+            Some(batch) => Json(batch.clone()).into_response(),
+            None => StatusCode::NOT_FOUND.into_response(),
+        }
+    }
+
+    async fn anchor_head(State(node): State<Node>, body: Bytes) -> Json<serde_json::Value> {
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let entry_hash = body["entry_hash"].as_str().unwrap().to_string();
+        let seq = body["seq"].as_u64().unwrap();
+        node.lock().unwrap().heads.insert(entry_hash.clone(), LogHeadAnchor { entry_hash, seq, timestamp: utils::current_timestamp() });
+        Json(serde_json::json!({ "hash": "0xabc", "status": "success" }))
+    }
+
+    async fn get_head(State(node): State<Node>, Path(entry_hash): Path<String>) -> Response {
+        match node.lock().unwrap().heads.get(&entry_hash) {
+            Some(head) => Json(head.clone()).into_response(),
+            None => StatusCode::NOT_FOUND.into_response(),
+        }
+    }
+
+    let app = Router::new()
+        .route("/anchor/batch", post(anchor_batch))
+        .route("/anchor/batch/:root", get(get_batch))
+        .route("/anchor/log-head", post(anchor_head))
+        .route("/anchor/log-head/:entry_hash", get(get_head))
+        .with_state(Node::default());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}", address)
+}
+
+/// 新建数据库并写入三条日志：两个DID的创建和第一个DID的停用
+async fn populated_store(name: &str) -> (SharedStore, PathBuf) {
+    let path = std::env::temp_dir().join(format!("did-system-oplog-{}-{}.db", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let store: SharedStore = Arc::new(SqliteStore::open(path.to_str().unwrap(), None).unwrap());
+
+    let key = utils::generate_keypair();
+    let document = did::create_did(store.as_ref(), &key, None).await.unwrap();
+    did::create_did(store.as_ref(), &utils::generate_keypair(), None).await.unwrap();
+    let deactivate = SignedOperation::sign(
+        &document.id,
+        DidOperation::Deactivate,
+        Some(1),
+        &format!("{}#keys-1", document.id),
+        &key,
+    ).unwrap();
+    did::submit_operation(store.as_ref(), &document.id, deactivate, None).await.unwrap();
+
+    (store, path)
+}
+
+/// 绕过只追加触发器直接修改数据库文件
+fn tamper(path: &std::path::Path, sql: &str, values: impl rusqlite::Params) {
+    let conn = Connection::open(path).unwrap();
+    conn.execute_batch("DROP TRIGGER operation_log_no_update; DROP TRIGGER operation_log_no_delete;").unwrap();
+    conn.execute(sql, values).unwrap();
+}
+
+async fn broken_reason(store: &SharedStore) -> String {
+    let report = oplog::verify_chain(store.as_ref()).await.unwrap();
+    assert!(!report.intact);
+    report.broken_link.expect("broken link").reason
+}
+
+#[tokio::test]
+async fn tampering_breaks_the_chain() {
+    blockchain::init(LedgersConfig {
+        ledgers: vec![BlockchainConfig { node_url: start_node().await, ..Default::default() }],
+        ..Default::default()
+    }).await.unwrap();
+
+    // 未篡改
+    let (store, path) = populated_store("intact").await;
+    let report = oplog::verify_chain(store.as_ref()).await.unwrap();
+    assert!(report.intact, "{:?}", report);
+    assert_eq!(report.entries, 3);
+    let anchor = oplog::anchor_head(store.as_ref()).await.unwrap();
+    let report = oplog::verify_chain(store.as_ref()).await.unwrap();
+    assert!(report.intact, "{:?}", report);
+    assert_eq!(report.anchored_head.unwrap().entry_hash, anchor.entry_hash);
+    std::fs::remove_file(path).unwrap();
+
+    // 修改日志内容
+    let (store, path) = populated_store("payload").await;
+    tamper(&path, "UPDATE operation_log SET document_hash = ? WHERE seq = 2", params!["00".repeat(32)]);
+    assert_eq!(broken_reason(&store).await, "entry hash does not match its contents");
+    std::fs::remove_file(path).unwrap();
+
+    // 修改链接并重新计算条目哈希
+    let (store, path) = populated_store("link").await;
+    let mut entry = store.list_operation_log(1, 1).await.unwrap().remove(0);
+    entry.prev_hash = None;
+    entry.entry_hash = entry.compute_hash().unwrap();
+    tamper(&path, "UPDATE operation_log SET prev_hash = NULL, entry_hash = ? WHERE seq = 2", params![entry.entry_hash]);
+    assert_eq!(broken_reason(&store).await, "previous hash does not match the preceding entry");
+    std::fs::remove_file(path).unwrap();
+
+    // 锚定后重新计算链头
+    let (store, path) = populated_store("rewrite").await;
+    oplog::anchor_head(store.as_ref()).await.unwrap();
+    let mut head = store.last_operation_log_entry().await.unwrap().unwrap();
+    head.recorded_at += 1;
+    head.entry_hash = head.compute_hash().unwrap();
+    tamper(&path, "UPDATE operation_log SET recorded_at = ?, entry_hash = ? WHERE seq = 3", params![head.recorded_at, head.entry_hash]);
+    assert_eq!(broken_reason(&store).await, "entry hash does not match the anchored head");
+    std::fs::remove_file(path).unwrap();
+
+    // 锚定后截断末尾
+    let (store, path) = populated_store("truncate").await;
+    oplog::anchor_head(store.as_ref()).await.unwrap();
+    tamper(&path, "DELETE FROM operation_log WHERE seq = 3", []);
+    assert_eq!(broken_reason(&store).await, "operation log ends before the anchored head");
+
+    // 截断后追加新的日志，锚定的链头缺失
+    did::create_did(store.as_ref(), &utils::generate_keypair(), None).await.unwrap();
+    assert_eq!(broken_reason(&store).await, "anchored head is missing from the operation log");
+    std::fs::remove_file(path).unwrap();
+
+    // 本地伪造的锚定记录；同一哈希作为批次根哈希上链不能代替链头锚定
+    let (store, path) = populated_store("forged").await;
+    let head = store.last_operation_log_entry().await.unwrap().unwrap();
+    blockchain::anchor_batch("default", &head.entry_hash, head.seq as u64, "forged-batch").await.unwrap();
+    store.record_head_anchor(&HeadAnchor {
+        seq: head.seq,
+        entry_hash: head.entry_hash,
+        ledger: "default".to_string(),
+        tx_hash: "0xdef".to_string(),
+    }).await.unwrap();
+    assert!(broken_reason(&store).await.contains("not recorded on ledger"));
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,61 @@
+//! 出站队列测试：同一个DID的操作按写入顺序投递，更早的记录等待重试或进入死信时不投递后续记录
+
+use std::sync::Arc;
+use did_system::blockchain::{self, LedgersConfig};
+use did_system::db::{MemoryStore, SharedStore, SqliteStore};
+use did_system::did;
+use did_system::oplog::{DidOperation, SignedOperation};
+use did_system::outbox::OutboxStatus;
+use did_system::utils;
+
+async fn check_per_did_order(store: SharedStore) {
+    let _ = blockchain::init(LedgersConfig::default()).await;
+    let key = utils::generate_keypair();
+    let document = did::create_did(store.as_ref(), &key, None).await.unwrap();
+    let deactivate = SignedOperation::sign(
+        &document.id,
+        DidOperation::Deactivate,
+        Some(1),
+        &format!("{}#keys-1", document.id),
+        &key,
+    ).unwrap();
+    did::submit_operation(store.as_ref(), &document.id, deactivate, None).await.unwrap();
+    did::create_did(store.as_ref(), &utils::generate_keypair(), None).await.unwrap();
+
+    let entries = store.list_outbox_entries(OutboxStatus::Pending).await.unwrap();
+    let [register, deactivation, other_register] = entries.as_slice() else {
+        panic!("unexpected outbox entries: {:?}", entries);
+    };
+    assert_eq!((register.did.as_str(), deactivation.did.as_str()), (document.id.as_str(), document.id.as_str()));
+
+    // 注册等待重试时，同一个DID的停用不投递，其他DID不受影响
+    let now = utils::current_timestamp();
+    store.mark_outbox_failed(register.id, 1, "node unavailable", Some(now + 600)).await.unwrap();
+    let due = store.fetch_due_outbox_entries("default", now, 10, false).await.unwrap();
+    assert_eq!(due.iter().map(|entry| entry.id).collect::<Vec<_>>(), [other_register.id]);
+
+    // 死信同样阻塞后续记录，重新排队后按顺序投递
+    store.mark_outbox_failed(register.id, 5, "node unavailable", None).await.unwrap();
+    assert!(store.fetch_due_outbox_entries("default", now, 10, false).await.unwrap()
+        .iter().all(|entry| entry.id != deactivation.id));
+    store.requeue_outbox_entry(register.id).await.unwrap();
+    let due = store.fetch_due_outbox_entries("default", now + 1, 10, false).await.unwrap();
+    assert_eq!(due.iter().map(|entry| entry.id).collect::<Vec<_>>(), [register.id, other_register.id]);
+
+    store.mark_outbox_delivered(register.id, "0xabc").await.unwrap();
+    let due = store.fetch_due_outbox_entries("default", now + 1, 10, false).await.unwrap();
+    assert_eq!(due.iter().map(|entry| entry.id).collect::<Vec<_>>(), [deactivation.id, other_register.id]);
+}
+
+#[tokio::test]
+async fn memory_store_delivers_in_order_per_did() {
+    check_per_did_order(Arc::new(MemoryStore::new())).await;
+}
+
+#[tokio::test]
+async fn sqlite_store_delivers_in_order_per_did() {
+    let path = std::env::temp_dir().join(format!("did-system-outbox-{}.db", std::process::id()));
+    let store = SqliteStore::open(path.to_str().unwrap(), None).unwrap();
+    check_per_did_order(Arc::new(store)).await;
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,162 @@
+//! DID查询测试：按状态、方法、控制者、验证方法类型、服务类型和时间范围过滤，
+//! 按DID排序以游标分页，无效的游标和每页数量被拒绝
+
+use std::sync::Arc;
+use axum::body::{to_bytes, Body};
+use axum::http::{Request, StatusCode};
+use axum::Router;
+use did_system::api;
+use did_system::blockchain::{self, LedgersConfig};
+use did_system::config::Config;
+use did_system::db::{MemoryStore, SharedStore, SqliteStore};
+use did_system::did::{self, DIDDocument, PublicKeyInfo, Service};
+use did_system::oplog::{DidOperation, SignedOperation};
+use did_system::utils;
+use tower::ServiceExt;
+
+async fn get(router: &Router, uri: &str) -> (StatusCode, serde_json::Value) {
+    let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+    let response = router.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+/// 一页v2查询结果中的DID和下一页的游标
+async fn page(router: &Router, query: &str) -> (Vec<String>, Option<String>) {
+    let (status, body) = get(router, &format!("/v2/dids?{}", query)).await;
+    assert_eq!(status, StatusCode::OK, "{}: {}", query, body);
+    let dids = body["items"].as_array().unwrap().iter()
+        .map(|item| item["didDocument"]["id"].as_str().unwrap().to_string())
+        .collect();
+    (dids, body["nextCursor"].as_str().map(str::to_string))
+}
+
+async fn dids(router: &Router, query: &str) -> Vec<String> {
+    page(router, query).await.0
+}
+
+/// 以客户端签名的创建操作登记创建时间和更新时间为`created`的DID
+async fn create(store: &SharedStore, created: u64, key_type: &str, controller: Option<&str>, service_type: Option<&str>) -> String {
+    let key = utils::generate_keypair();
+    let public_key = key.verifying_key().to_bytes();
+    let did = did::did_for_key(&public_key, None).unwrap();
+    let key_id = format!("{}#keys-1", did);
+    let document = DIDDocument {
+        id: did.clone(),
+        public_keys: vec![PublicKeyInfo {
+            id: key_id.clone(),
+            type_: key_type.to_string(),
+            controller: controller.unwrap_or(&did).to_string(),
+            public_key_base58: utils::encode_base58(&public_key),
+        }],
+        authentication: vec![key_id.clone()],
+        services: service_type.map(|type_| Service {
+            id: format!("{}#service", did),
+            type_: type_.to_string(),
+            endpoint: format!("https://{}.example.com", created),
+        }).into_iter().collect(),
+        created,
+        updated: created,
+    };
+    let operation = SignedOperation::sign(&did, DidOperation::Create { document }, None, &key_id, &key).unwrap();
+    did::submit_operation(store.as_ref(), &did, operation, None).await.unwrap();
+    if service_type.is_none() {
+        let deactivate = SignedOperation::sign(&did, DidOperation::Deactivate, Some(1), &key_id, &key).unwrap();
+        did::submit_operation(store.as_ref(), &did, deactivate, None).await.unwrap();
+    }
+    did
+}
+
+fn sorted(mut dids: Vec<String>) -> Vec<String> {
+    dids.sort();
+    dids
+}
+
+async fn check_filters_and_pagination(store: SharedStore) {
+    let _ = blockchain::init(LedgersConfig::default()).await;
+    let router = api::create_router(store.clone(), &Config::default()).unwrap();
+    let a = create(&store, 1000, "Ed25519VerificationKey2020", None, Some("LinkedDomains")).await;
+    let b = create(&store, 2000, "JsonWebKey2020", Some(&a), Some("DIDCommMessaging")).await;
+    // 没有服务端点的DID创建后立即停用，更新时间为当前时间
+    let c = create(&store, 3000, "Ed25519VerificationKey2020", None, None).await;
+    let all = sorted(vec![a.clone(), b.clone(), c.clone()]);
+
+    assert_eq!(dids(&router, "").await, all);
+    assert_eq!(dids(&router, "status=active").await, sorted(vec![a.clone(), b.clone()]));
+    assert_eq!(dids(&router, "status=deactivated").await, [c.as_str()]);
+    assert_eq!(dids(&router, "method=web").await, all);
+    assert!(dids(&router, "method=key").await.is_empty());
+    assert_eq!(dids(&router, &format!("controller={}", a)).await, sorted(vec![a.clone(), b.clone()]));
+    assert_eq!(dids(&router, &format!("controller={}", c)).await, [c.as_str()]);
+    assert_eq!(dids(&router, "verification_method_type=JsonWebKey2020").await, [b.as_str()]);
+    assert_eq!(dids(&router, "service_type=LinkedDomains").await, [a.as_str()]);
+    assert_eq!(dids(&router, "service_type=DIDCommMessaging&status=deactivated").await, Vec::<String>::new());
+
+    // 时间范围包含下界、不包含上界
+    assert_eq!(dids(&router, "created_after=2000").await, sorted(vec![b.clone(), c.clone()]));
+    assert_eq!(dids(&router, "created_before=2000").await, [a.as_str()]);
+    assert_eq!(dids(&router, "created_after=1000&created_before=3000").await, sorted(vec![a.clone(), b.clone()]));
+    assert_eq!(dids(&router, "updated_before=2001").await, sorted(vec![a.clone(), b.clone()]));
+    assert_eq!(dids(&router, "updated_after=3001").await, [c.as_str()]);
+
+    // 逐页读取：最后一页没有游标，结果数恰好等于每页数量时同样没有游标
+    let mut cursor = None;
+    let mut seen = Vec::new();
+    loop {
+        let query = match &cursor {
+            Some(cursor) => format!("limit=1&cursor={}", cursor),
+            None => "limit=1".to_string(),
+        };
+        let (dids, next) = page(&router, &query).await;
+        assert_eq!(dids.len(), 1);
+        seen.extend(dids);
+        match next {
+            Some(next) => cursor = Some(next),
+            None => break,
+        }
+    }
+    assert_eq!(seen, all);
+    let (first, cursor) = page(&router, "limit=2").await;
+    assert_eq!(first, all[..2]);
+    let (rest, next) = page(&router, &format!("limit=2&cursor={}", cursor.unwrap())).await;
+    assert_eq!((rest, next), (all[2..].to_vec(), None));
+    assert_eq!(page(&router, "limit=3").await, (all.clone(), None));
+
+    // 游标与过滤条件一起使用
+    let (first, cursor) = page(&router, "status=active&limit=1").await;
+    let (second, next) = page(&router, &format!("status=active&limit=1&cursor={}", cursor.unwrap())).await;
+    assert_eq!([first, second].concat(), sorted(vec![a.clone(), b.clone()]));
+    assert_eq!(next, None);
+
+    // v1返回同样的分页结果
+    let (status, body) = get(&router, "/v1/dids?status=active&limit=1").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["items"].as_array().unwrap().len(), 1);
+    assert!(body["data"]["next_cursor"].is_string());
+
+    for (query, field) in [
+        ("cursor=%21%21%21", "cursor"),
+        ("cursor=gA", "cursor"),
+        ("limit=0", "limit"),
+        ("limit=501", "limit"),
+        ("status=unknown", "status"),
+    ] {
+        let (status, problem) = get(&router, &format!("/v2/dids?{}", query)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{}", query);
+        assert_eq!(problem["errors"][0]["field"], field, "{}", query);
+    }
+}
+
+#[tokio::test]
+async fn memory_store_filters_and_paginates() {
+    check_filters_and_pagination(Arc::new(MemoryStore::new())).await;
+}
+
+#[tokio::test]
+async fn sqlite_store_filters_and_paginates() {
+    let path = std::env::temp_dir().join(format!("did-system-query-{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    check_filters_and_pagination(Arc::new(SqliteStore::open(path.to_str().unwrap(), None).unwrap())).await;
+    std::fs::remove_file(path).unwrap();
+}
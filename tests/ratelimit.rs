@@ -0,0 +1,186 @@
+//! 限流测试：读写请求分别按客户端限流，超出时返回429和限流头；信任反向代理时按代理追加的地址计数；
+//! 写操作用完每日配额后被拒绝，配额在执行前原子地占用，失败的写请求退还配额
+
+use std::sync::Arc;
+use axum::body::{to_bytes, Body};
+use axum::http::{header, Request, StatusCode};
+use axum::Router;
+use did_system::api;
+use did_system::auth::{self, Role};
+use did_system::config::Config;
+use did_system::db::{MemoryStore, SharedStore, SqliteStore};
+use did_system::ratelimit;
+use did_system::utils;
+use tower::ServiceExt;
+
+fn limited_config() -> Config {
+    let mut config = Config::default();
+    config.rate_limit.enabled = true;
+    config.rate_limit.read_per_minute = 1;
+    config.rate_limit.read_burst = 2;
+    config.rate_limit.write_per_minute = 1;
+    config.rate_limit.write_burst = 1;
+    config
+}
+
+async fn send(router: &Router, method: &str, uri: &str, forwarded_for: &str, credential: Option<&str>) -> axum::response::Response {
+    let mut request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("x-forwarded-for", forwarded_for)
+        .header(header::ACCEPT, "application/problem+json");
+    if let Some(credential) = credential {
+        request = request.header("x-api-key", credential);
+    }
+    router.clone().oneshot(request.body(Body::empty()).unwrap()).await.unwrap()
+}
+
+async fn error_code(response: axum::response::Response) -> String {
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let problem: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    problem["code"].as_str().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn reads_and_writes_use_separate_buckets() {
+    let mut config = limited_config();
+    config.rate_limit.trust_forwarded_for = true;
+    let store: SharedStore = Arc::new(MemoryStore::new());
+    let router = api::create_router(store, &config).unwrap();
+
+    let response = send(&router, "GET", "/v2/dids", "203.0.113.1", None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["ratelimit-limit"], "2");
+    assert_eq!(response.headers()["ratelimit-remaining"], "1");
+    assert_eq!(response.headers()["ratelimit-policy"], "1;w=60;burst=2");
+    assert_eq!(send(&router, "GET", "/v2/dids", "203.0.113.1", None).await.status(), StatusCode::OK);
+
+    let response = send(&router, "GET", "/v2/dids", "203.0.113.1", None).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let retry_after: u64 = response.headers()[header::RETRY_AFTER].to_str().unwrap().parse().unwrap();
+    assert!((1..=60).contains(&retry_after));
+    assert_eq!(response.headers()["ratelimit-remaining"], "0");
+    assert_eq!(error_code(response).await, "rate_limited");
+
+    // 写请求使用自己的桶，失败的写请求同样消耗令牌
+    assert_ne!(send(&router, "DELETE", "/v1/did/did:web:missing", "203.0.113.1", None).await.status(), StatusCode::TOO_MANY_REQUESTS);
+    let response = send(&router, "DELETE", "/v1/did/did:web:missing", "203.0.113.1", None).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    // 其他客户端和健康检查不受影响
+    assert_eq!(send(&router, "GET", "/v2/dids", "203.0.113.2", None).await.status(), StatusCode::OK);
+    let response = send(&router, "GET", "/health", "203.0.113.1", None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers().get("ratelimit-limit").is_none());
+}
+
+#[tokio::test]
+async fn forwarded_for_is_ignored_unless_trusted() {
+    let store: SharedStore = Arc::new(MemoryStore::new());
+    let router = api::create_router(store, &limited_config()).unwrap();
+
+    assert_eq!(send(&router, "GET", "/v2/dids", "203.0.113.1", None).await.status(), StatusCode::OK);
+    assert_eq!(send(&router, "GET", "/v2/dids", "203.0.113.2", None).await.status(), StatusCode::OK);
+    assert_eq!(send(&router, "GET", "/v2/dids", "203.0.113.3", None).await.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[tokio::test]
+async fn forwarded_for_uses_the_address_appended_by_the_proxy() {
+    let mut config = limited_config();
+    config.rate_limit.trust_forwarded_for = true;
+    let store: SharedStore = Arc::new(MemoryStore::new());
+    let router = api::create_router(store, &config).unwrap();
+
+    // 客户端填写在左边的地址不影响计数
+    assert_eq!(send(&router, "GET", "/v2/dids", "198.51.100.1, 203.0.113.1", None).await.status(), StatusCode::OK);
+    assert_eq!(send(&router, "GET", "/v2/dids", "198.51.100.2, 203.0.113.1", None).await.status(), StatusCode::OK);
+    assert_eq!(send(&router, "GET", "/v2/dids", "198.51.100.3,203.0.113.1", None).await.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(send(&router, "GET", "/v2/dids", "203.0.113.1, 203.0.113.2", None).await.status(), StatusCode::OK);
+
+    // 两层代理时取从右数第二个地址
+    config.rate_limit.trusted_proxy_hops = 2;
+    let store: SharedStore = Arc::new(MemoryStore::new());
+    let router = api::create_router(store, &config).unwrap();
+    assert_eq!(send(&router, "GET", "/v2/dids", "198.51.100.1, 203.0.113.1, 10.0.0.1", None).await.status(), StatusCode::OK);
+    assert_eq!(send(&router, "GET", "/v2/dids", "198.51.100.2, 203.0.113.1, 10.0.0.2", None).await.status(), StatusCode::OK);
+    assert_eq!(send(&router, "GET", "/v2/dids", "203.0.113.1, 10.0.0.1", None).await.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(send(&router, "GET", "/v2/dids", "203.0.113.2, 10.0.0.1", None).await.status(), StatusCode::OK);
+
+    assert!(config.validate().is_ok());
+    config.rate_limit.trusted_proxy_hops = 0;
+    assert!(config.validate().is_err());
+}
+
+#[tokio::test]
+async fn daily_write_quota_is_enforced_per_key() {
+    let mut config = limited_config();
+    config.auth.enabled = true;
+    config.rate_limit.write_burst = 10;
+    config.rate_limit.daily_write_quota = 100;
+    let store: SharedStore = Arc::new(MemoryStore::new());
+    let router = api::create_router(store.clone(), &config).unwrap();
+    let (limited, limited_key) = auth::create_api_key(store.as_ref(), "partner", Some(Role::Writer), vec![], None, Some(2)).await.unwrap();
+    let (default, default_key) = auth::create_api_key(store.as_ref(), "ci", Some(Role::Writer), vec![], None, None).await.unwrap();
+
+    let day = ratelimit::day_of(utils::current_timestamp());
+    for _ in 0..2 {
+        store.reserve_write(&format!("key:{}", limited.id), day, 2, 1).await.unwrap().unwrap();
+        store.reserve_write(&format!("key:{}", default.id), day, 2, 1).await.unwrap().unwrap();
+    }
+
+    let response = send(&router, "DELETE", "/v1/did/did:web:missing", "", Some(&limited_key)).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let retry_after: u64 = response.headers()[header::RETRY_AFTER].to_str().unwrap().parse().unwrap();
+    assert!(retry_after <= ratelimit::seconds_until_next_day(utils::current_timestamp()) + 1);
+    assert_eq!(error_code(response).await, "quota_exceeded");
+
+    // 读请求不受配额限制，其他密钥使用默认配额
+    assert_eq!(send(&router, "GET", "/v2/dids", "", Some(&limited_key)).await.status(), StatusCode::OK);
+    let response = send(&router, "DELETE", "/v1/did/did:web:missing", "", Some(&default_key)).await;
+    assert_ne!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    // 失败的写请求退还占用的配额
+    assert_eq!(store.get_write_usage(&format!("key:{}", default.id), day).await.unwrap(), 2);
+}
+
+#[tokio::test]
+async fn rate_limiting_is_enabled_by_default() {
+    assert!(Config::default().rate_limit.enabled);
+}
+
+async fn check_reservations(store: SharedStore) {
+    let day = ratelimit::day_of(utils::current_timestamp());
+
+    // 并发占用不会超出配额
+    let reservations = (0..20).map(|_| {
+        let store = store.clone();
+        tokio::spawn(async move { store.reserve_write("ip:203.0.113.1", day, 5, 1).await.unwrap() })
+    }).collect::<Vec<_>>();
+    let mut reserved = 0;
+    for reservation in reservations {
+        reserved += usize::from(reservation.await.unwrap().is_some());
+    }
+    assert_eq!(reserved, 5);
+    assert_eq!(store.get_write_usage("ip:203.0.113.1", day).await.unwrap(), 5);
+    assert!(store.reserve_write("ip:203.0.113.1", day, 5, 1).await.unwrap().is_none());
+
+    // 退还后可以再次占用，配额为0时不占用
+    store.release_write("ip:203.0.113.1", day, 1).await.unwrap();
+    assert_eq!(store.reserve_write("ip:203.0.113.1", day, 5, 1).await.unwrap(), Some(5));
+    assert!(store.reserve_write("ip:203.0.113.2", day, 0, 1).await.unwrap().is_none());
+    store.release_write("ip:203.0.113.2", day, 1).await.unwrap();
+    assert_eq!(store.get_write_usage("ip:203.0.113.2", day).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn memory_store_reserves_quota_atomically() {
+    check_reservations(Arc::new(MemoryStore::new())).await;
+}
+
+#[tokio::test]
+async fn sqlite_store_reserves_quota_atomically() {
+    let path = std::env::temp_dir().join(format!("did-system-ratelimit-{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    check_reservations(Arc::new(SqliteStore::open(path.to_str().unwrap(), None).unwrap())).await;
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,148 @@
+//! 对账测试：与模拟节点比较活跃状态和文档哈希，报告漂移；修复模式以区块链为准修复本地状态并生成新版本，
+//! 仍有待投递出站记录的DID被跳过
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use did_system::blockchain::{self, BlockchainConfig, LedgersConfig};
+use did_system::db::{MemoryStore, SharedStore, SqliteStore};
+use did_system::did::{self, DIDDocument, Service};
+use did_system::oplog::{DidOperation, SignedOperation};
+use did_system::outbox::OutboxStatus;
+use did_system::reconcile::{self, DriftKind};
+use did_system::utils;
+
+/// 链上每个DID的活跃状态和文档
+type Chain = Arc<Mutex<HashMap<String, (bool, Option<DIDDocument>)>>>;
+
+/// 完整上链模式的模拟节点
+async fn start_node() -> (String, Chain) {
+    async fn status(State(chain): State<Chain>, Path(did): Path<String>) -> Json<serde_json::Value> {
+        let active = chain.lock().unwrap().get(&did).is_some_and(|(active, _)| *active);
+        Json(serde_json::json!({ "active": active }))
+    }
+
+    async fn document(State(chain): State<Chain>, Path(did): Path<String>) -> Response {
+        match chain.lock().unwrap().get(&did) {
+            Some((_, Some(document))) => Json(document.clone()).into_response(),
+            _ => StatusCode::NOT_FOUND.into_response(),
+        }
+    }
+
+    let chain = Chain::default();
+    let app = Router::new()
+        .route("/did/:did", get(document))
+        .route("/did/:did/status", get(status))
+        .with_state(chain.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (format!("http://{}", address), chain)
+}
+
+/// 将所有待投递的出站记录标记为已投递
+async fn deliver_all(store: &SharedStore) {
+    for entry in store.list_outbox_entries(OutboxStatus::Pending).await.unwrap() {
+        store.mark_outbox_delivered(entry.id, "0xabc").await.unwrap();
+    }
+}
+
+fn drift_kinds(report: &reconcile::DriftReport) -> Vec<(String, DriftKind, bool)> {
+    let mut drifts: Vec<_> = report.drifts.iter()
+        .map(|drift| (drift.did.clone(), drift.kind, drift.repaired))
+        .collect();
+    drifts.sort_by(|a, b| a.0.cmp(&b.0));
+    drifts
+}
+
+async fn check_reconcile(store: SharedStore, chain: &Chain) {
+    let mut keys = Vec::new();
+    let mut documents = Vec::new();
+    for _ in 0..4 {
+        let key = utils::generate_keypair();
+        documents.push(did::create_did(store.as_ref(), &key, None).await.unwrap());
+        keys.push(key);
+    }
+    let [synced, stopped, changed, revived] = documents.as_slice() else { unreachable!() };
+
+    // 本地已停用，链上仍然活跃
+    let deactivate = SignedOperation::sign(&revived.id, DidOperation::Deactivate, Some(1), &format!("{}#keys-1", revived.id), &keys[3]).unwrap();
+    did::submit_operation(store.as_ref(), &revived.id, deactivate, None).await.unwrap();
+    deliver_all(&store).await;
+
+    // 链上的文档多一个服务端点
+    let mut chain_document = changed.clone();
+    chain_document.services.push(Service {
+        id: format!("{}#home", changed.id),
+        type_: "LinkedDomains".to_string(),
+        endpoint: "https://home.example.com".to_string(),
+    });
+    chain_document.updated += 1;
+    {
+        let mut chain = chain.lock().unwrap();
+        chain.insert(synced.id.clone(), (true, Some(synced.clone())));
+        chain.insert(stopped.id.clone(), (false, Some(stopped.clone())));
+        chain.insert(changed.id.clone(), (true, Some(chain_document.clone())));
+        chain.insert(revived.id.clone(), (true, Some(revived.clone())));
+    }
+
+    // 出站记录尚未投递的DID不参与对账
+    did::create_did(store.as_ref(), &utils::generate_keypair(), None).await.unwrap();
+
+    let mut expected = [
+        (stopped.id.clone(), DriftKind::StatusMismatch),
+        (changed.id.clone(), DriftKind::ContentMismatch),
+        (revived.id.clone(), DriftKind::StatusMismatch),
+    ];
+    expected.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let report = reconcile::reconcile(store.as_ref(), false).await.unwrap();
+    assert_eq!((report.checked, report.skipped_pending, report.failures.len()), (4, 1, 0));
+    assert_eq!(drift_kinds(&report), expected.iter().map(|(did, kind)| (did.clone(), *kind, false)).collect::<Vec<_>>());
+    let content = report.drifts.iter().find(|drift| drift.did == changed.id).unwrap();
+    assert_eq!(content.local_hash.as_deref(), Some(did::document_hash(changed).unwrap().as_str()));
+    assert_eq!(content.chain_hash.as_deref(), Some(did::document_hash(&chain_document).unwrap().as_str()));
+    let status = report.drifts.iter().find(|drift| drift.did == stopped.id).unwrap();
+    assert_eq!((status.local_active, status.chain_active), (Some(true), Some(false)));
+
+    // 只报告时不修改本地状态
+    assert_eq!(store.get_did_record(&changed.id).await.unwrap().unwrap().version_id, 1);
+    assert!(store.get_did_record(&stopped.id).await.unwrap().unwrap().is_active);
+
+    let report = reconcile::reconcile(store.as_ref(), true).await.unwrap();
+    assert!(report.repair);
+    assert_eq!(drift_kinds(&report), expected.iter().map(|(did, kind)| (did.clone(), *kind, true)).collect::<Vec<_>>());
+
+    // 修复生成新版本
+    let record = store.get_did_record(&stopped.id).await.unwrap().unwrap();
+    assert_eq!((record.is_active, record.version_id), (false, 2));
+    let record = store.get_did_record(&changed.id).await.unwrap().unwrap();
+    assert_eq!(record.version_id, 2);
+    assert_eq!(did::document_hash(&record.document).unwrap(), did::document_hash(&chain_document).unwrap());
+    let record = store.get_did_record(&revived.id).await.unwrap().unwrap();
+    assert_eq!((record.is_active, record.version_id), (true, 3));
+    assert_eq!(store.get_did_record(&synced.id).await.unwrap().unwrap().version_id, 1);
+
+    let report = reconcile::reconcile(store.as_ref(), false).await.unwrap();
+    assert!(report.drifts.is_empty(), "{:?}", report.drifts);
+}
+
+#[tokio::test]
+async fn drift_is_reported_and_repaired() {
+    let (node_url, chain) = start_node().await;
+    blockchain::init(LedgersConfig {
+        ledgers: vec![BlockchainConfig { node_url, ..Default::default() }],
+        ..Default::default()
+    }).await.unwrap();
+
+    check_reconcile(Arc::new(MemoryStore::new()), &chain).await;
+
+    let path = std::env::temp_dir().join(format!("did-system-reconcile-{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    check_reconcile(Arc::new(SqliteStore::open(path.to_str().unwrap(), None).unwrap()), &chain).await;
+    std::fs::remove_file(path).unwrap();
+}
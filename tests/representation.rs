@@ -0,0 +1,168 @@
+//! 表示测试：解析DID时按`Accept`头的权重和精确度选择解析结果或DID Core表示，`accept`参数优先于`Accept`头，
+//! 没有可接受的表示时返回406；CBOR表示可以解码为与JSON表示相同的文档；已停用的DID同样按协商的表示返回
+
+use std::sync::Arc;
+use axum::body::{to_bytes, Body};
+use axum::http::{header, Request, StatusCode};
+use axum::Router;
+use did_system::api;
+use did_system::blockchain::{self, LedgersConfig};
+use did_system::config::Config;
+use did_system::db::{MemoryStore, SharedStore};
+use did_system::did::{self, DIDDocument, Service};
+use did_system::did::representation::{self, Representation, DID_CONTEXT, ED25519_2020_CONTEXT};
+use did_system::oplog::{DidOperation, SignedOperation};
+use did_system::utils;
+use tower::ServiceExt;
+
+async fn resolve(router: &Router, uri: &str, accept: Option<&str>) -> (StatusCode, String, Vec<u8>) {
+    let mut request = Request::builder().uri(uri);
+    if let Some(accept) = accept {
+        request = request.header(header::ACCEPT, accept);
+    }
+    let response = router.clone().oneshot(request.body(Body::empty()).unwrap()).await.unwrap();
+    let status = response.status();
+    if status == StatusCode::OK || status == StatusCode::GONE {
+        assert_eq!(response.headers()[header::VARY], "accept");
+    }
+    let content_type = response.headers().get(header::CONTENT_TYPE)
+        .map(|value| value.to_str().unwrap().to_string())
+        .unwrap_or_default();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, content_type, body.to_vec())
+}
+
+/// 创建带一个服务端点的DID
+async fn setup() -> (Router, DIDDocument) {
+    let _ = blockchain::init(LedgersConfig::default()).await;
+    let store: SharedStore = Arc::new(MemoryStore::new());
+    let router = api::create_router(store.clone(), &Config::default()).unwrap();
+    let key = utils::generate_keypair();
+    let document = did::create_did(store.as_ref(), &key, None).await.unwrap();
+    let service = Service {
+        id: format!("{}#home", document.id),
+        type_: "LinkedDomains".to_string(),
+        endpoint: "https://home.example.com".to_string(),
+    };
+    let record = did::add_service(store.as_ref(), &document.id, &key, service, None).await.unwrap();
+    (router, record.document)
+}
+
+#[tokio::test]
+async fn representations_follow_the_did_core_data_model() {
+    let (router, document) = setup().await;
+    let uri = format!("/v2/dids/{}", document.id);
+
+    // 没有`Accept`头时返回解析结果
+    let (status, content_type, body) = resolve(&router, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(content_type.starts_with("application/json"), "{}", content_type);
+    let result: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(result["didDocument"]["id"], document.id);
+    assert_eq!(result["didDocumentMetadata"]["versionId"], "2");
+
+    let (status, content_type, body) = resolve(&router, &uri, Some("application/did+json")).await;
+    assert_eq!((status, content_type.as_str()), (StatusCode::OK, "application/did+json"));
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert!(json.get("@context").is_none());
+    assert_eq!(json["id"], document.id);
+    assert_eq!(json["verificationMethod"][0]["id"], document.public_keys[0].id);
+    assert_eq!(json["verificationMethod"][0]["controller"], document.id);
+    assert_eq!(json["authentication"][0], document.authentication[0]);
+    assert_eq!(json["service"][0]["serviceEndpoint"], "https://home.example.com");
+
+    let (status, content_type, body) = resolve(&router, &uri, Some("application/did+ld+json")).await;
+    assert_eq!((status, content_type.as_str()), (StatusCode::OK, "application/did+ld+json"));
+    let json_ld: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json_ld["@context"], serde_json::json!([DID_CONTEXT, ED25519_2020_CONTEXT]));
+    assert_eq!(json_ld["verificationMethod"], json["verificationMethod"]);
+
+    // CBOR表示解码后与JSON表示相同，可以读回原来的文档
+    let (status, content_type, body) = resolve(&router, &uri, Some("application/did+cbor")).await;
+    assert_eq!((status, content_type.as_str()), (StatusCode::OK, "application/did+cbor"));
+    let cbor: serde_json::Value = ciborium::from_reader(&body[..]).unwrap();
+    assert_eq!(cbor, json);
+    let decoded = representation::consume(&body, Representation::Cbor).unwrap();
+    assert_eq!(decoded.id, document.id);
+    assert_eq!(decoded.public_keys[0].public_key_base58, document.public_keys[0].public_key_base58);
+    assert_eq!(decoded.services[0].endpoint, document.services[0].endpoint);
+
+    // v1同样支持DID Core表示
+    let (status, content_type, _) = resolve(&router, &format!("/v1/did/{}", document.id), Some("application/did+cbor")).await;
+    assert_eq!((status, content_type.as_str()), (StatusCode::OK, "application/did+cbor"));
+}
+
+#[tokio::test]
+async fn accept_header_and_parameter_select_the_representation() {
+    let (router, document) = setup().await;
+    let uri = format!("/v2/dids/{}", document.id);
+
+    for (accept, expected) in [
+        // 权重最高的媒体类型
+        ("application/did+json;q=0.5, application/did+cbor;q=0.9", "application/did+cbor"),
+        ("application/did+ld+json;q=0.1, application/json;q=0.2", "application/json"),
+        // 权重相同时精确匹配优先于通配符
+        ("application/*;q=0.8, application/did+ld+json;q=0.8", "application/did+ld+json"),
+        // 权重和精确度都相同时按服务端的顺序
+        ("application/did+cbor, application/did+json", "application/did+json"),
+        ("*/*", "application/json"),
+        ("application/json;q=0, application/*", "application/did+json"),
+        ("text/html, application/did+ld+json;q=0.1", "application/did+ld+json"),
+    ] {
+        let (status, content_type, _) = resolve(&router, &uri, Some(accept)).await;
+        assert_eq!(status, StatusCode::OK, "{}", accept);
+        assert!(content_type.starts_with(expected), "{}: {}", accept, content_type);
+    }
+
+    // `accept`参数优先于`Accept`头，未编码的`+`被解码为空格时同样可以识别
+    for (parameter, expected) in [
+        ("did%2Bcbor", "application/did+cbor"),
+        ("application/did+ld+json", "application/did+ld+json"),
+        ("application%2Fjson", "application/json"),
+    ] {
+        let (status, content_type, _) = resolve(&router, &format!("{}?accept={}", uri, parameter), Some("application/did+json")).await;
+        assert_eq!(status, StatusCode::OK, "{}", parameter);
+        assert!(content_type.starts_with(expected), "{}: {}", parameter, content_type);
+    }
+
+    // 没有可接受的表示
+    for accept in ["text/html", "application/did+json;q=0", "image/*"] {
+        let (status, _, body) = resolve(&router, &uri, Some(accept)).await;
+        assert_eq!(status, StatusCode::NOT_ACCEPTABLE, "{}", accept);
+        let problem: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(problem["code"], "representationNotSupported");
+    }
+    let (status, _, _) = resolve(&router, &format!("{}?accept=text/html", uri), None).await;
+    assert_eq!(status, StatusCode::NOT_ACCEPTABLE);
+}
+
+#[tokio::test]
+async fn deactivated_dids_use_the_negotiated_representation() {
+    let _ = blockchain::init(LedgersConfig::default()).await;
+    let store: SharedStore = Arc::new(MemoryStore::new());
+    let router = api::create_router(store.clone(), &Config::default()).unwrap();
+    let key = utils::generate_keypair();
+    let document = did::create_did(store.as_ref(), &key, None).await.unwrap();
+    let deactivate = SignedOperation::sign(&document.id, DidOperation::Deactivate, Some(1), &format!("{}#keys-1", document.id), &key).unwrap();
+    did::submit_operation(store.as_ref(), &document.id, deactivate, None).await.unwrap();
+    let uri = format!("/v2/dids/{}", document.id);
+
+    let (status, content_type, body) = resolve(&router, &uri, None).await;
+    assert_eq!(status, StatusCode::GONE);
+    assert!(content_type.starts_with("application/json"), "{}", content_type);
+    let result: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(result["didDocumentMetadata"]["deactivated"], true);
+
+    let (status, content_type, body) = resolve(&router, &uri, Some("application/did+ld+json")).await;
+    assert_eq!((status, content_type.as_str()), (StatusCode::GONE, "application/did+ld+json"));
+    let json_ld: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json_ld["id"], document.id);
+    assert!(json_ld["@context"].is_array());
+
+    let (status, content_type, body) = resolve(&router, &format!("{}?accept=did%2Bcbor", uri), None).await;
+    assert_eq!((status, content_type.as_str()), (StatusCode::GONE, "application/did+cbor"));
+    assert_eq!(representation::consume(&body, Representation::Cbor).unwrap().id, document.id);
+
+    let (status, _, _) = resolve(&router, &uri, Some("text/html")).await;
+    assert_eq!(status, StatusCode::NOT_ACCEPTABLE);
+}
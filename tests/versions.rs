@@ -0,0 +1,55 @@
+//! 接口版本测试：v1及其不带前缀的别名带有弃用信息，v2不带弃用信息且错误总是问题详情
+
+use std::sync::Arc;
+use axum::body::{to_bytes, Body};
+use axum::http::{header, Request, StatusCode};
+use axum::Router;
+use did_system::api;
+use did_system::config::Config;
+use did_system::db::{MemoryStore, SharedStore};
+use tower::ServiceExt;
+
+fn router(config: &Config) -> Router {
+    let store: SharedStore = Arc::new(MemoryStore::new());
+    api::create_router(store, config).expect("router")
+}
+
+async fn get(router: &Router, uri: &str) -> axum::response::Response {
+    let request = Request::builder()
+        .uri(uri)
+        .header(header::ACCEPT, "application/json")
+        .body(Body::empty())
+        .unwrap();
+    router.clone().oneshot(request).await.unwrap()
+}
+
+#[tokio::test]
+async fn v1_responses_are_deprecated() {
+    let mut config = Config::default();
+    config.api.v1_sunset = Some("2027-04-18T00:00:00Z".parse().unwrap());
+    let router = router(&config);
+
+    for uri in ["/v1/dids", "/dids", "/v1/did/did:web:missing"] {
+        let response = get(&router, uri).await;
+        let headers = response.headers();
+        assert_eq!(headers["deprecation"], format!("@{}", config.api.v1_deprecated_at.timestamp()), "{}", uri);
+        assert_eq!(headers["sunset"], "Sun, 18 Apr 2027 00:00:00 GMT", "{}", uri);
+        assert_eq!(headers[header::LINK], "</v2>; rel=\"successor-version\"", "{}", uri);
+    }
+}
+
+#[tokio::test]
+async fn v2_responses_are_current_and_use_problem_details() {
+    let router = router(&Config::default());
+
+    let response = get(&router, "/v2/dids").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers().get("deprecation").is_none());
+
+    let response = get(&router, "/v2/dids/did:web:missing").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "application/problem+json");
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let problem: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(problem["code"], "not_found");
+}